@@ -577,11 +577,19 @@ class ApiClient {
     async fetchJson(path) {
         const response = await fetch(`${this.baseUrl}${path}`, { cache: 'no-store' });
         if (!response.ok) {
-            throw new Error(`Request failed (${response.status})`);
+            throw ApiClient.requestError(response);
         }
         return response.json();
     }
 
+    // Keeps the status around so callers can tell "sign in first" (401/403)
+    // apart from other failures.
+    static requestError(response) {
+        const error = new Error(`Request failed (${response.status})`);
+        error.status = response.status;
+        return error;
+    }
+
     async sendJson(method, path, payload) {
         const response = await fetch(`${this.baseUrl}${path}`, {
             method,
@@ -591,7 +599,7 @@ class ApiClient {
             body: JSON.stringify(payload)
         });
         if (!response.ok) {
-            throw new Error(`Request failed (${response.status})`);
+            throw ApiClient.requestError(response);
         }
         return response.json();
     }
@@ -657,6 +665,102 @@ class ApiClient {
     fetchDownloadStatus(jobId) {
         return this.fetchJson(`/downloads/${encodeURIComponent(jobId)}`);
     }
+
+    fetchProfiles() {
+        return this.fetchJson('/profiles');
+    }
+
+    fetchSession() {
+        return this.fetchJson('/session');
+    }
+
+    signIn(profileId, password) {
+        return this.postJson('/session', {
+            profileId,
+            password
+        });
+    }
+
+    async signOut() {
+        const response = await fetch(`${this.baseUrl}/session`, { method: 'DELETE' });
+        if (!response.ok) {
+            throw ApiClient.requestError(response);
+        }
+    }
+}
+
+// Minimal profile picker shown when the backend asks for a session
+class SignInDialog {
+    constructor(api) {
+        this.api = api;
+    }
+
+    // Resolves with the signed-in profile, or null when the user cancels
+    async open() {
+        const profiles = await this.api.fetchProfiles();
+        return new Promise((resolve) => {
+            const overlay = document.createElement('div');
+            overlay.className = 'signin-overlay';
+            overlay.innerHTML = `
+                <form class="signin-dialog" role="dialog" aria-label="Sign in">
+                    <h2>Sign in</h2>
+                    <label class="signin-field">
+                        <span>Profile</span>
+                        <select name="profile"></select>
+                    </label>
+                    <label class="signin-field signin-password">
+                        <span>Password</span>
+                        <input type="password" name="password" autocomplete="current-password" />
+                    </label>
+                    <p class="signin-error" role="alert"></p>
+                    <div class="signin-actions">
+                        <button type="button" class="signin-cancel">Cancel</button>
+                        <button type="submit" class="signin-submit">Sign in</button>
+                    </div>
+                </form>
+            `;
+
+            const form = overlay.querySelector('form');
+            const select = overlay.querySelector('select');
+            const passwordField = overlay.querySelector('.signin-password');
+            const passwordInput = overlay.querySelector('input[name="password"]');
+            const errorEl = overlay.querySelector('.signin-error');
+
+            profiles.forEach((profile) => {
+                const option = document.createElement('option');
+                option.value = String(profile.id);
+                option.textContent = profile.isAdmin ? `${profile.name} (admin)` : profile.name;
+                select.appendChild(option);
+            });
+
+            const selected = () => profiles.find((profile) => String(profile.id) === select.value);
+            const syncPassword = () => {
+                passwordField.hidden = !selected()?.hasPassword;
+                passwordInput.value = '';
+            };
+            select.addEventListener('change', syncPassword);
+            syncPassword();
+
+            const finish = (profile) => {
+                overlay.remove();
+                resolve(profile);
+            };
+            overlay.querySelector('.signin-cancel').addEventListener('click', () => finish(null));
+            form.addEventListener('submit', async (event) => {
+                event.preventDefault();
+                errorEl.textContent = '';
+                try {
+                    const profile = await this.api.signIn(Number(select.value), passwordInput.value);
+                    finish(profile);
+                } catch (error) {
+                    errorEl.textContent =
+                        error.status === 401 ? 'Wrong password.' : `Sign in failed: ${error.message}`;
+                }
+            });
+
+            document.body.appendChild(overlay);
+        });
+    }
 }
 
 // Global App Router
@@ -689,6 +793,7 @@ class App {
         this.currentPage = null;
         this.currentPageInstance = null;
         this.database = new DatabaseManager();
+        this.signInDialog = new SignInDialog(this.database.api);
         this.databaseReady = this.database.init().catch((error) => {
             console.error('❌ Failed to initialize database:', error);
             return null;
@@ -798,9 +903,9 @@ class App {
                 getCommentReplies: (commentId) => this.database.getCommentReplies(commentId),
                 getSettings: () => this.database.getSettings(),
                 startVideoDownload: (videoId, mediaKind) =>
-                    this.database.startVideoDownload(videoId, mediaKind),
+                    this.withSession(() => this.database.startVideoDownload(videoId, mediaKind)),
                 startChannelDownload: (videoId, mediaKind) =>
-                    this.database.startChannelDownload(videoId, mediaKind),
+                    this.withSession(() => this.database.startChannelDownload(videoId, mediaKind)),
                 getDownloadStatus: (jobId) => this.database.getDownloadStatus(jobId)
             };
         }
@@ -809,7 +914,10 @@ class App {
             return {
                 ready: () => this.databaseReady,
                 getSettings: () => this.database.getSettings(),
-                updateSettings: (settings) => this.database.updateSettings(settings)
+                updateSettings: (settings) =>
+                    this.withSession(() => this.database.updateSettings(settings)),
+                getSession: () => this.database.api.fetchSession().catch(() => null),
+                switchProfile: () => this.signInDialog.open()
             };
         }
 
@@ -817,6 +925,23 @@ class App {
             ready: () => Promise.resolve()
         };
     }
+
+    // Runs an API call and, when the backend wants a (different) profile,
+    // asks the user to sign in and retries once
+    async withSession(action) {
+        try {
+            return await action();
+        } catch (error) {
+            if (error.status !== 401 && error.status !== 403) {
+                throw error;
+            }
+            const profile = await this.signInDialog.open();
+            if (!profile) {
+                throw error;
+            }
+            return action();
+        }
+    }
 }
 
 if (typeof document !== 'undefined' && typeof window !== 'undefined' && !window.__NEWTUBE_TEST__) {
@@ -857,5 +982,5 @@ if (typeof document !== 'undefined' && typeof window !== 'undefined' && !window.
 }
 
 if (typeof module !== 'undefined' && module.exports) {
-    module.exports = { DatabaseManager, ApiClient, App };
+    module.exports = { DatabaseManager, ApiClient, SignInDialog, App };
 }
@@ -4,7 +4,9 @@ class AdminPage {
             {
                 ready: () => Promise.resolve(),
                 getSettings: () => Promise.resolve(null),
-                updateSettings: () => Promise.resolve(null)
+                updateSettings: () => Promise.resolve(null),
+                getSession: () => Promise.resolve(null),
+                switchProfile: () => Promise.resolve(null)
             },
             services || {}
         );
@@ -12,6 +14,7 @@ class AdminPage {
         this.statusEl = null;
         this.saveBtn = null;
         this.form = null;
+        this.sessionEl = null;
     }
 
     async init() {
@@ -26,7 +29,7 @@ class AdminPage {
             // Ignore readiness issues for admin.
         }
 
-        await this.loadSettings();
+        await Promise.all([this.loadSettings(), this.loadSession()]);
     }
 
     render() {
@@ -65,11 +68,15 @@ class AdminPage {
                     </div>
                 </section>
                 <section class="admin-card admin-note">
-                    <h2>Security</h2>
-                    <p>
-                        This admin page does not require authentication. Restrict access to <code>/admin</code>
-                        with your reverse proxy if the instance is public.
+                    <h2>Profile</h2>
+                    <p class="admin-session"></p>
+                    <p class="admin-help">
+                        Saving settings needs an admin profile. While the instance has a single profile
+                        without a password, everyone acts as that profile.
                     </p>
+                    <div class="admin-actions">
+                        <button class="admin-switch" type="button">Switch profile</button>
+                    </div>
                 </section>
             </main>
         `;
@@ -77,10 +84,18 @@ class AdminPage {
         this.statusEl = wrapper.querySelector('.admin-status');
         this.saveBtn = wrapper.querySelector('.admin-save');
         this.form = wrapper.querySelector('.admin-options');
+        this.sessionEl = wrapper.querySelector('.admin-session');
 
         if (this.saveBtn) {
             this.saveBtn.addEventListener('click', () => this.handleSave());
         }
+        const switchBtn = wrapper.querySelector('.admin-switch');
+        if (switchBtn) {
+            switchBtn.addEventListener('click', async () => {
+                await this.services.switchProfile();
+                await this.loadSession();
+            });
+        }
 
         return wrapper;
     }
@@ -106,6 +121,19 @@ class AdminPage {
         }
     }
 
+    async loadSession() {
+        const profile = await this.services.getSession();
+        if (!this.sessionEl) {
+            return;
+        }
+        if (!profile) {
+            this.sessionEl.textContent = 'Not signed in.';
+        } else {
+            const role = profile.isAdmin ? 'admin' : 'not an admin';
+            this.sessionEl.textContent = `Signed in as ${profile.name} (${role}).`;
+        }
+    }
+
     async handleSave() {
         if (!this.services || typeof this.services.updateSettings !== 'function') {
             this.setStatus('Settings API unavailable.', true);
@@ -123,6 +151,7 @@ class AdminPage {
             });
             const effective = updated?.missingMediaBehavior || missingMediaBehavior;
             this.setStatus(`Saved. Missing media behavior is now ${effective.replace('_', ' ')}.`);
+            await this.loadSession();
         } catch (error) {
             this.setStatus(`Failed to save settings: ${error.message}`, true);
         } finally {
@@ -5,6 +5,7 @@ class BaseViewer {
         this.container = null;
         this.videoData = null;
         this.downloadTimer = null;
+        this.downloadEvents = null;
     }
 
     async init() {
@@ -20,6 +21,10 @@ class BaseViewer {
             clearTimeout(this.downloadTimer);
             this.downloadTimer = null;
         }
+        if (this.downloadEvents) {
+            this.downloadEvents.close();
+            this.downloadEvents = null;
+        }
         if (this.container) {
             this.container.innerHTML = '';
         }
@@ -261,6 +266,29 @@ class BaseViewer {
             }
         };
 
+        // Returns true once the job has finished either way.
+        const showStatus = (status) => {
+            if (status && typeof status.progress === 'number') {
+                updateProgress(status.progress, status.message || 'Downloading...');
+            } else {
+                updateProgress(null, status?.message || 'Downloading...', true);
+            }
+
+            if (status?.status === 'completed') {
+                updateProgress(100, 'Download complete. Reloading...');
+                setTimeout(() => window.location.reload(), 1200);
+                return true;
+            }
+
+            if (status?.status === 'failed') {
+                updateProgress(null, status.message || 'Download failed.', true);
+                setBusy(false);
+                return true;
+            }
+
+            return false;
+        };
+
         const pollStatus = async (jobId) => {
             if (!this.services || typeof this.services.getDownloadStatus !== 'function') {
                 updateProgress(null, 'Download status unavailable.', true);
@@ -270,21 +298,7 @@ class BaseViewer {
 
             try {
                 const status = await this.services.getDownloadStatus(jobId);
-                if (status && typeof status.progress === 'number') {
-                    updateProgress(status.progress, status.message || 'Downloading...');
-                } else {
-                    updateProgress(null, status?.message || 'Downloading...', true);
-                }
-
-                if (status?.status === 'completed') {
-                    updateProgress(100, 'Download complete. Reloading...');
-                    setTimeout(() => window.location.reload(), 1200);
-                    return;
-                }
-
-                if (status?.status === 'failed') {
-                    updateProgress(null, status.message || 'Download failed.', true);
-                    setBusy(false);
+                if (showStatus(status)) {
                     return;
                 }
             } catch (error) {
@@ -296,6 +310,55 @@ class BaseViewer {
             this.downloadTimer = setTimeout(() => pollStatus(jobId), 2000);
         };
 
+        // Follows the job over /api/events; polling is the fallback when
+        // EventSource is missing or the stream cannot be opened.
+        const followStatus = (jobId) => {
+            if (
+                typeof window.EventSource !== 'function' ||
+                typeof this.services?.getDownloadStatus !== 'function'
+            ) {
+                pollStatus(jobId);
+                return;
+            }
+
+            const source = new window.EventSource('/api/events');
+            this.downloadEvents = source;
+            const stop = () => {
+                source.close();
+                if (this.downloadEvents === source) {
+                    this.downloadEvents = null;
+                }
+            };
+
+            source.addEventListener('download', (event) => {
+                let status = null;
+                try {
+                    status = JSON.parse(event.data);
+                } catch {
+                    return;
+                }
+                if (status?.id === jobId && showStatus(status)) {
+                    stop();
+                }
+            });
+            // Events sent before the stream opened are missed, so fetch the
+            // current state once.
+            source.onopen = () => {
+                this.services
+                    .getDownloadStatus(jobId)
+                    .then((status) => {
+                        if (showStatus(status)) {
+                            stop();
+                        }
+                    })
+                    .catch(() => {});
+            };
+            source.onerror = () => {
+                stop();
+                pollStatus(jobId);
+            };
+        };
+
         const startDownload = async (mode) => {
             if (!this.services) {
                 updateProgress(null, 'Download service unavailable.', true);
@@ -315,7 +378,7 @@ class BaseViewer {
                     throw new Error('No download job id returned');
                 }
 
-                pollStatus(response.id);
+                followStatus(response.id);
             } catch (error) {
                 updateProgress(null, `Failed to start download: ${error.message}`, true);
                 setBusy(false);
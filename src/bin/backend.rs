@@ -11,20 +11,26 @@ use std::{
     fs,
     net::{IpAddr, SocketAddr},
     path::{Component, Path, PathBuf},
+    pin::Pin,
     sync::{
         Arc,
         atomic::{AtomicUsize, Ordering},
     },
+    task::{self, Poll},
+    time::{Duration, Instant, SystemTime},
 };
 
 use anyhow::{Context, Result, anyhow, bail};
 use axum::{
     Json, Router,
     body::Body,
-    extract::{Path as AxumPath, State},
-    http::{HeaderMap, Request, StatusCode, header},
-    response::{IntoResponse, Response},
-    routing::{get, post},
+    extract::{FromRequestParts, Path as AxumPath, Query, State},
+    http::{HeaderMap, Request, StatusCode, header, request::Parts},
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
+    routing::{delete, get, post, put},
 };
 use mime_guess::{MimeGuess, mime::Mime};
 use newtube_tools::config::{
@@ -34,20 +40,37 @@ use newtube_tools::config::{
     resolve_runtime_paths,
     upsert_env_value,
 };
+use newtube_tools::dash;
+use newtube_tools::hls::{self, HlsVariant};
+#[cfg(test)]
+use newtube_tools::metadata::SubtitleTrack;
 use newtube_tools::metadata::{
-    CommentRecord, MetadataReader, SubtitleCollection, VideoRecord, VideoSource,
+    AdaptiveKind, AdaptiveStream, Availability, BlockedMedia, ChangeSet, ChannelRecord,
+    ChannelStatsPoint, Chapter, ChecksumStatus, CommentRecord, DEFAULT_READER_POOL_SIZE,
+    InvalidCursor, MediaListQuery, MediaPage, MediaSort, MetadataReader, MetadataStore,
+    PlaylistEntry, PlaylistRecord, SearchHit, SearchOptions, SkipSegment, SourceChecksum,
+    SubtitleCollection, TranscriptHit, VideoRecord, VideoSource, VideoStatsSnapshot,
 };
-#[cfg(test)]
-use newtube_tools::metadata::{MetadataStore, SubtitleTrack};
+use newtube_tools::scrub::{ScrubOptions, ScrubReport, scrub_library};
 use newtube_tools::security::ensure_not_root;
+use newtube_tools::transcript::{Transcript, subtitle_extension_rank};
+use newtube_tools::userdata::{
+    PlayStats, Profile, Reaction, SavedVideo, Session, Subscription, USER_DATA_DB_FILE,
+    UserDataImport, UserDataStore, UserPlaylist, UserSnapshot, WatchEntry,
+};
 use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
 #[cfg(test)]
 use serde_json::json;
 use tokio::{
     fs::File,
-    io::{AsyncReadExt, AsyncSeekExt},
+    io::{AsyncRead, AsyncReadExt, AsyncSeekExt, ReadBuf},
     signal,
+    sync::broadcast,
+};
+use tokio_stream::{
+    Stream, StreamExt,
+    wrappers::{BroadcastStream, errors::BroadcastStreamRecvError},
 };
 use tokio_util::io::ReaderStream;
 
@@ -57,6 +80,9 @@ const VIDEOS_SUBDIR: &str = "videos";
 const SHORTS_SUBDIR: &str = "shorts";
 const THUMBNAILS_SUBDIR: &str = "thumbnails";
 const SUBTITLES_SUBDIR: &str = "subtitles";
+const CHANNELS_SUBDIR: &str = "channels";
+const COMMENTS_SUBDIR: &str = "comments";
+const ARCHIVE_FILE: &str = "download-archive.txt";
 
 // SQLite database file relative to the media root.
 const METADATA_DB_FILE: &str = "metadata.db";
@@ -261,14 +287,20 @@ struct DownloadManagerInner {
     media_root: PathBuf,
     www_root: PathBuf,
     downloader: Option<PathBuf>,
+    events: EventBus,
 }
 
+/// `progress` mirrors the job's progress file. Only the job's own watcher
+/// reads the file, so status requests and event subscribers are served from
+/// memory.
 #[derive(Clone)]
 struct DownloadJob {
     id: String,
     status: DownloadStatus,
     progress_file: PathBuf,
     message: String,
+    progress: Option<ProgressReport>,
+    progress_modified: Option<SystemTime>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -296,7 +328,7 @@ struct DownloadJobResponse {
     id: String,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 struct DownloadJobStatus {
     id: String,
@@ -319,7 +351,7 @@ struct DownloadChannelRequest {
     media_kind: Option<String>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct ProgressReport {
     progress: u8,
@@ -327,7 +359,7 @@ struct ProgressReport {
 }
 
 impl DownloadManager {
-    fn new(media_root: PathBuf, www_root: PathBuf) -> Self {
+    fn new(media_root: PathBuf, www_root: PathBuf, events: EventBus) -> Self {
         let downloader = find_download_channel_executable().ok();
         Self {
             inner: Arc::new(DownloadManagerInner {
@@ -336,10 +368,32 @@ impl DownloadManager {
                 media_root,
                 www_root,
                 downloader,
+                events,
             }),
         }
     }
 
+    /// Registers a queued job whose progress file was just initialised with
+    /// `message`, and starts watching that file.
+    fn add_job(&self, job_id: &str, progress_file: &Path, message: &str) {
+        self.inner.jobs.lock().insert(
+            job_id.to_owned(),
+            DownloadJob {
+                id: job_id.to_owned(),
+                status: DownloadStatus::Queued,
+                progress_file: progress_file.to_path_buf(),
+                message: "Queued".to_string(),
+                progress: Some(ProgressReport {
+                    progress: 0,
+                    message: message.to_string(),
+                }),
+                progress_modified: None,
+            },
+        );
+        publish_job_status(&self.inner, job_id);
+        watch_job_progress(self.inner.clone(), job_id.to_owned());
+    }
+
     fn start_video_download(&self, video_id: String, media_kind: MediaCategory) -> Result<String> {
         let downloader = self
             .inner
@@ -349,16 +403,7 @@ impl DownloadManager {
         let job_id = self.next_job_id();
         let progress_file = self.progress_file_path(&job_id);
         write_progress_report(&progress_file, 0, "Queued download");
-
-        self.inner.jobs.lock().insert(
-            job_id.clone(),
-            DownloadJob {
-                id: job_id.clone(),
-                status: DownloadStatus::Queued,
-                progress_file: progress_file.clone(),
-                message: "Queued".to_string(),
-            },
-        );
+        self.add_job(&job_id, &progress_file, "Queued download");
 
         let inner = self.inner.clone();
         let job_id_clone = job_id.clone();
@@ -424,16 +469,7 @@ impl DownloadManager {
         let job_id = self.next_job_id();
         let progress_file = self.progress_file_path(&job_id);
         write_progress_report(&progress_file, 0, "Resolving channel");
-
-        self.inner.jobs.lock().insert(
-            job_id.clone(),
-            DownloadJob {
-                id: job_id.clone(),
-                status: DownloadStatus::Queued,
-                progress_file: progress_file.clone(),
-                message: "Queued".to_string(),
-            },
-        );
+        self.add_job(&job_id, &progress_file, "Resolving channel");
 
         let inner = self.inner.clone();
         let job_id_clone = job_id.clone();
@@ -519,19 +555,7 @@ impl DownloadManager {
     }
 
     fn get_status(&self, job_id: &str) -> Option<DownloadJobStatus> {
-        let job = self.inner.jobs.lock().get(job_id).cloned()?;
-        let progress = read_progress_report(&job.progress_file);
-
-        let (progress_value, message) = progress
-            .map(|report| (report.progress, report.message))
-            .unwrap_or((0, job.message.clone()));
-
-        Some(DownloadJobStatus {
-            id: job.id,
-            status: job.status.as_str().to_string(),
-            progress: progress_value,
-            message,
-        })
+        job_status(&self.inner, job_id)
     }
 
     fn next_job_id(&self) -> String {
@@ -547,20 +571,434 @@ impl DownloadManager {
     }
 }
 
+/// Runs at most one checksum scrub at a time, either on demand from the
+/// admin API or on the `NEWTUBE_SCRUB_INTERVAL_HOURS` schedule, and keeps the
+/// outcome of the last pass.
+#[derive(Clone, Default)]
+struct ScrubManager {
+    status: Arc<Mutex<ScrubStatus>>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+struct ScrubStatus {
+    running: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    started_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_report: Option<ScrubReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ScrubManager {
+    fn status(&self) -> ScrubStatus {
+        self.status.lock().clone()
+    }
+
+    /// Starts a pass in the background. Returns `false` when one is already
+    /// running.
+    fn start(
+        &self,
+        store: Arc<MetadataStore>,
+        reader: Arc<MetadataReader>,
+        options: ScrubOptions,
+    ) -> bool {
+        {
+            let mut status = self.status.lock();
+            if status.running {
+                return false;
+            }
+            status.running = true;
+            status.started_at = Some(now_rfc3339());
+            status.finished_at = None;
+        }
+
+        let manager = self.clone();
+        tokio::spawn(async move {
+            let outcome = scrub_library(&store, &reader, &options).await;
+            let mut status = manager.status.lock();
+            status.running = false;
+            status.finished_at = Some(now_rfc3339());
+            match outcome {
+                Ok(report) => {
+                    if report.has_problems() {
+                        eprintln!(
+                            "Scrub found {} mismatched and {} unreadable files",
+                            report.mismatched.len(),
+                            report.missing.len()
+                        );
+                    }
+                    status.last_report = Some(report);
+                    status.error = None;
+                }
+                Err(err) => {
+                    eprintln!("Scrub failed: {err:#}");
+                    status.error = Some(err.to_string());
+                }
+            }
+        });
+        true
+    }
+
+    /// Re-hashes files not verified within the last `interval`, once per
+    /// `interval`, for as long as the server runs.
+    fn schedule(
+        &self,
+        store: Arc<MetadataStore>,
+        reader: Arc<MetadataReader>,
+        interval: std::time::Duration,
+    ) {
+        let manager = self.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let cutoff =
+                    chrono::Utc::now() - chrono::Duration::from_std(interval).unwrap_or_default();
+                let options = ScrubOptions {
+                    verified_before: Some(
+                        cutoff.to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+                    ),
+                    limit: None,
+                };
+                manager.start(store.clone(), reader.clone(), options);
+            }
+        });
+    }
+}
+
+/// How often running downloads' progress files and `data_version` are
+/// checked for changes to push to `/api/events`.
+const PROGRESS_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const LIBRARY_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Events a slow subscriber may fall behind by before it skips ahead.
+const EVENT_BUFFER: usize = 256;
+const EVENT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// Pushed to `/api/events` subscribers.
+#[derive(Clone, Debug)]
+enum ServerEvent {
+    /// A download job changed state or reported progress.
+    Download(DownloadJobStatus),
+    /// `metadata.db` was written to.
+    Library(LibraryChange),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LibraryChange {
+    data_version: i64,
+    /// Latest change log position; clients catch up through
+    /// `/api/changes?since=`.
+    seq: i64,
+}
+
+impl ServerEvent {
+    fn to_sse(&self) -> Result<Event, axum::Error> {
+        match self {
+            Self::Download(status) => Event::default().event("download").json_data(status),
+            Self::Library(change) => Event::default().event("library").json_data(change),
+        }
+    }
+}
+
+/// Fan-out for server events. Publishing never blocks; without subscribers
+/// events are dropped.
+#[derive(Clone)]
+struct EventBus {
+    sender: broadcast::Sender<ServerEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_BUFFER).0,
+        }
+    }
+}
+
+impl EventBus {
+    fn publish(&self, event: ServerEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    fn subscribe(&self) -> broadcast::Receiver<ServerEvent> {
+        self.sender.subscribe()
+    }
+
+    fn has_subscribers(&self) -> bool {
+        self.sender.receiver_count() > 0
+    }
+
+    /// Publishes a `library` event whenever `data_version` moves. The check
+    /// only runs while someone is listening; the first check after that
+    /// sets the baseline.
+    fn watch_library(&self, reader: Arc<MetadataReader>) {
+        let events = self.clone();
+        tokio::spawn(async move {
+            let mut last = None;
+            loop {
+                tokio::time::sleep(LIBRARY_POLL_INTERVAL).await;
+                if !events.has_subscribers() {
+                    last = None;
+                    continue;
+                }
+                match library_change(&reader, last).await {
+                    Ok((version, change)) => {
+                        last = Some(version);
+                        if let Some(change) = change {
+                            events.publish(ServerEvent::Library(change));
+                        }
+                    }
+                    Err(err) => eprintln!("Failed to check for library changes: {err}"),
+                }
+            }
+        });
+    }
+}
+
+/// Reads `data_version` and returns it, plus the event to publish when it
+/// differs from `last`.
+async fn library_change(
+    reader: &MetadataReader,
+    last: Option<i64>,
+) -> Result<(i64, Option<LibraryChange>)> {
+    let data_version = reader.data_version().await?;
+    if last.is_none_or(|last| last == data_version) {
+        return Ok((data_version, None));
+    }
+    let seq = reader.latest_change_seq().await?;
+    Ok((data_version, Some(LibraryChange { data_version, seq })))
+}
+
+/// A stream session counts as a play once it has sent this share of the
+/// file, or [`PLAY_THRESHOLD_BYTES`] for large files, whichever is smaller.
+const PLAY_THRESHOLD_PERCENT: u64 = 10;
+const PLAY_THRESHOLD_BYTES: u64 = 16 * 1024 * 1024;
+/// Range requests for the same video and client further apart than this
+/// start a new session, so a rewatch later on counts again.
+const PLAY_SESSION_IDLE: Duration = Duration::from_secs(30 * 60);
+/// Long responses report their bytes in chunks of this size while they are
+/// still streaming instead of only when the client hangs up.
+const PLAY_FLUSH_BYTES: u64 = 4 * 1024 * 1024;
+
+/// Groups the range requests a player issues for one video into sessions
+/// and records streamed bytes and plays in `userdata.db`.
+#[derive(Clone)]
+struct PlayTracker {
+    user_data: Arc<UserDataStore>,
+    sessions: Arc<Mutex<HashMap<(String, String), PlaySession>>>,
+}
+
+struct PlaySession {
+    bytes: u64,
+    counted: bool,
+    last_seen: Instant,
+}
+
+impl PlayTracker {
+    fn new(user_data: Arc<UserDataStore>) -> Self {
+        Self {
+            user_data,
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Adds `bytes` to the client's session for `videoid`. Returns `true`
+    /// exactly once per session, when it crosses the play threshold.
+    fn observe(
+        &self,
+        videoid: &str,
+        client: &str,
+        file_size: u64,
+        bytes: u64,
+        now: Instant,
+    ) -> bool {
+        let mut sessions = self.sessions.lock();
+        sessions.retain(|_, session| now.duration_since(session.last_seen) < PLAY_SESSION_IDLE);
+        let session = sessions
+            .entry((videoid.to_owned(), client.to_owned()))
+            .or_insert(PlaySession {
+                bytes: 0,
+                counted: false,
+                last_seen: now,
+            });
+        session.bytes += bytes;
+        session.last_seen = now;
+        if session.counted || session.bytes < play_threshold(file_size) {
+            return false;
+        }
+        session.counted = true;
+        true
+    }
+
+    /// Updates the session and persists the bytes in the background; called
+    /// from the response body, which cannot await.
+    fn record(&self, videoid: &str, client: &str, file_size: u64, bytes: u64) {
+        if bytes == 0 {
+            return;
+        }
+        let counted = self.observe(videoid, client, file_size, bytes, Instant::now());
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let user_data = self.user_data.clone();
+        let videoid = videoid.to_owned();
+        runtime.spawn(async move {
+            if let Err(err) = user_data.record_play(&videoid, bytes, counted).await {
+                eprintln!("Failed to record play for {videoid}: {err}");
+            }
+        });
+    }
+}
+
+fn play_threshold(file_size: u64) -> u64 {
+    (file_size.saturating_mul(PLAY_THRESHOLD_PERCENT) / 100).clamp(1, PLAY_THRESHOLD_BYTES)
+}
+
+/// Identifies the viewer for play sessions. The backend sits behind the
+/// frontend proxy, so the forwarded address is the closest thing to a
+/// client id; the user agent tells apart devices sharing one address.
+fn play_client(headers: &HeaderMap) -> String {
+    let header_value = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+    };
+    let address = header_value("x-forwarded-for")
+        .split(',')
+        .next()
+        .map(str::trim)
+        .filter(|address| !address.is_empty())
+        .unwrap_or_else(|| header_value("x-real-ip").trim());
+    format!("{address}|{}", header_value(header::USER_AGENT.as_str()))
+}
+
+/// Per-variant locks of an [`HlsPackager`], keyed by output directory.
+type HlsLocks = Arc<Mutex<HashMap<PathBuf, Arc<tokio::sync::RwLock<()>>>>>;
+
+/// Packages downloaded formats for HLS on first request.
+///
+/// Each variant directory has a read/write lock while requests use it.
+/// Packaging holds it exclusively, so ffmpeg runs at most once per variant
+/// at a time and never swaps the directory while a request is about to open
+/// a file in it. Requests hold it shared until their file is open; an open
+/// file keeps its contents even if a later packaging replaces it.
+#[derive(Clone)]
+struct HlsPackager {
+    ffmpeg: Arc<PathBuf>,
+    locks: HlsLocks,
+}
+
+impl HlsPackager {
+    fn new(ffmpeg: PathBuf) -> Self {
+        Self {
+            ffmpeg: Arc::new(ffmpeg),
+            locks: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Makes sure `output_dir` holds an up-to-date packaging of `input` and
+    /// returns a guard that keeps it from being repackaged until dropped.
+    async fn ensure(&self, input: &Path, output_dir: &Path) -> Result<HlsReadGuard> {
+        let entry = HlsLockEntry::acquire(&self.locks, output_dir);
+        let read = entry.lock.clone().read_owned().await;
+        if hls::is_packaged(input, output_dir) {
+            return Ok(HlsReadGuard {
+                _read: read,
+                _entry: entry,
+            });
+        }
+        drop(read);
+
+        let write = entry.lock.clone().write_owned().await;
+        // Another request may have packaged it while we waited.
+        if !hls::is_packaged(input, output_dir) {
+            let ffmpeg = self.ffmpeg.clone();
+            let input = input.to_path_buf();
+            let output_dir = output_dir.to_path_buf();
+            tokio::task::spawn_blocking(move || hls::package(&ffmpeg, &input, &output_dir))
+                .await
+                .context("joining ffmpeg task")??;
+        }
+        Ok(HlsReadGuard {
+            _read: write.downgrade(),
+            _entry: entry,
+        })
+    }
+}
+
+/// Shared hold on a packaged variant, returned by [`HlsPackager::ensure`].
+/// Fields drop in order, so the lock is released before the entry is
+/// checked for removal.
+struct HlsReadGuard {
+    _read: tokio::sync::OwnedRwLockReadGuard<()>,
+    _entry: HlsLockEntry,
+}
+
+/// Use of one variant lock. The last user removes it from the map, so the
+/// map only holds variants that are being requested right now.
+struct HlsLockEntry {
+    locks: HlsLocks,
+    output_dir: PathBuf,
+    lock: Arc<tokio::sync::RwLock<()>>,
+}
+
+impl HlsLockEntry {
+    fn acquire(locks: &HlsLocks, output_dir: &Path) -> Self {
+        let lock = locks
+            .lock()
+            .entry(output_dir.to_path_buf())
+            .or_default()
+            .clone();
+        Self {
+            locks: locks.clone(),
+            output_dir: output_dir.to_path_buf(),
+            lock,
+        }
+    }
+}
+
+impl Drop for HlsLockEntry {
+    fn drop(&mut self) {
+        // Clones are only taken under the map lock, so nobody else can be
+        // about to use the lock once the map and this entry are the last two
+        // holders.
+        let mut locks = self.locks.lock();
+        if Arc::strong_count(&self.lock) == 2 {
+            locks.remove(&self.output_dir);
+        }
+    }
+}
+
+fn now_rfc3339() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+}
+
 /// Shared state injected into every Axum handler.
 ///
 /// * `reader` performs blocking SQLite reads via `spawn_blocking`.
 /// * `cache` prevents repeated deserialization for hot endpoints such as the
 ///   homepage feed.
+/// * `store` handles the few writes the API performs (deletes, block list).
 /// * `files` knows where audio/video/subtitle payloads live on disk.
+/// * `hls` remuxes those payloads into HLS segments on demand.
 #[derive(Clone)]
 struct AppState {
     reader: Arc<MetadataReader>,
+    store: Arc<MetadataStore>,
+    user_data: Arc<UserDataStore>,
     cache: Arc<ApiCache>,
     files: Arc<FilePaths>,
     www_root: Arc<PathBuf>,
     settings: Arc<SettingsStore>,
     downloads: DownloadManager,
+    scrub: ScrubManager,
+    plays: PlayTracker,
+    events: EventBus,
+    hls: HlsPackager,
 }
 
 /// Very small in-memory cache to avoid re-querying SQLite on every request.
@@ -568,8 +1006,8 @@ struct AppState {
 /// This keeps the backend stateless enough for systemd restarts yet vastly
 /// reduces IO for repeated playback of the same assets.
 struct ApiCache {
-    videos: RwLock<Option<Vec<VideoRecord>>>,
-    shorts: RwLock<Option<Vec<VideoRecord>>>,
+    videos: RwLock<Option<Arc<Vec<VideoRecord>>>>,
+    shorts: RwLock<Option<Arc<Vec<VideoRecord>>>>,
     video_details: RwLock<HashMap<String, VideoRecord>>,
     short_details: RwLock<HashMap<String, VideoRecord>>,
     comments: RwLock<HashMap<String, Vec<CommentRecord>>>,
@@ -594,7 +1032,7 @@ impl ApiCache {
         }
     }
 
-    fn media_list(&self, category: MediaCategory) -> &RwLock<Option<Vec<VideoRecord>>> {
+    fn media_list(&self, category: MediaCategory) -> &RwLock<Option<Arc<Vec<VideoRecord>>>> {
         match category {
             MediaCategory::Video => &self.videos,
             MediaCategory::Short => &self.shorts,
@@ -625,6 +1063,10 @@ struct FilePaths {
     shorts: PathBuf,
     thumbnails: PathBuf,
     subtitles: PathBuf,
+    channels: PathBuf,
+    comments: PathBuf,
+    hls: PathBuf,
+    archive: PathBuf,
     metadata_db: PathBuf,
 }
 
@@ -636,6 +1078,10 @@ impl FilePaths {
             shorts: media_root.join(SHORTS_SUBDIR),
             thumbnails: media_root.join(THUMBNAILS_SUBDIR),
             subtitles: media_root.join(SUBTITLES_SUBDIR),
+            channels: media_root.join(CHANNELS_SUBDIR),
+            comments: media_root.join(COMMENTS_SUBDIR),
+            hls: media_root.join(hls::HLS_SUBDIR),
+            archive: media_root.join(ARCHIVE_FILE),
             metadata_db: media_root.join(METADATA_DB_FILE),
         }
     }
@@ -657,6 +1103,8 @@ impl FilePaths {
         std::fs::create_dir_all(&paths.shorts).unwrap();
         std::fs::create_dir_all(&paths.thumbnails).unwrap();
         std::fs::create_dir_all(&paths.subtitles).unwrap();
+        std::fs::create_dir_all(&paths.channels).unwrap();
+        std::fs::create_dir_all(&paths.comments).unwrap();
         paths
     }
 }
@@ -676,6 +1124,38 @@ impl ApiError {
         }
     }
 
+    /// Creates a 400 error with the provided message.
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+
+    /// Creates a 401 error with the provided message.
+    fn unauthorized(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::UNAUTHORIZED,
+            message: message.into(),
+        }
+    }
+
+    /// Creates a 403 error with the provided message.
+    fn forbidden(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::FORBIDDEN,
+            message: message.into(),
+        }
+    }
+
+    /// Creates a 409 error with the provided message.
+    fn conflict(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::CONFLICT,
+            message: message.into(),
+        }
+    }
+
     /// Creates a 500 error with the provided message.
     fn internal(message: impl Into<String>) -> Self {
         Self {
@@ -722,23 +1202,53 @@ async fn main() -> Result<()> {
     };
 
     let metadata_path = media_root.join(METADATA_DB_FILE);
-    let reader = MetadataReader::new(&metadata_path)
+    let store = MetadataStore::open(&metadata_path)
         .await
-        .context("initializing metadata reader")?;
-
+        .context("initializing metadata database")?;
     let env_path = Path::new(DEFAULT_ENV_PATH);
     let env_vars = read_env_file(env_path).unwrap_or_default();
+    let reader_pool_size =
+        parse_reader_pool_size(env_or_file_value("NEWTUBE_DB_READERS", &env_vars).as_deref());
+    let reader = MetadataReader::with_pool_size(&metadata_path, reader_pool_size)
+        .await
+        .context("initializing metadata reader")?;
+    // Kept out of metadata.db because that file is served as-is at /metadata.db.
+    let user_data = UserDataStore::open(&media_root.join(USER_DATA_DB_FILE))
+        .await
+        .context("initializing user data database")?;
+
     let settings_defaults = InstanceSettings::from_env(&env_vars);
     let settings_store = Arc::new(SettingsStore::load(env_path, settings_defaults));
-    let downloads = DownloadManager::new(media_root.clone(), www_root.clone());
+    let events = EventBus::default();
+    let downloads = DownloadManager::new(media_root.clone(), www_root.clone(), events.clone());
+    let reader = Arc::new(reader);
+    let store = Arc::new(store);
+    let scrub = ScrubManager::default();
+    if let Some(interval) = parse_scrub_interval(
+        env_or_file_value("NEWTUBE_SCRUB_INTERVAL_HOURS", &env_vars).as_deref(),
+    ) {
+        scrub.schedule(store.clone(), reader.clone(), interval);
+    }
+    events.watch_library(reader.clone());
 
+    let user_data = Arc::new(user_data);
     let state = AppState {
-        reader: Arc::new(reader),
+        reader,
+        store,
+        plays: PlayTracker::new(user_data.clone()),
+        user_data,
+        events,
         cache: Arc::new(ApiCache::new()),
         files: Arc::new(FilePaths::new(&media_root)),
         www_root: Arc::new(www_root),
         settings: settings_store,
         downloads,
+        scrub,
+        hls: HlsPackager::new(
+            env_or_file_value("NEWTUBE_FFMPEG_BIN", &env_vars)
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("ffmpeg")),
+        ),
     };
 
     // Each route is extremely small; helpers supplement anything that is shared
@@ -749,10 +1259,78 @@ async fn main() -> Result<()> {
         .route("/api/downloads/video", post(start_video_download))
         .route("/api/downloads/channel", post(start_channel_download))
         .route("/api/downloads/{id}", get(get_download_status))
+        .route("/api/events", get(server_events))
         .route("/api/bootstrap", get(bootstrap))
+        .route("/api/changes", get(list_changes))
+        .route("/api/search", get(search_media))
+        .route("/api/search/transcripts", get(search_transcripts))
+        .route("/api/blocked", get(list_blocked_media))
+        .route("/api/blocked/{id}", delete(unblock_media))
+        .route("/api/scrub", get(get_scrub_status).post(start_scrub))
+        .route("/api/scrub/checksums", get(list_source_checksums))
+        .route("/api/channels", get(list_channels))
+        .route("/api/channels/{id}", get(get_channel))
+        .route("/api/channels/{id}/videos", get(list_channel_videos))
+        .route("/api/channels/{id}/shorts", get(list_channel_shorts))
+        .route("/api/channels/{id}/stats", get(get_channel_stats))
+        .route(
+            "/api/channels/{id}/images/{kind}",
+            get(download_channel_image),
+        )
+        .route("/api/profiles", get(list_profiles).post(create_profile))
+        .route("/api/profiles/{id}", delete(delete_profile))
+        .route("/api/profiles/{id}/password", put(set_profile_password))
+        .route("/api/session", get(get_session).post(login).delete(logout))
+        .route("/api/me", get(get_user_data))
+        .route("/api/me/sync", post(sync_user_data))
+        .route(
+            "/api/me/settings",
+            get(get_user_settings).put(update_user_settings),
+        )
+        .route("/api/me/likes", get(list_likes))
+        .route("/api/me/likes/{id}", put(put_like).delete(delete_like))
+        .route("/api/me/dislikes", get(list_dislikes))
+        .route(
+            "/api/me/dislikes/{id}",
+            put(put_dislike).delete(delete_dislike),
+        )
+        .route("/api/me/playlists", get(list_user_playlists))
+        .route(
+            "/api/me/playlists/{id}",
+            get(get_user_playlist)
+                .put(put_user_playlist)
+                .delete(delete_user_playlist),
+        )
+        .route("/api/me/playlists/{id}/items", post(add_user_playlist_item))
+        .route(
+            "/api/me/playlists/{id}/items/{videoid}",
+            delete(delete_user_playlist_item),
+        )
+        .route("/api/me/subscriptions", get(list_subscriptions))
+        .route(
+            "/api/me/subscriptions/{id}",
+            put(put_subscription).delete(delete_subscription),
+        )
+        .route("/api/me/history", get(list_history))
+        .route(
+            "/api/me/history/{id}",
+            put(put_history_entry).delete(delete_history_entry),
+        )
+        .route("/api/plays", get(list_most_played))
+        .route("/api/playlists", get(list_playlists))
+        .route("/api/playlists/{id}", get(get_playlist))
         .route("/api/videos", get(list_videos))
-        .route("/api/videos/{id}", get(get_video))
+        .route("/api/videos/{id}", get(get_video).delete(delete_video))
+        .route("/api/videos/{id}/chapters", get(get_video_chapters))
+        .route("/api/videos/{id}/chapters.vtt", get(get_video_chapters_vtt))
+        .route("/api/videos/{id}/transcript", get(get_video_transcript))
+        .route(
+            "/api/videos/{id}/segments",
+            get(get_video_segments).post(set_video_segments),
+        )
         .route("/api/videos/{id}/comments", get(get_video_comments))
+        .route("/api/videos/{id}/stats", get(get_video_stats))
+        .route("/api/videos/{id}/plays", get(get_video_plays))
         .route("/api/videos/{id}/subtitles", get(list_video_subtitles))
         .route(
             "/api/videos/{id}/subtitles/{code}",
@@ -763,9 +1341,25 @@ async fn main() -> Result<()> {
             get(download_video_thumbnail),
         )
         .route("/api/videos/{id}/streams/{format}", get(stream_video_file))
+        .route("/api/videos/{id}/hls/master.m3u8", get(video_hls_master))
+        .route("/api/videos/{id}/hls/{format}/{file}", get(video_hls_file))
+        .route(
+            "/api/videos/{id}/dash/manifest.mpd",
+            get(video_dash_manifest),
+        )
+        .route("/api/videos/{id}/dash/{format}", get(stream_video_adaptive))
         .route("/api/shorts", get(list_shorts))
-        .route("/api/shorts/{id}", get(get_short))
+        .route("/api/shorts/{id}", get(get_short).delete(delete_short))
+        .route("/api/shorts/{id}/chapters", get(get_short_chapters))
+        .route("/api/shorts/{id}/chapters.vtt", get(get_short_chapters_vtt))
+        .route("/api/shorts/{id}/transcript", get(get_short_transcript))
+        .route(
+            "/api/shorts/{id}/segments",
+            get(get_short_segments).post(set_short_segments),
+        )
         .route("/api/shorts/{id}/comments", get(get_video_comments))
+        .route("/api/shorts/{id}/stats", get(get_short_stats))
+        .route("/api/shorts/{id}/plays", get(get_short_plays))
         .route("/api/shorts/{id}/subtitles", get(list_short_subtitles))
         .route(
             "/api/shorts/{id}/subtitles/{code}",
@@ -776,6 +1370,13 @@ async fn main() -> Result<()> {
             get(download_short_thumbnail),
         )
         .route("/api/shorts/{id}/streams/{format}", get(stream_short_file))
+        .route("/api/shorts/{id}/hls/master.m3u8", get(short_hls_master))
+        .route("/api/shorts/{id}/hls/{format}/{file}", get(short_hls_file))
+        .route(
+            "/api/shorts/{id}/dash/manifest.mpd",
+            get(short_dash_manifest),
+        )
+        .route("/api/shorts/{id}/dash/{format}", get(stream_short_adaptive))
         .fallback(static_fallback)
         .with_state(state);
 
@@ -807,22 +1408,31 @@ async fn static_fallback(State(state): State<AppState>, req: Request<Body>) -> R
         return ApiError::not_found("endpoint not found").into_response();
     }
 
-    match serve_www_path(&state.www_root, path).await {
+    match serve_www_path(&state.www_root, path, req.headers()).await {
         Ok(response) => response,
         Err(err) => err.into_response(),
     }
 }
 
-async fn get_metadata_db(State(state): State<AppState>) -> ApiResult<Response> {
-    stream_file(state.files.metadata_db.clone(), None, None).await
+async fn get_metadata_db(State(state): State<AppState>, headers: HeaderMap) -> ApiResult<Response> {
+    stream_file(
+        state.files.metadata_db.clone(),
+        None,
+        Some(&headers),
+        CachePolicy::Revalidate,
+    )
+    .await
 }
 
 async fn get_settings(State(state): State<AppState>) -> ApiResult<Json<InstanceSettings>> {
     Ok(Json(state.settings.get()))
 }
 
+/// Instance-wide settings are shared by every profile, so only admins may
+/// change them.
 async fn update_settings(
     State(state): State<AppState>,
+    _admin: AdminProfile,
     Json(payload): Json<InstanceSettings>,
 ) -> ApiResult<Json<InstanceSettings>> {
     let updated = state
@@ -832,8 +1442,11 @@ async fn update_settings(
     Ok(Json(updated))
 }
 
+/// Open to every profile: the viewer offers these downloads for missing
+/// media, subject to the instance's missing-media setting.
 async fn start_video_download(
     State(state): State<AppState>,
+    _profile: CurrentProfile,
     Json(payload): Json<DownloadVideoRequest>,
 ) -> ApiResult<Json<DownloadJobResponse>> {
     let kind = parse_media_kind(payload.media_kind.as_deref());
@@ -846,6 +1459,7 @@ async fn start_video_download(
 
 async fn start_channel_download(
     State(state): State<AppState>,
+    _profile: CurrentProfile,
     Json(payload): Json<DownloadChannelRequest>,
 ) -> ApiResult<Json<DownloadJobResponse>> {
     let kind = parse_media_kind(payload.media_kind.as_deref());
@@ -867,20 +1481,48 @@ async fn get_download_status(
     Ok(Json(status))
 }
 
-async fn serve_www_path(root: &Path, request_path: &str) -> ApiResult<Response> {
+/// Server-sent events: `download` carries a job's status whenever it changes
+/// state or reports progress, `library` fires when `metadata.db` changes.
+/// Replaces polling `/api/downloads/{id}` and re-fetching the bootstrap.
+async fn server_events(State(state): State<AppState>) -> Response {
+    let mut response = Sse::new(event_stream(state.events.subscribe()))
+        .keep_alive(KeepAlive::new().interval(EVENT_KEEP_ALIVE))
+        .into_response();
+    // Tell nginx not to buffer the stream.
+    response
+        .headers_mut()
+        .insert("x-accel-buffering", "no".parse().unwrap());
+    response
+}
+
+/// A subscriber that fell behind skips the events it missed; job and
+/// library events carry full state, so the next one catches it up.
+fn event_stream(
+    receiver: broadcast::Receiver<ServerEvent>,
+) -> impl Stream<Item = Result<Event, axum::Error>> {
+    BroadcastStream::new(receiver).filter_map(|event| match event {
+        Ok(event) => Some(event.to_sse()),
+        Err(BroadcastStreamRecvError::Lagged(_)) => None,
+    })
+}
+
+/// SPA files keep their names across releases, so clients revalidate them
+/// on every load instead of caching them blindly.
+async fn serve_www_path(
+    root: &Path,
+    request_path: &str,
+    headers: &HeaderMap,
+) -> ApiResult<Response> {
     let target = resolve_www_path(root, request_path)?;
     let metadata = tokio::fs::metadata(&target).await;
+    let serve = |path: PathBuf| stream_file(path, None, Some(headers), CachePolicy::Revalidate);
 
     match metadata {
-        Ok(meta) if meta.is_dir() => {
-            let index = root.join("index.html");
-            stream_file(index, None, None).await
-        }
-        Ok(_) => stream_file(target, None, None).await,
+        Ok(meta) if meta.is_dir() => serve(root.join("index.html")).await,
+        Ok(_) => serve(target).await,
         Err(_) => {
             if should_fallback_to_index(request_path) {
-                let index = root.join("index.html");
-                stream_file(index, None, None).await
+                serve(root.join("index.html")).await
             } else {
                 Err(ApiError::not_found("file not found"))
             }
@@ -918,1238 +1560,5025 @@ async fn bootstrap(State(state): State<AppState>) -> ApiResult<Json<BootstrapPay
     Ok(Json((*payload).clone()))
 }
 
-async fn list_videos(State(state): State<AppState>) -> ApiResult<Json<Vec<VideoRecord>>> {
-    let videos = state.get_media_list(MediaCategory::Video).await?;
-    Ok(Json(sanitize_video_records(&videos)))
+/// Query string accepted by `/api/changes`.
+#[derive(Debug, Default, Deserialize)]
+struct ChangesParams {
+    since: Option<i64>,
+    limit: Option<u32>,
 }
 
-async fn list_shorts(State(state): State<AppState>) -> ApiResult<Json<Vec<VideoRecord>>> {
-    let shorts = state.get_media_list(MediaCategory::Short).await?;
-    Ok(Json(sanitize_video_records(&shorts)))
+/// Incremental alternative to `/api/bootstrap`: returns the rows changed
+/// after `since` (the `seq` of the bootstrap or of the previous call) plus
+/// tombstones for deleted ones. Clients keep calling while `has_more` is
+/// set and start over from `/api/bootstrap` when `reset` is.
+async fn list_changes(
+    State(state): State<AppState>,
+    Query(params): Query<ChangesParams>,
+) -> ApiResult<Json<ChangeSet>> {
+    let since = params.since.unwrap_or(0);
+    if since < 0 {
+        return Err(ApiError::bad_request("since must not be negative"));
+    }
+    let mut changes = state
+        .reader
+        .changes_since(since, params.limit)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    changes.videos = sanitize_video_records(&changes.videos);
+    changes.shorts = sanitize_video_records(&changes.shorts);
+    changes.subtitles = std::mem::take(&mut changes.subtitles)
+        .into_iter()
+        .map(sanitize_subtitle_collection)
+        .collect();
+    Ok(Json(changes))
 }
 
-async fn get_video(
-    State(state): State<AppState>,
-    AxumPath(id): AxumPath<String>,
-) -> ApiResult<Json<VideoRecord>> {
-    let record = state.get_media(MediaCategory::Video, &id).await?;
-    Ok(Json(sanitize_video_record(&record)))
+/// Query string accepted by `/api/search`.
+#[derive(Deserialize)]
+struct SearchParams {
+    q: Option<String>,
+    kind: Option<String>,
+    channel: Option<String>,
+    limit: Option<u32>,
 }
 
-async fn get_short(
+/// Full-text search across videos and shorts. `kind` narrows the results to
+/// one table and `channel` accepts either a channel id or channel URL.
+async fn search_media(
     State(state): State<AppState>,
-    AxumPath(id): AxumPath<String>,
-) -> ApiResult<Json<VideoRecord>> {
-    let record = state.get_media(MediaCategory::Short, &id).await?;
-    Ok(Json(sanitize_video_record(&record)))
+    Query(params): Query<SearchParams>,
+) -> ApiResult<Json<Vec<SearchHit>>> {
+    let query = params.q.unwrap_or_default();
+    let kind = params
+        .kind
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| media_kind_label(parse_media_kind(Some(&value))).to_string());
+    let options = SearchOptions {
+        kind,
+        channel: params.channel.filter(|value| !value.trim().is_empty()),
+        limit: params.limit,
+    };
+
+    let hits = state
+        .reader
+        .search(&query, &options)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(hits))
 }
 
-async fn get_video_comments(
+/// Query string accepted by `/api/search/transcripts`.
+#[derive(Deserialize)]
+struct TranscriptSearchParams {
+    q: Option<String>,
+    lang: Option<String>,
+    kind: Option<String>,
+    channel: Option<String>,
+    limit: Option<u32>,
+}
+
+/// Full-text search over transcript cues. Every hit names the video and the
+/// cue's start/end so the player can seek to the spoken words.
+async fn search_transcripts(
     State(state): State<AppState>,
-    AxumPath(id): AxumPath<String>,
-) -> ApiResult<Json<Vec<CommentRecord>>> {
-    let comments = state.get_comments(&id).await?;
-    Ok(Json(comments))
+    Query(params): Query<TranscriptSearchParams>,
+) -> ApiResult<Json<Vec<TranscriptHit>>> {
+    let query = params.q.unwrap_or_default();
+    let kind = params
+        .kind
+        .filter(|value| !value.trim().is_empty())
+        .map(|value| media_kind_label(parse_media_kind(Some(&value))).to_string());
+    let options = SearchOptions {
+        kind,
+        channel: params.channel.filter(|value| !value.trim().is_empty()),
+        limit: params.limit,
+    };
+    let language = params.lang.filter(|value| !value.trim().is_empty());
+
+    let hits = state
+        .reader
+        .search_transcripts(&query, language.as_deref(), &options)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(hits))
 }
 
-async fn list_video_subtitles(
+async fn list_channels(State(state): State<AppState>) -> ApiResult<Json<Vec<ChannelInfo>>> {
+    let channels = state
+        .reader
+        .list_channels()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(channels.into_iter().map(ChannelInfo::from).collect()))
+}
+
+async fn get_channel(
     State(state): State<AppState>,
     AxumPath(id): AxumPath<String>,
-) -> ApiResult<Json<Vec<SubtitleInfo>>> {
-    list_subtitles(state, id, "videos").await
+) -> ApiResult<Json<ChannelInfo>> {
+    let channel = state
+        .reader
+        .get_channel(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?
+        .ok_or_else(|| ApiError::not_found("channel not found"))?;
+    Ok(Json(ChannelInfo::from(channel)))
 }
 
-async fn list_short_subtitles(
+async fn list_channel_videos(
     State(state): State<AppState>,
     AxumPath(id): AxumPath<String>,
-) -> ApiResult<Json<Vec<SubtitleInfo>>> {
-    list_subtitles(state, id, "shorts").await
+) -> ApiResult<Json<Vec<VideoRecord>>> {
+    let videos = state
+        .reader
+        .list_channel_videos(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(sanitize_video_records(&videos)))
 }
 
-async fn list_subtitles(
-    state: AppState,
-    id: String,
-    slug: &'static str,
-) -> ApiResult<Json<Vec<SubtitleInfo>>> {
-    // Build lightweight DTOs that point the frontend to the download
-    // endpoints; the actual subtitle JSON remains cached server side.
-    let mut response = Vec::new();
-    if let Some(collection) = state.get_subtitles(&id).await? {
-        for track in collection.languages {
-            let url = format!("/api/{slug}/{}/subtitles/{}", id, track.code);
-            response.push(SubtitleInfo {
-                code: track.code,
-                name: track.name,
-                url,
-            });
+async fn list_channel_shorts(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<Vec<VideoRecord>>> {
+    let shorts = state
+        .reader
+        .list_channel_shorts(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(sanitize_video_records(&shorts)))
+}
+
+/// Daily growth series for a channel, built from `video_stats_history`.
+async fn get_channel_stats(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<Vec<ChannelStatsPoint>>> {
+    let points = state
+        .reader
+        .get_channel_stats_history(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(points))
+}
+
+/// Serves the stored avatar or banner for a channel. Only files below
+/// `MEDIA_ROOT/channels/<id>` are ever streamed.
+async fn download_channel_image(
+    State(state): State<AppState>,
+    AxumPath((id, kind)): AxumPath<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    ensure_safe_path_segment(&id)?;
+    let channel = state
+        .reader
+        .get_channel(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?
+        .ok_or_else(|| ApiError::not_found("channel not found"))?;
+
+    let stored = match kind.as_str() {
+        "avatar" => channel.avatar_path,
+        "banner" => channel.banner_path,
+        _ => None,
+    }
+    .ok_or_else(|| ApiError::not_found("image not found"))?;
+
+    let path = PathBuf::from(stored);
+    let base = state.files.channels.join(&id);
+    if path
+        .components()
+        .any(|component| matches!(component, Component::ParentDir))
+        || !path.starts_with(&base)
+    {
+        return Err(ApiError::not_found("image not found"));
+    }
+
+    // Refreshes overwrite the image in place, so it is not immutable.
+    stream_file(
+        path,
+        None,
+        Some(&headers),
+        CachePolicy::MaxAge(MEDIA_MAX_AGE),
+    )
+    .await
+}
+
+async fn list_playlists(State(state): State<AppState>) -> ApiResult<Json<Vec<PlaylistRecord>>> {
+    let playlists = state
+        .reader
+        .list_playlists()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(playlists))
+}
+
+/// Returns a playlist with its entries in playlist order. Entries that have
+/// not been downloaded yet are listed without a `record`.
+async fn get_playlist(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<PlaylistDetail>> {
+    let playlist = state
+        .reader
+        .get_playlist(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?
+        .ok_or_else(|| ApiError::not_found("playlist not found"))?;
+    let entries = state
+        .reader
+        .get_playlist_entries(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+
+    let mut items = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let record = match entry.kind.as_str() {
+            "short" => state.reader.get_short(&entry.videoid).await,
+            _ => state.reader.get_video(&entry.videoid).await,
         }
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+        items.push(PlaylistItem {
+            entry,
+            record: record.as_ref().map(sanitize_video_record),
+        });
     }
 
-    Ok(Json(response))
+    Ok(Json(PlaylistDetail {
+        playlist,
+        entries: items,
+    }))
 }
 
-async fn download_video_subtitle(
+/// Query parameters accepted by `/api/videos` and `/api/shorts`. Without any
+/// of them the endpoints keep returning the full (cached) array; with at
+/// least one they return a `MediaPage`.
+#[derive(Debug, Default, Deserialize)]
+struct ListParams {
+    cursor: Option<String>,
+    limit: Option<u32>,
+    sort: Option<String>,
+    /// `asc` or `desc`; defaults to the sort's natural direction.
+    order: Option<String>,
+    channel: Option<String>,
+    uploaded_after: Option<String>,
+    uploaded_before: Option<String>,
+    min_duration: Option<i64>,
+    max_duration: Option<i64>,
+    has_subtitles: Option<bool>,
+    /// Comma-separated availability values, e.g. `removed,private`.
+    availability: Option<String>,
+}
+
+impl ListParams {
+    fn is_empty(&self) -> bool {
+        self.cursor.is_none()
+            && self.limit.is_none()
+            && self.sort.is_none()
+            && self.order.is_none()
+            && self.channel.is_none()
+            && self.uploaded_after.is_none()
+            && self.uploaded_before.is_none()
+            && self.min_duration.is_none()
+            && self.max_duration.is_none()
+            && self.has_subtitles.is_none()
+            && self.availability.is_none()
+    }
+
+    fn into_query(self) -> ApiResult<MediaListQuery> {
+        let sort = match self.sort.as_deref() {
+            Some(value) => MediaSort::parse(value)
+                .ok_or_else(|| ApiError::bad_request(format!("unknown sort: {value}")))?,
+            None => MediaSort::default(),
+        };
+        let descending = match self
+            .order
+            .as_deref()
+            .map(str::to_ascii_lowercase)
+            .as_deref()
+        {
+            None => None,
+            Some("desc") => Some(true),
+            Some("asc") => Some(false),
+            Some(other) => return Err(ApiError::bad_request(format!("unknown order: {other}"))),
+        };
+        let availability = self
+            .availability
+            .as_deref()
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                Availability::parse(value)
+                    .ok_or_else(|| ApiError::bad_request(format!("unknown availability: {value}")))
+            })
+            .collect::<ApiResult<Vec<_>>>()?;
+        let non_empty = |value: Option<String>| value.filter(|value| !value.trim().is_empty());
+        Ok(MediaListQuery {
+            sort,
+            descending,
+            cursor: non_empty(self.cursor),
+            limit: self.limit,
+            channel: non_empty(self.channel),
+            uploaded_after: non_empty(self.uploaded_after),
+            uploaded_before: non_empty(self.uploaded_before),
+            min_duration: self.min_duration,
+            max_duration: self.max_duration,
+            has_subtitles: self.has_subtitles,
+            availability,
+        })
+    }
+}
+
+async fn list_videos(
     State(state): State<AppState>,
-    AxumPath((id, code)): AxumPath<(String, String)>,
+    Query(params): Query<ListParams>,
 ) -> ApiResult<Response> {
-    download_subtitle(state, id, code).await
+    list_media(&state, MediaCategory::Video, params).await
 }
 
-async fn download_short_subtitle(
+async fn list_shorts(
     State(state): State<AppState>,
-    AxumPath((id, code)): AxumPath<(String, String)>,
+    Query(params): Query<ListParams>,
 ) -> ApiResult<Response> {
-    download_subtitle(state, id, code).await
+    list_media(&state, MediaCategory::Short, params).await
 }
 
-async fn download_subtitle(state: AppState, id: String, code: String) -> ApiResult<Response> {
-    ensure_safe_path_segment(&id)?;
-    ensure_safe_path_segment(&code)?;
+/// Serves either the legacy full listing or one keyset page. Pages are
+/// queried straight from SQL and bypass `ApiCache`.
+async fn list_media(
+    state: &AppState,
+    category: MediaCategory,
+    params: ListParams,
+) -> ApiResult<Response> {
+    if params.is_empty() {
+        let records = state.get_media_list(category).await?;
+        return Ok(Json(sanitize_video_records(&records)).into_response());
+    }
 
-    let subtitles = state
-        .get_subtitles(&id)
-        .await?
-        .ok_or_else(|| ApiError::not_found("subtitles not available"))?;
+    let query = params.into_query()?;
+    let page = match category {
+        MediaCategory::Video => state.reader.list_videos_page(&query).await,
+        MediaCategory::Short => state.reader.list_shorts_page(&query).await,
+    }
+    .map_err(|err| {
+        if err.downcast_ref::<InvalidCursor>().is_some() {
+            ApiError::bad_request(err.to_string())
+        } else {
+            ApiError::internal(err.to_string())
+        }
+    })?;
 
-    let track = subtitles
-        .languages
-        .into_iter()
-        .find(|track| track.code == code)
-        .ok_or_else(|| ApiError::not_found("subtitle track not found"))?;
+    Ok(Json(MediaPage {
+        items: sanitize_video_records(&page.items),
+        next_cursor: page.next_cursor,
+    })
+    .into_response())
+}
 
-    // Prefer the explicit filesystem path recorded during download, but fall
-    // back to the standard `videoid/lang` layout when missing.
-    let path = if let Some(path) = track.path {
-        let path = PathBuf::from(path);
-        let base = state.files.subtitles.join(&id);
-        if path
-            .components()
-            .any(|component| matches!(component, Component::ParentDir))
-            || !path.starts_with(&base)
-        {
-            return Err(ApiError::not_found("subtitle track not found"));
-        }
-        path
-    } else {
-        find_subtitle_file(&state.files.subtitles, &id, &code).await?
-    };
+/// Detail payload for a single video: the record plus per-video extras the
+/// list endpoints leave out.
+#[derive(Debug, Serialize)]
+struct VideoDetail {
+    #[serde(flatten)]
+    record: VideoRecord,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    chapters: Vec<Chapter>,
+    /// Sponsor/intro/outro/self-promo spans the player may auto-skip.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    segments: Vec<SkipSegment>,
+}
 
-    let mime = MimeGuess::from_path(&path).first();
-    stream_file(path, mime, None).await
+async fn get_video(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<VideoDetail>> {
+    media_detail(&state, MediaCategory::Video, &id).await
 }
 
-async fn download_video_thumbnail(
+async fn get_short(
     State(state): State<AppState>,
-    AxumPath((id, file)): AxumPath<(String, String)>,
-) -> ApiResult<Response> {
-    download_thumbnail(state, id, file).await
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<VideoDetail>> {
+    media_detail(&state, MediaCategory::Short, &id).await
 }
 
-async fn download_short_thumbnail(
+async fn media_detail(
+    state: &AppState,
+    category: MediaCategory,
+    videoid: &str,
+) -> ApiResult<Json<VideoDetail>> {
+    let record = state.get_media(category, videoid).await?;
+    let chapters = media_chapters(state, videoid).await?;
+    let segments = media_segments(state, videoid).await?;
+    Ok(Json(VideoDetail {
+        record: sanitize_video_record(&record),
+        chapters,
+        segments,
+    }))
+}
+
+async fn get_video_chapters(
     State(state): State<AppState>,
-    AxumPath((id, file)): AxumPath<(String, String)>,
-) -> ApiResult<Response> {
-    download_thumbnail(state, id, file).await
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<Vec<Chapter>>> {
+    state.get_media(MediaCategory::Video, &id).await?;
+    media_chapters(&state, &id).await.map(Json)
 }
 
-async fn download_thumbnail(state: AppState, id: String, file: String) -> ApiResult<Response> {
-    ensure_safe_path_segment(&id)?;
-    ensure_safe_path_segment(&file)?;
-    let path = state.files.thumbnails.join(&id).join(&file);
-    stream_file(path, None, None).await
+async fn get_short_chapters(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<Vec<Chapter>>> {
+    state.get_media(MediaCategory::Short, &id).await?;
+    media_chapters(&state, &id).await.map(Json)
 }
 
-async fn stream_video_file(
+async fn media_chapters(state: &AppState, videoid: &str) -> ApiResult<Vec<Chapter>> {
+    state
+        .reader
+        .get_chapters(videoid)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))
+}
+
+async fn get_video_chapters_vtt(
     State(state): State<AppState>,
-    AxumPath((id, format)): AxumPath<(String, String)>,
-    headers: HeaderMap,
+    AxumPath(id): AxumPath<String>,
 ) -> ApiResult<Response> {
-    stream_media(state, MediaCategory::Video, id, format, &headers).await
+    chapters_vtt(&state, MediaCategory::Video, &id).await
 }
 
-async fn stream_short_file(
+async fn get_short_chapters_vtt(
     State(state): State<AppState>,
-    AxumPath((id, format)): AxumPath<(String, String)>,
-    headers: HeaderMap,
+    AxumPath(id): AxumPath<String>,
 ) -> ApiResult<Response> {
-    stream_media(state, MediaCategory::Short, id, format, &headers).await
+    chapters_vtt(&state, MediaCategory::Short, &id).await
 }
 
-async fn stream_media(
-    state: AppState,
-    category: MediaCategory,
-    id: String,
-    format: String,
-    headers: &HeaderMap,
-) -> ApiResult<Response> {
-    ensure_safe_path_segment(&id)?;
-    ensure_safe_path_segment(&format)?;
+async fn get_video_segments(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<Vec<SkipSegment>>> {
+    state.get_media(MediaCategory::Video, &id).await?;
+    media_segments(&state, &id).await.map(Json)
+}
 
-    // We load metadata first so we can map the requested format slug to a file
-    // path and mime type before hitting the disk.
-    let record = state.get_media(category, &id).await?;
+async fn get_short_segments(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<Vec<SkipSegment>>> {
+    state.get_media(MediaCategory::Short, &id).await?;
+    media_segments(&state, &id).await.map(Json)
+}
 
-    let source = record
-        .sources
-        .iter()
-        .find(|source| source_key(source).as_deref() == Some(format.as_str()))
-        .ok_or_else(|| ApiError::not_found("requested format not found"))?;
+async fn media_segments(state: &AppState, videoid: &str) -> ApiResult<Vec<SkipSegment>> {
+    state
+        .reader
+        .get_segments(videoid)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))
+}
 
-    let path = match &source.path {
-        Some(path) => PathBuf::from(path),
-        None => {
-            let ext = source.ext.as_deref().unwrap_or("mp4");
-            state
-                .files
-                .media_dir(category)
-                .join(&id)
-                .join(format!("{}_{}.{}", id, format, ext))
+async fn set_video_segments(
+    State(state): State<AppState>,
+    _admin: AdminProfile,
+    AxumPath(id): AxumPath<String>,
+    Json(segments): Json<Vec<SkipSegment>>,
+) -> ApiResult<Json<Vec<SkipSegment>>> {
+    store_segments(&state, MediaCategory::Video, &id, segments).await
+}
+
+async fn set_short_segments(
+    State(state): State<AppState>,
+    _admin: AdminProfile,
+    AxumPath(id): AxumPath<String>,
+    Json(segments): Json<Vec<SkipSegment>>,
+) -> ApiResult<Json<Vec<SkipSegment>>> {
+    store_segments(&state, MediaCategory::Short, &id, segments).await
+}
+
+/// Replaces the skip segments with a hand-edited list. Manual lists are kept
+/// across SponsorBlock dump imports; posting `[]` hands the video back to
+/// the importer.
+async fn store_segments(
+    state: &AppState,
+    category: MediaCategory,
+    videoid: &str,
+    segments: Vec<SkipSegment>,
+) -> ApiResult<Json<Vec<SkipSegment>>> {
+    let record = state.get_media(category, videoid).await?;
+    let duration = record.duration.map(|duration| duration as f64);
+    for segment in &segments {
+        if !segment.start.is_finite()
+            || !segment.end.is_finite()
+            || segment.start < 0.0
+            || segment.end <= segment.start
+        {
+            return Err(ApiError::bad_request(
+                "segments need 0 <= start < end in seconds",
+            ));
         }
-    };
+        if duration.is_some_and(|duration| segment.start >= duration) {
+            return Err(ApiError::bad_request("segment starts after the video ends"));
+        }
+    }
+    state
+        .store
+        .set_segments(videoid, &segments)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    media_segments(state, videoid).await.map(Json)
+}
 
-    stream_file(
-        path,
-        source.mime_type.as_ref().and_then(|mime| mime.parse().ok()),
-        Some(headers),
+/// Query string accepted by the transcript endpoints.
+#[derive(Deserialize)]
+struct TranscriptParams {
+    lang: Option<String>,
+}
+
+async fn get_video_transcript(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Query(params): Query<TranscriptParams>,
+) -> ApiResult<Json<Vec<Transcript>>> {
+    media_transcript(&state, MediaCategory::Video, &id, params).await
+}
+
+async fn get_short_transcript(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Query(params): Query<TranscriptParams>,
+) -> ApiResult<Json<Vec<Transcript>>> {
+    media_transcript(&state, MediaCategory::Short, &id, params).await
+}
+
+/// Returns the indexed transcript of every language, or only `lang`. Media
+/// without parsed subtitles yields an empty list rather than a 404.
+async fn media_transcript(
+    state: &AppState,
+    category: MediaCategory,
+    videoid: &str,
+    params: TranscriptParams,
+) -> ApiResult<Json<Vec<Transcript>>> {
+    state.get_media(category, videoid).await?;
+    let language = params.lang.filter(|value| !value.trim().is_empty());
+    let transcripts = state
+        .reader
+        .get_transcripts(videoid, language.as_deref())
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(transcripts))
+}
+
+/// Serves chapters as a WebVTT `kind="chapters"` track so `<track>` elements
+/// can show chapter markers without any JS.
+async fn chapters_vtt(
+    state: &AppState,
+    category: MediaCategory,
+    videoid: &str,
+) -> ApiResult<Response> {
+    let record = state.get_media(category, videoid).await?;
+    let chapters = media_chapters(state, videoid).await?;
+    let duration = record.duration.map(|duration| duration as f64);
+    let body = render_chapters_vtt(&chapters, duration);
+    Ok(([(header::CONTENT_TYPE, "text/vtt; charset=utf-8")], body).into_response())
+}
+
+/// Cues need an end time. The last chapter falls back to the video duration
+/// and then to a day-long cue, which players clamp to the media length.
+fn render_chapters_vtt(chapters: &[Chapter], duration: Option<f64>) -> String {
+    let mut vtt = String::from("WEBVTT\n");
+    for (index, chapter) in chapters.iter().enumerate() {
+        let end = chapter
+            .end
+            .or(duration)
+            .filter(|end| *end > chapter.start)
+            .unwrap_or(chapter.start + 86_400.0);
+        let title = chapter
+            .title
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace(['\r', '\n'], " ");
+        vtt.push_str(&format!(
+            "\n{}\n{} --> {}\n{}\n",
+            index + 1,
+            format_vtt_timestamp(chapter.start),
+            format_vtt_timestamp(end),
+            title
+        ));
+    }
+    vtt
+}
+
+fn format_vtt_timestamp(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
     )
-    .await
 }
 
-/// Lightweight response that exposes a download URL for each subtitle track.
-#[derive(serde::Serialize)]
-struct SubtitleInfo {
-    code: String,
-    name: String,
-    url: String,
+/// `?block=true` also adds the id to the "never re-download" list.
+#[derive(Debug, Default, Deserialize)]
+struct DeleteParams {
+    #[serde(default)]
+    block: bool,
+    reason: Option<String>,
 }
 
-/// Payload returned by `/api/bootstrap` so the client can hydrate offline.
-#[derive(Clone, Serialize)]
-struct BootstrapPayload {
-    videos: Vec<VideoRecord>,
-    shorts: Vec<VideoRecord>,
-    subtitles: Vec<SubtitleCollection>,
-    comments: Vec<CommentRecord>,
+/// Summary of what a delete removed, relative to `MEDIA_ROOT`.
+#[derive(Debug, Serialize)]
+struct DeleteReport {
+    videoid: String,
+    removed_paths: Vec<String>,
+    archive_entry_removed: bool,
+    blocked: bool,
 }
 
-impl AppState {
-    async fn ensure_fresh_cache(&self) -> ApiResult<()> {
-        let version = self
-            .reader
-            .data_version()
+async fn delete_video(
+    State(state): State<AppState>,
+    _admin: AdminProfile,
+    AxumPath(id): AxumPath<String>,
+    Query(params): Query<DeleteParams>,
+) -> ApiResult<Json<DeleteReport>> {
+    delete_media(&state, MediaCategory::Video, &id, params).await
+}
+
+async fn delete_short(
+    State(state): State<AppState>,
+    _admin: AdminProfile,
+    AxumPath(id): AxumPath<String>,
+    Query(params): Query<DeleteParams>,
+) -> ApiResult<Json<DeleteReport>> {
+    delete_media(&state, MediaCategory::Short, &id, params).await
+}
+
+/// Deletes the DB rows first, then the per-id artifact directories and the
+/// yt-dlp archive entry so a later explicit download starts from scratch.
+async fn delete_media(
+    state: &AppState,
+    category: MediaCategory,
+    videoid: &str,
+    params: DeleteParams,
+) -> ApiResult<Json<DeleteReport>> {
+    ensure_single_path_segment(videoid)?;
+    state.get_media(category, videoid).await?;
+
+    state
+        .store
+        .delete_media(videoid)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    if params.block {
+        state
+            .store
+            .block_media(videoid, params.reason.as_deref())
             .await
             .map_err(|err| ApiError::internal(err.to_string()))?;
-
-        let mut last = self.cache.last_db_version.write();
-        if let Some(previous) = *last
-            && version != previous
+    }
+    state.cache.clear();
+
+    let files = &state.files;
+    let mut removed_paths = Vec::new();
+    for (root, subdir) in [
+        (files.media_dir(category), media_kind_subdir(category)),
+        (files.thumbnails.as_path(), THUMBNAILS_SUBDIR),
+        (files.subtitles.as_path(), SUBTITLES_SUBDIR),
+        (files.comments.as_path(), COMMENTS_SUBDIR),
+        (files.hls.as_path(), hls::HLS_SUBDIR),
+    ] {
+        if remove_artifact_dir(root, videoid)
+            .await
+            .map_err(|err| ApiError::internal(err.to_string()))?
         {
-            self.cache.clear();
+            removed_paths.push(format!("{subdir}/{videoid}"));
         }
-        *last = Some(version);
-        Ok(())
     }
 
-    /// Returns a cached snapshot containing everything the SPA needs to boot
-    /// without hitting follow-up endpoints (videos, shorts, subtitles,
-    /// comments). The heavy lifting runs in a blocking task because SQLite is a
-    /// synchronous API.
-    async fn get_bootstrap(&self) -> ApiResult<Arc<BootstrapPayload>> {
-        self.ensure_fresh_cache().await?;
-        if let Some(cached) = self.cache.bootstrap.read().clone() {
-            return Ok(cached);
-        }
+    let archive_entry_removed = remove_archive_entry(&files.archive, videoid)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
 
-        let videos = self
-            .reader
-            .list_videos()
-            .await
-            .map_err(|err| ApiError::internal(err.to_string()))?;
-        let shorts = self
-            .reader
-            .list_shorts()
-            .await
-            .map_err(|err| ApiError::internal(err.to_string()))?;
-        let subtitles = self
-            .reader
-            .list_subtitles()
-            .await
-            .map_err(|err| ApiError::internal(err.to_string()))?
-            .into_iter()
-            .map(sanitize_subtitle_collection)
-            .collect();
-        let comments = self
-            .reader
-            .list_all_comments()
-            .await
-            .map_err(|err| ApiError::internal(err.to_string()))?;
-        let payload = BootstrapPayload {
-            videos: sanitize_video_records(&videos),
-            shorts: sanitize_video_records(&shorts),
-            subtitles,
-            comments,
-        };
+    Ok(Json(DeleteReport {
+        videoid: videoid.to_string(),
+        removed_paths,
+        archive_entry_removed,
+        blocked: params.block,
+    }))
+}
 
-        let payload = Arc::new(payload);
-        self.cache.bootstrap.write().replace(payload.clone());
-        Ok(payload)
+async fn list_blocked_media(State(state): State<AppState>) -> ApiResult<Json<Vec<BlockedMedia>>> {
+    let blocked = state
+        .reader
+        .list_blocked_media()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(blocked))
+}
+
+async fn unblock_media(
+    State(state): State<AppState>,
+    _admin: AdminProfile,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<StatusCode> {
+    let removed = state
+        .store
+        .unblock_media(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found("id is not blocked"))
     }
+}
 
-    /// Retrieves every video/short record, memoizing both the list and the
-    /// individual details map for quick follow-up lookups.
-    async fn get_media_list(&self, category: MediaCategory) -> ApiResult<Vec<VideoRecord>> {
-        self.ensure_fresh_cache().await?;
-        if let Some(cached) = self.cache.media_list(category).read().clone() {
-            return Ok(cached);
-        }
+/// Default and maximum length of the `/api/plays` listing.
+const DEFAULT_MOST_PLAYED: usize = 20;
+const MAX_MOST_PLAYED: usize = 100;
 
-        let records = match category {
-            MediaCategory::Video => self
-                .reader
-                .list_videos()
-                .await
-                .map_err(|err| ApiError::internal(err.to_string()))?,
-            MediaCategory::Short => self
-                .reader
-                .list_shorts()
-                .await
-                .map_err(|err| ApiError::internal(err.to_string()))?,
-        };
+#[derive(Debug, Default, Deserialize)]
+struct MostPlayedParams {
+    limit: Option<usize>,
+}
 
-        self.cache
-            .media_list(category)
-            .write()
-            .replace(records.clone());
+/// Entry of the "most watched locally" listing.
+#[derive(Serialize)]
+struct MostPlayedEntry {
+    #[serde(flatten)]
+    stats: PlayStats,
+    /// `video` or `short`.
+    kind: &'static str,
+    record: VideoRecord,
+}
 
-        let mut details = self.cache.media_details(category).write();
-        for record in &records {
-            details.insert(record.videoid.clone(), record.clone());
-        }
+/// Lists the videos and shorts played most on this instance. Media removed
+/// from the library since it was played is left out.
+async fn list_most_played(
+    State(state): State<AppState>,
+    Query(params): Query<MostPlayedParams>,
+) -> ApiResult<Json<Vec<MostPlayedEntry>>> {
+    let limit = params
+        .limit
+        .filter(|limit| *limit > 0)
+        .unwrap_or(DEFAULT_MOST_PLAYED)
+        .min(MAX_MOST_PLAYED);
+    let stats = state
+        .user_data
+        .most_played(limit)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
 
-        Ok(records)
+    let mut entries = Vec::with_capacity(stats.len());
+    'stats: for stats in stats {
+        for category in [MediaCategory::Video, MediaCategory::Short] {
+            match state.get_media(category, &stats.videoid).await {
+                Ok(record) => {
+                    entries.push(MostPlayedEntry {
+                        stats,
+                        kind: media_kind_label(category),
+                        record: sanitize_video_record(&record),
+                    });
+                    continue 'stats;
+                }
+                Err(err) if err.status == StatusCode::NOT_FOUND => {}
+                Err(err) => return Err(err),
+            }
+        }
     }
+    Ok(Json(entries))
+}
 
-    /// Loads metadata for a single video or short, preferring the cache before
-    /// falling back to SQLite.
-    async fn get_media(&self, category: MediaCategory, videoid: &str) -> ApiResult<VideoRecord> {
-        self.ensure_fresh_cache().await?;
-        if let Some(record) = self
-            .cache
-            .media_details(category)
-            .read()
-            .get(videoid)
-            .cloned()
-        {
-            return Ok(record);
-        }
+#[derive(Debug, Default, Deserialize)]
+struct ScrubParams {
+    /// Hash at most this many files, least recently verified first.
+    limit: Option<usize>,
+}
 
-        let result = match category {
-            MediaCategory::Video => self
-                .reader
-                .get_video(videoid)
-                .await
-                .map_err(|err| ApiError::internal(err.to_string()))?,
-            MediaCategory::Short => self
-                .reader
-                .get_short(videoid)
-                .await
-                .map_err(|err| ApiError::internal(err.to_string()))?,
-        };
+async fn get_scrub_status(
+    State(state): State<AppState>,
+    _admin: AdminProfile,
+) -> ApiResult<Json<ScrubStatus>> {
+    Ok(Json(state.scrub.status()))
+}
 
-        let record = result.ok_or_else(|| ApiError::not_found("video not found"))?;
+/// Starts a full scrub pass; poll `GET /api/scrub` for the report.
+async fn start_scrub(
+    State(state): State<AppState>,
+    _admin: AdminProfile,
+    Query(params): Query<ScrubParams>,
+) -> ApiResult<(StatusCode, Json<ScrubStatus>)> {
+    let options = ScrubOptions {
+        verified_before: None,
+        limit: params.limit.filter(|limit| *limit > 0),
+    };
+    if !state
+        .scrub
+        .start(state.store.clone(), state.reader.clone(), options)
+    {
+        return Err(ApiError::conflict("a scrub is already running"));
+    }
+    Ok((StatusCode::ACCEPTED, Json(state.scrub.status())))
+}
 
-        self.cache
-            .media_details(category)
-            .write()
-            .insert(videoid.to_owned(), record.clone());
+#[derive(Debug, Default, Deserialize)]
+struct ChecksumParams {
+    status: Option<String>,
+}
 
-        Ok(record)
-    }
+async fn list_source_checksums(
+    State(state): State<AppState>,
+    _admin: AdminProfile,
+    Query(params): Query<ChecksumParams>,
+) -> ApiResult<Json<Vec<SourceChecksum>>> {
+    let status = match params.status.as_deref() {
+        None | Some("") => None,
+        Some(value) => Some(
+            ChecksumStatus::parse(value)
+                .ok_or_else(|| ApiError::bad_request(format!("unknown status: {value}")))?,
+        ),
+    };
+    let checksums = state
+        .reader
+        .list_source_checksums(status)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(checksums))
+}
 
-    /// Lazy-loads comment threads; we store them keyed by id because comment
-    /// payloads are far smaller than video blobs.
-    async fn get_comments(&self, videoid: &str) -> ApiResult<Vec<CommentRecord>> {
-        self.ensure_fresh_cache().await?;
-        if let Some(cached) = self.cache.comments.read().get(videoid).cloned() {
-            return Ok(cached);
-        }
+/// Cookie carrying the session token. Every /api/me route acts on the
+/// profile resolved from it; instance-wide actions additionally need an admin.
+const SESSION_COOKIE: &str = "newtube_session";
 
-        let comments = self
-            .reader
-            .get_comments(videoid)
-            .await
-            .map_err(|err| ApiError::internal(err.to_string()))?;
+/// Profile the request acts as: the one behind the session cookie or, on a
+/// single-user instance, the only password-less profile.
+#[derive(Debug)]
+struct CurrentProfile(Profile);
 
-        self.cache
-            .comments
-            .write()
-            .insert(videoid.to_owned(), comments.clone());
+impl FromRequestParts<AppState> for CurrentProfile {
+    type Rejection = ApiError;
 
-        Ok(comments)
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> ApiResult<Self> {
+        let mut profile = None;
+        if let Some(token) = session_token(&parts.headers) {
+            profile = state
+                .user_data
+                .session_profile(token)
+                .await
+                .map_err(|err| ApiError::internal(err.to_string()))?;
+        }
+        if profile.is_none() {
+            profile = state
+                .user_data
+                .anonymous_profile()
+                .await
+                .map_err(|err| ApiError::internal(err.to_string()))?;
+        }
+        profile
+            .map(CurrentProfile)
+            .ok_or_else(|| ApiError::unauthorized("sign in to a profile first"))
     }
+}
 
-    /// Provides subtitle metadata if available. Not every video has subtitles
-    /// so the API returns an Option.
-    async fn get_subtitles(&self, videoid: &str) -> ApiResult<Option<SubtitleCollection>> {
-        self.ensure_fresh_cache().await?;
-        if let Some(cached) = self.cache.subtitles.read().get(videoid).cloned() {
-            return Ok(Some(cached));
-        }
+/// Like `CurrentProfile` but rejects non-admin profiles. Guards everything
+/// that changes the shared library or `SettingsStore`.
+#[derive(Debug)]
+struct AdminProfile;
 
-        let result = self
-            .reader
-            .get_subtitles(videoid)
-            .await
-            .map_err(|err| ApiError::internal(err.to_string()))?;
+impl FromRequestParts<AppState> for AdminProfile {
+    type Rejection = ApiError;
 
-        if let Some(collection) = &result {
-            self.cache
-                .subtitles
-                .write()
-                .insert(videoid.to_owned(), collection.clone());
+    async fn from_request_parts(parts: &mut Parts, state: &AppState) -> ApiResult<Self> {
+        let CurrentProfile(profile) = CurrentProfile::from_request_parts(parts, state).await?;
+        if profile.is_admin {
+            Ok(AdminProfile)
+        } else {
+            Err(ApiError::forbidden("admin profile required"))
         }
-
-        Ok(result)
     }
 }
 
-/// Normalizes a VideoSource URL by keeping only the trailing segment. During
-/// download we store files named `{videoid}_{format}` and the format parameter
-/// is the only piece users need to specify.
-fn source_key(source: &VideoSource) -> Option<String> {
-    let format_id = source.format_id.trim();
-    if format_id.is_empty() {
-        return None;
-    }
-    Some(normalize_format_id(format_id))
+/// Extracts our session token from the `Cookie` header(s), if any.
+fn session_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .find_map(|pair| {
+            let (name, value) = pair.trim().split_once('=')?;
+            (name == SESSION_COOKIE && !value.is_empty()).then_some(value)
+        })
 }
 
-fn normalize_format_id(value: &str) -> String {
-    value
-        .chars()
-        .map(|c| match c {
-            '/' | ':' | ' ' => '_',
-            _ => c,
-        })
-        .collect()
+/// `Set-Cookie` value for a session. `Secure` is left off on purpose because
+/// most instances are reached over plain HTTP on the LAN.
+fn session_cookie(token: &str, max_age_secs: i64) -> String {
+    format!("{SESSION_COOKIE}={token}; Path=/; HttpOnly; SameSite=Lax; Max-Age={max_age_secs}")
 }
 
-/// Validates that a single dynamic path segment never escapes its base folder.
-fn ensure_safe_path_segment(value: &str) -> ApiResult<()> {
-    if value.is_empty()
-        || Path::new(value)
-            .components()
-            .any(|component| !matches!(component, Component::Normal(_)))
-    {
-        return Err(ApiError::not_found("file not found"));
-    }
+fn session_response(session: &Session) -> Response {
+    let max_age = (session.expires_at - chrono::Utc::now().timestamp_millis()) / 1000;
+    (
+        [(header::SET_COOKIE, session_cookie(&session.token, max_age))],
+        Json(session.profile.clone()),
+    )
+        .into_response()
+}
 
-    Ok(())
+/// Empty passwords mean "no password", so a PIN can be removed from the UI.
+fn normalize_password(password: Option<String>) -> Option<String> {
+    password.filter(|password| !password.is_empty())
 }
 
-async fn find_subtitle_file(subtitles_root: &Path, id: &str, code: &str) -> ApiResult<PathBuf> {
-    let dir = subtitles_root.join(id);
-    let mut entries = tokio::fs::read_dir(&dir)
-        .await
-        .map_err(|_| ApiError::not_found("subtitle track not found"))?;
-    let prefix = format!("{id}.{code}.");
-    let mut best: Option<(usize, PathBuf)> = None;
-    while let Some(entry) = entries
-        .next_entry()
-        .await
-        .map_err(|_| ApiError::not_found("subtitle track not found"))?
-    {
-        let path = entry.path();
-        if !path.is_file() {
-            continue;
-        }
-        let name = entry
-            .file_name()
-            .into_string()
-            .unwrap_or_else(|os| os.to_string_lossy().into_owned());
-        if name.starts_with(&prefix) {
-            let ext = Path::new(&name)
-                .extension()
-                .and_then(|ext| ext.to_str())
-                .unwrap_or("");
-            let rank = subtitle_extension_rank(ext);
-            if rank >= 10 {
-                continue;
-            }
-            match best {
-                Some((best_rank, _)) if rank >= best_rank => {}
-                _ => best = Some((rank, path)),
-            }
-        }
-    }
-    best.map(|(_, path)| path)
-        .ok_or_else(|| ApiError::not_found("subtitle track not found"))
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LoginRequest {
+    profile_id: Option<i64>,
+    name: Option<String>,
+    password: Option<String>,
 }
 
-fn subtitle_extension_rank(ext: &str) -> usize {
-    match ext.to_ascii_lowercase().as_str() {
-        "vtt" => 0,
-        "srv3" => 1,
-        "srv2" => 2,
-        "srv1" => 3,
-        "srt" => 4,
-        "ttml" => 5,
-        "xml" => 6,
-        "ass" => 7,
-        _ => 10,
-    }
+#[derive(Debug, Deserialize)]
+struct NewProfileRequest {
+    name: String,
+    password: Option<String>,
+    #[serde(default)]
+    admin: bool,
 }
 
-async fn stream_file(
-    path: PathBuf,
-    mime: Option<Mime>,
-    headers: Option<&HeaderMap>,
-) -> ApiResult<Response> {
-    let mut file = File::open(&path)
-        .await
-        .map_err(|_| ApiError::not_found("file not found"))?;
-    let metadata = file
-        .metadata()
-        .await
-        .map_err(|_| ApiError::not_found("file not found"))?;
-    let size = metadata.len();
-
-    let guessed = mime.or_else(|| MimeGuess::from_path(&path).first());
-    let range = headers
-        .and_then(|headers| headers.get(header::RANGE))
-        .and_then(|value| parse_range_header(value, size));
+#[derive(Debug, Deserialize)]
+struct PasswordRequest {
+    password: Option<String>,
+}
 
-    let mut response = if let Some((start, end)) = range {
-        if start >= size {
-            let mut response = Response::new(Body::empty());
-            *response.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
-            response.headers_mut().insert(
-                header::CONTENT_RANGE,
-                format!("bytes */{}", size).parse().unwrap(),
-            );
-            response
-        } else {
-            let end = end.min(size.saturating_sub(1));
-            let length = end - start + 1;
-            file.seek(std::io::SeekFrom::Start(start))
-                .await
-                .map_err(|_| ApiError::not_found("file not found"))?;
-            let stream = ReaderStream::new(file.take(length));
-            let body = Body::from_stream(stream);
-            let mut response = body.into_response();
-            *response.status_mut() = StatusCode::PARTIAL_CONTENT;
-            response.headers_mut().insert(
-                header::CONTENT_RANGE,
-                format!("bytes {}-{}/{}", start, end, size).parse().unwrap(),
-            );
-            response
-                .headers_mut()
-                .insert(header::CONTENT_LENGTH, length.to_string().parse().unwrap());
-            response
-        }
-    } else {
-        let stream = ReaderStream::new(file);
-        let body = Body::from_stream(stream);
-        body.into_response()
-    };
+/// Public so the SPA can render a profile picker before anyone signs in.
+async fn list_profiles(State(state): State<AppState>) -> ApiResult<Json<Vec<Profile>>> {
+    let profiles = state
+        .user_data
+        .list_profiles()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(profiles))
+}
 
-    response
-        .headers_mut()
-        .insert(header::ACCEPT_RANGES, "bytes".parse().unwrap());
-    if let Some(mime) = guessed
-        && let Ok(value) = mime.to_string().parse()
+/// Adds a profile. Sessions only protect anything once an admin has a
+/// password, so a second profile is refused until one does.
+async fn create_profile(
+    State(state): State<AppState>,
+    _admin: AdminProfile,
+    Json(request): Json<NewProfileRequest>,
+) -> ApiResult<Json<Profile>> {
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(ApiError::bad_request("profile name must not be empty"));
+    }
+    let existing = state
+        .user_data
+        .find_profile(name)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    if existing.is_some() {
+        return Err(ApiError::bad_request("profile name already in use"));
+    }
+    let profiles = state
+        .user_data
+        .list_profiles()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    if !profiles
+        .iter()
+        .any(|profile| profile.is_admin && profile.has_password)
     {
-        response.headers_mut().insert(header::CONTENT_TYPE, value);
+        return Err(ApiError::bad_request(
+            "set a password on an admin profile before adding profiles",
+        ));
     }
-
-    Ok(response)
+    let password = normalize_password(request.password);
+    let profile = state
+        .user_data
+        .create_profile(name, password.as_deref(), request.admin)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(profile))
 }
 
-fn sanitize_video_records(records: &[VideoRecord]) -> Vec<VideoRecord> {
-    records.iter().map(sanitize_video_record).collect()
+/// Deletes a profile and everything it owns. The last admin cannot be
+/// removed, otherwise nobody could manage the instance any more.
+async fn delete_profile(
+    State(state): State<AppState>,
+    _admin: AdminProfile,
+    AxumPath(id): AxumPath<i64>,
+) -> ApiResult<StatusCode> {
+    let profiles = state
+        .user_data
+        .list_profiles()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    let Some(target) = profiles.iter().find(|profile| profile.id == id) else {
+        return Err(ApiError::not_found("profile not found"));
+    };
+    let admins = profiles.iter().filter(|profile| profile.is_admin).count();
+    if target.is_admin && admins == 1 {
+        return Err(ApiError::bad_request(
+            "cannot delete the last admin profile",
+        ));
+    }
+    state
+        .user_data
+        .delete_profile(id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
-fn sanitize_video_record(record: &VideoRecord) -> VideoRecord {
-    let mut clone = record.clone();
-    for source in &mut clone.sources {
-        source.path = None;
+/// Sets or clears a password. Profiles may change their own; admins may
+/// change anyone's. All sessions of the target are revoked, so the caller
+/// gets a fresh cookie when it changed its own password.
+async fn set_profile_password(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<i64>,
+    Json(request): Json<PasswordRequest>,
+) -> ApiResult<Response> {
+    if profile.id != id && !profile.is_admin {
+        return Err(ApiError::forbidden(
+            "cannot change another profile's password",
+        ));
     }
-    clone
+    let password = normalize_password(request.password);
+    if password.is_none() {
+        let profiles = state
+            .user_data
+            .list_profiles()
+            .await
+            .map_err(|err| ApiError::internal(err.to_string()))?;
+        let other_admin_password = profiles
+            .iter()
+            .any(|other| other.id != id && other.is_admin && other.has_password);
+        let target_is_admin = profiles
+            .iter()
+            .any(|other| other.id == id && other.is_admin);
+        if profiles.len() > 1 && target_is_admin && !other_admin_password {
+            return Err(ApiError::bad_request(
+                "the last admin with a password cannot clear it while other profiles exist",
+            ));
+        }
+    }
+    let updated = state
+        .user_data
+        .set_password(id, password.as_deref())
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    if !updated {
+        return Err(ApiError::not_found("profile not found"));
+    }
+    if profile.id != id {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+    let session = state
+        .user_data
+        .create_session(id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(session_response(&session))
 }
 
-fn sanitize_subtitle_collection(collection: SubtitleCollection) -> SubtitleCollection {
-    let mut clone = collection;
-    for track in &mut clone.languages {
-        track.path = None;
-    }
-    clone
+async fn get_session(CurrentProfile(profile): CurrentProfile) -> ApiResult<Json<Profile>> {
+    Ok(Json(profile))
 }
 
-fn parse_range_header(value: &header::HeaderValue, size: u64) -> Option<(u64, u64)> {
-    let value = value.to_str().ok()?;
-    let value = value.trim();
-    let mut parts = value.split('=');
-    let unit = parts.next()?.trim();
-    if unit != "bytes" {
-        return None;
-    }
-    let range = parts.next()?.trim();
-    if range.is_empty() {
-        return None;
+/// Signs in by profile id or name and sets the session cookie.
+async fn login(
+    State(state): State<AppState>,
+    Json(request): Json<LoginRequest>,
+) -> ApiResult<Response> {
+    let profile = match (request.profile_id, request.name.as_deref()) {
+        (Some(id), _) => state.user_data.get_profile(id).await,
+        (None, Some(name)) => state.user_data.find_profile(name.trim()).await,
+        (None, None) => return Err(ApiError::bad_request("profileId or name is required")),
     }
-    let (start_str, end_str) = range.split_once('-')?;
+    .map_err(|err| ApiError::internal(err.to_string()))?;
+    let Some(profile) = profile else {
+        return Err(ApiError::unauthorized("invalid profile or password"));
+    };
 
-    if start_str.is_empty() {
-        // Suffix range: "-N" means last N bytes.
-        let suffix_len: u64 = end_str.parse().ok()?;
-        if suffix_len == 0 {
-            return None;
-        }
-        if suffix_len >= size {
-            return Some((0, size.saturating_sub(1)));
-        }
-        return Some((size - suffix_len, size.saturating_sub(1)));
+    let password = normalize_password(request.password);
+    let valid = state
+        .user_data
+        .verify_password(profile.id, password.as_deref())
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    if !valid {
+        return Err(ApiError::unauthorized("invalid profile or password"));
     }
+    let session = state
+        .user_data
+        .create_session(profile.id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(session_response(&session))
+}
 
-    let start: u64 = start_str.parse().ok()?;
-    let end = if end_str.is_empty() {
-        size.saturating_sub(1)
-    } else {
-        end_str.parse().ok()?
-    };
-    if end < start {
-        return None;
+async fn logout(State(state): State<AppState>, headers: HeaderMap) -> ApiResult<Response> {
+    if let Some(token) = session_token(&headers) {
+        state
+            .user_data
+            .delete_session(token)
+            .await
+            .map_err(|err| ApiError::internal(err.to_string()))?;
     }
-    Some((start, end))
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(header::SET_COOKIE, session_cookie("", 0))],
+    )
+        .into_response())
 }
 
-fn env_or_file_value(key: &str, file_vars: &HashMap<String, String>) -> Option<String> {
-    std::env::var(key)
-        .ok()
-        .and_then(|value| {
-            let trimmed = value.trim();
-            if trimmed.is_empty() {
-                None
-            } else {
-                Some(trimmed.to_string())
-            }
-        })
-        .or_else(|| file_vars.get(key).cloned())
+// /api/me: server-side copy of the frontend's localStorage user data, scoped
+// to the current profile.
+
+/// Same wrapper `exportToString()` produces, so sync responses can be fed
+/// straight back into `importFromString` on the client.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct UserDataExport {
+    exported_at: String,
+    data: UserSnapshot,
 }
 
-fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<()> {
-    if let Some(parent) = path.parent() {
-        fs::create_dir_all(parent).with_context(|| format!("creating {}", parent.display()))?;
-    }
-    let tmp_path = path.with_extension("tmp");
-    let payload = serde_json::to_vec_pretty(value)?;
-    fs::write(&tmp_path, payload)?;
-    fs::rename(&tmp_path, path)?;
-    Ok(())
+/// Watch history row with its video id inlined for list responses.
+#[derive(Debug, Serialize)]
+struct HistoryItem {
+    videoid: String,
+    #[serde(flatten)]
+    entry: WatchEntry,
 }
 
-fn write_progress_report(path: &Path, progress: u8, message: &str) {
-    let report = ProgressReport {
-        progress: progress.min(100),
-        message: message.to_string(),
-    };
-    if let Err(err) = write_json_atomic(path, &report) {
-        eprintln!("Failed to write progress report: {err}");
-    }
+#[derive(Debug, Deserialize)]
+struct PlaylistUpdate {
+    name: Option<String>,
 }
 
-fn read_progress_report(path: &Path) -> Option<ProgressReport> {
-    let raw = fs::read_to_string(path).ok()?;
-    serde_json::from_str(&raw).ok()
+async fn get_user_data(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+) -> ApiResult<Json<UserSnapshot>> {
+    let snapshot = state
+        .user_data
+        .profile(profile.id)
+        .snapshot()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(snapshot))
 }
 
-fn update_job_status(
-    inner: &DownloadManagerInner,
-    job_id: &str,
-    status: DownloadStatus,
-    message: &str,
-) {
-    if let Some(job) = inner.jobs.lock().get_mut(job_id) {
-        job.status = status;
-        job.message = message.to_string();
-    }
+/// Merges a device's export into the server copy and answers with the merged
+/// state in the same export format.
+async fn sync_user_data(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    Json(import): Json<UserDataImport>,
+) -> ApiResult<Json<UserDataExport>> {
+    let merged = state
+        .user_data
+        .profile(profile.id)
+        .merge(&import.into_snapshot())
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(UserDataExport {
+        exported_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+        data: merged,
+    }))
 }
 
-fn media_kind_label(kind: MediaCategory) -> &'static str {
-    match kind {
-        MediaCategory::Video => "video",
-        MediaCategory::Short => "short",
-    }
+/// Per-profile frontend preferences. Instance settings live in
+/// `/api/settings` and are admin-only.
+async fn get_user_settings(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+) -> ApiResult<Json<serde_json::Value>> {
+    let settings = state
+        .user_data
+        .profile(profile.id)
+        .settings()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(settings))
 }
 
-fn parse_media_kind(value: Option<&str>) -> MediaCategory {
-    match value.map(|value| value.trim().to_ascii_lowercase()) {
-        Some(ref value) if value == "short" || value == "shorts" => MediaCategory::Short,
-        _ => MediaCategory::Video,
+async fn update_user_settings(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    Json(settings): Json<serde_json::Value>,
+) -> ApiResult<Json<serde_json::Value>> {
+    if !settings.is_object() {
+        return Err(ApiError::bad_request("settings must be a JSON object"));
     }
+    state
+        .user_data
+        .profile(profile.id)
+        .set_settings(&settings)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(settings))
 }
 
-#[cfg(test)]
-static DOWNLOAD_CHANNEL_STUB: std::sync::Mutex<Option<PathBuf>> = std::sync::Mutex::new(None);
+async fn list_likes(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+) -> ApiResult<Json<Vec<SavedVideo>>> {
+    list_reactions(&state, &profile, Reaction::Like).await
+}
 
-#[cfg(test)]
-static YT_DLP_STUB: std::sync::Mutex<Option<PathBuf>> = std::sync::Mutex::new(None);
+async fn list_dislikes(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+) -> ApiResult<Json<Vec<SavedVideo>>> {
+    list_reactions(&state, &profile, Reaction::Dislike).await
+}
 
-#[cfg(test)]
-struct StubGuard {
-    slot: &'static std::sync::Mutex<Option<PathBuf>>,
+async fn put_like(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<String>,
+    Json(saved): Json<SavedVideo>,
+) -> ApiResult<StatusCode> {
+    set_reaction(&state, &profile, Reaction::Like, &id, saved).await
 }
 
-#[cfg(test)]
-impl Drop for StubGuard {
-    fn drop(&mut self) {
-        if let Ok(mut slot) = self.slot.lock() {
-            *slot = None;
-        }
-    }
+async fn put_dislike(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<String>,
+    Json(saved): Json<SavedVideo>,
+) -> ApiResult<StatusCode> {
+    set_reaction(&state, &profile, Reaction::Dislike, &id, saved).await
 }
 
-#[cfg(test)]
-fn set_download_channel_stub(path: PathBuf) -> StubGuard {
-    *DOWNLOAD_CHANNEL_STUB.lock().unwrap() = Some(path);
-    StubGuard {
-        slot: &DOWNLOAD_CHANNEL_STUB,
-    }
+async fn delete_like(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<StatusCode> {
+    clear_reaction(&state, &profile, Reaction::Like, &id).await
 }
 
-#[cfg(test)]
-fn set_ytdlp_stub(path: PathBuf) -> StubGuard {
-    *YT_DLP_STUB.lock().unwrap() = Some(path);
-    StubGuard { slot: &YT_DLP_STUB }
+async fn delete_dislike(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<StatusCode> {
+    clear_reaction(&state, &profile, Reaction::Dislike, &id).await
 }
 
-fn find_download_channel_executable() -> Result<PathBuf> {
-    #[cfg(test)]
-    {
-        if let Some(path) = DOWNLOAD_CHANNEL_STUB.lock().unwrap().clone()
-            && path.exists()
-        {
-            return Ok(path);
-        }
-    }
+async fn list_reactions(
+    state: &AppState,
+    profile: &Profile,
+    reaction: Reaction,
+) -> ApiResult<Json<Vec<SavedVideo>>> {
+    let reactions = state
+        .user_data
+        .profile(profile.id)
+        .list_reactions(reaction)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(
+        reactions.into_iter().map(|(_, saved)| saved).collect(),
+    ))
+}
 
-    if let Ok(path) = std::env::var("NEWTUBE_DOWNLOAD_BIN") {
-        let trimmed = path.trim();
-        if !trimmed.is_empty() {
-            let candidate = PathBuf::from(trimmed);
-            if candidate.exists() {
-                return Ok(candidate);
-            }
-        }
-    }
+async fn set_reaction(
+    state: &AppState,
+    profile: &Profile,
+    reaction: Reaction,
+    videoid: &str,
+    mut saved: SavedVideo,
+) -> ApiResult<StatusCode> {
+    // Always stamp server time so a PUT beats older copies during sync.
+    saved.saved_at = None;
+    state
+        .user_data
+        .profile(profile.id)
+        .set_reaction(videoid, reaction, &saved)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    if let Ok(path) = std::env::var("CARGO_BIN_EXE_download_channel") {
-        let candidate = PathBuf::from(path);
-        if candidate.exists() {
-            return Ok(candidate);
-        }
+async fn clear_reaction(
+    state: &AppState,
+    profile: &Profile,
+    reaction: Reaction,
+    videoid: &str,
+) -> ApiResult<StatusCode> {
+    let removed = state
+        .user_data
+        .profile(profile.id)
+        .clear_reaction(videoid, reaction)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found("reaction not found"))
     }
+}
 
-    let docker_path = PathBuf::from("/usr/local/bin/download_channel");
-    if docker_path.exists() {
-        return Ok(docker_path);
-    }
+async fn list_user_playlists(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+) -> ApiResult<Json<Vec<UserPlaylist>>> {
+    let playlists = state
+        .user_data
+        .profile(profile.id)
+        .list_playlists()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(playlists))
+}
 
-    let mut sibling = std::env::current_exe().context("locating backend executable")?;
-    sibling.set_file_name("download_channel");
-    if sibling.exists() {
-        return Ok(sibling);
-    }
+async fn get_user_playlist(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<UserPlaylist>> {
+    state
+        .user_data
+        .profile(profile.id)
+        .get_playlist(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?
+        .map(Json)
+        .ok_or_else(|| ApiError::not_found("playlist not found"))
+}
 
-    bail!("download_channel binary not found");
+/// Creates or renames a playlist. The name defaults to the id.
+async fn put_user_playlist(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<String>,
+    Json(update): Json<PlaylistUpdate>,
+) -> ApiResult<Json<UserPlaylist>> {
+    let name = update
+        .name
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| id.clone());
+    let playlist = state
+        .user_data
+        .profile(profile.id)
+        .upsert_playlist(&id, &name)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(playlist))
 }
 
-fn run_download_channel(binary: &Path, args: Vec<String>) -> Result<()> {
-    let status = std::process::Command::new(binary)
-        .args(args)
-        .status()
-        .context("launching download_channel")?;
-    if status.success() {
-        Ok(())
+async fn delete_user_playlist(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<StatusCode> {
+    let removed = state
+        .user_data
+        .profile(profile.id)
+        .delete_playlist(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
     } else {
-        bail!("download_channel exited with {}", status)
+        Err(ApiError::not_found("playlist not found"))
     }
 }
 
-#[derive(Deserialize)]
-#[serde(untagged)]
-enum OneOrMany<T> {
-    One(T),
-    Many(Vec<T>),
+async fn add_user_playlist_item(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<String>,
+    Json(saved): Json<SavedVideo>,
+) -> ApiResult<Json<UserPlaylist>> {
+    let Some(videoid) = saved.videoid.clone().filter(|value| !value.is_empty()) else {
+        return Err(ApiError::bad_request("videoid is required"));
+    };
+    let data = state.user_data.profile(profile.id);
+    let exists = data
+        .get_playlist(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?
+        .is_some();
+    if !exists {
+        return Err(ApiError::not_found("playlist not found"));
+    }
+    let playlist = data
+        .add_to_playlist(&id, &videoid, &saved)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(playlist))
 }
 
-impl<T> OneOrMany<T> {
-    fn iter(&self) -> Box<dyn Iterator<Item = &T> + '_> {
-        match self {
-            OneOrMany::One(value) => Box::new(std::iter::once(value)),
-            OneOrMany::Many(values) => Box::new(values.iter()),
-        }
+async fn delete_user_playlist_item(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath((id, videoid)): AxumPath<(String, String)>,
+) -> ApiResult<StatusCode> {
+    let removed = state
+        .user_data
+        .profile(profile.id)
+        .remove_from_playlist(&id, &videoid)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found("video not in playlist"))
     }
 }
 
-#[allow(dead_code)]
-#[derive(Deserialize)]
-#[serde(untagged)]
-enum CreatorInfo {
-    Name(String),
-    Object {
-        url: Option<String>,
-        channel_url: Option<String>,
-        uploader_url: Option<String>,
-    },
+async fn list_subscriptions(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+) -> ApiResult<Json<Vec<Subscription>>> {
+    let subscriptions = state
+        .user_data
+        .profile(profile.id)
+        .list_subscriptions()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(subscriptions))
 }
 
-impl CreatorInfo {
-    fn url(&self) -> Option<&str> {
-        match self {
-            CreatorInfo::Name(_) => None,
-            CreatorInfo::Object {
-                url,
-                channel_url,
-                uploader_url,
-            } => url
-                .as_deref()
-                .or(channel_url.as_deref())
-                .or(uploader_url.as_deref()),
-        }
+async fn put_subscription(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<String>,
+    Json(mut subscription): Json<Subscription>,
+) -> ApiResult<StatusCode> {
+    subscription.channel_id = id;
+    state
+        .user_data
+        .profile(profile.id)
+        .subscribe(&subscription)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_subscription(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<StatusCode> {
+    let removed = state
+        .user_data
+        .profile(profile.id)
+        .unsubscribe(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found("subscription not found"))
     }
 }
 
-#[derive(Deserialize)]
-struct MinimalInfo {
-    channel_url: Option<OneOrMany<String>>,
-    uploader_url: Option<OneOrMany<String>>,
-    #[serde(default)]
-    creators: Option<Vec<CreatorInfo>>,
-    channel: Option<OneOrMany<CreatorInfo>>,
-    uploader: Option<OneOrMany<CreatorInfo>>,
+async fn list_history(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+) -> ApiResult<Json<Vec<HistoryItem>>> {
+    let history = state
+        .user_data
+        .profile(profile.id)
+        .list_history()
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(
+        history
+            .into_iter()
+            .map(|(videoid, entry)| HistoryItem { videoid, entry })
+            .collect(),
+    ))
 }
 
-fn first_channel_url(info: &MinimalInfo) -> Option<&str> {
-    if let Some(channel_urls) = &info.channel_url {
-        for url in channel_urls.iter() {
-            if !url.trim().is_empty() {
-                return Some(url);
-            }
-        }
-    }
-    if let Some(uploader_urls) = &info.uploader_url {
-        for url in uploader_urls.iter() {
-            if !url.trim().is_empty() {
-                return Some(url);
-            }
-        }
-    }
-    if let Some(channel) = &info.channel {
-        for creator in channel.iter() {
-            if let Some(url) = creator.url()
-                && !url.trim().is_empty()
-            {
-                return Some(url);
-            }
-        }
-    }
-    if let Some(uploader) = &info.uploader {
-        for creator in uploader.iter() {
-            if let Some(url) = creator.url()
-                && !url.trim().is_empty()
-            {
-                return Some(url);
-            }
-        }
-    }
-    if let Some(creators) = &info.creators {
-        for creator in creators {
-            if let Some(url) = creator.url()
-                && !url.trim().is_empty()
-            {
-                return Some(url);
-            }
-        }
-    }
-    None
+async fn put_history_entry(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<String>,
+    Json(entry): Json<WatchEntry>,
+) -> ApiResult<StatusCode> {
+    state
+        .user_data
+        .profile(profile.id)
+        .record_watch(&id, &entry)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
-fn video_url_for_kind(video_id: &str, kind: MediaCategory) -> String {
-    match kind {
-        MediaCategory::Video => format!("https://www.youtube.com/watch?v={video_id}"),
-        MediaCategory::Short => format!("https://www.youtube.com/shorts/{video_id}"),
+async fn delete_history_entry(
+    State(state): State<AppState>,
+    CurrentProfile(profile): CurrentProfile,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<StatusCode> {
+    let removed = state
+        .user_data
+        .profile(profile.id)
+        .delete_history_entry(&id)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    if removed {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::not_found("history entry not found"))
     }
 }
 
-fn yt_dlp_command() -> std::process::Command {
-    #[cfg(test)]
-    {
-        if let Some(path) = YT_DLP_STUB.lock().unwrap().clone() {
-            return std::process::Command::new(path);
-        }
-    }
-    std::process::Command::new("yt-dlp")
+async fn get_video_stats(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<Vec<VideoStatsSnapshot>>> {
+    media_stats(&state, MediaCategory::Video, &id).await
 }
 
-fn resolve_channel_url(video_id: &str, kind: MediaCategory) -> Result<String> {
-    let video_url = video_url_for_kind(video_id, kind);
-    let output = yt_dlp_command()
-        .arg("--dump-single-json")
-        .arg("--skip-download")
-        .arg("--no-warnings")
-        .arg("--no-progress")
-        .arg(&video_url)
-        .output()
-        .with_context(|| format!("fetching metadata for {video_url}"))?;
+async fn get_short_stats(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<Vec<VideoStatsSnapshot>>> {
+    media_stats(&state, MediaCategory::Short, &id).await
+}
 
-    if !output.status.success() {
-        bail!("yt-dlp failed for {} (status {})", video_url, output.status);
-    }
+async fn get_video_plays(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<PlayStats>> {
+    media_plays(&state, MediaCategory::Video, &id).await
+}
 
-    let info: MinimalInfo =
-        serde_json::from_slice(&output.stdout).context("parsing yt-dlp metadata response")?;
-    let url = first_channel_url(&info).ok_or_else(|| anyhow!("channel url not found"))?;
-    Ok(url.trim().to_string())
+async fn get_short_plays(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<PlayStats>> {
+    media_plays(&state, MediaCategory::Short, &id).await
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use axum::Json;
-    use axum::http::HeaderMap;
-    use axum::{body::to_bytes, extract::State as AxumState};
-    use libsql::{Builder, params};
-    use serde_json::Value;
-    #[cfg(unix)]
-    use std::os::unix::fs::PermissionsExt;
-    use std::sync::Mutex;
-    use std::{env, fs, path::PathBuf, sync::Arc};
-    use tempfile::tempdir;
-    use tokio::time::{Duration, sleep};
+/// Local play count, watched bytes and last play time of one video.
+async fn media_plays(
+    state: &AppState,
+    category: MediaCategory,
+    videoid: &str,
+) -> ApiResult<Json<PlayStats>> {
+    state.get_media(category, videoid).await?;
+    let stats = state
+        .user_data
+        .play_stats(videoid)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(stats))
+}
 
-    struct BackendTestContext {
-        _temp: tempfile::TempDir,
-        db_path: PathBuf,
-        store: MetadataStore,
-        state: AppState,
-    }
+/// Returns the snapshot history after checking the record exists, so unknown
+/// ids answer 404 instead of an empty series.
+async fn media_stats(
+    state: &AppState,
+    category: MediaCategory,
+    videoid: &str,
+) -> ApiResult<Json<Vec<VideoStatsSnapshot>>> {
+    state.get_media(category, videoid).await?;
+    let history = state
+        .reader
+        .get_video_stats_history(videoid)
+        .await
+        .map_err(|err| ApiError::internal(err.to_string()))?;
+    Ok(Json(history))
+}
 
-    static ENV_LOCK: Mutex<()> = Mutex::new(());
+#[derive(Debug, Default, Deserialize)]
+struct CommentParams {
+    /// Also return comments that are gone from YouTube (they carry
+    /// `deleted_upstream_at`).
+    #[serde(default)]
+    include_deleted: bool,
+}
 
-    fn with_env_file(vars: &[(&str, &str)], f: impl FnOnce()) {
-        let _lock = ENV_LOCK.lock().unwrap();
-        let dir = tempdir().unwrap();
-        let mut contents = String::new();
-        for (key, value) in vars {
-            contents.push_str(&format!("{key}=\"{value}\"\n"));
-        }
-        std::fs::write(dir.path().join(".env"), contents).unwrap();
-        let cwd = env::current_dir().unwrap();
-        env::set_current_dir(dir.path()).unwrap();
-        f();
-        env::set_current_dir(cwd).unwrap();
+async fn get_video_comments(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+    Query(params): Query<CommentParams>,
+) -> ApiResult<Json<Vec<CommentRecord>>> {
+    let mut comments = state.get_comments(&id).await?;
+    if !params.include_deleted {
+        comments.retain(|comment| comment.deleted_upstream_at.is_none());
     }
+    Ok(Json(comments))
+}
+
+async fn list_video_subtitles(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<Vec<SubtitleInfo>>> {
+    list_subtitles(state, id, "videos").await
+}
+
+async fn list_short_subtitles(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Json<Vec<SubtitleInfo>>> {
+    list_subtitles(state, id, "shorts").await
+}
+
+async fn list_subtitles(
+    state: AppState,
+    id: String,
+    slug: &'static str,
+) -> ApiResult<Json<Vec<SubtitleInfo>>> {
+    // Build lightweight DTOs that point the frontend to the download
+    // endpoints; the actual subtitle JSON remains cached server side.
+    let mut response = Vec::new();
+    if let Some(collection) = state.get_subtitles(&id).await? {
+        for track in collection.languages {
+            let url = format!("/api/{slug}/{}/subtitles/{}", id, track.code);
+            response.push(SubtitleInfo {
+                code: track.code,
+                name: track.name,
+                url,
+            });
+        }
+    }
+
+    Ok(Json(response))
+}
+
+async fn download_video_subtitle(
+    State(state): State<AppState>,
+    AxumPath((id, code)): AxumPath<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    download_subtitle(state, id, code, &headers).await
+}
+
+async fn download_short_subtitle(
+    State(state): State<AppState>,
+    AxumPath((id, code)): AxumPath<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    download_subtitle(state, id, code, &headers).await
+}
+
+async fn download_subtitle(
+    state: AppState,
+    id: String,
+    code: String,
+    headers: &HeaderMap,
+) -> ApiResult<Response> {
+    ensure_safe_path_segment(&id)?;
+    ensure_safe_path_segment(&code)?;
+
+    let subtitles = state
+        .get_subtitles(&id)
+        .await?
+        .ok_or_else(|| ApiError::not_found("subtitles not available"))?;
+
+    let track = subtitles
+        .languages
+        .into_iter()
+        .find(|track| track.code == code)
+        .ok_or_else(|| ApiError::not_found("subtitle track not found"))?;
+
+    // Prefer the explicit filesystem path recorded during download, but fall
+    // back to the standard `videoid/lang` layout when missing.
+    let path = if let Some(path) = track.path {
+        let path = PathBuf::from(path);
+        let base = state.files.subtitles.join(&id);
+        if path
+            .components()
+            .any(|component| matches!(component, Component::ParentDir))
+            || !path.starts_with(&base)
+        {
+            return Err(ApiError::not_found("subtitle track not found"));
+        }
+        path
+    } else {
+        find_subtitle_file(&state.files.subtitles, &id, &code).await?
+    };
+
+    let mime = MimeGuess::from_path(&path).first();
+    stream_file(
+        path,
+        mime,
+        Some(headers),
+        CachePolicy::MaxAge(MEDIA_MAX_AGE),
+    )
+    .await
+}
+
+async fn download_video_thumbnail(
+    State(state): State<AppState>,
+    AxumPath((id, file)): AxumPath<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    download_thumbnail(state, id, file, &headers).await
+}
+
+async fn download_short_thumbnail(
+    State(state): State<AppState>,
+    AxumPath((id, file)): AxumPath<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    download_thumbnail(state, id, file, &headers).await
+}
+
+async fn download_thumbnail(
+    state: AppState,
+    id: String,
+    file: String,
+    headers: &HeaderMap,
+) -> ApiResult<Response> {
+    ensure_safe_path_segment(&id)?;
+    ensure_safe_path_segment(&file)?;
+    let path = state.files.thumbnails.join(&id).join(&file);
+    stream_file(path, None, Some(headers), CachePolicy::Immutable).await
+}
+
+async fn stream_video_file(
+    State(state): State<AppState>,
+    AxumPath((id, format)): AxumPath<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    stream_media(state, MediaCategory::Video, id, format, &headers).await
+}
+
+async fn stream_short_file(
+    State(state): State<AppState>,
+    AxumPath((id, format)): AxumPath<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    stream_media(state, MediaCategory::Short, id, format, &headers).await
+}
+
+async fn stream_media(
+    state: AppState,
+    category: MediaCategory,
+    id: String,
+    format: String,
+    headers: &HeaderMap,
+) -> ApiResult<Response> {
+    ensure_safe_path_segment(&id)?;
+    ensure_safe_path_segment(&format)?;
+
+    // We load metadata first so we can map the requested format slug to a file
+    // path and mime type before hitting the disk.
+    let record = state.get_media(category, &id).await?;
+    let source = find_source(&record, &format)?;
+    let path = source_path(&state.files, category, &id, source);
+
+    let play = PlayTap {
+        tracker: state.plays.clone(),
+        videoid: id,
+        client: play_client(headers),
+        file_size: 0,
+        pending: 0,
+    };
+    stream_file_with_play(
+        path,
+        source.mime_type.as_ref().and_then(|mime| mime.parse().ok()),
+        Some(headers),
+        CachePolicy::MaxAge(MEDIA_MAX_AGE),
+        Some(play),
+    )
+    .await
+}
+
+fn find_source<'a>(record: &'a VideoRecord, format: &str) -> ApiResult<&'a VideoSource> {
+    record
+        .sources
+        .iter()
+        .find(|source| source_key(source).as_deref() == Some(format))
+        .ok_or_else(|| ApiError::not_found("requested format not found"))
+}
+
+/// Where the file behind `source` lives: its recorded path, or the default
+/// `<kind>/<id>/<id>_<format>.<ext>` layout for older rows without one.
+fn source_path(
+    files: &FilePaths,
+    category: MediaCategory,
+    id: &str,
+    source: &VideoSource,
+) -> PathBuf {
+    match &source.path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let format = source_key(source).unwrap_or_default();
+            let ext = source.ext.as_deref().unwrap_or("mp4");
+            files
+                .media_dir(category)
+                .join(id)
+                .join(format!("{}_{}.{}", id, format, ext))
+        }
+    }
+}
+
+async fn video_hls_master(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Response> {
+    hls_master(state, MediaCategory::Video, id).await
+}
+
+async fn short_hls_master(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Response> {
+    hls_master(state, MediaCategory::Short, id).await
+}
+
+/// Master playlist with one variant per downloaded format. Variants point
+/// at `{format}/index.m3u8`, which is packaged on first request, so listing
+/// them costs nothing up front.
+async fn hls_master(state: AppState, category: MediaCategory, id: String) -> ApiResult<Response> {
+    ensure_single_path_segment(&id)?;
+    let record = state.get_media(category, &id).await?;
+
+    let variants: Vec<HlsVariant> = record
+        .sources
+        .iter()
+        .filter(|source| {
+            !source
+                .mime_type
+                .as_deref()
+                .is_some_and(|mime| mime.starts_with("audio/"))
+        })
+        .filter_map(|source| {
+            let key = source_key(source)?;
+            source_path(&state.files, category, &id, source)
+                .is_file()
+                .then(|| {
+                    HlsVariant::for_source(
+                        source,
+                        record.duration,
+                        format!("{key}/{}", hls::VARIANT_PLAYLIST),
+                    )
+                })
+        })
+        .collect();
+    if variants.is_empty() {
+        return Err(ApiError::not_found("no downloaded formats to stream"));
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, hls::PLAYLIST_MIME),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        hls::master_playlist(&variants),
+    )
+        .into_response())
+}
+
+async fn video_hls_file(
+    State(state): State<AppState>,
+    AxumPath((id, format, file)): AxumPath<(String, String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    hls_file(state, MediaCategory::Video, id, format, file, &headers).await
+}
+
+async fn short_hls_file(
+    State(state): State<AppState>,
+    AxumPath((id, format, file)): AxumPath<(String, String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    hls_file(state, MediaCategory::Short, id, format, file, &headers).await
+}
+
+/// Serves a variant playlist, init segment or media segment from the HLS
+/// cache, packaging the variant with ffmpeg first when the cache is missing
+/// or older than the downloaded file.
+async fn hls_file(
+    state: AppState,
+    category: MediaCategory,
+    id: String,
+    format: String,
+    file: String,
+    headers: &HeaderMap,
+) -> ApiResult<Response> {
+    ensure_single_path_segment(&id)?;
+    ensure_single_path_segment(&format)?;
+    ensure_single_path_segment(&file)?;
+    // Repackaging reuses the same names (`init.mp4`, `0.m4s`, ...), so
+    // every file is revalidated against its new ETag.
+    let mime = match Path::new(&file).extension().and_then(|ext| ext.to_str()) {
+        Some("m3u8") => hls::PLAYLIST_MIME,
+        Some("m4s") => "video/iso.segment",
+        Some("mp4") => "video/mp4",
+        _ => return Err(ApiError::not_found("file not found")),
+    };
+
+    let record = state.get_media(category, &id).await?;
+    let source = find_source(&record, &format)?;
+    let input = source_path(&state.files, category, &id, source);
+    if !input.is_file() {
+        return Err(ApiError::not_found("file not found"));
+    }
+    let output_dir = state.files.hls.join(&id).join(&format);
+    let _packaged = state
+        .hls
+        .ensure(&input, &output_dir)
+        .await
+        .map_err(|err| ApiError::internal(format!("{err:#}")))?;
+
+    // Segments count towards the play threshold of the whole download.
+    let play = (mime != hls::PLAYLIST_MIME).then(|| PlayTap {
+        tracker: state.plays.clone(),
+        client: play_client(headers),
+        file_size: fs::metadata(&input).map(|meta| meta.len()).unwrap_or(0),
+        videoid: id,
+        pending: 0,
+    });
+    stream_file_with_play(
+        output_dir.join(&file),
+        mime.parse().ok(),
+        Some(headers),
+        CachePolicy::Revalidate,
+        play,
+    )
+    .await
+}
+
+async fn video_dash_manifest(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Response> {
+    dash_manifest(state, MediaCategory::Video, id).await
+}
+
+async fn short_dash_manifest(
+    State(state): State<AppState>,
+    AxumPath(id): AxumPath<String>,
+) -> ApiResult<Response> {
+    dash_manifest(state, MediaCategory::Short, id).await
+}
+
+/// DASH manifest over the video's separate video and audio downloads.
+/// Only indexed streams whose file is on disk are listed, and at least one
+/// of them has to carry video.
+async fn dash_manifest(
+    state: AppState,
+    category: MediaCategory,
+    id: String,
+) -> ApiResult<Response> {
+    ensure_single_path_segment(&id)?;
+    let record = state.get_media(category, &id).await?;
+
+    let streams: Vec<AdaptiveStream> = record
+        .adaptive_streams
+        .iter()
+        .filter(|stream| {
+            dash::is_streamable(stream)
+                && adaptive_path(&state.files, category, &id, stream).is_file()
+        })
+        .cloned()
+        .collect();
+    if !streams
+        .iter()
+        .any(|stream| stream.kind == AdaptiveKind::Video)
+    {
+        return Err(ApiError::not_found("no adaptive streams downloaded"));
+    }
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, dash::MANIFEST_MIME),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        dash::manifest(record.duration, &streams),
+    )
+        .into_response())
+}
+
+async fn stream_video_adaptive(
+    State(state): State<AppState>,
+    AxumPath((id, format)): AxumPath<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    stream_adaptive(state, MediaCategory::Video, id, format, &headers).await
+}
+
+async fn stream_short_adaptive(
+    State(state): State<AppState>,
+    AxumPath((id, format)): AxumPath<(String, String)>,
+    headers: HeaderMap,
+) -> ApiResult<Response> {
+    stream_adaptive(state, MediaCategory::Short, id, format, &headers).await
+}
+
+/// Serves one adaptive stream for the DASH player, which fetches the
+/// ranges listed in the manifest. Only the video track counts towards play
+/// stats so a session is not counted twice.
+async fn stream_adaptive(
+    state: AppState,
+    category: MediaCategory,
+    id: String,
+    format: String,
+    headers: &HeaderMap,
+) -> ApiResult<Response> {
+    ensure_single_path_segment(&id)?;
+    ensure_single_path_segment(&format)?;
+    let record = state.get_media(category, &id).await?;
+    let stream = record
+        .adaptive_streams
+        .iter()
+        .find(|stream| normalize_format_id(stream.format_id.trim()) == format)
+        .ok_or_else(|| ApiError::not_found("requested format not found"))?;
+    let path = adaptive_path(&state.files, category, &id, stream);
+
+    let play = (stream.kind == AdaptiveKind::Video).then(|| PlayTap {
+        tracker: state.plays.clone(),
+        videoid: id,
+        client: play_client(headers),
+        file_size: 0,
+        pending: 0,
+    });
+    stream_file_with_play(
+        path,
+        stream.mime_type.parse().ok(),
+        Some(headers),
+        CachePolicy::MaxAge(MEDIA_MAX_AGE),
+        play,
+    )
+    .await
+}
+
+/// Same as [`source_path`], for adaptive streams.
+fn adaptive_path(
+    files: &FilePaths,
+    category: MediaCategory,
+    id: &str,
+    stream: &AdaptiveStream,
+) -> PathBuf {
+    match &stream.path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            let format = normalize_format_id(stream.format_id.trim());
+            let ext = stream.ext.as_deref().unwrap_or("mp4");
+            files
+                .media_dir(category)
+                .join(id)
+                .join(format!("{}_{}.{}", id, format, ext))
+        }
+    }
+}
+
+/// Lightweight response that exposes a download URL for each subtitle track.
+#[derive(serde::Serialize)]
+struct SubtitleInfo {
+    code: String,
+    name: String,
+    url: String,
+}
+
+/// Channel row exposed by the API. Filesystem paths are swapped for URLs that
+/// point at `/api/channels/{id}/images/{kind}`.
+#[derive(Serialize)]
+struct ChannelInfo {
+    #[serde(flatten)]
+    channel: ChannelRecord,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    avatar_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    banner_url: Option<String>,
+}
+
+impl From<ChannelRecord> for ChannelInfo {
+    fn from(mut channel: ChannelRecord) -> Self {
+        let image_url = |path: Option<String>, kind: &str| {
+            path.map(|_| format!("/api/channels/{}/images/{kind}", channel.channel_id))
+        };
+        let avatar_url = image_url(channel.avatar_path.take(), "avatar");
+        let banner_url = image_url(channel.banner_path.take(), "banner");
+        Self {
+            channel,
+            avatar_url,
+            banner_url,
+        }
+    }
+}
+
+/// Playlist metadata plus its ordered entries, returned by `/api/playlists/{id}`.
+#[derive(Serialize)]
+struct PlaylistDetail {
+    #[serde(flatten)]
+    playlist: PlaylistRecord,
+    entries: Vec<PlaylistItem>,
+}
+
+#[derive(Serialize)]
+struct PlaylistItem {
+    #[serde(flatten)]
+    entry: PlaylistEntry,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    record: Option<VideoRecord>,
+}
+
+/// Payload returned by `/api/bootstrap` so the client can hydrate offline.
+#[derive(Clone, Serialize)]
+struct BootstrapPayload {
+    /// Change log position the payload is current to; the starting point
+    /// for `/api/changes?since=`.
+    seq: i64,
+    videos: Vec<VideoRecord>,
+    shorts: Vec<VideoRecord>,
+    subtitles: Vec<SubtitleCollection>,
+    comments: Vec<CommentRecord>,
+}
+
+impl AppState {
+    async fn ensure_fresh_cache(&self) -> ApiResult<()> {
+        let version = self
+            .reader
+            .data_version()
+            .await
+            .map_err(|err| ApiError::internal(err.to_string()))?;
+
+        let mut last = self.cache.last_db_version.write();
+        if let Some(previous) = *last
+            && version != previous
+        {
+            self.cache.clear();
+        }
+        *last = Some(version);
+        Ok(())
+    }
+
+    /// Returns a cached snapshot containing everything the SPA needs to boot
+    /// without hitting follow-up endpoints (videos, shorts, subtitles,
+    /// comments). The heavy lifting runs in a blocking task because SQLite is a
+    /// synchronous API.
+    async fn get_bootstrap(&self) -> ApiResult<Arc<BootstrapPayload>> {
+        self.ensure_fresh_cache().await?;
+        if let Some(cached) = self.cache.bootstrap.read().clone() {
+            return Ok(cached);
+        }
+
+        // Read before the listings so a write racing with them is re-sent by
+        // the next delta rather than lost.
+        let seq = self
+            .reader
+            .latest_change_seq()
+            .await
+            .map_err(|err| ApiError::internal(err.to_string()))?;
+        let videos = self
+            .reader
+            .list_videos()
+            .await
+            .map_err(|err| ApiError::internal(err.to_string()))?;
+        let shorts = self
+            .reader
+            .list_shorts()
+            .await
+            .map_err(|err| ApiError::internal(err.to_string()))?;
+        let subtitles = self
+            .reader
+            .list_subtitles()
+            .await
+            .map_err(|err| ApiError::internal(err.to_string()))?
+            .into_iter()
+            .map(sanitize_subtitle_collection)
+            .collect();
+        let comments = self
+            .reader
+            .list_all_comments(false)
+            .await
+            .map_err(|err| ApiError::internal(err.to_string()))?;
+        let payload = BootstrapPayload {
+            seq,
+            videos: sanitize_video_records(&videos),
+            shorts: sanitize_video_records(&shorts),
+            subtitles,
+            comments,
+        };
+
+        let payload = Arc::new(payload);
+        self.cache.bootstrap.write().replace(payload.clone());
+        Ok(payload)
+    }
+
+    /// Retrieves every video/short record, memoizing both the list and the
+    /// individual details map for quick follow-up lookups.
+    async fn get_media_list(&self, category: MediaCategory) -> ApiResult<Arc<Vec<VideoRecord>>> {
+        self.ensure_fresh_cache().await?;
+        if let Some(cached) = self.cache.media_list(category).read().clone() {
+            return Ok(cached);
+        }
+
+        let records = match category {
+            MediaCategory::Video => self
+                .reader
+                .list_videos()
+                .await
+                .map_err(|err| ApiError::internal(err.to_string()))?,
+            MediaCategory::Short => self
+                .reader
+                .list_shorts()
+                .await
+                .map_err(|err| ApiError::internal(err.to_string()))?,
+        };
+
+        let records = Arc::new(records);
+        self.cache
+            .media_list(category)
+            .write()
+            .replace(records.clone());
+
+        let mut details = self.cache.media_details(category).write();
+        for record in records.iter() {
+            details.insert(record.videoid.clone(), record.clone());
+        }
+
+        Ok(records)
+    }
+
+    /// Loads metadata for a single video or short, preferring the cache before
+    /// falling back to SQLite.
+    async fn get_media(&self, category: MediaCategory, videoid: &str) -> ApiResult<VideoRecord> {
+        self.ensure_fresh_cache().await?;
+        if let Some(record) = self
+            .cache
+            .media_details(category)
+            .read()
+            .get(videoid)
+            .cloned()
+        {
+            return Ok(record);
+        }
+
+        let result = match category {
+            MediaCategory::Video => self
+                .reader
+                .get_video(videoid)
+                .await
+                .map_err(|err| ApiError::internal(err.to_string()))?,
+            MediaCategory::Short => self
+                .reader
+                .get_short(videoid)
+                .await
+                .map_err(|err| ApiError::internal(err.to_string()))?,
+        };
+
+        let record = result.ok_or_else(|| ApiError::not_found("video not found"))?;
+
+        self.cache
+            .media_details(category)
+            .write()
+            .insert(videoid.to_owned(), record.clone());
+
+        Ok(record)
+    }
+
+    /// Lazy-loads comment threads; we store them keyed by id because comment
+    /// payloads are far smaller than video blobs. Comments deleted upstream
+    /// are included; handlers filter them.
+    async fn get_comments(&self, videoid: &str) -> ApiResult<Vec<CommentRecord>> {
+        self.ensure_fresh_cache().await?;
+        if let Some(cached) = self.cache.comments.read().get(videoid).cloned() {
+            return Ok(cached);
+        }
+
+        let comments = self
+            .reader
+            .get_comments(videoid, true)
+            .await
+            .map_err(|err| ApiError::internal(err.to_string()))?;
+
+        self.cache
+            .comments
+            .write()
+            .insert(videoid.to_owned(), comments.clone());
+
+        Ok(comments)
+    }
+
+    /// Provides subtitle metadata if available. Not every video has subtitles
+    /// so the API returns an Option.
+    async fn get_subtitles(&self, videoid: &str) -> ApiResult<Option<SubtitleCollection>> {
+        self.ensure_fresh_cache().await?;
+        if let Some(cached) = self.cache.subtitles.read().get(videoid).cloned() {
+            return Ok(Some(cached));
+        }
+
+        let result = self
+            .reader
+            .get_subtitles(videoid)
+            .await
+            .map_err(|err| ApiError::internal(err.to_string()))?;
+
+        if let Some(collection) = &result {
+            self.cache
+                .subtitles
+                .write()
+                .insert(videoid.to_owned(), collection.clone());
+        }
+
+        Ok(result)
+    }
+}
+
+/// Normalizes a VideoSource URL by keeping only the trailing segment. During
+/// download we store files named `{videoid}_{format}` and the format parameter
+/// is the only piece users need to specify.
+fn source_key(source: &VideoSource) -> Option<String> {
+    let format_id = source.format_id.trim();
+    if format_id.is_empty() {
+        return None;
+    }
+    Some(normalize_format_id(format_id))
+}
+
+fn normalize_format_id(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| match c {
+            '/' | ':' | ' ' => '_',
+            _ => c,
+        })
+        .collect()
+}
+
+/// Validates that a single dynamic path segment never escapes its base folder.
+fn ensure_safe_path_segment(value: &str) -> ApiResult<()> {
+    if value.is_empty()
+        || Path::new(value)
+            .components()
+            .any(|component| !matches!(component, Component::Normal(_)))
+    {
+        return Err(ApiError::not_found("file not found"));
+    }
+
+    Ok(())
+}
+
+/// Accepts exactly one normal path component, so `root.join(value)` is always
+/// a direct child of `root`.
+fn ensure_single_path_segment(value: &str) -> ApiResult<()> {
+    let mut components = Path::new(value).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => Ok(()),
+        _ => Err(ApiError::not_found("file not found")),
+    }
+}
+
+fn media_kind_subdir(category: MediaCategory) -> &'static str {
+    match category {
+        MediaCategory::Video => VIDEOS_SUBDIR,
+        MediaCategory::Short => SHORTS_SUBDIR,
+    }
+}
+
+/// Removes `root/<id>` if present. A symlink is unlinked rather than
+/// followed, so nothing outside `MEDIA_ROOT` is ever touched.
+async fn remove_artifact_dir(root: &Path, id: &str) -> std::io::Result<bool> {
+    let path = root.join(id);
+    let metadata = match tokio::fs::symlink_metadata(&path).await {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+    if metadata.is_dir() {
+        tokio::fs::remove_dir_all(&path).await?;
+    } else {
+        tokio::fs::remove_file(&path).await?;
+    }
+    Ok(true)
+}
+
+/// Rewrites yt-dlp's archive without the `<extractor> <id>` line for `id`.
+async fn remove_archive_entry(archive: &Path, id: &str) -> std::io::Result<bool> {
+    let contents = match tokio::fs::read_to_string(archive).await {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err),
+    };
+
+    let mut removed = false;
+    let mut kept = String::with_capacity(contents.len());
+    for line in contents.lines() {
+        if line.split_whitespace().last() == Some(id) {
+            removed = true;
+            continue;
+        }
+        kept.push_str(line);
+        kept.push('\n');
+    }
+
+    if removed {
+        let tmp_path = archive.with_extension("tmp");
+        tokio::fs::write(&tmp_path, kept).await?;
+        tokio::fs::rename(&tmp_path, archive).await?;
+    }
+    Ok(removed)
+}
+
+async fn find_subtitle_file(subtitles_root: &Path, id: &str, code: &str) -> ApiResult<PathBuf> {
+    let dir = subtitles_root.join(id);
+    let mut entries = tokio::fs::read_dir(&dir)
+        .await
+        .map_err(|_| ApiError::not_found("subtitle track not found"))?;
+    let prefix = format!("{id}.{code}.");
+    let mut best: Option<(usize, PathBuf)> = None;
+    while let Some(entry) = entries
+        .next_entry()
+        .await
+        .map_err(|_| ApiError::not_found("subtitle track not found"))?
+    {
+        let path = entry.path();
+        if !path.is_file() {
+            continue;
+        }
+        let name = entry
+            .file_name()
+            .into_string()
+            .unwrap_or_else(|os| os.to_string_lossy().into_owned());
+        if name.starts_with(&prefix) {
+            let ext = Path::new(&name)
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .unwrap_or("");
+            let rank = subtitle_extension_rank(ext);
+            if rank >= 10 {
+                continue;
+            }
+            match best {
+                Some((best_rank, _)) if rank >= best_rank => {}
+                _ => best = Some((rank, path)),
+            }
+        }
+    }
+    best.map(|(_, path)| path)
+        .ok_or_else(|| ApiError::not_found("subtitle track not found"))
+}
+
+async fn stream_file(
+    path: PathBuf,
+    mime: Option<Mime>,
+    headers: Option<&HeaderMap>,
+    cache: CachePolicy,
+) -> ApiResult<Response> {
+    stream_file_with_play(path, mime, headers, cache, None).await
+}
+
+/// How long clients may reuse a file response without asking again. Every
+/// file response also carries `ETag` and `Last-Modified`, so revalidating
+/// costs a 304 rather than the whole file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CachePolicy {
+    /// Ask every time: `index.html` and other SPA assets, `metadata.db` and
+    /// HLS playlists, which change in place.
+    Revalidate,
+    /// Reuse for a while: media, subtitles and channel images only change
+    /// when something is downloaded again.
+    MaxAge(Duration),
+    /// Never changes under the same URL: thumbnails.
+    Immutable,
+}
+
+/// Media, subtitles and channel images are reused for a day.
+const MEDIA_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+impl CachePolicy {
+    fn header_value(self) -> String {
+        match self {
+            CachePolicy::Revalidate => "no-cache".to_owned(),
+            CachePolicy::MaxAge(age) => format!("max-age={}", age.as_secs()),
+            CachePolicy::Immutable => "max-age=31536000, immutable".to_owned(),
+        }
+    }
+}
+
+/// Validators of a file, derived from its size and modification time like
+/// nginx does, so they change whenever the file is replaced.
+struct FileValidators {
+    etag: String,
+    /// Modification time truncated to whole seconds, as HTTP dates are.
+    modified: Option<i64>,
+}
+
+impl FileValidators {
+    fn new(metadata: &std::fs::Metadata) -> Self {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok());
+        let etag = match modified {
+            Some(since) => format!(
+                "\"{:x}-{:x}-{:x}\"",
+                since.as_secs(),
+                since.subsec_nanos(),
+                metadata.len()
+            ),
+            None => format!("\"{:x}\"", metadata.len()),
+        };
+        Self {
+            etag,
+            modified: modified.and_then(|since| i64::try_from(since.as_secs()).ok()),
+        }
+    }
+
+    fn last_modified(&self) -> Option<String> {
+        let modified = chrono::DateTime::from_timestamp(self.modified?, 0)?;
+        Some(modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string())
+    }
+
+    /// `If-None-Match` wins over `If-Modified-Since` (RFC 9110 13.2.2).
+    fn not_modified(&self, headers: &HeaderMap) -> bool {
+        if let Some(value) = headers.get(header::IF_NONE_MATCH) {
+            let Ok(value) = value.to_str() else {
+                return false;
+            };
+            return value
+                .split(',')
+                .map(str::trim)
+                .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == self.etag);
+        }
+        match (
+            headers
+                .get(header::IF_MODIFIED_SINCE)
+                .and_then(parse_http_date),
+            self.modified,
+        ) {
+            (Some(since), Some(modified)) => modified <= since,
+            _ => false,
+        }
+    }
+
+    /// Whether a `Range` request may be answered partially. A stale
+    /// `If-Range` means the client holds bytes of an older file and gets
+    /// the whole new one instead. Only strong validators count, so weak
+    /// tags never match.
+    fn range_applies(&self, headers: &HeaderMap) -> bool {
+        let Some(value) = headers.get(header::IF_RANGE) else {
+            return true;
+        };
+        if let Ok(tag) = value.to_str()
+            && tag.trim().starts_with('"')
+        {
+            return tag.trim() == self.etag;
+        }
+        parse_http_date(value).is_some_and(|date| Some(date) == self.modified)
+    }
+}
+
+/// Parses an IMF-fixdate (`Sun, 06 Nov 1994 08:49:37 GMT`) into seconds
+/// since the epoch.
+fn parse_http_date(value: &header::HeaderValue) -> Option<i64> {
+    let value = value.to_str().ok()?;
+    chrono::DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.timestamp())
+}
+
+/// Reports the bytes of a media response to the [`PlayTracker`] as the
+/// body is read.
+struct PlayTap {
+    tracker: PlayTracker,
+    videoid: String,
+    client: String,
+    /// Size the play threshold is measured against; 0 means the size of
+    /// the served file.
+    file_size: u64,
+    pending: u64,
+}
+
+impl PlayTap {
+    fn flush(&mut self) {
+        let bytes = std::mem::take(&mut self.pending);
+        self.tracker
+            .record(&self.videoid, &self.client, self.file_size, bytes);
+    }
+}
+
+/// File reader that counts what the client actually received, so aborted
+/// and seeked-away range requests only count the bytes that were sent.
+struct PlayCountingReader<R> {
+    inner: R,
+    tap: PlayTap,
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for PlayCountingReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let before = buf.filled().len();
+        let poll = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = poll {
+            this.tap.pending += (buf.filled().len() - before) as u64;
+            if this.tap.pending >= PLAY_FLUSH_BYTES {
+                this.tap.flush();
+            }
+        }
+        poll
+    }
+}
+
+impl<R> Drop for PlayCountingReader<R> {
+    fn drop(&mut self) {
+        self.tap.flush();
+    }
+}
+
+fn file_body<R>(reader: R, play: Option<PlayTap>) -> Body
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    match play {
+        Some(tap) => {
+            Body::from_stream(ReaderStream::new(PlayCountingReader { inner: reader, tap }))
+        }
+        None => Body::from_stream(ReaderStream::new(reader)),
+    }
+}
+
+/// Serves a file with Range support. Media streams pass a [`PlayTap`] so
+/// the bytes they send count towards the video's play stats.
+async fn stream_file_with_play(
+    path: PathBuf,
+    mime: Option<Mime>,
+    headers: Option<&HeaderMap>,
+    cache: CachePolicy,
+    mut play: Option<PlayTap>,
+) -> ApiResult<Response> {
+    let mut file = File::open(&path)
+        .await
+        .map_err(|_| ApiError::not_found("file not found"))?;
+    let metadata = file
+        .metadata()
+        .await
+        .map_err(|_| ApiError::not_found("file not found"))?;
+    let size = metadata.len();
+    if let Some(tap) = play.as_mut().filter(|tap| tap.file_size == 0) {
+        tap.file_size = size;
+    }
+
+    let guessed = mime.or_else(|| MimeGuess::from_path(&path).first());
+    let validators = FileValidators::new(&metadata);
+    let range = headers
+        .filter(|headers| validators.range_applies(headers))
+        .and_then(|headers| headers.get(header::RANGE))
+        .and_then(|value| parse_range_header(value, size));
+
+    let mut response = if headers.is_some_and(|headers| validators.not_modified(headers)) {
+        // Nothing is streamed, so nothing counts as played either.
+        let mut response = Response::new(Body::empty());
+        *response.status_mut() = StatusCode::NOT_MODIFIED;
+        response
+    } else if let Some((start, end)) = range {
+        if start >= size {
+            let mut response = Response::new(Body::empty());
+            *response.status_mut() = StatusCode::RANGE_NOT_SATISFIABLE;
+            response.headers_mut().insert(
+                header::CONTENT_RANGE,
+                format!("bytes */{}", size).parse().unwrap(),
+            );
+            response
+        } else {
+            let end = end.min(size.saturating_sub(1));
+            let length = end - start + 1;
+            file.seek(std::io::SeekFrom::Start(start))
+                .await
+                .map_err(|_| ApiError::not_found("file not found"))?;
+            let mut response = file_body(file.take(length), play).into_response();
+            *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+            response.headers_mut().insert(
+                header::CONTENT_RANGE,
+                format!("bytes {}-{}/{}", start, end, size).parse().unwrap(),
+            );
+            response
+                .headers_mut()
+                .insert(header::CONTENT_LENGTH, length.to_string().parse().unwrap());
+            response
+        }
+    } else {
+        file_body(file, play).into_response()
+    };
+
+    let response_headers = response.headers_mut();
+    response_headers.insert(header::ACCEPT_RANGES, "bytes".parse().unwrap());
+    if let Ok(value) = validators.etag.parse() {
+        response_headers.insert(header::ETAG, value);
+    }
+    if let Some(value) = validators
+        .last_modified()
+        .and_then(|date| date.parse().ok())
+    {
+        response_headers.insert(header::LAST_MODIFIED, value);
+    }
+    response_headers.insert(header::CACHE_CONTROL, cache.header_value().parse().unwrap());
+    if let Some(mime) = guessed
+        && let Ok(value) = mime.to_string().parse()
+    {
+        response_headers.insert(header::CONTENT_TYPE, value);
+    }
+
+    Ok(response)
+}
+
+fn sanitize_video_records(records: &[VideoRecord]) -> Vec<VideoRecord> {
+    records.iter().map(sanitize_video_record).collect()
+}
+
+fn sanitize_video_record(record: &VideoRecord) -> VideoRecord {
+    let mut clone = record.clone();
+    for source in &mut clone.sources {
+        source.path = None;
+    }
+    for stream in &mut clone.adaptive_streams {
+        stream.path = None;
+    }
+    clone
+}
+
+fn sanitize_subtitle_collection(collection: SubtitleCollection) -> SubtitleCollection {
+    let mut clone = collection;
+    for track in &mut clone.languages {
+        track.path = None;
+    }
+    clone
+}
+
+fn parse_range_header(value: &header::HeaderValue, size: u64) -> Option<(u64, u64)> {
+    let value = value.to_str().ok()?;
+    let value = value.trim();
+    let mut parts = value.split('=');
+    let unit = parts.next()?.trim();
+    if unit != "bytes" {
+        return None;
+    }
+    let range = parts.next()?.trim();
+    if range.is_empty() {
+        return None;
+    }
+    let (start_str, end_str) = range.split_once('-')?;
+
+    if start_str.is_empty() {
+        // Suffix range: "-N" means last N bytes.
+        let suffix_len: u64 = end_str.parse().ok()?;
+        if suffix_len == 0 {
+            return None;
+        }
+        if suffix_len >= size {
+            return Some((0, size.saturating_sub(1)));
+        }
+        return Some((size - suffix_len, size.saturating_sub(1)));
+    }
+
+    let start: u64 = start_str.parse().ok()?;
+    let end = if end_str.is_empty() {
+        size.saturating_sub(1)
+    } else {
+        end_str.parse().ok()?
+    };
+    if end < start {
+        return None;
+    }
+    Some((start, end))
+}
+
+/// Number of pooled metadata read connections from `NEWTUBE_DB_READERS`.
+/// Missing, unparsable or zero values fall back to the default.
+fn parse_reader_pool_size(raw: Option<&str>) -> usize {
+    match raw.map(|value| value.parse::<usize>()) {
+        Some(Ok(size)) if size > 0 => size,
+        Some(_) => {
+            eprintln!(
+                "Ignoring invalid NEWTUBE_DB_READERS value; using {DEFAULT_READER_POOL_SIZE}"
+            );
+            DEFAULT_READER_POOL_SIZE
+        }
+        None => DEFAULT_READER_POOL_SIZE,
+    }
+}
+
+/// One year; longer intervals are treated as typos.
+const MAX_SCRUB_INTERVAL_HOURS: u64 = 24 * 366;
+
+/// Period of the background scrub from `NEWTUBE_SCRUB_INTERVAL_HOURS`.
+/// Missing, zero or unparsable values leave the schedule off.
+fn parse_scrub_interval(raw: Option<&str>) -> Option<std::time::Duration> {
+    match raw.map(|value| value.parse::<u64>()) {
+        Some(Ok(0)) | None => None,
+        Some(Ok(hours)) if hours <= MAX_SCRUB_INTERVAL_HOURS => {
+            Some(std::time::Duration::from_secs(hours * 3600))
+        }
+        Some(_) => {
+            eprintln!("Ignoring invalid NEWTUBE_SCRUB_INTERVAL_HOURS value; scrub schedule is off");
+            None
+        }
+    }
+}
+
+fn env_or_file_value(key: &str, file_vars: &HashMap<String, String>) -> Option<String> {
+    std::env::var(key)
+        .ok()
+        .and_then(|value| {
+            let trimmed = value.trim();
+            if trimmed.is_empty() {
+                None
+            } else {
+                Some(trimmed.to_string())
+            }
+        })
+        .or_else(|| file_vars.get(key).cloned())
+}
+
+fn write_json_atomic<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("creating {}", parent.display()))?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    let payload = serde_json::to_vec_pretty(value)?;
+    fs::write(&tmp_path, payload)?;
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn write_progress_report(path: &Path, progress: u8, message: &str) {
+    let report = ProgressReport {
+        progress: progress.min(100),
+        message: message.to_string(),
+    };
+    if let Err(err) = write_json_atomic(path, &report) {
+        eprintln!("Failed to write progress report: {err}");
+    }
+}
+
+fn read_progress_report(path: &Path) -> Option<ProgressReport> {
+    let raw = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&raw).ok()
+}
+
+/// Status as served by `/api/downloads/{id}` and pushed to `/api/events`.
+fn job_status(inner: &DownloadManagerInner, job_id: &str) -> Option<DownloadJobStatus> {
+    let jobs = inner.jobs.lock();
+    let job = jobs.get(job_id)?;
+    let (progress, message) = job
+        .progress
+        .as_ref()
+        .map(|report| (report.progress, report.message.clone()))
+        .unwrap_or((0, job.message.clone()));
+    Some(DownloadJobStatus {
+        id: job.id.clone(),
+        status: job.status.as_str().to_string(),
+        progress,
+        message,
+    })
+}
+
+fn publish_job_status(inner: &DownloadManagerInner, job_id: &str) {
+    if let Some(status) = job_status(inner, job_id) {
+        inner.events.publish(ServerEvent::Download(status));
+    }
+}
+
+/// Re-reads the job's progress file when its modification time moved (or
+/// always with `force`). Returns whether the in-memory report changed.
+fn refresh_job_progress(inner: &DownloadManagerInner, job_id: &str, force: bool) -> bool {
+    let Some((path, seen)) = inner
+        .jobs
+        .lock()
+        .get(job_id)
+        .map(|job| (job.progress_file.clone(), job.progress_modified))
+    else {
+        return false;
+    };
+    let modified = fs::metadata(&path).and_then(|meta| meta.modified()).ok();
+    if !force && modified.is_some() && modified == seen {
+        return false;
+    }
+    let report = read_progress_report(&path);
+    let mut jobs = inner.jobs.lock();
+    let Some(job) = jobs.get_mut(job_id) else {
+        return false;
+    };
+    job.progress_modified = modified;
+    if report.is_none() || report == job.progress {
+        return false;
+    }
+    job.progress = report;
+    true
+}
+
+/// Polls a job's progress file while it runs and pushes every change to
+/// event subscribers. The download runs in a separate process, so the file
+/// is the only progress channel it has.
+fn watch_job_progress(inner: Arc<DownloadManagerInner>, job_id: String) {
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(PROGRESS_POLL_INTERVAL).await;
+            let finished = match inner.jobs.lock().get(&job_id) {
+                Some(job) => matches!(job.status, DownloadStatus::Success | DownloadStatus::Failed),
+                None => true,
+            };
+            if finished {
+                break;
+            }
+            if refresh_job_progress(&inner, &job_id, false) {
+                publish_job_status(&inner, &job_id);
+            }
+        }
+    });
+}
+
+fn update_job_status(
+    inner: &DownloadManagerInner,
+    job_id: &str,
+    status: DownloadStatus,
+    message: &str,
+) {
+    // Transitions are rare, so pick up the final report unconditionally.
+    refresh_job_progress(inner, job_id, true);
+    if let Some(job) = inner.jobs.lock().get_mut(job_id) {
+        job.status = status;
+        job.message = message.to_string();
+    }
+    publish_job_status(inner, job_id);
+}
+
+fn media_kind_label(kind: MediaCategory) -> &'static str {
+    match kind {
+        MediaCategory::Video => "video",
+        MediaCategory::Short => "short",
+    }
+}
+
+fn parse_media_kind(value: Option<&str>) -> MediaCategory {
+    match value.map(|value| value.trim().to_ascii_lowercase()) {
+        Some(ref value) if value == "short" || value == "shorts" => MediaCategory::Short,
+        _ => MediaCategory::Video,
+    }
+}
+
+#[cfg(test)]
+static DOWNLOAD_CHANNEL_STUB: std::sync::Mutex<Option<PathBuf>> = std::sync::Mutex::new(None);
+
+#[cfg(test)]
+static YT_DLP_STUB: std::sync::Mutex<Option<PathBuf>> = std::sync::Mutex::new(None);
+
+#[cfg(test)]
+struct StubGuard {
+    slot: &'static std::sync::Mutex<Option<PathBuf>>,
+}
+
+#[cfg(test)]
+impl Drop for StubGuard {
+    fn drop(&mut self) {
+        if let Ok(mut slot) = self.slot.lock() {
+            *slot = None;
+        }
+    }
+}
+
+#[cfg(test)]
+fn set_download_channel_stub(path: PathBuf) -> StubGuard {
+    *DOWNLOAD_CHANNEL_STUB.lock().unwrap() = Some(path);
+    StubGuard {
+        slot: &DOWNLOAD_CHANNEL_STUB,
+    }
+}
+
+#[cfg(test)]
+fn set_ytdlp_stub(path: PathBuf) -> StubGuard {
+    *YT_DLP_STUB.lock().unwrap() = Some(path);
+    StubGuard { slot: &YT_DLP_STUB }
+}
+
+fn find_download_channel_executable() -> Result<PathBuf> {
+    #[cfg(test)]
+    {
+        if let Some(path) = DOWNLOAD_CHANNEL_STUB.lock().unwrap().clone()
+            && path.exists()
+        {
+            return Ok(path);
+        }
+    }
+
+    if let Ok(path) = std::env::var("NEWTUBE_DOWNLOAD_BIN") {
+        let trimmed = path.trim();
+        if !trimmed.is_empty() {
+            let candidate = PathBuf::from(trimmed);
+            if candidate.exists() {
+                return Ok(candidate);
+            }
+        }
+    }
+
+    if let Ok(path) = std::env::var("CARGO_BIN_EXE_download_channel") {
+        let candidate = PathBuf::from(path);
+        if candidate.exists() {
+            return Ok(candidate);
+        }
+    }
+
+    let docker_path = PathBuf::from("/usr/local/bin/download_channel");
+    if docker_path.exists() {
+        return Ok(docker_path);
+    }
+
+    let mut sibling = std::env::current_exe().context("locating backend executable")?;
+    sibling.set_file_name("download_channel");
+    if sibling.exists() {
+        return Ok(sibling);
+    }
+
+    bail!("download_channel binary not found");
+}
+
+fn run_download_channel(binary: &Path, args: Vec<String>) -> Result<()> {
+    let status = std::process::Command::new(binary)
+        .args(args)
+        .status()
+        .context("launching download_channel")?;
+    if status.success() {
+        Ok(())
+    } else {
+        bail!("download_channel exited with {}", status)
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    One(T),
+    Many(Vec<T>),
+}
+
+impl<T> OneOrMany<T> {
+    fn iter(&self) -> Box<dyn Iterator<Item = &T> + '_> {
+        match self {
+            OneOrMany::One(value) => Box::new(std::iter::once(value)),
+            OneOrMany::Many(values) => Box::new(values.iter()),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CreatorInfo {
+    Name(String),
+    Object {
+        url: Option<String>,
+        channel_url: Option<String>,
+        uploader_url: Option<String>,
+    },
+}
+
+impl CreatorInfo {
+    fn url(&self) -> Option<&str> {
+        match self {
+            CreatorInfo::Name(_) => None,
+            CreatorInfo::Object {
+                url,
+                channel_url,
+                uploader_url,
+            } => url
+                .as_deref()
+                .or(channel_url.as_deref())
+                .or(uploader_url.as_deref()),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct MinimalInfo {
+    channel_url: Option<OneOrMany<String>>,
+    uploader_url: Option<OneOrMany<String>>,
+    #[serde(default)]
+    creators: Option<Vec<CreatorInfo>>,
+    channel: Option<OneOrMany<CreatorInfo>>,
+    uploader: Option<OneOrMany<CreatorInfo>>,
+}
+
+fn first_channel_url(info: &MinimalInfo) -> Option<&str> {
+    if let Some(channel_urls) = &info.channel_url {
+        for url in channel_urls.iter() {
+            if !url.trim().is_empty() {
+                return Some(url);
+            }
+        }
+    }
+    if let Some(uploader_urls) = &info.uploader_url {
+        for url in uploader_urls.iter() {
+            if !url.trim().is_empty() {
+                return Some(url);
+            }
+        }
+    }
+    if let Some(channel) = &info.channel {
+        for creator in channel.iter() {
+            if let Some(url) = creator.url()
+                && !url.trim().is_empty()
+            {
+                return Some(url);
+            }
+        }
+    }
+    if let Some(uploader) = &info.uploader {
+        for creator in uploader.iter() {
+            if let Some(url) = creator.url()
+                && !url.trim().is_empty()
+            {
+                return Some(url);
+            }
+        }
+    }
+    if let Some(creators) = &info.creators {
+        for creator in creators {
+            if let Some(url) = creator.url()
+                && !url.trim().is_empty()
+            {
+                return Some(url);
+            }
+        }
+    }
+    None
+}
+
+fn video_url_for_kind(video_id: &str, kind: MediaCategory) -> String {
+    match kind {
+        MediaCategory::Video => format!("https://www.youtube.com/watch?v={video_id}"),
+        MediaCategory::Short => format!("https://www.youtube.com/shorts/{video_id}"),
+    }
+}
+
+fn yt_dlp_command() -> std::process::Command {
+    #[cfg(test)]
+    {
+        if let Some(path) = YT_DLP_STUB.lock().unwrap().clone() {
+            return std::process::Command::new(path);
+        }
+    }
+    std::process::Command::new("yt-dlp")
+}
+
+fn resolve_channel_url(video_id: &str, kind: MediaCategory) -> Result<String> {
+    let video_url = video_url_for_kind(video_id, kind);
+    let output = yt_dlp_command()
+        .arg("--dump-single-json")
+        .arg("--skip-download")
+        .arg("--no-warnings")
+        .arg("--no-progress")
+        .arg(&video_url)
+        .output()
+        .with_context(|| format!("fetching metadata for {video_url}"))?;
+
+    if !output.status.success() {
+        bail!("yt-dlp failed for {} (status {})", video_url, output.status);
+    }
+
+    let info: MinimalInfo =
+        serde_json::from_slice(&output.stdout).context("parsing yt-dlp metadata response")?;
+    let url = first_channel_url(&info).ok_or_else(|| anyhow!("channel url not found"))?;
+    Ok(url.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Json;
+    use axum::http::HeaderMap;
+    use axum::{body::to_bytes, extract::State as AxumState};
+    use libsql::{Builder, params};
+    use newtube_tools::metadata::{ByteRange, SegmentCategory};
+    use newtube_tools::transcript::TranscriptCue;
+    use newtube_tools::userdata::DEFAULT_PROFILE_ID;
+    use serde_json::Value;
+    #[cfg(unix)]
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::Mutex;
+    use std::{env, fs, path::PathBuf, sync::Arc};
+    use tempfile::tempdir;
+    use tokio::time::{Duration, sleep};
+
+    struct BackendTestContext {
+        _temp: tempfile::TempDir,
+        db_path: PathBuf,
+        store: Arc<MetadataStore>,
+        state: AppState,
+    }
+
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    fn with_env_file(vars: &[(&str, &str)], f: impl FnOnce()) {
+        let _lock = ENV_LOCK.lock().unwrap();
+        let dir = tempdir().unwrap();
+        let mut contents = String::new();
+        for (key, value) in vars {
+            contents.push_str(&format!("{key}=\"{value}\"\n"));
+        }
+        std::fs::write(dir.path().join(".env"), contents).unwrap();
+        let cwd = env::current_dir().unwrap();
+        env::set_current_dir(dir.path()).unwrap();
+        f();
+        env::set_current_dir(cwd).unwrap();
+    }
+
+    impl BackendTestContext {
+        /// Profile the migration creates; it is the only admin in tests.
+        async fn current_profile(&self) -> CurrentProfile {
+            let profile = self
+                .state
+                .user_data
+                .get_profile(DEFAULT_PROFILE_ID)
+                .await
+                .unwrap()
+                .unwrap();
+            CurrentProfile(profile)
+        }
+
+        async fn new() -> Self {
+            let temp = tempdir().unwrap();
+            let db_path = temp.path().join("metadata.db");
+            let store = Arc::new(MetadataStore::open(&db_path).await.unwrap());
+            let reader = MetadataReader::new(&db_path).await.unwrap();
+            let files = FilePaths::for_base(temp.path());
+            let www_root = temp.path().join("www");
+            std::fs::create_dir_all(&www_root).unwrap();
+            let env_path = temp.path().join(".env");
+            std::fs::write(&env_path, "").unwrap();
+            let user_data = Arc::new(
+                UserDataStore::open(&temp.path().join(USER_DATA_DB_FILE))
+                    .await
+                    .unwrap(),
+            );
+            let events = EventBus::default();
+
+            Self {
+                state: AppState {
+                    reader: Arc::new(reader),
+                    store: store.clone(),
+                    plays: PlayTracker::new(user_data.clone()),
+                    user_data,
+                    cache: Arc::new(ApiCache::new()),
+                    files: Arc::new(files),
+                    www_root: Arc::new(www_root),
+                    settings: Arc::new(SettingsStore::load(
+                        &env_path,
+                        InstanceSettings {
+                            missing_media_behavior: MissingMediaBehavior::NotFound,
+                        },
+                    )),
+                    downloads: DownloadManager::new(
+                        temp.path().to_path_buf(),
+                        temp.path().join("www"),
+                        events.clone(),
+                    ),
+                    events,
+                    scrub: ScrubManager::default(),
+                    hls: HlsPackager::new(temp.path().join("ffmpeg")),
+                },
+                db_path,
+                store,
+                _temp: temp,
+            }
+        }
+
+        async fn insert_video(&mut self, id: &str) {
+            self.store.upsert_video(&sample_video(id)).await.unwrap();
+        }
+
+        async fn insert_short(&mut self, id: &str) {
+            self.store.upsert_short(&sample_video(id)).await.unwrap();
+        }
+
+        async fn insert_subtitles(&mut self, id: &str, tracks: Vec<SubtitleTrack>) {
+            self.store
+                .upsert_subtitles(&SubtitleCollection {
+                    videoid: id.into(),
+                    languages: tracks,
+                })
+                .await
+                .unwrap();
+        }
+
+        async fn insert_comments(&mut self, id: &str, comments: Vec<CommentRecord>) {
+            self.store
+                .replace_comments(id, &comments)
+                .await
+                .expect("comments persisted");
+        }
+
+        async fn delete_by_videoid(&self, table: &str, value: &str) {
+            let db = Builder::new_local(&self.db_path).build().await.unwrap();
+            let conn = db.connect().unwrap();
+            conn.execute(
+                &format!("DELETE FROM {table} WHERE videoid = ?1"),
+                params![value],
+            )
+            .await
+            .unwrap();
+        }
+    }
+
+    fn install_stub(dir: &std::path::Path, name: &str, script: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, script).unwrap();
+        #[cfg(unix)]
+        {
+            let mut perms = fs::metadata(&path).unwrap().permissions();
+            perms.set_mode(0o755);
+            fs::set_permissions(&path, perms).unwrap();
+        }
+        path
+    }
+
+    async fn wait_for_terminal_status(
+        downloads: &DownloadManager,
+        job_id: &str,
+    ) -> DownloadJobStatus {
+        for _ in 0..80 {
+            if let Some(status) = downloads.get_status(job_id)
+                && (status.status == "completed" || status.status == "failed")
+            {
+                return status;
+            }
+            sleep(Duration::from_millis(25)).await;
+        }
+        downloads
+            .get_status(job_id)
+            .expect("download status available")
+    }
+
+    fn sample_video(id: &str) -> VideoRecord {
+        VideoRecord {
+            videoid: id.into(),
+            title: format!("Video {id}"),
+            description: "desc".into(),
+            likes: Some(1),
+            dislikes: Some(0),
+            views: Some(10),
+            upload_date: Some("2024-01-01T00:00:00Z".into()),
+            author: Some("Channel".into()),
+            subscriber_count: Some(100),
+            duration: Some(60),
+            duration_text: Some("1:00".into()),
+            channel_url: Some("https://example.test/channel".into()),
+            channel_id: Some("UC_test".into()),
+            thumbnail_url: Some("/thumb.jpg".into()),
+            tags: vec![],
+            thumbnails: vec![],
+            extras: json!(null),
+            sources: vec![VideoSource {
+                format_id: "1080p".into(),
+                quality_label: Some("1080p".into()),
+                width: Some(1920),
+                height: Some(1080),
+                fps: Some(30.0),
+                mime_type: Some("video/mp4".into()),
+                ext: Some("mp4".into()),
+                file_size: Some(1024),
+                url: format!("/api/videos/{id}/streams/1080p"),
+                path: None,
+                sha256: None,
+            }],
+            availability: None,
+            availability_checked_at: None,
+            adaptive_streams: Vec::new(),
+        }
+    }
+
+    fn sample_comment(id: &str, videoid: &str) -> CommentRecord {
+        CommentRecord {
+            id: id.into(),
+            videoid: videoid.into(),
+            author: "tester".into(),
+            text: "hello world".into(),
+            likes: Some(1),
+            time_posted: Some("2024-01-01T00:00:00Z".into()),
+            parent_comment_id: None,
+            status_likedbycreator: false,
+            reply_count: Some(0),
+            deleted_upstream_at: None,
+        }
+    }
+
+    fn parse_backend_args(env_values: &[(&str, &str)], extra: &[&str]) -> BackendArgs {
+        let argv = extra
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>();
+        let mut parsed = None;
+        with_env_file(env_values, || {
+            parsed = Some(BackendArgs::from_iter(argv.clone()).expect("parsed args"));
+        });
+        parsed.expect("args set")
+    }
+
+    #[test]
+    fn backend_args_default_media_root() {
+        let args = parse_backend_args(
+            &[
+                ("MEDIA_ROOT", "/yt/test"),
+                ("WWW_ROOT", "/www/test"),
+                ("NEWTUBE_PORT", "4242"),
+                ("NEWTUBE_HOST", "127.0.0.1"),
+            ],
+            &[],
+        );
+        assert_eq!(args.media_root, PathBuf::from("/yt/test"));
+        assert_eq!(args.www_root, PathBuf::from("/www/test"));
+        assert_eq!(args.newtube_port, 4242);
+    }
+
+    #[test]
+    fn backend_args_override_media_root() {
+        let args = parse_backend_args(
+            &[
+                ("MEDIA_ROOT", "/yt/test"),
+                ("WWW_ROOT", "/www/test"),
+                ("NEWTUBE_PORT", "4242"),
+                ("NEWTUBE_HOST", "127.0.0.1"),
+            ],
+            &["--media-root", "/custom/media"],
+        );
+        assert_eq!(args.media_root, PathBuf::from("/custom/media"));
+    }
+
+    #[test]
+    fn backend_args_override_www_root() {
+        let args = parse_backend_args(
+            &[
+                ("MEDIA_ROOT", "/yt/test"),
+                ("WWW_ROOT", "/www/test"),
+                ("NEWTUBE_PORT", "4242"),
+                ("NEWTUBE_HOST", "127.0.0.1"),
+            ],
+            &["--www-root", "/custom/www"],
+        );
+        assert_eq!(args.www_root, PathBuf::from("/custom/www"));
+    }
+
+    #[test]
+    fn backend_args_override_port() {
+        let args = parse_backend_args(
+            &[
+                ("MEDIA_ROOT", "/yt/test"),
+                ("WWW_ROOT", "/www/test"),
+                ("NEWTUBE_PORT", "4242"),
+                ("NEWTUBE_HOST", "127.0.0.1"),
+            ],
+            &["--port", "9000"],
+        );
+        assert_eq!(args.newtube_port, 9000);
+    }
+
+    #[test]
+    fn backend_args_override_host() {
+        let args = parse_backend_args(
+            &[
+                ("MEDIA_ROOT", "/yt/test"),
+                ("WWW_ROOT", "/www/test"),
+                ("NEWTUBE_PORT", "4242"),
+                ("NEWTUBE_HOST", "127.0.0.1"),
+            ],
+            &["--host", "0.0.0.0"],
+        );
+        assert_eq!(args.listen_host, "0.0.0.0".parse::<IpAddr>().unwrap());
+    }
+
+    #[test]
+    fn reader_pool_size_falls_back_to_default() {
+        assert_eq!(parse_reader_pool_size(Some("12")), 12);
+        assert_eq!(parse_reader_pool_size(None), DEFAULT_READER_POOL_SIZE);
+        assert_eq!(parse_reader_pool_size(Some("0")), DEFAULT_READER_POOL_SIZE);
+        assert_eq!(
+            parse_reader_pool_size(Some("many")),
+            DEFAULT_READER_POOL_SIZE
+        );
+    }
+
+    #[test]
+    fn scrub_interval_is_off_unless_positive() {
+        assert_eq!(
+            parse_scrub_interval(Some("24")),
+            Some(std::time::Duration::from_secs(24 * 3600))
+        );
+        assert_eq!(parse_scrub_interval(None), None);
+        assert_eq!(parse_scrub_interval(Some("0")), None);
+        assert_eq!(parse_scrub_interval(Some("daily")), None);
+    }
+
+    #[tokio::test]
+    async fn bootstrap_caches_payload() {
+        let mut ctx = BackendTestContext::new().await;
+        ctx.insert_video("alpha").await;
+        ctx.insert_short("beta").await;
+        ctx.insert_subtitles(
+            "alpha",
+            vec![SubtitleTrack {
+                code: "en".into(),
+                name: "English".into(),
+                url: "/api/videos/alpha/subtitles/en".into(),
+                path: None,
+            }],
+        )
+        .await;
+        ctx.insert_comments("alpha", vec![sample_comment("1", "alpha")])
+            .await;
+
+        let first = ctx.state.get_bootstrap().await.unwrap();
+        assert_eq!(first.videos.len(), 1);
+
+        ctx.insert_video("gamma").await;
+        let second = ctx.state.get_bootstrap().await.unwrap();
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert!(second.videos.iter().any(|video| video.videoid == "gamma"));
+    }
+
+    /// A client that bootstrapped only receives what changed afterwards,
+    /// with file paths stripped like everywhere else.
+    #[tokio::test]
+    async fn changes_endpoint_returns_delta_since_bootstrap() {
+        let mut ctx = BackendTestContext::new().await;
+        ctx.insert_video("alpha").await;
+        let bootstrap = ctx.state.get_bootstrap().await.unwrap();
+        assert!(bootstrap.seq > 0);
+
+        let mut gamma = sample_video("gamma");
+        gamma.sources[0].path = Some("/media/videos/gamma/gamma_1080p.mp4".into());
+        ctx.store.upsert_video(&gamma).await.unwrap();
+        ctx.store.delete_media("alpha").await.unwrap();
+
+        let Json(changes) = list_changes(
+            AxumState(ctx.state.clone()),
+            Query(ChangesParams {
+                since: Some(bootstrap.seq),
+                limit: None,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(changes.videos.len(), 1);
+        assert_eq!(changes.videos[0].videoid, "gamma");
+        assert!(changes.videos[0].sources[0].path.is_none());
+        assert_eq!(changes.deleted.len(), 1);
+        assert_eq!(changes.deleted[0].key, "alpha");
+        assert!(changes.seq > bootstrap.seq);
+
+        let err = list_changes(
+            AxumState(ctx.state.clone()),
+            Query(ChangesParams {
+                since: Some(-1),
+                limit: None,
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn media_list_populates_cache() {
+        let mut ctx = BackendTestContext::new().await;
+        ctx.insert_video("alpha").await;
+
+        let list = ctx
+            .state
+            .get_media_list(MediaCategory::Video)
+            .await
+            .unwrap();
+        assert_eq!(list.len(), 1);
+        ctx.delete_by_videoid("videos", "alpha").await;
+
+        let cached = ctx
+            .state
+            .get_media_list(MediaCategory::Video)
+            .await
+            .unwrap();
+        assert_eq!(cached.len(), 0);
+    }
+
+    /// Any listing parameter switches the response to a keyset page; the
+    /// returned cursor fetches the remainder and bad input is a 400.
+    #[tokio::test]
+    async fn list_endpoints_paginate_when_requested() {
+        let ctx = BackendTestContext::new().await;
+        for (id, views) in [("alpha", 10), ("beta", 30), ("gamma", 20)] {
+            let mut video = sample_video(id);
+            video.views = Some(views);
+            video.sources[0].path = Some(format!("/yt/videos/{id}/secret.mp4"));
+            ctx.store.upsert_video(&video).await.unwrap();
+        }
+
+        let fetch_page = |params: ListParams| {
+            let state = ctx.state.clone();
+            async move {
+                let response = super::list_videos(AxumState(state), Query(params))
+                    .await
+                    .unwrap();
+                let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+                serde_json::from_slice::<Value>(&bytes).unwrap()
+            }
+        };
+
+        let first = fetch_page(ListParams {
+            sort: Some("views".into()),
+            limit: Some(2),
+            ..ListParams::default()
+        })
+        .await;
+        let ids: Vec<&str> = first["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| item["videoid"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["beta", "gamma"]);
+        assert!(first["items"][0]["sources"][0].get("path").is_none());
+        let cursor = first["next_cursor"].as_str().unwrap().to_string();
+
+        let second = fetch_page(ListParams {
+            sort: Some("views".into()),
+            limit: Some(2),
+            cursor: Some(cursor.clone()),
+            ..ListParams::default()
+        })
+        .await;
+        assert_eq!(second["items"][0]["videoid"], "alpha");
+        assert!(second.get("next_cursor").is_none());
+
+        let err = super::list_videos(
+            AxumState(ctx.state.clone()),
+            Query(ListParams {
+                sort: Some("title".into()),
+                cursor: Some(cursor),
+                ..ListParams::default()
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        let err = super::list_videos(
+            AxumState(ctx.state.clone()),
+            Query(ListParams {
+                sort: Some("loudness".into()),
+                ..ListParams::default()
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        let mut batch = ctx.store.batch();
+        batch.set_availability("beta", Availability::Private);
+        batch.commit().await.unwrap();
+        let gone = fetch_page(ListParams {
+            availability: Some("removed, private".into()),
+            ..ListParams::default()
+        })
+        .await;
+        assert_eq!(gone["items"].as_array().unwrap().len(), 1);
+        assert_eq!(gone["items"][0]["availability"], "private");
+        let err = super::list_videos(
+            AxumState(ctx.state.clone()),
+            Query(ListParams {
+                availability: Some("vanished".into()),
+                ..ListParams::default()
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn api_responses_strip_file_paths() {
+        let ctx = BackendTestContext::new().await;
+        let mut video = sample_video("alpha");
+        video.sources[0].path = Some("/yt/videos/alpha/secret.mp4".into());
+        ctx.store.upsert_video(&video).await.unwrap();
+
+        let response =
+            super::list_videos(AxumState(ctx.state.clone()), Query(ListParams::default()))
+                .await
+                .unwrap();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let videos: Vec<VideoRecord> = serde_json::from_slice(&bytes).unwrap();
+        assert!(videos[0].sources[0].path.is_none());
+
+        let Json(single) = super::get_video(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
+            .await
+            .unwrap();
+        assert!(single.record.sources[0].path.is_none());
+
+        let bootstrap = ctx.state.get_bootstrap().await.unwrap();
+        assert!(bootstrap.videos[0].sources[0].path.is_none());
+    }
+
+    #[tokio::test]
+    async fn search_endpoint_filters_by_kind() {
+        let ctx = BackendTestContext::new().await;
+        let mut video = sample_video("alpha");
+        video.title = "Rust async deep dive".into();
+        ctx.store.upsert_video(&video).await.unwrap();
+        let mut short = sample_video("beta");
+        short.title = "Rust in sixty seconds".into();
+        ctx.store.upsert_short(&short).await.unwrap();
+
+        let params = SearchParams {
+            q: Some("rust".into()),
+            kind: None,
+            channel: None,
+            limit: None,
+        };
+        let Json(hits) = search_media(AxumState(ctx.state.clone()), Query(params))
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 2);
+
+        let params = SearchParams {
+            q: Some("rust".into()),
+            kind: Some("shorts".into()),
+            channel: None,
+            limit: None,
+        };
+        let Json(hits) = search_media(AxumState(ctx.state.clone()), Query(params))
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].videoid, "beta");
+        assert_eq!(hits[0].kind, "short");
+    }
+
+    #[tokio::test]
+    async fn channel_endpoints_expose_urls_not_paths() {
+        let ctx = BackendTestContext::new().await;
+        let avatar_dir = ctx.state.files.channels.join("UC_test");
+        fs::create_dir_all(&avatar_dir).unwrap();
+        let avatar = avatar_dir.join("UC_test.avatar_uncropped.jpg");
+        fs::write(&avatar, "avatar-bytes").unwrap();
+        ctx.store
+            .upsert_channel(&ChannelRecord {
+                channel_id: "UC_test".into(),
+                name: "Channel".into(),
+                handle: Some("@channel".into()),
+                url: Some("https://example.test/channel".into()),
+                subscriber_count: Some(100),
+                avatar_path: Some(avatar.to_string_lossy().into_owned()),
+                banner_path: Some("/etc/passwd".into()),
+                last_refreshed_at: None,
+            })
+            .await
+            .unwrap();
+        ctx.store
+            .upsert_video(&sample_video("alpha"))
+            .await
+            .unwrap();
+        ctx.store.upsert_short(&sample_video("beta")).await.unwrap();
+
+        let Json(channels) = list_channels(AxumState(ctx.state.clone())).await.unwrap();
+        assert_eq!(channels.len(), 1);
+        let body = serde_json::to_value(&channels[0]).unwrap();
+        assert_eq!(body["channel_id"], "UC_test");
+        assert_eq!(body["avatar_url"], "/api/channels/UC_test/images/avatar");
+        assert!(body.get("avatar_path").is_none());
+
+        let Json(videos) =
+            list_channel_videos(AxumState(ctx.state.clone()), AxumPath("UC_test".into()))
+                .await
+                .unwrap();
+        assert_eq!(videos.len(), 1);
+        let Json(shorts) =
+            list_channel_shorts(AxumState(ctx.state.clone()), AxumPath("UC_test".into()))
+                .await
+                .unwrap();
+        assert_eq!(shorts[0].videoid, "beta");
+
+        let response = download_channel_image(
+            AxumState(ctx.state.clone()),
+            AxumPath(("UC_test".into(), "avatar".into())),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&bytes[..], b"avatar-bytes");
 
-    impl BackendTestContext {
-        async fn new() -> Self {
-            let temp = tempdir().unwrap();
-            let db_path = temp.path().join("metadata.db");
-            let store = MetadataStore::open(&db_path).await.unwrap();
-            let reader = MetadataReader::new(&db_path).await.unwrap();
-            let files = FilePaths::for_base(temp.path());
-            let www_root = temp.path().join("www");
-            std::fs::create_dir_all(&www_root).unwrap();
-            let env_path = temp.path().join(".env");
-            std::fs::write(&env_path, "").unwrap();
+        let escaped = download_channel_image(
+            AxumState(ctx.state.clone()),
+            AxumPath(("UC_test".into(), "banner".into())),
+            HeaderMap::new(),
+        )
+        .await;
+        assert!(escaped.is_err());
 
-            Self {
-                state: AppState {
-                    reader: Arc::new(reader),
-                    cache: Arc::new(ApiCache::new()),
-                    files: Arc::new(files),
-                    www_root: Arc::new(www_root),
-                    settings: Arc::new(SettingsStore::load(
-                        &env_path,
-                        InstanceSettings {
-                            missing_media_behavior: MissingMediaBehavior::NotFound,
-                        },
-                    )),
-                    downloads: DownloadManager::new(
-                        temp.path().to_path_buf(),
-                        temp.path().join("www"),
-                    ),
+        let missing = get_channel(AxumState(ctx.state.clone()), AxumPath("UC_none".into())).await;
+        assert!(missing.is_err());
+    }
+
+    /// Playlist detail keeps upstream order and lists entries that are not
+    /// downloaded yet without a record.
+    #[tokio::test]
+    async fn playlist_endpoints_return_ordered_entries() {
+        let ctx = BackendTestContext::new().await;
+        ctx.store
+            .upsert_video(&sample_video("alpha"))
+            .await
+            .unwrap();
+        ctx.store.upsert_short(&sample_video("beta")).await.unwrap();
+        let entry = |position: i64, videoid: &str, kind: &str| PlaylistEntry {
+            position,
+            videoid: videoid.into(),
+            kind: kind.into(),
+        };
+        ctx.store
+            .replace_playlist(
+                &PlaylistRecord {
+                    playlist_id: "PL1".into(),
+                    title: "Mix".into(),
+                    description: String::new(),
+                    owner: Some("Channel".into()),
+                    owner_channel_id: Some("UC_test".into()),
+                    url: None,
+                    tracked: true,
+                    last_refreshed_at: None,
+                    entry_count: 0,
                 },
-                db_path,
-                store,
-                _temp: temp,
-            }
+                &[
+                    entry(0, "beta", "short"),
+                    entry(1, "missing", "video"),
+                    entry(2, "alpha", "video"),
+                ],
+            )
+            .await
+            .unwrap();
+
+        let Json(playlists) = list_playlists(AxumState(ctx.state.clone())).await.unwrap();
+        assert_eq!(playlists.len(), 1);
+        assert_eq!(playlists[0].entry_count, 3);
+
+        let Json(detail) = get_playlist(AxumState(ctx.state.clone()), AxumPath("PL1".into()))
+            .await
+            .unwrap();
+        let body = serde_json::to_value(&detail).unwrap();
+        assert_eq!(body["title"], "Mix");
+        let entries = body["entries"].as_array().unwrap();
+        let ids: Vec<&str> = entries
+            .iter()
+            .map(|entry| entry["videoid"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["beta", "missing", "alpha"]);
+        assert_eq!(entries[0]["record"]["videoid"], "beta");
+        assert!(entries[1].get("record").is_none());
+
+        let missing = get_playlist(AxumState(ctx.state.clone()), AxumPath("PL_none".into())).await;
+        assert!(missing.is_err());
+    }
+
+    #[tokio::test]
+    async fn stats_endpoints_return_history_and_channel_series() {
+        let ctx = BackendTestContext::new().await;
+        let mut video = sample_video("alpha");
+        ctx.store.upsert_video(&video).await.unwrap();
+        video.views = Some(video.views.unwrap_or_default() + 10);
+        ctx.store.upsert_video(&video).await.unwrap();
+
+        let Json(history) = get_video_stats(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].views, video.views);
+
+        let err = get_video_stats(AxumState(ctx.state.clone()), AxumPath("missing".into()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+        let err = get_short_stats(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+
+        let Json(points) =
+            get_channel_stats(AxumState(ctx.state.clone()), AxumPath("UC_test".into()))
+                .await
+                .unwrap();
+        assert_eq!(points.len(), 1);
+        assert_eq!(points[0].video_count, 1);
+        assert_eq!(points[0].views, video.views.unwrap());
+    }
+
+    /// Deleting removes rows, per-id artifact directories and the archive
+    /// line, and `block=true` lands the id on the never re-download list.
+    #[tokio::test]
+    async fn delete_video_removes_artifacts_and_blocks() {
+        let ctx = BackendTestContext::new().await;
+        ctx.store
+            .upsert_video(&sample_video("alpha"))
+            .await
+            .unwrap();
+        let files = ctx.state.files.clone();
+        for dir in [&files.videos, &files.thumbnails, &files.subtitles] {
+            fs::create_dir_all(dir.join("alpha")).unwrap();
+            fs::write(dir.join("alpha").join("alpha.bin"), "x").unwrap();
         }
+        let outside = ctx._temp.path().join("outside");
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("keep.txt"), "keep").unwrap();
+        std::os::unix::fs::symlink(&outside, files.comments.join("alpha")).unwrap();
+        fs::write(&files.archive, "youtube beta\nyoutube alpha\n").unwrap();
+
+        let Json(report) = delete_video(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            AxumPath("alpha".into()),
+            Query(DeleteParams {
+                block: true,
+                reason: Some("reupload".into()),
+            }),
+        )
+        .await
+        .unwrap();
 
-        async fn insert_video(&mut self, id: &str) {
-            self.store.upsert_video(&sample_video(id)).await.unwrap();
+        assert_eq!(report.removed_paths.len(), 4);
+        assert!(report.archive_entry_removed);
+        assert!(report.blocked);
+        assert!(!files.videos.join("alpha").exists());
+        assert!(!files.comments.join("alpha").exists());
+        assert!(outside.join("keep.txt").exists());
+        assert_eq!(
+            fs::read_to_string(&files.archive).unwrap(),
+            "youtube beta\n"
+        );
+        assert!(ctx.state.reader.get_video("alpha").await.unwrap().is_none());
+
+        let Json(blocked) = list_blocked_media(AxumState(ctx.state.clone()))
+            .await
+            .unwrap();
+        assert_eq!(blocked[0].videoid, "alpha");
+        assert_eq!(blocked[0].reason.as_deref(), Some("reupload"));
+        let status = unblock_media(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            AxumPath("alpha".into()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::NO_CONTENT);
+
+        let err = delete_video(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            AxumPath("alpha".into()),
+            Query(DeleteParams::default()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn delete_rejects_traversal_and_wrong_kind() {
+        let ctx = BackendTestContext::new().await;
+        ctx.store
+            .upsert_video(&sample_video("alpha"))
+            .await
+            .unwrap();
+        for id in ["..", "a/b", "../alpha"] {
+            let err = delete_video(
+                AxumState(ctx.state.clone()),
+                AdminProfile,
+                AxumPath(id.into()),
+                Query(DeleteParams::default()),
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.status, StatusCode::NOT_FOUND);
+        }
+        let err = delete_short(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            AxumPath("alpha".into()),
+            Query(DeleteParams::default()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+        assert!(ctx.state.reader.get_video("alpha").await.unwrap().is_some());
+    }
+
+    /// Likes set through the REST endpoints show up in the snapshot and a
+    /// second like for the same video flips an earlier dislike.
+    #[tokio::test]
+    async fn me_reaction_endpoints_roundtrip() {
+        let ctx = BackendTestContext::new().await;
+        let status = put_dislike(
+            AxumState(ctx.state.clone()),
+            ctx.current_profile().await,
+            AxumPath("alpha".into()),
+            Json(SavedVideo::default()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        put_like(
+            AxumState(ctx.state.clone()),
+            ctx.current_profile().await,
+            AxumPath("alpha".into()),
+            Json(SavedVideo {
+                title: Some("Alpha".into()),
+                ..SavedVideo::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+        let Json(likes) = list_likes(AxumState(ctx.state.clone()), ctx.current_profile().await)
+            .await
+            .unwrap();
+        assert_eq!(likes.len(), 1);
+        assert_eq!(likes[0].videoid.as_deref(), Some("alpha"));
+        assert_eq!(likes[0].title.as_deref(), Some("Alpha"));
+        let Json(dislikes) =
+            list_dislikes(AxumState(ctx.state.clone()), ctx.current_profile().await)
+                .await
+                .unwrap();
+        assert!(dislikes.is_empty());
+
+        let err = delete_dislike(
+            AxumState(ctx.state.clone()),
+            ctx.current_profile().await,
+            AxumPath("alpha".into()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn me_playlist_endpoints_require_existing_playlist() {
+        let ctx = BackendTestContext::new().await;
+        let item = || SavedVideo {
+            videoid: Some("alpha".into()),
+            ..SavedVideo::default()
+        };
+        let err = add_user_playlist_item(
+            AxumState(ctx.state.clone()),
+            ctx.current_profile().await,
+            AxumPath("later".into()),
+            Json(item()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+
+        let Json(playlist) = put_user_playlist(
+            AxumState(ctx.state.clone()),
+            ctx.current_profile().await,
+            AxumPath("later".into()),
+            Json(PlaylistUpdate {
+                name: Some(" Watch later ".into()),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(playlist.name, "Watch later");
+        let Json(playlist) = add_user_playlist_item(
+            AxumState(ctx.state.clone()),
+            ctx.current_profile().await,
+            AxumPath("later".into()),
+            Json(item()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(playlist.video_ids, ["alpha"]);
+
+        let err = add_user_playlist_item(
+            AxumState(ctx.state.clone()),
+            ctx.current_profile().await,
+            AxumPath("later".into()),
+            Json(SavedVideo::default()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    }
+
+    /// The sync endpoint takes the frontend export verbatim and answers in
+    /// the same `{exportedAt, data}` shape.
+    #[tokio::test]
+    async fn me_sync_accepts_frontend_export() {
+        let ctx = BackendTestContext::new().await;
+        put_history_entry(
+            AxumState(ctx.state.clone()),
+            ctx.current_profile().await,
+            AxumPath("alpha".into()),
+            Json(WatchEntry {
+                progress: Some(0.4),
+                ..WatchEntry::default()
+            }),
+        )
+        .await
+        .unwrap();
+
+        let import: UserDataImport = serde_json::from_value(json!({
+            "exportedAt": "2024-05-01T00:00:00.000Z",
+            "data": {
+                "version": 1,
+                "likes": {"beta": {"videoid": "beta", "savedAt": 10}},
+                "dislikes": {},
+                "playlists": {},
+                "subscriptions": {"UC_test": {"channelId": "UC_test", "name": "Test", "subscribedAt": 5}},
+                "watchHistory": {"alpha": {"progress": 1.0, "watched": true, "updatedAt": 1}},
+                "metadata": {"createdAt": 1, "updatedAt": 10}
+            }
+        }))
+        .unwrap();
+        let Json(export) = sync_user_data(
+            AxumState(ctx.state.clone()),
+            ctx.current_profile().await,
+            Json(import),
+        )
+        .await
+        .unwrap();
+        let body = serde_json::to_value(&export).unwrap();
+        assert!(body["exportedAt"].is_string());
+        assert_eq!(body["data"]["likes"]["beta"]["savedAt"], 10);
+        assert_eq!(body["data"]["subscriptions"]["UC_test"]["name"], "Test");
+        // Server progress is newer, but the device's "watched" flag sticks.
+        assert_eq!(body["data"]["watchHistory"]["alpha"]["progress"], 0.4);
+        assert_eq!(body["data"]["watchHistory"]["alpha"]["watched"], true);
+
+        let Json(snapshot) =
+            get_user_data(AxumState(ctx.state.clone()), ctx.current_profile().await)
+                .await
+                .unwrap();
+        assert_eq!(snapshot, export.data);
+    }
+
+    fn request_parts(cookie: Option<&str>) -> Parts {
+        let mut builder = Request::builder().uri("/api/me");
+        if let Some(cookie) = cookie {
+            builder = builder.header(header::COOKIE, cookie);
         }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    /// Requests without a session act as the default profile only while it
+    /// is the sole password-less profile; afterwards a login is required and
+    /// admin routes check the signed-in profile.
+    #[tokio::test]
+    async fn sessions_resolve_profiles_and_gate_admin_routes() {
+        let ctx = BackendTestContext::new().await;
+        let CurrentProfile(anonymous) =
+            CurrentProfile::from_request_parts(&mut request_parts(None), &ctx.state)
+                .await
+                .unwrap();
+        assert_eq!(anonymous.id, DEFAULT_PROFILE_ID);
+        ctx.state
+            .user_data
+            .set_password(DEFAULT_PROFILE_ID, Some("admin"))
+            .await
+            .unwrap();
+
+        let Json(kid) = create_profile(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            Json(NewProfileRequest {
+                name: "Kid".into(),
+                password: Some("1234".into()),
+                admin: false,
+            }),
+        )
+        .await
+        .unwrap();
+        let err = CurrentProfile::from_request_parts(&mut request_parts(None), &ctx.state)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+
+        let login_as = |password: &str| LoginRequest {
+            profile_id: None,
+            name: Some("kid".into()),
+            password: Some(password.into()),
+        };
+        let err = login(AxumState(ctx.state.clone()), Json(login_as("0000")))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+        let response = login(AxumState(ctx.state.clone()), Json(login_as("1234")))
+            .await
+            .unwrap();
+        let set_cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(set_cookie.contains("HttpOnly"));
+        let cookie = set_cookie.split(';').next().unwrap().to_string();
+
+        let mut parts = request_parts(Some(&format!("theme=dark; {cookie}")));
+        let CurrentProfile(current) = CurrentProfile::from_request_parts(&mut parts, &ctx.state)
+            .await
+            .unwrap();
+        assert_eq!(current.id, kid.id);
+        let err = AdminProfile::from_request_parts(&mut parts, &ctx.state)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::FORBIDDEN);
+
+        let response = logout(AxumState(ctx.state.clone()), parts.headers.clone())
+            .await
+            .unwrap();
+        assert!(
+            response.headers()[header::SET_COOKIE]
+                .to_str()
+                .unwrap()
+                .contains("Max-Age=0")
+        );
+        let err = CurrentProfile::from_request_parts(&mut parts, &ctx.state)
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn me_endpoints_are_scoped_to_the_profile() {
+        let ctx = BackendTestContext::new().await;
+        let kid = ctx
+            .state
+            .user_data
+            .create_profile("Kid", None, false)
+            .await
+            .unwrap();
+        put_like(
+            AxumState(ctx.state.clone()),
+            CurrentProfile(kid.clone()),
+            AxumPath("alpha".into()),
+            Json(SavedVideo::default()),
+        )
+        .await
+        .unwrap();
+        let Json(saved) = update_user_settings(
+            AxumState(ctx.state.clone()),
+            CurrentProfile(kid.clone()),
+            Json(json!({"autoplay": false})),
+        )
+        .await
+        .unwrap();
+        assert_eq!(saved["autoplay"], false);
+
+        let Json(likes) = list_likes(AxumState(ctx.state.clone()), ctx.current_profile().await)
+            .await
+            .unwrap();
+        assert!(likes.is_empty());
+        let Json(settings) =
+            get_user_settings(AxumState(ctx.state.clone()), ctx.current_profile().await)
+                .await
+                .unwrap();
+        assert_eq!(settings, json!({}));
+        let Json(settings) = get_user_settings(AxumState(ctx.state.clone()), CurrentProfile(kid))
+            .await
+            .unwrap();
+        assert_eq!(settings["autoplay"], false);
+    }
+
+    #[tokio::test]
+    async fn profile_management_protects_last_admin() {
+        let ctx = BackendTestContext::new().await;
+        let err = delete_profile(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            AxumPath(DEFAULT_PROFILE_ID),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        let err = create_profile(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            Json(NewProfileRequest {
+                name: "default".into(),
+                password: None,
+                admin: false,
+            }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        // Changing your own password re-issues the session cookie.
+        let response = set_profile_password(
+            AxumState(ctx.state.clone()),
+            ctx.current_profile().await,
+            AxumPath(DEFAULT_PROFILE_ID),
+            Json(PasswordRequest {
+                password: Some("secret".into()),
+            }),
+        )
+        .await
+        .unwrap();
+        assert!(response.headers().contains_key(header::SET_COOKIE));
+        let Json(profiles) = list_profiles(AxumState(ctx.state.clone())).await.unwrap();
+        assert!(profiles[0].has_password);
+    }
+
+    /// A second profile needs an admin with a password first, and that
+    /// password cannot be cleared again while other profiles exist;
+    /// otherwise anyone could sign in as the admin.
+    #[tokio::test]
+    async fn profiles_require_a_password_protected_admin() {
+        let ctx = BackendTestContext::new().await;
+        let new_kid = || {
+            Json(NewProfileRequest {
+                name: "Kid".into(),
+                password: None,
+                admin: false,
+            })
+        };
+        let err = create_profile(AxumState(ctx.state.clone()), AdminProfile, new_kid())
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        ctx.state
+            .user_data
+            .set_password(DEFAULT_PROFILE_ID, Some("admin"))
+            .await
+            .unwrap();
+        let Json(kid) = create_profile(AxumState(ctx.state.clone()), AdminProfile, new_kid())
+            .await
+            .unwrap();
+        let admin = ctx
+            .state
+            .user_data
+            .get_profile(DEFAULT_PROFILE_ID)
+            .await
+            .unwrap()
+            .unwrap();
+        let clear = || Json(PasswordRequest { password: None });
+        let err = set_profile_password(
+            AxumState(ctx.state.clone()),
+            CurrentProfile(admin.clone()),
+            AxumPath(DEFAULT_PROFILE_ID),
+            clear(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+
+        // Other profiles may still go without one.
+        set_profile_password(
+            AxumState(ctx.state.clone()),
+            CurrentProfile(admin),
+            AxumPath(kid.id),
+            clear(),
+        )
+        .await
+        .unwrap();
+    }
 
-        async fn insert_short(&mut self, id: &str) {
-            self.store.upsert_short(&sample_video(id)).await.unwrap();
-        }
+    #[tokio::test]
+    async fn chapter_endpoints_serve_json_detail_and_webvtt() {
+        let ctx = BackendTestContext::new().await;
+        let mut video = sample_video("alpha");
+        video.duration = Some(300);
+        ctx.store.upsert_video(&video).await.unwrap();
+        let chapters = [
+            Chapter {
+                start: 0.0,
+                end: Some(65.5),
+                title: "Intro".into(),
+            },
+            Chapter {
+                start: 65.5,
+                end: None,
+                title: "Q&A <live>".into(),
+            },
+        ];
+        ctx.store
+            .replace_chapters("alpha", &chapters)
+            .await
+            .unwrap();
 
-        async fn insert_subtitles(&mut self, id: &str, tracks: Vec<SubtitleTrack>) {
-            self.store
-                .upsert_subtitles(&SubtitleCollection {
-                    videoid: id.into(),
-                    languages: tracks,
-                })
+        let Json(listed) =
+            get_video_chapters(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
                 .await
                 .unwrap();
-        }
+        assert_eq!(listed, chapters);
+        let Json(detail) = get_video(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
+            .await
+            .unwrap();
+        let body = serde_json::to_value(&detail).unwrap();
+        assert_eq!(body["videoid"], "alpha");
+        assert_eq!(body["chapters"][1]["title"], "Q&A <live>");
 
-        async fn insert_comments(&mut self, id: &str, comments: Vec<CommentRecord>) {
-            self.store
-                .replace_comments(id, &comments)
+        let response =
+            get_video_chapters_vtt(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
                 .await
-                .expect("comments persisted");
-        }
+                .unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/vtt; charset=utf-8"
+        );
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let vtt = String::from_utf8(bytes.to_vec()).unwrap();
+        assert_eq!(
+            vtt,
+            "WEBVTT\n\n1\n00:00:00.000 --> 00:01:05.500\nIntro\n\n\
+             2\n00:01:05.500 --> 00:05:00.000\nQ&amp;A &lt;live&gt;\n"
+        );
 
-        async fn delete_by_videoid(&self, table: &str, value: &str) {
-            let db = Builder::new_local(&self.db_path).build().await.unwrap();
-            let conn = db.connect().unwrap();
-            conn.execute(
-                &format!("DELETE FROM {table} WHERE videoid = ?1"),
-                params![value],
-            )
+        let err = get_short_chapters(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
             .await
-            .unwrap();
-        }
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
     }
 
-    fn install_stub(dir: &std::path::Path, name: &str, script: &str) -> PathBuf {
-        let path = dir.join(name);
-        fs::write(&path, script).unwrap();
-        #[cfg(unix)]
-        {
-            let mut perms = fs::metadata(&path).unwrap().permissions();
-            perms.set_mode(0o755);
-            fs::set_permissions(&path, perms).unwrap();
-        }
-        path
-    }
+    #[tokio::test]
+    async fn transcript_endpoints_return_cues_and_timed_hits() {
+        let ctx = BackendTestContext::new().await;
+        ctx.store
+            .upsert_video(&sample_video("alpha"))
+            .await
+            .unwrap();
+        ctx.store
+            .replace_transcripts(
+                "alpha",
+                &[Transcript {
+                    language: "en".into(),
+                    cues: vec![
+                        TranscriptCue {
+                            start: 0.0,
+                            end: 2.5,
+                            text: "welcome back".into(),
+                        },
+                        TranscriptCue {
+                            start: 42.0,
+                            end: 45.0,
+                            text: "the secret ingredient is patience".into(),
+                        },
+                    ],
+                }],
+            )
+            .await
+            .unwrap();
 
-    async fn wait_for_terminal_status(
-        downloads: &DownloadManager,
-        job_id: &str,
-    ) -> DownloadJobStatus {
-        for _ in 0..80 {
-            if let Some(status) = downloads.get_status(job_id)
-                && (status.status == "completed" || status.status == "failed")
-            {
-                return status;
-            }
-            sleep(Duration::from_millis(25)).await;
-        }
-        downloads
-            .get_status(job_id)
-            .expect("download status available")
-    }
+        let Json(transcripts) = get_video_transcript(
+            AxumState(ctx.state.clone()),
+            AxumPath("alpha".into()),
+            Query(TranscriptParams { lang: None }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(transcripts.len(), 1);
+        assert_eq!(transcripts[0].cues[1].start, 42.0);
+        let Json(filtered) = get_video_transcript(
+            AxumState(ctx.state.clone()),
+            AxumPath("alpha".into()),
+            Query(TranscriptParams {
+                lang: Some("fr".into()),
+            }),
+        )
+        .await
+        .unwrap();
+        assert!(filtered.is_empty());
+        let err = get_short_transcript(
+            AxumState(ctx.state.clone()),
+            AxumPath("alpha".into()),
+            Query(TranscriptParams { lang: None }),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
 
-    fn sample_video(id: &str) -> VideoRecord {
-        VideoRecord {
-            videoid: id.into(),
-            title: format!("Video {id}"),
-            description: "desc".into(),
-            likes: Some(1),
-            dislikes: Some(0),
-            views: Some(10),
-            upload_date: Some("2024-01-01T00:00:00Z".into()),
-            author: Some("Channel".into()),
-            subscriber_count: Some(100),
-            duration: Some(60),
-            duration_text: Some("1:00".into()),
-            channel_url: Some("https://example.test/channel".into()),
-            thumbnail_url: Some("/thumb.jpg".into()),
-            tags: vec![],
-            thumbnails: vec![],
-            extras: json!(null),
-            sources: vec![VideoSource {
-                format_id: "1080p".into(),
-                quality_label: Some("1080p".into()),
-                width: Some(1920),
-                height: Some(1080),
-                fps: Some(30.0),
-                mime_type: Some("video/mp4".into()),
-                ext: Some("mp4".into()),
-                file_size: Some(1024),
-                url: format!("/api/videos/{id}/streams/1080p"),
-                path: None,
-            }],
-        }
+        let Json(hits) = search_transcripts(
+            AxumState(ctx.state.clone()),
+            Query(TranscriptSearchParams {
+                q: Some("secret ingred".into()),
+                lang: None,
+                kind: None,
+                channel: None,
+                limit: None,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].videoid, "alpha");
+        assert_eq!(hits[0].start, 42.0);
+        assert_eq!(hits[0].language, "en");
     }
 
-    fn sample_comment(id: &str, videoid: &str) -> CommentRecord {
-        CommentRecord {
-            id: id.into(),
-            videoid: videoid.into(),
-            author: "tester".into(),
-            text: "hello world".into(),
-            likes: Some(1),
-            time_posted: Some("2024-01-01T00:00:00Z".into()),
-            parent_comment_id: None,
-            status_likedbycreator: false,
-            reply_count: Some(0),
-        }
-    }
+    #[tokio::test]
+    async fn segment_endpoints_validate_and_show_in_detail() {
+        let ctx = BackendTestContext::new().await;
+        ctx.store
+            .upsert_video(&sample_video("alpha"))
+            .await
+            .unwrap();
+        let segment = |category, start: f64, end: f64| SkipSegment {
+            category,
+            start,
+            end,
+        };
 
-    fn parse_backend_args(env_values: &[(&str, &str)], extra: &[&str]) -> BackendArgs {
-        let argv = extra
-            .iter()
-            .map(|value| value.to_string())
-            .collect::<Vec<_>>();
-        let mut parsed = None;
-        with_env_file(env_values, || {
-            parsed = Some(BackendArgs::from_iter(argv.clone()).expect("parsed args"));
-        });
-        parsed.expect("args set")
-    }
+        let Json(detail) = get_video(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
+            .await
+            .unwrap();
+        let body = serde_json::to_value(&detail).unwrap();
+        assert!(body.get("segments").is_none());
+
+        let Json(stored) = set_video_segments(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            AxumPath("alpha".into()),
+            Json(vec![
+                segment(SegmentCategory::Outro, 50.0, 60.0),
+                segment(SegmentCategory::Sponsor, 5.0, 20.5),
+            ]),
+        )
+        .await
+        .unwrap();
+        assert_eq!(stored[0].category, SegmentCategory::Sponsor);
 
-    #[test]
-    fn backend_args_default_media_root() {
-        let args = parse_backend_args(
-            &[
-                ("MEDIA_ROOT", "/yt/test"),
-                ("WWW_ROOT", "/www/test"),
-                ("NEWTUBE_PORT", "4242"),
-                ("NEWTUBE_HOST", "127.0.0.1"),
-            ],
-            &[],
-        );
-        assert_eq!(args.media_root, PathBuf::from("/yt/test"));
-        assert_eq!(args.www_root, PathBuf::from("/www/test"));
-        assert_eq!(args.newtube_port, 4242);
-    }
+        let Json(detail) = get_video(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
+            .await
+            .unwrap();
+        let body = serde_json::to_value(&detail).unwrap();
+        assert_eq!(body["segments"][0]["category"], "sponsor");
+        assert_eq!(body["segments"][0]["end"], 20.5);
+        assert_eq!(body["segments"][1]["category"], "outro");
+
+        for invalid in [
+            segment(SegmentCategory::Intro, 10.0, 10.0),
+            segment(SegmentCategory::Intro, -1.0, 10.0),
+            segment(SegmentCategory::Intro, 61.0, 70.0),
+        ] {
+            let err = set_video_segments(
+                AxumState(ctx.state.clone()),
+                AdminProfile,
+                AxumPath("alpha".into()),
+                Json(vec![invalid]),
+            )
+            .await
+            .unwrap_err();
+            assert_eq!(err.status, StatusCode::BAD_REQUEST);
+        }
+        let Json(unchanged) =
+            get_video_segments(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
+                .await
+                .unwrap();
+        assert_eq!(unchanged.len(), 2);
 
-    #[test]
-    fn backend_args_override_media_root() {
-        let args = parse_backend_args(
-            &[
-                ("MEDIA_ROOT", "/yt/test"),
-                ("WWW_ROOT", "/www/test"),
-                ("NEWTUBE_PORT", "4242"),
-                ("NEWTUBE_HOST", "127.0.0.1"),
-            ],
-            &["--media-root", "/custom/media"],
-        );
-        assert_eq!(args.media_root, PathBuf::from("/custom/media"));
+        let err = set_short_segments(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            AxumPath("alpha".into()),
+            Json(Vec::new()),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
     }
 
-    #[test]
-    fn backend_args_override_www_root() {
-        let args = parse_backend_args(
-            &[
-                ("MEDIA_ROOT", "/yt/test"),
-                ("WWW_ROOT", "/www/test"),
-                ("NEWTUBE_PORT", "4242"),
-                ("NEWTUBE_HOST", "127.0.0.1"),
-            ],
-            &["--www-root", "/custom/www"],
-        );
-        assert_eq!(args.www_root, PathBuf::from("/custom/www"));
-    }
+    /// The admin API runs one scrub at a time, reports the last pass and
+    /// lists the files it flagged.
+    #[tokio::test]
+    async fn scrub_api_flags_changed_files() {
+        let ctx = BackendTestContext::new().await;
+        let file = ctx.state.files.videos.join("alpha/alpha_1080p.mp4");
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(&file, "original").unwrap();
+        let mut video = sample_video("alpha");
+        video.sources[0].path = Some(file.to_string_lossy().into_owned());
+        video.sources[0].sha256 = Some("0".repeat(64));
+        ctx.store.upsert_video(&video).await.unwrap();
 
-    #[test]
-    fn backend_args_override_port() {
-        let args = parse_backend_args(
-            &[
-                ("MEDIA_ROOT", "/yt/test"),
-                ("WWW_ROOT", "/www/test"),
-                ("NEWTUBE_PORT", "4242"),
-                ("NEWTUBE_HOST", "127.0.0.1"),
-            ],
-            &["--port", "9000"],
-        );
-        assert_eq!(args.newtube_port, 9000);
-    }
+        let (status, Json(started)) = start_scrub(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            Query(ScrubParams::default()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert!(started.running);
+        let second = start_scrub(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            Query(ScrubParams::default()),
+        )
+        .await;
+        assert_eq!(second.unwrap_err().status, StatusCode::CONFLICT);
 
-    #[test]
-    fn backend_args_override_host() {
-        let args = parse_backend_args(
-            &[
-                ("MEDIA_ROOT", "/yt/test"),
-                ("WWW_ROOT", "/www/test"),
-                ("NEWTUBE_PORT", "4242"),
-                ("NEWTUBE_HOST", "127.0.0.1"),
-            ],
-            &["--host", "0.0.0.0"],
-        );
-        assert_eq!(args.listen_host, "0.0.0.0".parse::<IpAddr>().unwrap());
+        let report = loop {
+            let Json(status) = get_scrub_status(AxumState(ctx.state.clone()), AdminProfile)
+                .await
+                .unwrap();
+            if !status.running {
+                break status.last_report.expect("scrub report");
+            }
+            sleep(Duration::from_millis(10)).await;
+        };
+        assert_eq!(report.checked, 1);
+        assert_eq!(report.mismatched[0].videoid, "alpha");
+
+        let Json(flagged) = list_source_checksums(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            Query(ChecksumParams {
+                status: Some("mismatch".into()),
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(flagged.len(), 1);
+        assert!(flagged[0].observed_sha256.is_some());
+        let unknown = list_source_checksums(
+            AxumState(ctx.state.clone()),
+            AdminProfile,
+            Query(ChecksumParams {
+                status: Some("bogus".into()),
+            }),
+        )
+        .await;
+        assert_eq!(unknown.unwrap_err().status, StatusCode::BAD_REQUEST);
     }
 
+    /// Comments gone from YouTube are hidden unless asked for.
     #[tokio::test]
-    async fn bootstrap_caches_payload() {
+    async fn comments_endpoint_filters_deleted_upstream() {
         let mut ctx = BackendTestContext::new().await;
         ctx.insert_video("alpha").await;
-        ctx.insert_short("beta").await;
-        ctx.insert_subtitles(
-            "alpha",
-            vec![SubtitleTrack {
-                code: "en".into(),
-                name: "English".into(),
-                url: "/api/videos/alpha/subtitles/en".into(),
-                path: None,
-            }],
-        )
-        .await;
-        ctx.insert_comments("alpha", vec![sample_comment("1", "alpha")])
-            .await;
+        ctx.state
+            .store
+            .merge_comments(
+                "alpha",
+                &[sample_comment("1", "alpha"), sample_comment("2", "alpha")],
+            )
+            .await
+            .unwrap();
+        ctx.state
+            .store
+            .merge_comments("alpha", &[sample_comment("1", "alpha")])
+            .await
+            .unwrap();
 
-        let first = ctx.state.get_bootstrap().await.unwrap();
-        assert_eq!(first.videos.len(), 1);
+        let Json(visible) = get_video_comments(
+            AxumState(ctx.state.clone()),
+            AxumPath("alpha".into()),
+            Query(CommentParams::default()),
+        )
+        .await
+        .unwrap();
+        assert_eq!(visible.len(), 1);
+        let Json(all) = get_video_comments(
+            AxumState(ctx.state.clone()),
+            AxumPath("alpha".into()),
+            Query(CommentParams {
+                include_deleted: true,
+            }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(all[1].deleted_upstream_at.is_some());
+    }
 
-        ctx.insert_video("gamma").await;
-        let second = ctx.state.get_bootstrap().await.unwrap();
-        assert!(!Arc::ptr_eq(&first, &second));
-        assert!(second.videos.iter().any(|video| video.videoid == "gamma"));
+    /// A session counts once it passes the threshold; requests after the
+    /// idle gap start a new session that can count again.
+    #[tokio::test]
+    async fn play_tracker_counts_once_per_session() {
+        let ctx = BackendTestContext::new().await;
+        let plays = &ctx.state.plays;
+        let start = Instant::now();
+        let size = 1000;
+        assert_eq!(play_threshold(size), 100);
+        assert_eq!(play_threshold(u64::MAX), PLAY_THRESHOLD_BYTES);
+
+        assert!(!plays.observe("alpha", "tv", size, 60, start));
+        assert!(plays.observe("alpha", "tv", size, 60, start));
+        assert!(!plays.observe("alpha", "tv", size, 500, start));
+        assert!(plays.observe("alpha", "phone", size, 200, start));
+
+        let later = start + PLAY_SESSION_IDLE + Duration::from_secs(1);
+        assert!(plays.observe("alpha", "tv", size, 100, later));
+        assert_eq!(plays.sessions.lock().len(), 1);
     }
 
+    /// Streaming a file records its bytes and a play per client, exposed
+    /// per video and in the most-played listing.
     #[tokio::test]
-    async fn media_list_populates_cache() {
+    async fn streaming_records_local_plays() {
         let mut ctx = BackendTestContext::new().await;
         ctx.insert_video("alpha").await;
-
-        let list = ctx
+        ctx.insert_video("beta").await;
+        let media_dir = ctx
             .state
-            .get_media_list(MediaCategory::Video)
+            .files
+            .media_dir(MediaCategory::Video)
+            .join("alpha");
+        std::fs::create_dir_all(&media_dir).unwrap();
+        std::fs::write(media_dir.join("alpha_1080p.mp4"), "0123456789").unwrap();
+
+        for (agent, range) in [
+            ("tv", "bytes=0-3"),
+            ("tv", "bytes=4-"),
+            ("phone", "bytes=0-"),
+        ] {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::USER_AGENT, agent.parse().unwrap());
+            headers.insert(header::RANGE, range.parse().unwrap());
+            let response = stream_media(
+                ctx.state.clone(),
+                MediaCategory::Video,
+                "alpha".into(),
+                "1080p".into(),
+                &headers,
+            )
             .await
             .unwrap();
-        assert_eq!(list.len(), 1);
-        ctx.delete_by_videoid("videos", "alpha").await;
+            to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        }
 
-        let cached = ctx
-            .state
-            .get_media_list(MediaCategory::Video)
+        let mut stats = PlayStats::default();
+        for _ in 0..50 {
+            let Json(current) =
+                get_video_plays(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
+                    .await
+                    .unwrap();
+            stats = current;
+            if stats.watched_bytes == 20 {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!((stats.plays, stats.watched_bytes), (2, 20));
+        assert!(stats.last_played_at.is_some());
+
+        let Json(unplayed) = get_video_plays(AxumState(ctx.state.clone()), AxumPath("beta".into()))
             .await
             .unwrap();
-        assert_eq!(cached.len(), 0);
+        assert_eq!(unplayed.plays, 0);
+        let err = get_short_plays(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+
+        ctx.state
+            .user_data
+            .record_play("gone", 10, true)
+            .await
+            .unwrap();
+        let Json(top) = list_most_played(
+            AxumState(ctx.state.clone()),
+            Query(MostPlayedParams { limit: None }),
+        )
+        .await
+        .unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].stats.videoid, "alpha");
+        assert_eq!(top[0].kind, "video");
+        assert!(
+            top[0]
+                .record
+                .sources
+                .iter()
+                .all(|source| source.path.is_none())
+        );
     }
 
+    /// The master playlist lists formats with a file on disk; variant files
+    /// are packaged once by ffmpeg and then served from the cache.
     #[tokio::test]
-    async fn api_responses_strip_file_paths() {
+    async fn hls_packages_variants_on_demand() {
         let ctx = BackendTestContext::new().await;
         let mut video = sample_video("alpha");
-        video.sources[0].path = Some("/yt/videos/alpha/secret.mp4".into());
+        let mut missing = video.sources[0].clone();
+        missing.format_id = "720p".into();
+        video.sources.push(missing);
         ctx.store.upsert_video(&video).await.unwrap();
+        let media_dir = ctx
+            .state
+            .files
+            .media_dir(MediaCategory::Video)
+            .join("alpha");
+        fs::create_dir_all(&media_dir).unwrap();
+        fs::write(media_dir.join("alpha_1080p.mp4"), "bytes").unwrap();
+        let runs = ctx._temp.path().join("ffmpeg-runs");
+        install_stub(
+            ctx._temp.path(),
+            "ffmpeg",
+            &format!(
+                "#!/bin/sh\necho run >> {}\nfor last; do :; done\nout=$(dirname \"$last\")\n\
+                 printf '#EXTM3U\\n' > \"$last\"\nprintf init > \"$out/init.mp4\"\n\
+                 printf seg > \"$out/0.m4s\"\n",
+                runs.display()
+            ),
+        );
 
-        let Json(videos) = super::list_videos(AxumState(ctx.state.clone()))
+        let response = video_hls_master(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
             .await
             .unwrap();
-        assert!(videos[0].sources[0].path.is_none());
+        assert_eq!(response.headers()[header::CONTENT_TYPE], hls::PLAYLIST_MIME);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let playlist = String::from_utf8(body.to_vec()).unwrap();
+        assert!(playlist.contains("RESOLUTION=1920x1080"));
+        assert!(playlist.contains("\n1080p/index.m3u8\n"));
+        assert!(!playlist.contains("720p"));
+
+        let fetch = |file: &str| {
+            video_hls_file(
+                AxumState(ctx.state.clone()),
+                AxumPath(("alpha".into(), "1080p".into(), file.into())),
+                HeaderMap::new(),
+            )
+        };
+        let response = fetch("index.m3u8").await.unwrap();
+        assert_eq!(response.headers()[header::CONTENT_TYPE], hls::PLAYLIST_MIME);
+        let response = fetch("0.m4s").await.unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "video/iso.segment"
+        );
+        // Repackaged segments reuse their names, so caches must revalidate.
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(&body[..], b"seg");
+        assert_eq!(fs::read_to_string(&runs).unwrap().lines().count(), 1);
+        // Segment bytes count as playback of the video; playlists do not.
+        let mut stats = PlayStats::default();
+        for _ in 0..50 {
+            let Json(current) =
+                get_video_plays(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
+                    .await
+                    .unwrap();
+            stats = current;
+            if stats.watched_bytes == 3 {
+                break;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!((stats.plays, stats.watched_bytes), (1, 3));
+        assert!(ctx.state.files.hls.join("alpha/1080p/init.mp4").is_file());
+        // Variant locks are dropped once no request uses them.
+        assert!(ctx.state.hls.locks.lock().is_empty());
 
-        let Json(single) = super::get_video(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
+        let err = fetch("notes.txt").await.unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+        let err = video_hls_file(
+            AxumState(ctx.state.clone()),
+            AxumPath(("alpha".into(), "720p".into(), "index.m3u8".into())),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+    }
+
+    /// Repackaging a stale variant waits until requests holding the current
+    /// output have opened their files.
+    #[tokio::test]
+    async fn hls_repackaging_waits_for_readers() {
+        let temp = tempdir().unwrap();
+        let runs = temp.path().join("ffmpeg-runs");
+        let ffmpeg = install_stub(
+            temp.path(),
+            "ffmpeg",
+            &format!(
+                "#!/bin/sh\necho run >> {}\nfor last; do :; done\n\
+                 printf '#EXTM3U\\n' > \"$last\"\n",
+                runs.display()
+            ),
+        );
+        let packager = HlsPackager::new(ffmpeg);
+        let input = temp.path().join("alpha_1080p.mp4");
+        fs::write(&input, "bytes").unwrap();
+        let output_dir = temp.path().join("hls/alpha/1080p");
+
+        let reading = packager.ensure(&input, &output_dir).await.unwrap();
+        let later = fs::metadata(output_dir.join(hls::VARIANT_PLAYLIST))
+            .unwrap()
+            .modified()
+            .unwrap()
+            + Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&input)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        let repackage = tokio::spawn({
+            let packager = packager.clone();
+            let input = input.clone();
+            let output_dir = output_dir.clone();
+            async move { packager.ensure(&input, &output_dir).await.map(drop) }
+        });
+        sleep(Duration::from_millis(100)).await;
+        assert!(!repackage.is_finished());
+        assert_eq!(fs::read_to_string(&runs).unwrap().lines().count(), 1);
+
+        drop(reading);
+        repackage.await.unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&runs).unwrap().lines().count(), 2);
+        assert!(packager.locks.lock().is_empty());
+    }
+
+    /// The manifest lists indexed adaptive streams that exist on disk, and
+    /// the streams it points at answer range requests.
+    #[tokio::test]
+    async fn dash_manifest_lists_indexed_streams() {
+        let ctx = BackendTestContext::new().await;
+        let media_dir = ctx
+            .state
+            .files
+            .media_dir(MediaCategory::Video)
+            .join("alpha");
+        fs::create_dir_all(&media_dir).unwrap();
+        let stream = |format_id: &str, kind: AdaptiveKind, mime: &str, ext: &str| {
+            let path = media_dir.join(format!("alpha_{format_id}.{ext}"));
+            fs::write(&path, vec![0u8; 300]).unwrap();
+            AdaptiveStream {
+                format_id: format_id.into(),
+                kind,
+                mime_type: mime.into(),
+                codecs: "vp9".into(),
+                bandwidth: Some(1_000_000),
+                quality_label: None,
+                width: None,
+                height: None,
+                fps: None,
+                audio_sample_rate: None,
+                audio_channels: None,
+                init_range: Some(ByteRange { start: 0, end: 99 }),
+                index_range: Some(ByteRange {
+                    start: 100,
+                    end: 199,
+                }),
+                ext: Some(ext.into()),
+                file_size: Some(300),
+                url: format!("/api/videos/alpha/dash/{format_id}"),
+                path: Some(path.to_string_lossy().into_owned()),
+                sha256: None,
+            }
+        };
+        let mut video = sample_video("alpha");
+        let mut unindexed = stream("248", AdaptiveKind::Video, "video/webm", "webm");
+        unindexed.index_range = None;
+        video.adaptive_streams = vec![
+            stream("313", AdaptiveKind::Video, "video/webm", "webm"),
+            stream("251", AdaptiveKind::Audio, "audio/webm", "webm"),
+            unindexed,
+        ];
+        ctx.store.upsert_video(&video).await.unwrap();
+
+        let response = video_dash_manifest(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
             .await
             .unwrap();
-        assert!(single.sources[0].path.is_none());
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            dash::MANIFEST_MIME
+        );
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mpd = String::from_utf8(body.to_vec()).unwrap();
+        assert!(mpd.contains("mediaPresentationDuration=\"PT60S\""));
+        assert!(mpd.contains("<BaseURL>/api/videos/alpha/dash/313</BaseURL>"));
+        assert!(mpd.contains("<BaseURL>/api/videos/alpha/dash/251</BaseURL>"));
+        assert!(!mpd.contains("dash/248"));
 
-        let bootstrap = ctx.state.get_bootstrap().await.unwrap();
-        assert!(bootstrap.videos[0].sources[0].path.is_none());
+        let mut headers = HeaderMap::new();
+        headers.insert(header::RANGE, "bytes=100-199".parse().unwrap());
+        let response = stream_video_adaptive(
+            AxumState(ctx.state.clone()),
+            AxumPath(("alpha".into(), "251".into())),
+            headers,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "audio/webm");
+        let err = stream_video_adaptive(
+            AxumState(ctx.state.clone()),
+            AxumPath(("alpha".into(), "1080p".into())),
+            HeaderMap::new(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
+
+        // Without a video track there is nothing to play.
+        video
+            .adaptive_streams
+            .retain(|stream| stream.kind == AdaptiveKind::Audio);
+        ctx.store.upsert_video(&video).await.unwrap();
+        ctx.state.cache.clear();
+        let err = video_dash_manifest(AxumState(ctx.state.clone()), AxumPath("alpha".into()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.status, StatusCode::NOT_FOUND);
     }
 
     #[tokio::test]
@@ -2250,9 +6679,14 @@ mod tests {
         std::fs::create_dir_all(&subtitle_dir).unwrap();
         std::fs::write(subtitle_dir.join("alpha.en.vtt"), "WEBVTT").unwrap();
 
-        let response = download_subtitle(ctx.state.clone(), "alpha".into(), "en".into())
-            .await
-            .unwrap();
+        let response = download_subtitle(
+            ctx.state.clone(),
+            "alpha".into(),
+            "en".into(),
+            &HeaderMap::new(),
+        )
+        .await
+        .unwrap();
         assert_eq!(response.status(), StatusCode::OK);
     }
 
@@ -2263,9 +6697,14 @@ mod tests {
         std::fs::create_dir_all(&thumb_dir).unwrap();
         std::fs::write(thumb_dir.join("poster.png"), b"PNG").unwrap();
 
-        let response = download_thumbnail(ctx.state.clone(), "alpha".into(), "poster.png".into())
-            .await
-            .unwrap();
+        let response = download_thumbnail(
+            ctx.state.clone(),
+            "alpha".into(),
+            "poster.png".into(),
+            &HeaderMap::new(),
+        )
+        .await
+        .unwrap();
         assert_eq!(response.status(), StatusCode::OK);
         let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
         assert_eq!(body.as_ref(), b"PNG");
@@ -2274,9 +6713,14 @@ mod tests {
     #[tokio::test]
     async fn download_thumbnail_rejects_path_traversal() {
         let ctx = BackendTestContext::new().await;
-        let err = download_thumbnail(ctx.state.clone(), "alpha".into(), "../secret.txt".into())
-            .await
-            .unwrap_err();
+        let err = download_thumbnail(
+            ctx.state.clone(),
+            "alpha".into(),
+            "../secret.txt".into(),
+            &HeaderMap::new(),
+        )
+        .await
+        .unwrap_err();
         assert_eq!(err.status, StatusCode::NOT_FOUND);
     }
 
@@ -2387,7 +6831,7 @@ mod tests {
 
         let mut headers = HeaderMap::new();
         headers.insert(header::RANGE, header::HeaderValue::from_static("bytes=1-3"));
-        let response = stream_file(path.clone(), None, Some(&headers))
+        let response = stream_file(path.clone(), None, Some(&headers), CachePolicy::Revalidate)
             .await
             .unwrap();
         assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
@@ -2401,7 +6845,7 @@ mod tests {
 
         let mut headers = HeaderMap::new();
         headers.insert(header::RANGE, header::HeaderValue::from_static("bytes=-2"));
-        let response = stream_file(path.clone(), None, Some(&headers))
+        let response = stream_file(path.clone(), None, Some(&headers), CachePolicy::Revalidate)
             .await
             .unwrap();
         assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
@@ -2413,7 +6857,9 @@ mod tests {
             header::RANGE,
             header::HeaderValue::from_static("bytes=10-20"),
         );
-        let response = stream_file(path, None, Some(&headers)).await.unwrap();
+        let response = stream_file(path, None, Some(&headers), CachePolicy::Revalidate)
+            .await
+            .unwrap();
         assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
         assert_eq!(
             response.headers().get(header::CONTENT_RANGE).unwrap(),
@@ -2421,6 +6867,125 @@ mod tests {
         );
     }
 
+    /// Validators come from file metadata: matching `If-None-Match` or
+    /// `If-Modified-Since` yields a 304, and a stale `If-Range` turns a
+    /// range request into a full response.
+    #[tokio::test]
+    async fn stream_file_honours_conditional_requests() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sample.txt");
+        fs::write(&path, b"abcdef").unwrap();
+        let request = |pairs: &[(header::HeaderName, &str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(name.clone(), value.parse().unwrap());
+            }
+            let path = path.clone();
+            async move {
+                stream_file(path, None, Some(&headers), CachePolicy::Revalidate)
+                    .await
+                    .unwrap()
+            }
+        };
+
+        let response = request(&[]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[header::CACHE_CONTROL], "no-cache");
+        let etag = response.headers()[header::ETAG]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let modified = response.headers()[header::LAST_MODIFIED]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        assert!(etag.starts_with('"') && modified.ends_with(" GMT"));
+
+        let response = request(&[(header::IF_NONE_MATCH, &format!("\"other\", W/{etag}"))]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[header::ETAG], etag.as_str());
+        assert!(
+            to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let response = request(&[(header::IF_MODIFIED_SINCE, &modified)]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        // A non-matching tag wins over a matching date.
+        let response = request(&[
+            (header::IF_NONE_MATCH, "\"other\""),
+            (header::IF_MODIFIED_SINCE, &modified),
+        ])
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response =
+            request(&[(header::IF_MODIFIED_SINCE, "Thu, 01 Jan 1970 00:00:00 GMT")]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        for if_range in [etag.as_str(), modified.as_str()] {
+            let response =
+                request(&[(header::RANGE, "bytes=1-3"), (header::IF_RANGE, if_range)]).await;
+            assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        }
+        for stale in ["\"stale\"", "Thu, 01 Jan 1970 00:00:00 GMT"] {
+            let response =
+                request(&[(header::RANGE, "bytes=1-3"), (header::IF_RANGE, stale)]).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(body.as_ref(), b"abcdef");
+        }
+    }
+
+    /// Thumbnails are immutable, media is cached for a while and the SPA
+    /// shell is always revalidated.
+    #[tokio::test]
+    async fn file_routes_apply_cache_policies() {
+        let mut ctx = BackendTestContext::new().await;
+        ctx.insert_video("alpha").await;
+        let thumb_dir = ctx.state.files.thumbnails.join("alpha");
+        fs::create_dir_all(&thumb_dir).unwrap();
+        fs::write(thumb_dir.join("poster.png"), b"PNG").unwrap();
+        let media_dir = ctx
+            .state
+            .files
+            .media_dir(MediaCategory::Video)
+            .join("alpha");
+        fs::create_dir_all(&media_dir).unwrap();
+        fs::write(media_dir.join("alpha_1080p.mp4"), "bytes").unwrap();
+        fs::write(ctx.state.www_root.join("index.html"), "<html>").unwrap();
+        let cache_control = |response: &Response| {
+            response.headers()[header::CACHE_CONTROL]
+                .to_str()
+                .unwrap()
+                .to_owned()
+        };
+
+        let thumbnail = download_thumbnail(
+            ctx.state.clone(),
+            "alpha".into(),
+            "poster.png".into(),
+            &HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(cache_control(&thumbnail), "max-age=31536000, immutable");
+        let media = stream_media(
+            ctx.state.clone(),
+            MediaCategory::Video,
+            "alpha".into(),
+            "1080p".into(),
+            &HeaderMap::new(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(cache_control(&media), "max-age=86400");
+        let index = serve_www_path(&ctx.state.www_root, "/watch/alpha", &HeaderMap::new())
+            .await
+            .unwrap();
+        assert_eq!(cache_control(&index), "no-cache");
+    }
+
     #[test]
     fn ensure_safe_path_segment_rejects_invalid() {
         assert!(ensure_safe_path_segment("").is_err());
@@ -2469,6 +7034,7 @@ mod tests {
             file_size: None,
             url: "/api/videos/alpha/streams/http_1080p_60".into(),
             path: None,
+            sha256: None,
         };
         assert_eq!(source_key(&source).as_deref(), Some("http_1080p_60"));
 
@@ -2561,9 +7127,10 @@ mod tests {
         let payload = InstanceSettings {
             missing_media_behavior: MissingMediaBehavior::Prompt,
         };
-        let Json(updated) = update_settings(AxumState(ctx.state.clone()), Json(payload))
-            .await
-            .unwrap();
+        let Json(updated) =
+            update_settings(AxumState(ctx.state.clone()), AdminProfile, Json(payload))
+                .await
+                .unwrap();
         assert_eq!(updated.missing_media_behavior, MissingMediaBehavior::Prompt);
 
         let Json(current) = get_settings(AxumState(ctx.state.clone())).await.unwrap();
@@ -2593,9 +7160,14 @@ mod tests {
         )
         .await;
 
-        let err = download_subtitle(ctx.state.clone(), "alpha".into(), "en".into())
-            .await
-            .unwrap_err();
+        let err = download_subtitle(
+            ctx.state.clone(),
+            "alpha".into(),
+            "en".into(),
+            &HeaderMap::new(),
+        )
+        .await
+        .unwrap_err();
         assert_eq!(err.status, StatusCode::NOT_FOUND);
     }
 
@@ -2614,9 +7186,14 @@ mod tests {
         )
         .await;
 
-        let err = download_subtitle(ctx.state.clone(), "alpha".into(), "fr".into())
-            .await
-            .unwrap_err();
+        let err = download_subtitle(
+            ctx.state.clone(),
+            "alpha".into(),
+            "fr".into(),
+            &HeaderMap::new(),
+        )
+        .await
+        .unwrap_err();
         assert_eq!(err.status, StatusCode::NOT_FOUND);
     }
 
@@ -2628,7 +7205,11 @@ mod tests {
         let success_bin = install_stub(dir.path(), "download_channel", success_script);
         let _success_guard = set_download_channel_stub(success_bin);
 
-        let downloads = DownloadManager::new(dir.path().to_path_buf(), dir.path().join("www"));
+        let downloads = DownloadManager::new(
+            dir.path().to_path_buf(),
+            dir.path().join("www"),
+            EventBus::default(),
+        );
         let job_id = downloads
             .start_video_download("alpha".into(), MediaCategory::Video)
             .unwrap();
@@ -2643,7 +7224,11 @@ mod tests {
         let fail_script = "#!/usr/bin/env bash\nexit 1\n";
         let fail_bin = install_stub(dir.path(), "download_channel_fail", fail_script);
         let _fail_guard = set_download_channel_stub(fail_bin);
-        let failing = DownloadManager::new(dir.path().to_path_buf(), dir.path().join("www"));
+        let failing = DownloadManager::new(
+            dir.path().to_path_buf(),
+            dir.path().join("www"),
+            EventBus::default(),
+        );
         let fail_id = failing
             .start_video_download("beta".into(), MediaCategory::Video)
             .unwrap();
@@ -2652,6 +7237,86 @@ mod tests {
         assert_eq!(fail_status.progress, 100);
     }
 
+    /// Subscribers see every state transition plus the progress the
+    /// downloader writes, and status requests are answered from memory.
+    #[tokio::test]
+    async fn download_events_report_transitions_and_progress() {
+        let dir = tempdir().unwrap();
+        let script = "#!/usr/bin/env bash\n\
+            while [ $# -gt 0 ]; do\n\
+              if [ \"$1\" = --progress-file ]; then progress=\"$2\"; fi\n\
+              shift\n\
+            done\n\
+            sleep 0.3\n\
+            echo '{\"progress\":50,\"message\":\"Halfway\"}' > \"$progress\"\n\
+            sleep 1.2\n";
+        let stub = install_stub(dir.path(), "download_channel_progress", script);
+        let events = EventBus::default();
+        let downloads = DownloadManager {
+            inner: Arc::new(DownloadManagerInner {
+                jobs: parking_lot::Mutex::new(HashMap::new()),
+                counter: AtomicUsize::new(1),
+                media_root: dir.path().to_path_buf(),
+                www_root: dir.path().join("www"),
+                downloader: Some(stub),
+                events: events.clone(),
+            }),
+        };
+        let mut receiver = events.subscribe();
+        let job_id = downloads
+            .start_video_download("alpha".into(), MediaCategory::Video)
+            .unwrap();
+
+        let mut seen = Vec::new();
+        while let Ok(Ok(ServerEvent::Download(status))) =
+            tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await
+        {
+            assert_eq!(status.id, job_id);
+            let done = status.status == "completed";
+            seen.push((status.status, status.progress, status.message));
+            if done {
+                break;
+            }
+        }
+        assert_eq!(seen[0], ("queued".into(), 0, "Queued download".into()));
+        assert!(seen.iter().any(|(status, _, _)| status == "running"));
+        assert!(seen.contains(&("running".into(), 50, "Halfway".into())));
+        assert_eq!(seen.last().unwrap().0, "completed");
+
+        // Later changes to the file are no longer picked up per request.
+        write_progress_report(&downloads.progress_file_path(&job_id), 75, "Stale");
+        assert_eq!(downloads.get_status(&job_id).unwrap().progress, 50);
+    }
+
+    /// `library` events fire once `data_version` moves past the baseline
+    /// and point at the change log; the stream is served as SSE.
+    #[tokio::test]
+    async fn library_events_follow_data_version() {
+        let mut ctx = BackendTestContext::new().await;
+        let reader = ctx.state.reader.clone();
+        let (version, change) = library_change(&reader, None).await.unwrap();
+        assert_eq!(change, None);
+        assert_eq!(
+            library_change(&reader, Some(version)).await.unwrap().1,
+            None
+        );
+
+        ctx.insert_video("alpha").await;
+        let (moved, change) = library_change(&reader, Some(version)).await.unwrap();
+        assert_ne!(moved, version);
+        let change = change.expect("library change");
+        assert_eq!(change.seq, reader.latest_change_seq().await.unwrap());
+
+        let response = server_events(AxumState(ctx.state.clone())).await;
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "text/event-stream"
+        );
+        assert!(ctx.state.events.has_subscribers());
+        drop(response);
+        assert!(!ctx.state.events.has_subscribers());
+    }
+
     #[tokio::test]
     async fn download_manager_handles_channel_lookup_failure() {
         let dir = tempdir().unwrap();
@@ -2665,7 +7330,11 @@ mod tests {
         let yt_stub = install_stub(&stub_dir, "yt-dlp", "#!/usr/bin/env bash\nexit 1\n");
         let _yt_guard = set_ytdlp_stub(yt_stub);
 
-        let downloads = DownloadManager::new(dir.path().to_path_buf(), dir.path().join("www"));
+        let downloads = DownloadManager::new(
+            dir.path().to_path_buf(),
+            dir.path().join("www"),
+            EventBus::default(),
+        );
         let job_id = downloads
             .start_channel_download("alpha".into(), MediaCategory::Video)
             .unwrap();
@@ -10,16 +10,22 @@
 use anyhow::{Context, Result, bail};
 use chrono::{NaiveDate, Utc};
 use newtube_tools::config::{RuntimeOverrides, resolve_runtime_paths};
+use newtube_tools::dash::segment_base;
+use newtube_tools::library::{collect_sources_from_disk, load_archive, mime_from_extension};
 use newtube_tools::metadata::{
-    CommentRecord, MetadataStore, SubtitleCollection, SubtitleTrack, VideoRecord, VideoSource,
+    AdaptiveKind, AdaptiveStream, Availability, ChannelRecord, Chapter, CommentRecord,
+    MetadataStore, PlaylistEntry, PlaylistRecord, SubtitleCollection, SubtitleTrack, VideoRecord,
+    VideoSource, WriteBatch,
 };
+use newtube_tools::scrub::fill_checksums;
 use newtube_tools::security::ensure_not_root;
+use newtube_tools::transcript::read_transcripts;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
-use std::collections::{BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::fs::{self, File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
+use std::io::{BufReader, Write};
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 #[cfg(test)]
@@ -32,6 +38,7 @@ const SHORTS_SUBDIR: &str = "shorts";
 const SUBTITLES_SUBDIR: &str = "subtitles";
 const THUMBNAILS_SUBDIR: &str = "thumbnails";
 const COMMENTS_SUBDIR: &str = "comments";
+const CHANNELS_SUBDIR: &str = "channels";
 const ARCHIVE_FILE: &str = "download-archive.txt";
 const COOKIES_FILE: &str = "cookies.txt";
 #[cfg(test)]
@@ -84,6 +91,7 @@ struct Paths {
     subtitles: PathBuf,
     thumbnails: PathBuf,
     comments: PathBuf,
+    channels: PathBuf,
     archive: PathBuf,
     cookies: PathBuf,
     www_root: PathBuf,
@@ -96,6 +104,11 @@ struct DownloaderArgs {
     video_id: Option<String>,
     media_kind: Option<MediaKind>,
     progress_file: Option<PathBuf>,
+    /// Rebuild the DB from files already on disk instead of downloading.
+    reindex: bool,
+    /// Store a downloaded playlist as tracked so `routine_update` refreshes
+    /// it. `--no-track` downloads it once, or stops tracking it.
+    track: bool,
     media_root: PathBuf,
     www_root: PathBuf,
 }
@@ -120,6 +133,8 @@ impl DownloaderArgs {
         let mut video_id: Option<String> = None;
         let mut media_kind: Option<MediaKind> = None;
         let mut progress_file: Option<PathBuf> = None;
+        let mut reindex = false;
+        let mut track = true;
         let mut args = iter.into_iter();
 
         while let Some(arg) = args.next() {
@@ -182,6 +197,8 @@ impl DownloaderArgs {
                         .ok_or_else(|| anyhow::anyhow!("--progress-file requires a value"))?;
                     progress_file = Some(PathBuf::from(value));
                 }
+                "--reindex" => reindex = true,
+                "--no-track" => track = false,
                 _ if arg.starts_with('-') => {
                     bail!("unknown argument: {arg}");
                 }
@@ -197,9 +214,18 @@ impl DownloaderArgs {
         if channel_url.is_some() && media_kind.is_some() {
             bail!("--media-kind can only be used with --video-id");
         }
-        if channel_url.is_none() && video_id.is_none() {
+        if reindex && (channel_url.is_some() || video_id.is_some() || media_kind.is_some()) {
+            bail!("--reindex works on the whole library and takes no channel or video");
+        }
+        let playlist_url = channel_url
+            .as_deref()
+            .is_some_and(|url| playlist_id_from_url(url).is_some());
+        if !track && !playlist_url {
+            bail!("--no-track can only be used with a playlist URL");
+        }
+        if channel_url.is_none() && video_id.is_none() && !reindex {
             bail!(
-                "Usage: download_channel [--media-root <path>] [--www-root <path>] [--progress-file <path>] <channel_url>\n       download_channel [--media-root <path>] [--www-root <path>] [--progress-file <path>] --video-id <id> [--media-kind video|short]"
+                "Usage: download_channel [--media-root <path>] [--www-root <path>] [--progress-file <path>] [--no-track] <channel_url|playlist_url>\n       download_channel [--media-root <path>] [--www-root <path>] [--progress-file <path>] --video-id <id> [--media-kind video|short]\n       download_channel [--media-root <path>] [--www-root <path>] --reindex"
             );
         }
 
@@ -216,6 +242,8 @@ impl DownloaderArgs {
             video_id,
             media_kind,
             progress_file,
+            reindex,
+            track,
             media_root,
             www_root,
         })
@@ -311,6 +339,23 @@ struct InfoJson {
 struct FormatEntry {
     #[serde(rename = "format_id")]
     format_id: Option<String>,
+    vcodec: Option<String>,
+    acodec: Option<String>,
+}
+
+impl FormatEntry {
+    /// Video-only or audio-only, i.e. one of the DASH streams.
+    fn is_adaptive(&self) -> bool {
+        let missing = |codec: Option<&str>| codec.is_some_and(|c| c.eq_ignore_ascii_case("none"));
+        missing(self.vcodec.as_deref()) || missing(self.acodec.as_deref())
+    }
+}
+
+/// A format to download and whether it is a video-only or audio-only stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct DownloadFormat {
+    id: String,
+    adaptive: bool,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -400,6 +445,49 @@ struct VideoInfo {
     #[serde(default, rename = "automatic_captions")]
     automatic_captions: Option<HashMap<String, Vec<SubtitleInfo>>>,
     formats: Option<Vec<FormatInfo>>,
+    #[serde(default)]
+    chapters: Option<Vec<ChapterInfo>>,
+}
+
+/// Channel-level fields returned by `yt-dlp --dump-single-json` for a channel
+/// URL when no entries are requested.
+#[derive(Debug, Deserialize)]
+struct ChannelInfo {
+    id: Option<String>,
+    channel_id: Option<String>,
+    channel: Option<String>,
+    title: Option<String>,
+    uploader_id: Option<String>,
+    channel_url: Option<String>,
+    channel_follower_count: Option<i64>,
+}
+
+/// Playlist-level fields returned by `yt-dlp --flat-playlist --dump-single-json`
+/// for a playlist URL. Entries keep the upstream order.
+#[derive(Debug, Deserialize)]
+struct PlaylistInfo {
+    id: Option<String>,
+    title: Option<String>,
+    description: Option<String>,
+    uploader: Option<String>,
+    channel: Option<String>,
+    channel_id: Option<String>,
+    #[serde(default)]
+    entries: Vec<Option<PlaylistEntryInfo>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PlaylistEntryInfo {
+    id: Option<String>,
+    url: Option<String>,
+}
+
+/// Entry of yt-dlp's `chapters` list (seconds from the start).
+#[derive(Debug, Deserialize)]
+struct ChapterInfo {
+    start_time: Option<f64>,
+    end_time: Option<f64>,
+    title: Option<String>,
 }
 
 #[allow(dead_code)]
@@ -420,6 +508,10 @@ struct FormatInfo {
     ext: Option<String>,
     vcodec: Option<String>,
     acodec: Option<String>,
+    /// Average bitrate in kbit/s.
+    tbr: Option<f64>,
+    asr: Option<i64>,
+    audio_channels: Option<i64>,
     filesize: Option<i64>,
     #[serde(rename = "filesize_approx")]
     filesize_approx: Option<i64>,
@@ -476,17 +568,26 @@ async fn main() -> Result<()> {
         video_id,
         media_kind,
         progress_file,
+        reindex,
+        track,
         media_root,
         www_root,
     } = DownloaderArgs::parse()?;
 
-    ensure_program_available("yt-dlp")?;
-
     let paths = Paths::with_roots(&media_root, &www_root);
     paths.prepare()?;
     let metadata = MetadataStore::open(&paths.metadata_db)
         .await
         .context("initializing metadata database")?;
+
+    if reindex {
+        println!("Re-indexing {} from files on disk...", paths.base.display());
+        let indexed = reindex_library(&paths, &metadata).await?;
+        println!("Re-indexed {indexed} entries.");
+        return Ok(());
+    }
+
+    ensure_program_available("yt-dlp")?;
     let progress = progress_file.map(ProgressWriter::new);
 
     println!("===================================");
@@ -499,7 +600,10 @@ async fn main() -> Result<()> {
         };
         println!("Target: {kind_label} {video_id}");
     } else if let Some(channel_url) = &channel_url {
-        println!("Channel: {}", channel_url);
+        match playlist_id_from_url(channel_url) {
+            Some(playlist_id) => println!("Playlist: {}", playlist_id),
+            None => println!("Channel: {}", channel_url),
+        }
     }
     println!("Base directory: {}", paths.base.display());
     println!("WWW root: {}", paths.www_root.display());
@@ -522,6 +626,17 @@ async fn main() -> Result<()> {
             progress.as_ref(),
         )
         .await?;
+    } else if let Some(playlist_id) = channel_url.as_deref().and_then(playlist_id_from_url) {
+        update_progress(progress.as_ref(), 0, "Fetching playlist");
+        download_playlist_entries(
+            &playlist_id,
+            track,
+            &paths,
+            &mut archive,
+            &metadata,
+            progress.as_ref(),
+        )
+        .await?;
     } else if let Some(channel_url) = &channel_url {
         update_progress(progress.as_ref(), 0, "Fetching channel list");
         download_channel_entries(
@@ -567,6 +682,7 @@ impl Paths {
         let subtitles = base.join(SUBTITLES_SUBDIR);
         let thumbnails = base.join(THUMBNAILS_SUBDIR);
         let comments = base.join(COMMENTS_SUBDIR);
+        let channels = base.join(CHANNELS_SUBDIR);
         let archive = base.join(ARCHIVE_FILE);
         let cookies = base.join(COOKIES_FILE);
         let www_root = www_root.to_path_buf();
@@ -579,6 +695,7 @@ impl Paths {
             subtitles,
             thumbnails,
             comments,
+            channels,
             archive,
             cookies,
             www_root,
@@ -599,6 +716,8 @@ impl Paths {
             .with_context(|| format!("creating {}", self.thumbnails.display()))?;
         fs::create_dir_all(&self.comments)
             .with_context(|| format!("creating {}", self.comments.display()))?;
+        fs::create_dir_all(&self.channels)
+            .with_context(|| format!("creating {}", self.channels.display()))?;
         fs::create_dir_all(&self.www_root)
             .with_context(|| format!("creating {}", self.www_root.display()))?;
         Ok(())
@@ -637,28 +756,6 @@ fn ensure_program_available(name: &str) -> Result<()> {
     }
 }
 
-/// Parses yt-dlp's archive file to avoid duplicate downloads.
-fn load_archive(path: &Path) -> Result<HashSet<String>> {
-    if !path.exists() {
-        return Ok(HashSet::new());
-    }
-
-    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
-    let reader = BufReader::new(file);
-    let mut entries = HashSet::new();
-
-    for line in reader.lines() {
-        let line = line?;
-        if let Some(id) = line.split_whitespace().last()
-            && !id.is_empty()
-        {
-            entries.insert(id.to_owned());
-        }
-    }
-
-    Ok(entries)
-}
-
 /// Mirrors yt-dlp's archive format by writing `youtube <id>` per line.
 fn append_to_archive(path: &Path, video_id: &str) -> Result<()> {
     let mut file = OpenOptions::new()
@@ -697,16 +794,19 @@ async fn download_collection(
     println!("Found {} {}", total, label);
     println!();
 
+    let mut batch = metadata.batch();
     for (index, video_id) in ids.iter().enumerate() {
         let current = index + 1;
         if let Err(err) = process_media_entry(
-            video_id, current, total, paths, archive, media_kind, metadata,
+            video_id, current, total, paths, archive, media_kind, &mut batch,
         )
         .await
         {
             eprintln!("  Warning: failed to process {}: {}", video_id, err);
         }
+        flush_metadata(&mut batch, false).await?;
     }
+    flush_metadata(&mut batch, true).await?;
 
     println!();
     println!(
@@ -729,6 +829,11 @@ async fn download_channel_entries(
     metadata: &MetadataStore,
     progress: Option<&ProgressWriter>,
 ) -> Result<()> {
+    println!("Refreshing channel metadata...");
+    if let Err(err) = refresh_channel(channel_url, paths, metadata).await {
+        eprintln!("  Warning: channel metadata refresh failed: {}", err);
+    }
+
     println!("Getting list of regular videos...");
     let videos = get_video_ids(
         &build_channel_list_url(channel_url, MediaKind::Video),
@@ -777,6 +882,139 @@ async fn download_channel_entries(
     Ok(())
 }
 
+/// Downloads every member of a playlist into the regular videos/shorts layout
+/// and stores the playlist with its entries in upstream order. Re-running it
+/// replaces the stored entries, so removals and reordering are mirrored.
+/// `track` is stored on the playlist and decides whether `routine_update`
+/// picks it up.
+async fn download_playlist_entries(
+    playlist_id: &str,
+    track: bool,
+    paths: &Paths,
+    archive: &mut HashSet<String>,
+    metadata: &MetadataStore,
+    progress: Option<&ProgressWriter>,
+) -> Result<()> {
+    let playlist_url = playlist_url_for_id(playlist_id);
+    println!("Getting playlist entries...");
+    let info = fetch_playlist_info(&playlist_url, paths)?;
+
+    let entries = playlist_entries_from_info(&info);
+    let (shorts, videos): (Vec<&PlaylistEntry>, Vec<&PlaylistEntry>) = entries
+        .iter()
+        .partition(|entry| entry.kind == media_kind_label(MediaKind::Short));
+    let videos: Vec<String> = videos.iter().map(|entry| entry.videoid.clone()).collect();
+    let shorts: Vec<String> = shorts.iter().map(|entry| entry.videoid.clone()).collect();
+
+    let total = videos.len() + shorts.len();
+    let mut completed = 0usize;
+    process_media_list(
+        "playlist videos",
+        &videos,
+        MediaKind::Video,
+        paths,
+        archive,
+        metadata,
+        &mut completed,
+        total,
+        progress,
+    )
+    .await?;
+    process_media_list(
+        "playlist shorts",
+        &shorts,
+        MediaKind::Short,
+        paths,
+        archive,
+        metadata,
+        &mut completed,
+        total,
+        progress,
+    )
+    .await?;
+
+    let playlist = PlaylistRecord {
+        playlist_id: info
+            .id
+            .clone()
+            .filter(|id| !id.is_empty())
+            .unwrap_or_else(|| playlist_id.to_string()),
+        title: info.title.clone().unwrap_or_default(),
+        description: info.description.clone().unwrap_or_default(),
+        owner: info.channel.clone().or_else(|| info.uploader.clone()),
+        owner_channel_id: info.channel_id.clone(),
+        url: Some(playlist_url),
+        tracked: track,
+        last_refreshed_at: Some(Utc::now().to_rfc3339()),
+        entry_count: entries.len() as i64,
+    };
+    metadata.replace_playlist(&playlist, &entries).await?;
+    println!("Stored playlist with {} entries", entries.len());
+
+    Ok(())
+}
+
+/// Runs `yt-dlp --flat-playlist --dump-single-json` to list a playlist
+/// without resolving every entry.
+fn fetch_playlist_info(playlist_url: &str, paths: &Paths) -> Result<PlaylistInfo> {
+    let mut command = yt_dlp_command();
+    command
+        .arg("--flat-playlist")
+        .arg("--dump-single-json")
+        .arg("--ignore-errors")
+        .arg("--no-warnings")
+        .arg(playlist_url);
+
+    if paths.cookies.exists() {
+        command
+            .arg("--cookies")
+            .arg(paths.cookies.to_string_lossy().to_string());
+    }
+
+    let output = command
+        .output()
+        .with_context(|| format!("fetching playlist {}", playlist_url))?;
+    if !output.status.success() {
+        bail!(
+            "playlist command failed for {} (status {})",
+            playlist_url,
+            output.status
+        );
+    }
+
+    serde_json::from_slice(&output.stdout).context("deserializing playlist JSON")
+}
+
+/// Turns flat playlist entries into ordered rows, dropping unavailable items
+/// and duplicates while keeping the first occurrence's position.
+fn playlist_entries_from_info(info: &PlaylistInfo) -> Vec<PlaylistEntry> {
+    let mut seen = HashSet::new();
+    let mut entries = Vec::new();
+    for entry in info.entries.iter().flatten() {
+        let Some(videoid) = entry.id.as_deref().filter(|id| !id.is_empty()) else {
+            continue;
+        };
+        if !seen.insert(videoid.to_string()) {
+            continue;
+        }
+        let kind = if entry
+            .url
+            .as_deref()
+            .is_some_and(|url| url.contains("/shorts/"))
+        {
+            MediaKind::Short
+        } else {
+            MediaKind::Video
+        };
+        entries.push(PlaylistEntry {
+            position: entries.len() as i64,
+            videoid: videoid.to_string(),
+            kind: media_kind_label(kind).to_string(),
+        });
+    }
+    entries
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn process_media_list(
     label: &str,
@@ -798,27 +1036,36 @@ async fn process_media_list(
     println!("Found {} {}", ids.len(), label);
     println!();
 
+    // Ids deleted with "never re-download" stay out of the library even
+    // though they are still listed upstream.
+    let blocked = metadata.blocked_media_ids().await?;
+
+    let mut batch = metadata.batch();
     for video_id in ids {
         let current = *completed + 1;
-        if let Err(err) = process_media_entry(
-            video_id, current, total, paths, archive, media_kind, metadata,
+        if blocked.contains(video_id) {
+            println!("[{}/{}] Skipping blocked {}", current, total, video_id);
+        } else if let Err(err) = process_media_entry(
+            video_id, current, total, paths, archive, media_kind, &mut batch,
         )
         .await
         {
             eprintln!("  Warning: failed to process {}: {}", video_id, err);
         }
+        flush_metadata(&mut batch, false).await?;
 
         *completed += 1;
-        if total > 0 {
-            let percent = ((*completed * 100) / total) as u8;
+        if let Some(percent) = (*completed * 100).checked_div(total) {
             update_progress(
                 progress,
-                percent,
+                percent as u8,
                 &format!("Downloading {}/{}", *completed, total),
             );
         }
     }
 
+    flush_metadata(&mut batch, true).await?;
+
     println!();
     println!(
         "{} download complete!",
@@ -860,10 +1107,17 @@ async fn download_single_video(
     }
 
     update_progress(progress, 75, "Refreshing metadata");
-    refresh_metadata(
-        video_id, &video_url, output_dir, paths, media_kind, metadata,
-    )
-    .await?;
+    let mut batch = metadata.batch();
+    let downloaded = !already_downloaded && !download_failed;
+    let refreshed = refresh_metadata(
+        video_id, &video_url, output_dir, paths, media_kind, downloaded, &mut batch,
+    );
+    if let Err(err) = refreshed
+        && !record_unavailable(video_id, &err, &mut batch)
+    {
+        return Err(err);
+    }
+    batch.commit().await?;
 
     if download_failed {
         bail!("download failed for {}", video_id);
@@ -881,7 +1135,7 @@ async fn process_media_entry(
     paths: &Paths,
     archive: &mut HashSet<String>,
     media_kind: MediaKind,
-    metadata: &MetadataStore,
+    batch: &mut WriteBatch<'_>,
 ) -> Result<()> {
     let output_dir = paths.media_dir(media_kind);
     // Archive entries let us skip heavy downloads when the file tree already
@@ -889,6 +1143,7 @@ async fn process_media_entry(
     // change over time.
     let already_downloaded = archive.contains(video_id);
     let video_url = video_url_for_kind(video_id, media_kind);
+    let mut downloaded = false;
 
     if already_downloaded {
         println!(
@@ -905,13 +1160,13 @@ async fn process_media_entry(
         } else {
             append_to_archive(&paths.archive, video_id)?;
             archive.insert(video_id.to_owned());
+            downloaded = true;
         }
     }
 
     if let Err(err) = refresh_metadata(
-        video_id, &video_url, output_dir, paths, media_kind, metadata,
-    )
-    .await
+        video_id, &video_url, output_dir, paths, media_kind, downloaded, batch,
+    ) && !record_unavailable(video_id, &err, batch)
     {
         eprintln!(
             "  Warning: metadata refresh failed for {}: {}",
@@ -919,35 +1174,225 @@ async fn process_media_entry(
         );
     }
 
+    // Fresh downloads should show up in the library right away; metadata-only
+    // refreshes wait for the batch to fill up.
+    if !already_downloaded {
+        flush_metadata(batch, true).await?;
+    }
+
+    Ok(())
+}
+
+/// Queued DB writes after which a refresh commits its batch. One video
+/// queues seven writes, so this is roughly forty videos per transaction.
+const METADATA_BATCH_WRITES: usize = 300;
+
+/// Commits queued metadata writes once the batch is large enough, or
+/// whenever `force` is set. Failed writes are reported but do not abort the
+/// run; the next refresh rewrites the same rows. A commit that fails as a
+/// whole keeps the batch queued and is retried on the next flush. If that
+/// retry fails too (a lock that will not clear, a full disk) the run is
+/// aborted rather than queueing writes without bound.
+async fn flush_metadata(batch: &mut WriteBatch<'_>, force: bool) -> Result<()> {
+    let retry = batch.failed_commits() > 0;
+    if batch.is_empty() || (!force && !retry && batch.len() < METADATA_BATCH_WRITES) {
+        return Ok(());
+    }
+    if let Err(err) = batch.commit().await {
+        if batch.failed_commits() > 1 {
+            return Err(err.context("saving metadata failed again; aborting"));
+        }
+        eprintln!("  Warning: failed to save metadata: {}", err);
+    }
     Ok(())
 }
 
-/// Fetches info JSON, updates DB rows, and syncs subtitles/comments.
-async fn refresh_metadata(
+/// Returned (wrapped in `anyhow::Error`) when yt-dlp reports that the video
+/// is gone or locked upstream rather than failing for a transient reason.
+#[derive(Debug)]
+struct UpstreamUnavailable(Availability);
+
+impl std::fmt::Display for UpstreamUnavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "video is {} upstream", self.0.as_str())
+    }
+}
+
+impl std::error::Error for UpstreamUnavailable {}
+
+/// Maps yt-dlp's error output to the reason a video can no longer be
+/// fetched. Network hiccups and other failures return `None`.
+fn classify_unavailable(stderr: &str) -> Option<Availability> {
+    let message = stderr.to_ascii_lowercase();
+    let has = |needle: &str| message.contains(needle);
+    if has("private video") {
+        Some(Availability::Private)
+    } else if has("members-only") || has("channel's members") || has("join this channel") {
+        Some(Availability::MembersOnly)
+    } else if has("confirm your age")
+        || has("age-restricted")
+        || has("inappropriate for some users")
+    {
+        Some(Availability::AgeRestricted)
+    } else if has("copyright") {
+        Some(Availability::Copyright)
+    } else if has("removed by the uploader")
+        || has("has been removed")
+        || has("account associated with this video has been terminated")
+        || has("video has been deleted")
+    {
+        Some(Availability::Removed)
+    } else if has("video unavailable") || has("this video is not available") {
+        Some(Availability::Unavailable)
+    } else {
+        None
+    }
+}
+
+/// Queues the availability behind a refresh failure, if it was one, and
+/// leaves the stored metadata as it was. Returns whether `err` was handled.
+fn record_unavailable(video_id: &str, err: &anyhow::Error, batch: &mut WriteBatch<'_>) -> bool {
+    let Some(UpstreamUnavailable(availability)) = err.downcast_ref::<UpstreamUnavailable>() else {
+        return false;
+    };
+    println!(
+        "  {} is {} upstream; keeping the archived metadata",
+        video_id,
+        availability.as_str()
+    );
+    batch.set_availability(video_id, *availability);
+    true
+}
+
+/// Fetches info JSON and queues the record, subtitle, transcript and comment
+/// writes for this video on `batch`. `hash_files` is set right after a
+/// download so the new files get their checksums.
+fn refresh_metadata(
     video_id: &str,
     video_url: &str,
     output_dir: &Path,
     paths: &Paths,
     media_kind: MediaKind,
-    metadata: &MetadataStore,
+    hash_files: bool,
+    batch: &mut WriteBatch<'_>,
 ) -> Result<()> {
     let info = fetch_video_info(video_id, video_url, output_dir, paths)?;
-    let record = build_video_record(video_id, &info, output_dir, media_kind, paths)?;
+    queue_video_metadata(
+        video_id, &info, output_dir, paths, media_kind, hash_files, batch,
+    )?;
+    batch.set_availability(video_id, Availability::Available);
+
+    match fetch_comments(video_id, video_url, paths)? {
+        Some(comments) => batch.merge_comments(video_id, comments),
+        None => println!("  Keeping the archived comments of {}", video_id),
+    }
 
+    Ok(())
+}
+
+/// Queues everything derived from `info` and the files on disk: the media
+/// row, its channel, chapters, subtitle tracks and parsed transcripts.
+/// Sources without a checksum keep the one already stored for their file;
+/// `hash_files` computes it from disk instead.
+fn queue_video_metadata(
+    video_id: &str,
+    info: &VideoInfo,
+    output_dir: &Path,
+    paths: &Paths,
+    media_kind: MediaKind,
+    hash_files: bool,
+    batch: &mut WriteBatch<'_>,
+) -> Result<()> {
+    let mut record = build_video_record(video_id, info, output_dir, media_kind, paths)?;
+    if hash_files && let Err(err) = fill_checksums(&mut record) {
+        eprintln!("  Warning: failed to hash files of {}: {}", video_id, err);
+    }
     match media_kind {
-        MediaKind::Video => metadata.upsert_video(&record).await?,
-        MediaKind::Short => metadata.upsert_short(&record).await?,
+        MediaKind::Video => batch.upsert_video(record),
+        MediaKind::Short => batch.upsert_short(record),
     }
 
-    let subtitles = collect_subtitles(video_id, &info, paths, media_kind)?;
-    metadata.upsert_subtitles(&subtitles).await?;
+    if let Some(channel) = channel_record_from_video(info) {
+        batch.upsert_channel(channel);
+    }
+
+    batch.replace_chapters(video_id, extract_chapters(info));
+
+    let subtitles = collect_subtitles(video_id, info, paths, media_kind)?;
+    batch.upsert_subtitles(subtitles);
 
-    let comments = fetch_comments(video_id, video_url, paths)?;
-    metadata.replace_comments(video_id, &comments).await?;
+    let transcripts = read_transcripts(&paths.subtitles.join(video_id), video_id)?;
+    batch.replace_transcripts(video_id, transcripts);
 
     Ok(())
 }
 
+/// Offline re-indexer: rebuilds the rows of every downloaded video and short
+/// from the cached `info.json`, the files on disk and the last comment dump.
+/// It never runs yt-dlp, so it is the way to backfill new tables or recover a
+/// lost `metadata.db`. Returns how many entries were indexed.
+async fn reindex_library(paths: &Paths, metadata: &MetadataStore) -> Result<usize> {
+    let blocked = metadata.blocked_media_ids().await?;
+    let mut batch = metadata.batch();
+    let mut indexed = 0;
+
+    for media_kind in [MediaKind::Video, MediaKind::Short] {
+        let output_dir = paths.media_dir(media_kind);
+        if !output_dir.is_dir() {
+            continue;
+        }
+        let mut ids = Vec::new();
+        for entry in
+            fs::read_dir(output_dir).with_context(|| format!("reading {}", output_dir.display()))?
+        {
+            let entry = entry?;
+            let Ok(video_id) = entry.file_name().into_string() else {
+                continue;
+            };
+            if entry.path().join(format!("{video_id}.info.json")).is_file() {
+                ids.push(video_id);
+            }
+        }
+        ids.sort();
+
+        for video_id in ids {
+            if blocked.contains(&video_id) {
+                continue;
+            }
+            let info_path = output_dir
+                .join(&video_id)
+                .join(format!("{video_id}.info.json"));
+            let queued = fs::read_to_string(&info_path)
+                .with_context(|| format!("reading {}", info_path.display()))
+                .and_then(|raw| {
+                    serde_json::from_str::<VideoInfo>(&raw)
+                        .with_context(|| format!("parsing {}", info_path.display()))
+                })
+                .and_then(|info| {
+                    queue_video_metadata(
+                        &video_id, &info, output_dir, paths, media_kind, false, &mut batch,
+                    )?;
+                    // The dump may predate the stored comments, so it must
+                    // not tombstone any of them.
+                    if let Some(comments) = read_comments(&video_id, paths)? {
+                        batch.upsert_comments(&video_id, comments);
+                    }
+                    Ok(())
+                });
+            match queued {
+                Ok(()) => indexed += 1,
+                Err(err) => eprintln!("  Warning: failed to re-index {}: {}", video_id, err),
+            }
+            if batch.len() >= METADATA_BATCH_WRITES {
+                batch.commit().await?;
+            }
+        }
+    }
+
+    batch.commit().await?;
+    Ok(indexed)
+}
+
 /// Runs `yt-dlp --dump-single-json` and caches the response alongside the
 /// downloaded assets.
 fn fetch_video_info(
@@ -975,6 +1420,9 @@ fn fetch_video_info(
         .with_context(|| format!("fetching metadata for {}", video_url))?;
 
     if !output.status.success() {
+        if let Some(availability) = classify_unavailable(&String::from_utf8_lossy(&output.stderr)) {
+            return Err(UpstreamUnavailable(availability).into());
+        }
         bail!(
             "metadata command failed for {} (status {})",
             video_url,
@@ -1002,6 +1450,261 @@ fn fetch_video_info(
     Ok(info)
 }
 
+/// Fetches channel-level metadata (name, handle, follower count) plus the
+/// avatar/banner images and stores them in the `channels` table.
+async fn refresh_channel(
+    channel_url: &str,
+    paths: &Paths,
+    metadata: &MetadataStore,
+) -> Result<ChannelRecord> {
+    let mut command = yt_dlp_command();
+    command
+        .arg("--dump-single-json")
+        .arg("--flat-playlist")
+        .arg("--playlist-items")
+        .arg("0")
+        .arg("--no-warnings")
+        .arg(channel_url);
+
+    if paths.cookies.exists() {
+        command
+            .arg("--cookies")
+            .arg(paths.cookies.to_string_lossy().to_string());
+    }
+
+    let output = command
+        .output()
+        .with_context(|| format!("fetching channel metadata for {}", channel_url))?;
+    if !output.status.success() {
+        bail!(
+            "channel metadata command failed for {} (status {})",
+            channel_url,
+            output.status
+        );
+    }
+
+    let info: ChannelInfo =
+        serde_json::from_slice(&output.stdout).context("deserializing channel metadata JSON")?;
+    let channel_id = info
+        .channel_id
+        .clone()
+        .or_else(|| info.id.clone())
+        .filter(|id| !id.is_empty())
+        .ok_or_else(|| anyhow::anyhow!("channel id missing for {}", channel_url))?;
+
+    let image_dir = paths.channels.join(&channel_id);
+    run_channel_images_command(channel_url, &image_dir, &channel_id, &paths.cookies);
+
+    let channel = ChannelRecord {
+        name: info
+            .channel
+            .clone()
+            .or_else(|| info.title.clone())
+            .unwrap_or_default(),
+        handle: info.uploader_id.clone().filter(|id| id.starts_with('@')),
+        url: info.channel_url.clone(),
+        subscriber_count: info.channel_follower_count,
+        avatar_path: find_channel_image(&image_dir, "avatar"),
+        banner_path: find_channel_image(&image_dir, "banner"),
+        last_refreshed_at: Some(Utc::now().to_rfc3339()),
+        channel_id,
+    };
+    metadata.upsert_channel(&channel).await?;
+    Ok(channel)
+}
+
+/// Derives a partial channel row from a single video's info JSON so channels
+/// exist even when only individual videos were downloaded.
+fn channel_record_from_video(info: &VideoInfo) -> Option<ChannelRecord> {
+    let channel_id = collect_channel_ids(info).into_iter().next()?;
+    let name = collect_channel_names(info)
+        .into_iter()
+        .chain(collect_uploader_names(info))
+        .next()
+        .unwrap_or_default();
+
+    Some(ChannelRecord {
+        channel_id,
+        name,
+        handle: None,
+        url: collect_channel_urls(info).into_iter().next(),
+        subscriber_count: info.channel_follower_count,
+        avatar_path: None,
+        banner_path: None,
+        last_refreshed_at: None,
+    })
+}
+
+/// Downloads every channel thumbnail (avatar, banner, ...) into `target_dir`.
+fn run_channel_images_command(
+    channel_url: &str,
+    target_dir: &Path,
+    channel_id: &str,
+    cookies: &Path,
+) {
+    if let Err(err) = fs::create_dir_all(target_dir) {
+        eprintln!(
+            "  Warning: could not create channel directory {}: {}",
+            target_dir.display(),
+            err
+        );
+        return;
+    }
+
+    let output_pattern = target_dir.join(channel_id).to_string_lossy().to_string();
+
+    let mut command = yt_dlp_command();
+    command
+        .arg("--write-all-thumbnails")
+        .arg("--skip-download")
+        .arg("--playlist-items")
+        .arg("0")
+        .arg("--output")
+        .arg(&output_pattern)
+        .arg("--output")
+        .arg(format!("pl_thumbnail:{output_pattern}"))
+        .arg(channel_url);
+
+    if cookies.exists() {
+        command
+            .arg("--cookies")
+            .arg(cookies.to_string_lossy().to_string());
+    }
+
+    run_silent(command, "channel images");
+}
+
+/// Picks the downloaded channel image for `kind` (`avatar` or `banner`),
+/// preferring yt-dlp's uncropped variant.
+fn find_channel_image(dir: &Path, kind: &str) -> Option<String> {
+    let mut candidates: Vec<(bool, String, PathBuf)> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+            if name.ends_with(".part") || !name.contains(kind) {
+                return None;
+            }
+            let uncropped = name.contains(&format!("{kind}_uncropped"));
+            Some((!uncropped, name, path))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+        .into_iter()
+        .next()
+        .map(|(_, _, path)| path.to_string_lossy().into_owned())
+}
+
+/// Chapters from yt-dlp's `chapters` list, falling back to timestamps in the
+/// description for videos whose uploader never enabled YouTube chapters.
+fn extract_chapters(info: &VideoInfo) -> Vec<Chapter> {
+    let chapters: Vec<Chapter> = info
+        .chapters
+        .iter()
+        .flatten()
+        .filter_map(|chapter| {
+            let title = chapter.title.as_deref()?.trim();
+            Some(Chapter {
+                start: chapter.start_time?,
+                end: chapter.end_time,
+                title: title.to_string(),
+            })
+        })
+        .collect();
+    if !chapters.is_empty() {
+        return chapters;
+    }
+    let duration = info.duration.map(|duration| duration as f64);
+    info.description
+        .as_deref()
+        .map(|description| chapters_from_description(description, duration))
+        .unwrap_or_default()
+}
+
+/// Parses `0:00 Intro` / `Intro - 1:02:03` style lines using YouTube's own
+/// rules: the list starts at 0:00, timestamps strictly increase and there are
+/// at least three entries. Anything else yields no chapters.
+fn chapters_from_description(description: &str, duration: Option<f64>) -> Vec<Chapter> {
+    let mut entries: Vec<(f64, String)> = Vec::new();
+    for line in description.lines() {
+        let Some((start, title)) = parse_chapter_line(line) else {
+            continue;
+        };
+        match entries.last() {
+            None if start != 0.0 => continue,
+            // A second timestamp list (credits, "best moments", ...) ends
+            // the chapter block.
+            Some((previous, _)) if start <= *previous => break,
+            _ => {}
+        }
+        if duration.is_some_and(|duration| start >= duration) {
+            break;
+        }
+        entries.push((start, title));
+    }
+    if entries.len() < 3 {
+        return Vec::new();
+    }
+
+    let starts: Vec<f64> = entries.iter().map(|(start, _)| *start).collect();
+    entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, (start, title))| Chapter {
+            start,
+            end: starts.get(index + 1).copied().or(duration),
+            title,
+        })
+        .collect()
+}
+
+/// Splits a description line into a timestamp and title. The timestamp may
+/// lead (after bullets like `-` or `•`) or trail the title.
+fn parse_chapter_line(line: &str) -> Option<(f64, String)> {
+    let tokens: Vec<&str> = line
+        .split_whitespace()
+        .skip_while(|token| !token.chars().any(char::is_alphanumeric))
+        .collect();
+    let (start, rest) = match tokens.as_slice() {
+        [first, rest @ ..] if parse_timestamp(first).is_some() => (parse_timestamp(first)?, rest),
+        [rest @ .., last] if parse_timestamp(last).is_some() => (parse_timestamp(last)?, rest),
+        _ => return None,
+    };
+    let title = rest
+        .join(" ")
+        .trim_matches(|ch: char| ch.is_whitespace() || "-–—:|•".contains(ch))
+        .to_string();
+    (!title.is_empty()).then_some((start, title))
+}
+
+/// Parses `M:SS`, `MM:SS` or `H:MM:SS`, optionally wrapped in brackets.
+fn parse_timestamp(token: &str) -> Option<f64> {
+    let token = token.trim_matches(|ch: char| "[]()".contains(ch));
+    let parts: Vec<&str> = token.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+    let mut seconds = 0u64;
+    for (index, part) in parts.iter().enumerate() {
+        let leading = index == 0;
+        if part.is_empty() || !part.chars().all(|ch| ch.is_ascii_digit()) {
+            return None;
+        }
+        if (!leading && part.len() != 2) || part.len() > 2 {
+            return None;
+        }
+        let value: u64 = part.parse().ok()?;
+        if !leading && value >= 60 {
+            return None;
+        }
+        seconds = seconds * 60 + value;
+    }
+    Some(seconds as f64)
+}
+
 /// Translates `VideoInfo` from yt-dlp into the structured `VideoRecord` that
 /// the backend expects.
 fn build_video_record(
@@ -1051,7 +1754,14 @@ fn build_video_record(
     let thumbnails = collect_thumbnails(video_id, paths, slug)?;
     let thumbnail_url = thumbnails.first().cloned();
 
-    let sources = collect_sources(video_id, info, output_dir, slug)?;
+    let mut sources = collect_sources(video_id, info, output_dir, slug)?;
+    let adaptive_streams = collect_adaptive_streams(video_id, info, output_dir, slug)?;
+    // The on-disk fallback cannot tell video-only files from muxed ones.
+    sources.retain(|source| {
+        !adaptive_streams
+            .iter()
+            .any(|stream| stream.path == source.path)
+    });
 
     let extras = json!({
         "channelId": channel_ids.first().cloned(),
@@ -1077,11 +1787,15 @@ fn build_video_record(
         duration,
         duration_text,
         channel_url,
+        channel_id: channel_ids.first().cloned(),
         thumbnail_url,
         tags: info.tags.clone().unwrap_or_default(),
         thumbnails,
         extras,
         sources,
+        availability: None,
+        availability_checked_at: None,
+        adaptive_streams,
     })
 }
 
@@ -1389,6 +2103,7 @@ fn collect_sources(
                 file_size,
                 url: format!("/api/{slug}/{}/streams/{}", video_id, sanitized),
                 path: Some(path.to_string_lossy().into_owned()),
+                sha256: None,
             });
         }
     }
@@ -1397,71 +2112,112 @@ fn collect_sources(
         sources = collect_sources_from_disk(video_id, &base_dir, slug)?;
     }
 
-    Ok(sources)
-}
-
-fn collect_sources_from_disk(
-    video_id: &str,
-    base_dir: &Path,
-    slug: &str,
-) -> Result<Vec<VideoSource>> {
-    let mut sources = Vec::new();
-    let prefix = format!("{video_id}_");
-    for entry in fs::read_dir(base_dir)
-        .with_context(|| format!("reading media dir {}", base_dir.display()))?
-    {
-        let entry = entry?;
-        if !entry.path().is_file() {
-            continue;
-        }
-        let file_name = entry
-            .file_name()
-            .into_string()
-            .unwrap_or_else(|os| os.to_string_lossy().into_owned());
-        if !file_name.starts_with(&prefix) {
-            continue;
-        }
-        if file_name.ends_with(".part") {
-            continue;
-        }
-        let rest = &file_name[prefix.len()..];
-        let Some((format_id, ext)) = rest.rsplit_once('.') else {
+    Ok(sources)
+}
+
+/// Records the video-only and audio-only downloads that `collect_sources`
+/// leaves out, with the byte ranges a DASH manifest needs. Files whose
+/// index cannot be found are still recorded so the library knows about
+/// them, but the manifest skips them.
+fn collect_adaptive_streams(
+    video_id: &str,
+    info: &VideoInfo,
+    output_dir: &Path,
+    slug: &str,
+) -> Result<Vec<AdaptiveStream>> {
+    let mut streams = Vec::new();
+    let base_dir = output_dir.join(video_id);
+    let Some(formats) = &info.formats else {
+        return Ok(streams);
+    };
+    if !base_dir.exists() {
+        return Ok(streams);
+    }
+
+    let codec = |codec: Option<&str>| {
+        codec
+            .map(str::trim)
+            .filter(|codec| !codec.is_empty() && !codec.eq_ignore_ascii_case("none"))
+            .map(str::to_owned)
+    };
+    for format in formats {
+        let Some(format_id) = format.format_id.as_deref() else {
             continue;
         };
-        if format_id.is_empty() {
-            continue;
-        }
-        if matches!(ext, "m4a" | "mp3" | "aac" | "opus" | "flac" | "wav") {
-            continue;
-        }
-        if matches!(
-            ext,
-            "mhtml" | "json" | "txt" | "m3u8" | "mpd" | "ytdl" | "aria2"
+        let (kind, codecs) = match (
+            codec(format.vcodec.as_deref()),
+            codec(format.acodec.as_deref()),
         ) {
+            (Some(video), None) => (AdaptiveKind::Video, video),
+            (None, Some(audio)) => (AdaptiveKind::Audio, audio),
+            // Muxed formats are sources; storyboards carry neither.
+            _ => continue,
+        };
+        let ext = format.ext.as_deref().unwrap_or("mp4");
+        let mime_type = match (kind, ext) {
+            (AdaptiveKind::Video, "mp4") => "video/mp4",
+            (AdaptiveKind::Video, "webm") => "video/webm",
+            (AdaptiveKind::Audio, "m4a" | "mp4") => "audio/mp4",
+            (AdaptiveKind::Audio, "webm") => "audio/webm",
+            _ => continue,
+        };
+
+        let sanitized = sanitize_format_id(format_id);
+        let mut path = base_dir.join(format!("{video_id}_{sanitized}"));
+        path.set_extension(ext);
+        if !path.exists() {
             continue;
         }
-        let size = entry.metadata().ok().map(|meta| meta.len() as i64);
-        sources.push(VideoSource {
+        let ranges = segment_base(&path).unwrap_or_else(|err| {
+            eprintln!("  Warning: could not index {}: {:#}", path.display(), err);
+            None
+        });
+
+        let video = kind == AdaptiveKind::Video;
+        streams.push(AdaptiveStream {
             format_id: format_id.to_owned(),
-            quality_label: None,
-            width: None,
-            height: None,
-            fps: None,
-            mime_type: Some(mime_from_extension(ext)),
+            kind,
+            mime_type: mime_type.to_owned(),
+            codecs,
+            bandwidth: format.tbr.map(|kbps| (kbps * 1000.0).round() as i64),
+            quality_label: if video {
+                format.format_note.clone().or_else(|| {
+                    format_quality_label(format.height, format.dynamic_range.as_deref())
+                })
+            } else {
+                format.format_note.clone()
+            },
+            width: format.width.filter(|_| video),
+            height: format.height.filter(|_| video),
+            fps: format.fps.filter(|_| video),
+            audio_sample_rate: format.asr.filter(|_| !video),
+            audio_channels: format.audio_channels.filter(|_| !video),
+            init_range: ranges.map(|ranges| ranges.init),
+            index_range: ranges.map(|ranges| ranges.index),
             ext: Some(ext.to_owned()),
-            file_size: size,
-            url: format!("/api/{slug}/{}/streams/{}", video_id, format_id),
-            path: Some(entry.path().to_string_lossy().into_owned()),
+            file_size: fs::metadata(&path)
+                .ok()
+                .map(|meta| meta.len() as i64)
+                .or(format.filesize)
+                .or(format.filesize_approx),
+            url: format!("/api/{slug}/{}/dash/{}", video_id, sanitized),
+            path: Some(path.to_string_lossy().into_owned()),
+            sha256: None,
         });
     }
 
-    sources.sort_by(|a, b| a.format_id.cmp(&b.format_id));
-    Ok(sources)
+    Ok(streams)
 }
 
 /// Downloads every available comment via yt-dlp, writes them to disk, and then
-/// normalizes into `CommentRecord` rows while removing duplicates.
-fn fetch_comments(video_id: &str, video_url: &str, paths: &Paths) -> Result<Vec<CommentRecord>> {
+/// normalizes into `CommentRecord` rows while removing duplicates. Returns
+/// `None` when yt-dlp failed or left no dump, so callers never mistake a
+/// failed fetch for a video whose comments were all deleted.
+fn fetch_comments(
+    video_id: &str,
+    video_url: &str,
+    paths: &Paths,
+) -> Result<Option<Vec<CommentRecord>>> {
     let comments_dir = paths.comments.join(video_id);
     fs::create_dir_all(&comments_dir)
         .with_context(|| format!("creating comments dir {}", comments_dir.display()))?;
@@ -1491,18 +2247,29 @@ fn fetch_comments(video_id: &str, video_url: &str, paths: &Paths) -> Result<Vec<
                 "  Warning: comment extraction failed for {} (status {})",
                 video_id, status
             );
+            return Ok(None);
         }
         Err(err) => {
             eprintln!(
                 "  Warning: unable to execute comment extraction for {}: {}",
                 video_id, err
             );
+            return Ok(None);
         }
     }
 
-    let comments_path = comments_dir.join(format!("{}.comments.json", video_id));
+    read_comments(video_id, paths)
+}
+
+/// Parses the comment dump yt-dlp left in `comments/<id>/`. Returns `None`
+/// when there is no dump.
+fn read_comments(video_id: &str, paths: &Paths) -> Result<Option<Vec<CommentRecord>>> {
+    let comments_path = paths
+        .comments
+        .join(video_id)
+        .join(format!("{}.comments.json", video_id));
     if !comments_path.exists() {
-        return Ok(Vec::new());
+        return Ok(None);
     }
 
     let file = File::open(&comments_path)
@@ -1548,10 +2315,11 @@ fn fetch_comments(video_id: &str, video_url: &str, paths: &Paths) -> Result<Vec<
             parent_comment_id: raw.parent,
             status_likedbycreator: raw.author_is_channel_owner || raw.author_is_uploader,
             reply_count: raw.reply_count,
+            deleted_upstream_at: None,
         });
     }
 
-    Ok(comments)
+    Ok(Some(comments))
 }
 
 fn collect_raw_comments(value: Value, parent_hint: Option<&str>, out: &mut Vec<RawComment>) {
@@ -1621,16 +2389,6 @@ fn format_quality_label(height: Option<i64>, dynamic_range: Option<&str>) -> Opt
     }
 }
 
-/// Guesses the MIME type for each downloaded file based on its extension.
-fn mime_from_extension(ext: &str) -> String {
-    match ext {
-        "mp4" => "video/mp4".to_owned(),
-        "mkv" => "video/x-matroska".to_owned(),
-        "webm" => "video/webm".to_owned(),
-        other => format!("video/{other}"),
-    }
-}
-
 /// Maps the enum to the slug portion used in API URLs and folder names.
 fn media_kind_slug(kind: MediaKind) -> &'static str {
     match kind {
@@ -1639,6 +2397,30 @@ fn media_kind_slug(kind: MediaKind) -> &'static str {
     }
 }
 
+/// Singular label stored in the `kind` column of playlist entries.
+fn media_kind_label(kind: MediaKind) -> &'static str {
+    match kind {
+        MediaKind::Video => "video",
+        MediaKind::Short => "short",
+    }
+}
+
+/// Extracts the `list=` id from a playlist (or watch-with-playlist) URL.
+/// Returns `None` for plain channel URLs.
+fn playlist_id_from_url(url: &str) -> Option<String> {
+    let without_fragment = url.split('#').next().unwrap_or(url);
+    let (_, query) = without_fragment.split_once('?')?;
+    query
+        .split('&')
+        .filter_map(|pair| pair.strip_prefix("list="))
+        .find(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+fn playlist_url_for_id(playlist_id: &str) -> String {
+    format!("https://www.youtube.com/playlist?list={playlist_id}")
+}
+
 /// Normalizes a channel URL so we don't double-append `/videos` or `/shorts`.
 fn build_channel_list_url(channel_url: &str, kind: MediaKind) -> String {
     let (without_fragment, fragment) = match channel_url.split_once('#') {
@@ -1761,8 +2543,8 @@ fn get_video_ids(list_url: &str, filter: Option<&str>) -> Result<Vec<String>> {
     Ok(ids)
 }
 
-/// Downloads every available muxed format for the provided video id, skipping
-/// streams we already grabbed.
+/// Downloads every available format for the provided video id, muxed and
+/// video-only/audio-only alike, skipping streams we already grabbed.
 fn download_video_all_formats(
     video_id: &str,
     video_url: &str,
@@ -1789,7 +2571,11 @@ fn download_video_all_formats(
     }
 
     let mut downloaded_any = false;
-    for format_id in formats {
+    for DownloadFormat {
+        id: format_id,
+        adaptive,
+    } in formats
+    {
         let safe_format_id = sanitize_format_id(&format_id);
         let mut output_path = video_dir.join(format!("{}_{}", video_id, safe_format_id));
         output_path.set_extension("%(ext)s");
@@ -1813,8 +2599,14 @@ fn download_video_all_formats(
             .arg("--no-overwrites")
             .arg("--continue")
             .arg("--ignore-errors")
-            .arg("--no-warnings")
-            .arg(video_url);
+            .arg("--no-warnings");
+        // The m4a fixup remuxes DASH audio into a progressive file and drops
+        // the segment index the DASH manifest points at. Muxed formats keep
+        // their fixups; HLS-delivered ones need them to become valid MP4s.
+        if adaptive {
+            command.arg("--fixup").arg("never");
+        }
+        command.arg(video_url);
 
         if paths.cookies.exists() {
             command
@@ -1982,8 +2774,8 @@ fn run_silent(mut command: Command, label: &str) {
 
 /// Reads format IDs from the downloaded `.info.json`. If the file is missing or
 /// incomplete we fall back to invoking `yt-dlp -F`.
-fn collect_format_ids(info_json_path: &Path, video_url: &str) -> Result<Vec<String>> {
-    let mut formats = BTreeSet::new();
+fn collect_format_ids(info_json_path: &Path, video_url: &str) -> Result<Vec<DownloadFormat>> {
+    let mut formats = BTreeMap::new();
 
     if info_json_path.exists()
         && let Ok(file) = File::open(info_json_path)
@@ -1992,10 +2784,11 @@ fn collect_format_ids(info_json_path: &Path, video_url: &str) -> Result<Vec<Stri
         match serde_json::from_reader::<_, InfoJson>(reader) {
             Ok(info) => {
                 for entry in info.formats {
+                    let adaptive = entry.is_adaptive();
                     if let Some(id) = entry.format_id {
                         let trimmed = id.trim();
                         if !trimmed.is_empty() {
-                            formats.insert(trimmed.to_owned());
+                            formats.insert(trimmed.to_owned(), adaptive);
                         }
                     }
                 }
@@ -2045,14 +2838,19 @@ fn collect_format_ids(info_json_path: &Path, video_url: &str) -> Result<Vec<Stri
                         .next()
                         .is_some_and(|c| c.is_ascii_alphanumeric())
                     {
-                        formats.insert(first.to_owned());
+                        let adaptive =
+                            trimmed.contains("video only") || trimmed.contains("audio only");
+                        formats.insert(first.to_owned(), adaptive);
                     }
                 }
             }
         }
     }
 
-    Ok(formats.into_iter().collect())
+    Ok(formats
+        .into_iter()
+        .map(|(id, adaptive)| DownloadFormat { id, adaptive })
+        .collect())
 }
 
 /// Normalizes yt-dlp format identifiers so they become safe filenames.
@@ -2122,6 +2920,8 @@ mod tests {
         assert!(args.video_id.is_none());
         assert!(args.media_kind.is_none());
         assert!(args.progress_file.is_none());
+        assert!(!args.reindex);
+        assert!(args.track);
         assert_eq!(args.media_root, PathBuf::from(DEFAULT_MEDIA_ROOT));
         assert_eq!(args.www_root, PathBuf::from(DEFAULT_WWW_ROOT));
     }
@@ -2183,7 +2983,15 @@ mod tests {
             ],
             || {
                 assert!(DownloaderArgs::from_slice(&["--unknown"]).is_err());
+                assert!(
+                    DownloaderArgs::from_slice(&["--reindex", "https://www.youtube.com/@One"])
+                        .is_err()
+                );
                 assert!(DownloaderArgs::from_slice(&["--media-kind", "video"]).is_err());
+                assert!(
+                    DownloaderArgs::from_slice(&["--no-track", "https://www.youtube.com/@One"])
+                        .is_err()
+                );
                 assert!(
                     DownloaderArgs::from_slice(&[
                         "--video-id",
@@ -2258,6 +3066,7 @@ mod tests {
             subtitles: Some(HashMap::new()),
             automatic_captions: Some(HashMap::new()),
             formats: Some(Vec::new()),
+            chapters: None,
         }
     }
 
@@ -2271,6 +3080,9 @@ mod tests {
             ext: Some(ext.into()),
             vcodec: Some("avc1".into()),
             acodec: Some("mp4a".into()),
+            tbr: None,
+            asr: None,
+            audio_channels: None,
             filesize: Some(1234),
             filesize_approx: None,
             dynamic_range: Some("HDR".into()),
@@ -2357,6 +3169,31 @@ sb0 mhtml 320x180      1    |                  mhtml | images
 271 webm  2560x1440   25    |   51.65MiB 2434k https | vp9         2434k video only          1440p, webm_dash
 313 webm  3840x2160   25    |  147.52MiB 6950k https | vp9         6950k video only          2160p, webm_dash'
 
+if printf '%s\n' "${args[@]}" | grep -q -- 'playlist?list='; then
+  cat <<'JSON'
+{"id": "PLtest", "title": "Test Playlist", "description": "Curated picks", "channel": "Channel", "channel_id": "chan123", "entries": [
+  {"id": "alpha", "url": "https://www.youtube.com/watch?v=alpha"},
+  null,
+  {"id": "beta", "url": "https://www.youtube.com/shorts/beta"},
+  {"id": "alpha", "url": "https://www.youtube.com/watch?v=alpha"}
+]}
+JSON
+  exit 0
+fi
+
+if printf '%s\n' "${args[@]}" | grep -q -- '--playlist-items'; then
+  if printf '%s\n' "${args[@]}" | grep -q -- '--write-all-thumbnails'; then
+    output="${output#pl_thumbnail:}"
+    mkdir -p "$(dirname "$output")"
+    echo "avatar" > "${output}.avatar_uncropped.jpg"
+    echo "banner" > "${output}.banner_uncropped.jpg"
+    echo "other" > "${output}.7.jpg"
+    exit 0
+  fi
+  printf '%s\n' '{"id": "chan123", "channel": "Channel", "channel_id": "chan123", "uploader_id": "@Channel", "channel_url": "https://www.youtube.com/channel/chan123", "channel_follower_count": 250}'
+  exit 0
+fi
+
 if printf '%s\n' "${args[@]}" | grep -q -- '--flat-playlist'; then
   echo "alpha"
   exit 0
@@ -2432,6 +3269,7 @@ exit 0
         assert!(paths.subtitles.exists());
         assert!(paths.thumbnails.exists());
         assert!(paths.comments.exists());
+        assert!(paths.channels.exists());
         assert!(paths.www_root.exists());
         Ok(())
     }
@@ -2451,6 +3289,74 @@ exit 0
         Ok(())
     }
 
+    #[test]
+    fn parse_timestamp_accepts_youtube_formats() {
+        assert_eq!(parse_timestamp("0:00"), Some(0.0));
+        assert_eq!(parse_timestamp("(12:34)"), Some(754.0));
+        assert_eq!(parse_timestamp("[1:02:03]"), Some(3723.0));
+        assert_eq!(parse_timestamp("1:2"), None);
+        assert_eq!(parse_timestamp("0:60"), None);
+        assert_eq!(parse_timestamp("123:00"), None);
+        assert_eq!(parse_timestamp("12:00:00:00"), None);
+        assert_eq!(parse_timestamp("v1.2"), None);
+    }
+
+    /// Leading and trailing timestamps both work, bullets are ignored and a
+    /// later non-increasing list (credits etc.) does not leak into chapters.
+    #[test]
+    fn chapters_from_description_follows_youtube_rules() {
+        let description = "Thanks for watching!\n\
+            Timestamps:\n\
+            - 0:00 Intro\n\
+            • 1:05 - Setting up\n\
+            Soldering the board (4:30)\n\
+            1:02:03 Outro\n\
+            \n\
+            Favourite moments:\n\
+            2:00 The spark";
+        let chapters = chapters_from_description(description, Some(4000.0));
+        let summary: Vec<(f64, Option<f64>, &str)> = chapters
+            .iter()
+            .map(|chapter| (chapter.start, chapter.end, chapter.title.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (0.0, Some(65.0), "Intro"),
+                (65.0, Some(270.0), "Setting up"),
+                (270.0, Some(3723.0), "Soldering the board"),
+                (3723.0, Some(4000.0), "Outro"),
+            ]
+        );
+
+        assert!(chapters_from_description("0:00 Intro\n1:00 Outro", None).is_empty());
+        assert!(chapters_from_description("0:10 A\n1:00 B\n2:00 C", None).is_empty());
+    }
+
+    #[test]
+    fn extract_chapters_prefers_info_json() {
+        let mut info = sample_video_info();
+        info.description = Some("0:00 A\n0:10 B\n0:20 C".into());
+        assert_eq!(extract_chapters(&info).len(), 3);
+        assert_eq!(extract_chapters(&info)[2].end, Some(120.0));
+
+        info.chapters = Some(vec![
+            ChapterInfo {
+                start_time: Some(0.0),
+                end_time: Some(60.0),
+                title: Some("Upstream".into()),
+            },
+            ChapterInfo {
+                start_time: None,
+                end_time: Some(120.0),
+                title: Some("Broken".into()),
+            },
+        ]);
+        let chapters = extract_chapters(&info);
+        assert_eq!(chapters.len(), 1);
+        assert_eq!(chapters[0].title, "Upstream");
+    }
+
     #[test]
     fn build_video_record_populates_fields() -> Result<()> {
         let (_temp, paths) = temp_paths();
@@ -2553,6 +3459,9 @@ exit 0
                 ext: Some("mp4".into()),
                 vcodec: Some("avc1".into()),
                 acodec: Some("mp4a".into()),
+                tbr: None,
+                asr: None,
+                audio_channels: None,
                 filesize: Some(100),
                 filesize_approx: None,
                 dynamic_range: Some("HDR".into()),
@@ -2566,6 +3475,9 @@ exit 0
                 ext: Some("m4a".into()),
                 vcodec: Some("none".into()),
                 acodec: Some("mp4a".into()),
+                tbr: None,
+                asr: None,
+                audio_channels: None,
                 filesize: Some(50),
                 filesize_approx: None,
                 dynamic_range: None,
@@ -2579,6 +3491,73 @@ exit 0
         Ok(())
     }
 
+    /// Video-only and audio-only downloads become adaptive streams with
+    /// their codec, bitrate and (when indexed) byte ranges.
+    #[test]
+    fn collect_adaptive_streams_records_split_formats() -> Result<()> {
+        let (_temp, paths) = temp_paths();
+        let video_dir = paths.media_dir(MediaKind::Video).join("abc");
+        fs::create_dir_all(&video_dir)?;
+        let mp4_box = |kind: &[u8; 4]| {
+            let mut data = 16u32.to_be_bytes().to_vec();
+            data.extend_from_slice(kind);
+            data.resize(16, 0);
+            data
+        };
+        let audio: Vec<u8> = [b"ftyp", b"moov", b"sidx", b"moof"]
+            .into_iter()
+            .flat_map(mp4_box)
+            .collect();
+        fs::write(video_dir.join("abc_140.m4a"), &audio)?;
+        fs::write(video_dir.join("abc_313.webm"), "not indexed")?;
+        fs::write(video_dir.join("abc_18.mp4"), "muxed")?;
+
+        let mut video = sample_format("313", "webm");
+        video.vcodec = Some("vp9".into());
+        video.acodec = Some("none".into());
+        video.height = Some(2160);
+        video.tbr = Some(12_345.6);
+        let mut sound = sample_format("140", "m4a");
+        sound.vcodec = Some("none".into());
+        sound.acodec = Some("mp4a.40.2".into());
+        sound.asr = Some(44_100);
+        sound.audio_channels = Some(2);
+        let mut info = sample_video_info();
+        info.formats = Some(vec![sample_format("18", "mp4"), video, sound]);
+
+        let streams =
+            collect_adaptive_streams("abc", &info, paths.media_dir(MediaKind::Video), "videos")?;
+        assert_eq!(streams.len(), 2);
+        let video = &streams[0];
+        assert_eq!(video.kind, AdaptiveKind::Video);
+        assert_eq!(
+            (video.mime_type.as_str(), video.codecs.as_str()),
+            ("video/webm", "vp9")
+        );
+        assert_eq!(video.bandwidth, Some(12_345_600));
+        assert_eq!(video.quality_label.as_deref(), Some("2160p HDR"));
+        assert_eq!(video.url, "/api/videos/abc/dash/313");
+        assert!(video.init_range.is_none() && video.index_range.is_none());
+
+        let sound = &streams[1];
+        assert_eq!(sound.kind, AdaptiveKind::Audio);
+        assert_eq!(sound.mime_type, "audio/mp4");
+        assert_eq!(
+            (sound.audio_sample_rate, sound.audio_channels),
+            (Some(44_100), Some(2))
+        );
+        assert!(sound.width.is_none());
+        assert_eq!(
+            sound.init_range.map(|range| range.to_string()).as_deref(),
+            Some("0-31")
+        );
+        assert_eq!(
+            sound.index_range.map(|range| range.to_string()).as_deref(),
+            Some("32-47")
+        );
+        Ok(())
+    }
+
     #[test]
     fn subtitle_name_map_prefers_manual_over_auto() {
         let mut info = sample_video_info();
@@ -2690,14 +3669,19 @@ exit 0
         let info_path = dir.path().join("info.json");
         let json = serde_json::json!({
             "formats": [
-                { "format_id": " 136 " },
-                { "format_id": "249" },
+                { "format_id": " 136 ", "vcodec": "avc1", "acodec": "none" },
+                { "format_id": "18", "vcodec": "avc1", "acodec": "mp4a" },
+                { "format_id": "249", "vcodec": "none", "acodec": "opus" },
                 { "format_id": null }
             ]
         });
         fs::write(&info_path, serde_json::to_vec(&json)?)?;
-        let ids = collect_format_ids(&info_path, "https://example.com/video")?;
-        assert_eq!(ids, vec!["136".to_string(), "249".to_string()]);
+        let formats = collect_format_ids(&info_path, "https://example.com/video")?;
+        let found: Vec<(&str, bool)> = formats
+            .iter()
+            .map(|format| (format.id.as_str(), format.adaptive))
+            .collect();
+        assert_eq!(found, [("136", true), ("18", false), ("249", true)]);
         Ok(())
     }
 
@@ -2723,6 +3707,7 @@ exit 0
 
         let metadata = MetadataStore::open(&paths.metadata_db).await?;
         let mut archive = HashSet::from([String::from("alpha")]);
+        let mut batch = metadata.batch();
         process_media_entry(
             "alpha",
             1,
@@ -2730,16 +3715,297 @@ exit 0
             &paths,
             &mut archive,
             MediaKind::Video,
-            &metadata,
+            &mut batch,
         )
         .await?;
+        assert!(!batch.is_empty());
+        batch.commit().await?;
 
         let reader = MetadataReader::new(&paths.metadata_db).await?;
         let video = reader.get_video("alpha").await?.expect("video stored");
         assert_eq!(video.title, "Alpha Title");
-        let comments = reader.get_comments("alpha").await?;
+        let comments = reader.get_comments("alpha", true).await?;
         assert_eq!(comments.len(), 2);
         assert!(comments.iter().any(|c| c.status_likedbycreator));
+        assert_eq!(video.channel_id.as_deref(), Some("chan123"));
+        let channel = reader
+            .get_channel("chan123")
+            .await?
+            .expect("channel stored");
+        assert_eq!(channel.name, "Channel");
+        assert_eq!(channel.subscriber_count, Some(100));
+        Ok(())
+    }
+
+    #[test]
+    fn classify_unavailable_maps_yt_dlp_errors() {
+        let cases = [
+            (
+                "ERROR: [youtube] abc: Private video. Sign in if you've been granted access to this video",
+                Some(Availability::Private),
+            ),
+            (
+                "ERROR: [youtube] abc: Video unavailable. This video has been removed by the uploader",
+                Some(Availability::Removed),
+            ),
+            (
+                "ERROR: [youtube] abc: Video unavailable. This video is no longer available due to a copyright claim by Label",
+                Some(Availability::Copyright),
+            ),
+            (
+                "ERROR: [youtube] abc: Join this channel to get access to members-only content like this video",
+                Some(Availability::MembersOnly),
+            ),
+            (
+                "ERROR: [youtube] abc: Sign in to confirm your age. This video may be inappropriate for some users.",
+                Some(Availability::AgeRestricted),
+            ),
+            (
+                "ERROR: [youtube] abc: Video unavailable",
+                Some(Availability::Unavailable),
+            ),
+            (
+                "ERROR: [youtube] abc: Unable to download API page: HTTP Error 503",
+                None,
+            ),
+        ];
+        for (stderr, expected) in cases {
+            assert_eq!(classify_unavailable(stderr), expected, "{stderr}");
+        }
+    }
+
+    /// A video that went private upstream keeps its archived metadata and
+    /// is flagged instead of logging a bare refresh failure.
+    #[tokio::test]
+    async fn process_entry_records_unavailable_upstream() -> Result<()> {
+        let (temp, paths) = temp_paths();
+        let stub = install_ytdlp_stub(temp.path())?;
+        let guard = set_ytdlp_stub_path(stub);
+        paths.prepare()?;
+        let media_dir = paths.media_dir(MediaKind::Video).join("alpha");
+        fs::create_dir_all(&media_dir)?;
+        fs::write(media_dir.join("alpha_1080p.mp4"), "video-bytes")?;
+
+        let metadata = MetadataStore::open(&paths.metadata_db).await?;
+        let mut archive = HashSet::from([String::from("alpha")]);
+        let mut batch = metadata.batch();
+        process_media_entry(
+            "alpha",
+            1,
+            1,
+            &paths,
+            &mut archive,
+            MediaKind::Video,
+            &mut batch,
+        )
+        .await?;
+        batch.commit().await?;
+        drop(guard);
+
+        let reader = MetadataReader::new(&paths.metadata_db).await?;
+        let video = reader.get_video("alpha").await?.expect("video stored");
+        assert_eq!(video.availability, Some(Availability::Available));
+
+        let failing = temp.path().join("yt-dlp-private");
+        fs::write(
+            &failing,
+            "#!/usr/bin/env bash\necho \"ERROR: [youtube] alpha: Private video. Sign in if you've been granted access to this video\" >&2\nexit 1\n",
+        )?;
+        fs::set_permissions(&failing, fs::Permissions::from_mode(0o755))?;
+        let _guard = set_ytdlp_stub_path(failing);
+        let mut batch = metadata.batch();
+        process_media_entry(
+            "alpha",
+            1,
+            1,
+            &paths,
+            &mut archive,
+            MediaKind::Video,
+            &mut batch,
+        )
+        .await?;
+        batch.commit().await?;
+
+        let video = reader.get_video("alpha").await?.expect("video kept");
+        assert_eq!(video.title, "Alpha Title");
+        assert_eq!(video.availability, Some(Availability::Private));
+        assert!(video.availability_checked_at.is_some());
+        assert_eq!(reader.get_comments("alpha", false).await?.len(), 2);
+        Ok(())
+    }
+
+    /// `--reindex` rebuilds rows from the cached info.json and comment dump
+    /// without calling yt-dlp again.
+    #[tokio::test]
+    async fn reindex_library_rebuilds_rows_from_disk() -> Result<()> {
+        let (temp, paths) = temp_paths();
+        let stub = install_ytdlp_stub(temp.path())?;
+        let guard = set_ytdlp_stub_path(stub);
+        paths.prepare()?;
+
+        let media_dir = paths.media_dir(MediaKind::Video).join("alpha");
+        fs::create_dir_all(&media_dir)?;
+        fs::write(media_dir.join("alpha_1080p.mp4"), "video-bytes")?;
+        let metadata = MetadataStore::open(&paths.metadata_db).await?;
+        let mut archive = HashSet::from([String::from("alpha")]);
+        let mut batch = metadata.batch();
+        process_media_entry(
+            "alpha",
+            1,
+            1,
+            &paths,
+            &mut archive,
+            MediaKind::Video,
+            &mut batch,
+        )
+        .await?;
+        batch.commit().await?;
+        drop(guard);
+
+        let subtitle_dir = paths.subtitles.join("alpha");
+        fs::create_dir_all(&subtitle_dir)?;
+        fs::write(
+            subtitle_dir.join("alpha.en.vtt"),
+            "WEBVTT\n\n00:00:01.000 --> 00:00:02.000\nindexed offline\n",
+        )?;
+
+        let rebuilt_path = temp.path().join("rebuilt.db");
+        let rebuilt = MetadataStore::open(&rebuilt_path).await?;
+        assert_eq!(reindex_library(&paths, &rebuilt).await?, 1);
+
+        let reader = MetadataReader::new(&rebuilt_path).await?;
+        let video = reader.get_video("alpha").await?.expect("video rebuilt");
+        assert_eq!(video.title, "Alpha Title");
+        assert_eq!(reader.get_comments("alpha", true).await?.len(), 2);
+        assert!(reader.get_channel("chan123").await?.is_some());
+        let transcripts = reader.get_transcripts("alpha", None).await?;
+        assert_eq!(transcripts[0].cues[0].text, "indexed offline");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn refresh_channel_stores_metadata_and_images() -> Result<()> {
+        let (temp, paths) = temp_paths();
+        let stub = install_ytdlp_stub(temp.path())?;
+        let _guard = set_ytdlp_stub_path(stub);
+        paths.prepare()?;
+        let metadata = MetadataStore::open(&paths.metadata_db).await?;
+
+        let channel = refresh_channel("https://youtube.com/@Channel", &paths, &metadata).await?;
+        assert_eq!(channel.channel_id, "chan123");
+        assert_eq!(channel.handle.as_deref(), Some("@Channel"));
+        assert!(channel.last_refreshed_at.is_some());
+
+        let reader = MetadataReader::new(&paths.metadata_db).await?;
+        let stored = reader
+            .get_channel("chan123")
+            .await?
+            .expect("channel stored");
+        assert_eq!(stored.subscriber_count, Some(250));
+        assert!(
+            stored
+                .avatar_path
+                .as_deref()
+                .is_some_and(|path| path.ends_with("chan123.avatar_uncropped.jpg"))
+        );
+        assert!(
+            stored
+                .banner_path
+                .as_deref()
+                .is_some_and(|path| path.ends_with("chan123.banner_uncropped.jpg"))
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn process_media_list_skips_blocked_ids() -> Result<()> {
+        let (temp, paths) = temp_paths();
+        let stub = install_ytdlp_stub(temp.path())?;
+        let _guard = set_ytdlp_stub_path(stub);
+        paths.prepare()?;
+        let metadata = MetadataStore::open(&paths.metadata_db).await?;
+        metadata.block_media("alpha", None).await?;
+        let mut archive = HashSet::new();
+        let mut completed = 0;
+
+        process_media_list(
+            "videos",
+            &["alpha".to_string()],
+            MediaKind::Video,
+            &paths,
+            &mut archive,
+            &metadata,
+            &mut completed,
+            1,
+            None,
+        )
+        .await?;
+
+        assert_eq!(completed, 1);
+        assert!(archive.is_empty());
+        assert!(!paths.videos.join("alpha").exists());
+        let reader = MetadataReader::new(&paths.metadata_db).await?;
+        assert!(reader.get_video("alpha").await?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn playlist_id_from_url_reads_list_parameter() {
+        assert_eq!(
+            playlist_id_from_url("https://www.youtube.com/playlist?list=PL123").as_deref(),
+            Some("PL123")
+        );
+        assert_eq!(
+            playlist_id_from_url("https://www.youtube.com/watch?v=abc&list=PL9#t=1").as_deref(),
+            Some("PL9")
+        );
+        assert!(playlist_id_from_url("https://www.youtube.com/@Channel").is_none());
+        assert!(playlist_id_from_url("https://www.youtube.com/playlist?list=").is_none());
+    }
+
+    /// Playlist downloads route members into the normal layout and store the
+    /// playlist in upstream order, skipping unavailable and duplicate entries.
+    #[tokio::test]
+    async fn download_playlist_entries_stores_ordered_playlist() -> Result<()> {
+        let (temp, paths) = temp_paths();
+        let stub = install_ytdlp_stub(temp.path())?;
+        let _guard = set_ytdlp_stub_path(stub);
+        paths.prepare()?;
+        let metadata = MetadataStore::open(&paths.metadata_db).await?;
+        let mut archive = HashSet::new();
+
+        download_playlist_entries("PLtest", true, &paths, &mut archive, &metadata, None).await?;
+
+        assert!(archive.contains("alpha"));
+        assert!(archive.contains("beta"));
+        let reader = MetadataReader::new(&paths.metadata_db).await?;
+        assert!(reader.get_video("alpha").await?.is_some());
+        assert!(reader.get_short("beta").await?.is_some());
+
+        let playlist = reader
+            .get_playlist("PLtest")
+            .await?
+            .expect("playlist stored");
+        assert_eq!(playlist.title, "Test Playlist");
+        assert_eq!(playlist.owner.as_deref(), Some("Channel"));
+        assert_eq!(playlist.owner_channel_id.as_deref(), Some("chan123"));
+        assert!(playlist.tracked);
+        assert_eq!(
+            playlist.url.as_deref(),
+            Some("https://www.youtube.com/playlist?list=PLtest")
+        );
+
+        let entries = reader.get_playlist_entries("PLtest").await?;
+        let summary: Vec<(i64, &str, &str)> = entries
+            .iter()
+            .map(|entry| (entry.position, entry.videoid.as_str(), entry.kind.as_str()))
+            .collect();
+        assert_eq!(summary, [(0, "alpha", "video"), (1, "beta", "short")]);
+
+        // `--no-track` keeps the playlist but stops routine refreshes.
+        download_playlist_entries("PLtest", false, &paths, &mut archive, &metadata, None).await?;
+        let playlist = reader.get_playlist("PLtest").await?.expect("playlist kept");
+        assert!(!playlist.tracked);
         Ok(())
     }
 
@@ -2748,7 +4014,8 @@ exit 0
         let (temp, paths) = temp_paths();
         let stub = install_ytdlp_stub(temp.path())?;
         let _guard = set_ytdlp_stub_path(stub);
-        let comments = fetch_comments("alpha", "https://youtube.com/watch?v=alpha", &paths)?;
+        let comments = fetch_comments("alpha", "https://youtube.com/watch?v=alpha", &paths)?
+            .expect("comment dump");
         assert_eq!(comments.len(), 2);
         assert!(
             comments[0]
@@ -2761,6 +4028,82 @@ exit 0
         Ok(())
     }
 
+    /// A batch that cannot be committed is kept for one retry; when the retry
+    /// fails as well the run is aborted instead of queueing more writes.
+    #[tokio::test]
+    async fn flush_metadata_aborts_when_the_retry_fails() -> Result<()> {
+        let (_temp, paths) = temp_paths();
+        paths.prepare()?;
+        let metadata = MetadataStore::open(&paths.metadata_db).await?;
+        // Without the search tables every commit fails as a whole.
+        let conn = libsql::Builder::new_local(&paths.metadata_db)
+            .build()
+            .await?
+            .connect()?;
+        conn.execute_batch("DROP TABLE search_documents;").await?;
+
+        let mut batch = metadata.batch();
+        batch.upsert_comments("alpha", Vec::new());
+        flush_metadata(&mut batch, true).await?;
+        assert_eq!(batch.len(), 1);
+        assert!(flush_metadata(&mut batch, false).await.is_err());
+        Ok(())
+    }
+
+    /// A comment fetch that fails (rate limit, network error) keeps the
+    /// archived comments live instead of tombstoning all of them.
+    #[tokio::test]
+    async fn failed_comment_fetch_keeps_archived_comments() -> Result<()> {
+        let (temp, paths) = temp_paths();
+        let stub = install_ytdlp_stub(temp.path())?;
+        let guard = set_ytdlp_stub_path(stub.clone());
+        paths.prepare()?;
+        let output_dir = paths.media_dir(MediaKind::Video);
+        let metadata = MetadataStore::open(&paths.metadata_db).await?;
+        let mut batch = metadata.batch();
+        refresh_metadata(
+            "alpha",
+            "https://youtube.com/watch?v=alpha",
+            output_dir,
+            &paths,
+            MediaKind::Video,
+            false,
+            &mut batch,
+        )?;
+        batch.commit().await?;
+        drop(guard);
+        fs::remove_dir_all(paths.comments.join("alpha"))?;
+
+        let failing = temp.path().join("yt-dlp-no-comments");
+        fs::write(
+            &failing,
+            format!(
+                "#!/usr/bin/env bash\nif printf '%s\\n' \"$@\" | grep -q -- '--write-comments'; then\n  echo 'ERROR: HTTP Error 429: Too Many Requests' >&2\n  exit 1\nfi\nexec {} \"$@\"\n",
+                stub.display()
+            ),
+        )?;
+        fs::set_permissions(&failing, fs::Permissions::from_mode(0o755))?;
+        let _guard = set_ytdlp_stub_path(failing);
+        assert!(fetch_comments("alpha", "https://youtube.com/watch?v=alpha", &paths)?.is_none());
+        let mut batch = metadata.batch();
+        refresh_metadata(
+            "alpha",
+            "https://youtube.com/watch?v=alpha",
+            output_dir,
+            &paths,
+            MediaKind::Video,
+            false,
+            &mut batch,
+        )?;
+        batch.commit().await?;
+
+        let reader = MetadataReader::new(&paths.metadata_db).await?;
+        let comments = reader.get_comments("alpha", true).await?;
+        assert_eq!(comments.len(), 2);
+        assert!(comments.iter().all(|c| c.deleted_upstream_at.is_none()));
+        Ok(())
+    }
+
     #[tokio::test]
     async fn download_collection_downloads_new_entries() -> Result<()> {
         let (temp, paths) = temp_paths();
@@ -2864,7 +4207,18 @@ exit 0
         let _guard = set_ytdlp_stub_path(stub);
         let info_path = temp.path().join("empty.json");
         fs::write(&info_path, r#"{"formats":[]}"#)?;
-        let actual = collect_format_ids(&info_path, "https://www.youtube.com/watch?v=6QZz04e6gqE")?;
+        let formats =
+            collect_format_ids(&info_path, "https://www.youtube.com/watch?v=6QZz04e6gqE")?;
+        let adaptive = |id: &str| {
+            formats
+                .iter()
+                .find(|format| format.id == id)
+                .map(|format| format.adaptive)
+        };
+        assert_eq!(adaptive("18"), Some(false));
+        assert_eq!(adaptive("137"), Some(true));
+        assert_eq!(adaptive("251"), Some(true));
+        let actual: Vec<String> = formats.into_iter().map(|format| format.id).collect();
         assert_eq!(actual, expected_format_ids());
         Ok(())
     }
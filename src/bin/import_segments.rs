@@ -0,0 +1,351 @@
+#![forbid(unsafe_code)]
+
+//! Imports skip segments from a local SponsorBlock database dump.
+//!
+//! SponsorBlock publishes its database as CSV (`sponsorTimes.csv`). The dump
+//! covers millions of videos, so rows are streamed and only those for media
+//! already in the library are kept. Nothing is fetched over the network: grab
+//! the dump however you like and point this binary at it.
+
+use anyhow::{Context, Result, bail};
+use newtube_tools::{
+    config::{RuntimeOverrides, resolve_runtime_paths},
+    metadata::{MetadataReader, MetadataStore, SegmentCategory, SkipSegment},
+    security::ensure_not_root,
+};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::PathBuf;
+
+const METADATA_DB_FILE: &str = "metadata.db";
+const USAGE: &str =
+    "Usage: import_segments [--media-root <path>] [--min-votes <n>] <sponsorTimes.csv>";
+
+#[derive(Debug, Clone)]
+struct ImportArgs {
+    dump: PathBuf,
+    media_root: PathBuf,
+    min_votes: i64,
+}
+
+impl ImportArgs {
+    fn parse() -> Result<Self> {
+        Self::from_iter(env::args().skip(1))
+    }
+
+    #[cfg(test)]
+    fn from_slice(values: &[&str]) -> Result<Self> {
+        Self::from_iter(values.iter().map(|value| value.to_string()))
+    }
+
+    fn from_iter<I>(iter: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut media_root_override: Option<PathBuf> = None;
+        let mut min_votes = 0;
+        let mut dump: Option<PathBuf> = None;
+        let mut args = iter.into_iter();
+
+        while let Some(arg) = args.next() {
+            if let Some(value) = arg.strip_prefix("--media-root=") {
+                media_root_override = Some(PathBuf::from(value));
+                continue;
+            }
+            if let Some(value) = arg.strip_prefix("--min-votes=") {
+                min_votes = parse_min_votes(value)?;
+                continue;
+            }
+
+            match arg.as_str() {
+                "--media-root" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--media-root requires a value"))?;
+                    media_root_override = Some(PathBuf::from(value));
+                }
+                "--min-votes" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--min-votes requires a value"))?;
+                    min_votes = parse_min_votes(&value)?;
+                }
+                _ if arg.starts_with("--") => bail!("unknown argument: {arg}"),
+                _ if dump.is_some() => bail!("only one dump file can be imported at a time"),
+                _ => dump = Some(PathBuf::from(arg)),
+            }
+        }
+
+        let Some(dump) = dump else {
+            bail!(USAGE);
+        };
+        let media_root = match media_root_override {
+            Some(path) => path,
+            None => resolve_runtime_paths(RuntimeOverrides::default())?.media_root,
+        };
+
+        Ok(Self {
+            dump,
+            media_root,
+            min_votes,
+        })
+    }
+}
+
+fn parse_min_votes(value: &str) -> Result<i64> {
+    value
+        .trim()
+        .parse()
+        .with_context(|| format!("invalid --min-votes value: {value}"))
+}
+
+/// Columns we read from `sponsorTimes.csv`. Older dumps lack `actionType`
+/// and the hidden flags, so those default to "visible skip".
+#[derive(Debug, Deserialize)]
+struct DumpRow {
+    #[serde(rename = "videoID")]
+    video_id: String,
+    #[serde(rename = "startTime")]
+    start_time: f64,
+    #[serde(rename = "endTime")]
+    end_time: f64,
+    #[serde(default)]
+    votes: Option<i64>,
+    category: String,
+    #[serde(rename = "actionType", default)]
+    action_type: Option<String>,
+    #[serde(default)]
+    hidden: Option<i64>,
+    #[serde(rename = "shadowHidden", default)]
+    shadow_hidden: Option<i64>,
+}
+
+/// Submission that passed the filters, with its votes for deduplication.
+#[derive(Debug, Clone)]
+struct Candidate {
+    segment: SkipSegment,
+    votes: i64,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct DumpStats {
+    rows: usize,
+    malformed: usize,
+    kept: usize,
+}
+
+/// Streams the dump and groups usable submissions by video. Rows for videos
+/// outside `library`, other categories/actions, hidden or under-voted
+/// submissions and malformed lines are skipped.
+fn collect_candidates<R: Read>(
+    dump: R,
+    library: &HashSet<String>,
+    min_votes: i64,
+) -> Result<(HashMap<String, Vec<Candidate>>, DumpStats)> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(dump);
+    let mut candidates: HashMap<String, Vec<Candidate>> = HashMap::new();
+    let mut stats = DumpStats::default();
+    for row in reader.deserialize::<DumpRow>() {
+        stats.rows += 1;
+        let row = match row {
+            Ok(row) => row,
+            Err(err) if err.is_io_error() => return Err(err).context("reading dump"),
+            Err(_) => {
+                stats.malformed += 1;
+                continue;
+            }
+        };
+        if !library.contains(&row.video_id) {
+            continue;
+        }
+        let votes = row.votes.unwrap_or(0);
+        let skippable = row
+            .action_type
+            .as_deref()
+            .is_none_or(|action| action.is_empty() || action == "skip");
+        let visible = row.hidden.unwrap_or(0) == 0 && row.shadow_hidden.unwrap_or(0) == 0;
+        let Some(category) = SegmentCategory::parse(&row.category) else {
+            continue;
+        };
+        if !skippable
+            || !visible
+            || votes < min_votes
+            || !row.start_time.is_finite()
+            || !row.end_time.is_finite()
+            || row.start_time < 0.0
+            || row.end_time <= row.start_time
+        {
+            continue;
+        }
+        stats.kept += 1;
+        candidates.entry(row.video_id).or_default().push(Candidate {
+            segment: SkipSegment {
+                category,
+                start: row.start_time,
+                end: row.end_time,
+            },
+            votes,
+        });
+    }
+    Ok((candidates, stats))
+}
+
+/// SponsorBlock keeps every competing submission, so the same sponsor read
+/// usually shows up several times. Keep the best-voted one and drop others
+/// of the same category that overlap it.
+fn select_segments(mut candidates: Vec<Candidate>) -> Vec<SkipSegment> {
+    candidates.sort_by(|a, b| {
+        b.votes
+            .cmp(&a.votes)
+            .then(a.segment.start.total_cmp(&b.segment.start))
+    });
+    let mut selected: Vec<SkipSegment> = Vec::new();
+    for candidate in candidates {
+        let overlaps = selected.iter().any(|kept| {
+            kept.category == candidate.segment.category
+                && kept.start < candidate.segment.end
+                && candidate.segment.start < kept.end
+        });
+        if !overlaps {
+            selected.push(candidate.segment);
+        }
+    }
+    selected.sort_by(|a, b| a.start.total_cmp(&b.start));
+    selected
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    ensure_not_root("import_segments")?;
+
+    let ImportArgs {
+        dump,
+        media_root,
+        min_votes,
+    } = ImportArgs::parse()?;
+
+    let metadata_path = media_root.join(METADATA_DB_FILE);
+    let store = MetadataStore::open(&metadata_path)
+        .await
+        .context("initializing metadata database")?;
+    let reader = MetadataReader::new(&metadata_path)
+        .await
+        .context("opening metadata database")?;
+    let library = reader.list_media_ids().await?;
+
+    println!("Reading {}", dump.display());
+    let file = File::open(&dump).with_context(|| format!("opening {}", dump.display()))?;
+    let (mut candidates, stats) = collect_candidates(BufReader::new(file), &library, min_votes)?;
+    println!(
+        "Scanned {} rows ({} malformed), {} segments match the library.",
+        stats.rows, stats.malformed, stats.kept
+    );
+
+    // Videos missing from the dump are cleared too, so segments that were
+    // voted away upstream disappear on the next import.
+    let mut updated = 0;
+    let mut segments_written = 0;
+    let mut kept_manual = 0;
+    let mut ids: Vec<&String> = library.iter().collect();
+    ids.sort();
+    for videoid in ids {
+        let segments = select_segments(candidates.remove(videoid).unwrap_or_default());
+        if store.import_segments(videoid, &segments).await? {
+            if !segments.is_empty() {
+                updated += 1;
+                segments_written += segments.len();
+            }
+        } else {
+            kept_manual += 1;
+        }
+    }
+
+    println!(
+        "Imported {segments_written} segments for {updated} videos; {kept_manual} videos with manual edits were left untouched."
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DUMP: &str = "\
+videoID,startTime,endTime,votes,locked,incorrectVotes,UUID,userID,timeSubmitted,views,category,actionType,service,videoDuration,hidden,reputation,shadowHidden,hashedVideoID,userAgent,description
+alpha,10.5,40,12,0,1,u1,x,0,0,sponsor,skip,YouTube,300,0,0,0,h,\"agent, with comma\",
+alpha,11,39,3,0,1,u2,x,0,0,sponsor,skip,YouTube,300,0,0,0,h,,
+alpha,0,8,5,0,1,u3,x,0,0,intro,skip,YouTube,300,0,0,0,h,,
+alpha,100,110,5,0,1,u4,x,0,0,sponsor,mute,YouTube,300,0,0,0,h,,
+alpha,120,130,5,0,1,u5,x,0,0,music_offtopic,skip,YouTube,300,0,0,0,h,,
+alpha,140,150,-3,0,1,u6,x,0,0,selfpromo,skip,YouTube,300,0,0,0,h,,
+alpha,160,170,5,0,1,u7,x,0,0,outro,skip,YouTube,300,1,0,0,h,,
+alpha,not-a-number,170,5,0,1,u8,x,0,0,outro,skip,YouTube,300,0,0,0,h,,
+other,0,10,50,0,1,u9,x,0,0,sponsor,skip,YouTube,300,0,0,0,h,,
+";
+
+    fn library() -> HashSet<String> {
+        HashSet::from(["alpha".to_string()])
+    }
+
+    #[test]
+    fn collect_candidates_filters_dump_rows() -> Result<()> {
+        let (candidates, stats) = collect_candidates(DUMP.as_bytes(), &library(), 0)?;
+        assert_eq!(
+            stats,
+            DumpStats {
+                rows: 9,
+                malformed: 1,
+                kept: 3,
+            }
+        );
+        assert!(!candidates.contains_key("other"));
+        assert_eq!(candidates["alpha"].len(), 3);
+
+        let (strict, _) = collect_candidates(DUMP.as_bytes(), &library(), 10)?;
+        assert_eq!(strict["alpha"].len(), 1);
+        Ok(())
+    }
+
+    /// Overlapping submissions of the same category collapse to the best
+    /// voted one; different categories may overlap.
+    #[test]
+    fn select_segments_dedupes_overlapping_submissions() -> Result<()> {
+        let (mut candidates, _) = collect_candidates(DUMP.as_bytes(), &library(), 0)?;
+        let selected = select_segments(candidates.remove("alpha").unwrap());
+        assert_eq!(
+            selected,
+            [
+                SkipSegment {
+                    category: SegmentCategory::Intro,
+                    start: 0.0,
+                    end: 8.0,
+                },
+                SkipSegment {
+                    category: SegmentCategory::Sponsor,
+                    start: 10.5,
+                    end: 40.0,
+                },
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn import_args_require_a_dump() -> Result<()> {
+        let args = ImportArgs::from_slice(&[
+            "--media-root",
+            "/srv/media",
+            "--min-votes=2",
+            "sponsorTimes.csv",
+        ])?;
+        assert_eq!(args.dump, PathBuf::from("sponsorTimes.csv"));
+        assert_eq!(args.media_root, PathBuf::from("/srv/media"));
+        assert_eq!(args.min_votes, 2);
+        assert!(ImportArgs::from_slice(&["--media-root", "/srv/media"]).is_err());
+        assert!(ImportArgs::from_slice(&["--media-root", "/srv/media", "--bogus", "a"]).is_err());
+        Ok(())
+    }
+}
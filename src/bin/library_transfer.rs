@@ -0,0 +1,1095 @@
+#![forbid(unsafe_code)]
+
+//! Moves library metadata between instances.
+//!
+//! `export` streams `metadata.db` as NDJSON: a header line with the format
+//! version followed by one JSON object per line, tagged with its `type`.
+//! Media, subtitle and channel image paths under MEDIA_ROOT are written
+//! relative to it, so the file can be imported on a machine that mounts the
+//! library somewhere else. `import` merges such a file into the local
+//! database and resolves those paths against the local MEDIA_ROOT.
+
+use anyhow::{Context, Result, bail};
+use newtube_tools::{
+    config::{RuntimeOverrides, resolve_runtime_paths},
+    metadata::{
+        BlockedMedia, ChannelRecord, Chapter, CommentRecord, MetadataReader, MetadataStore,
+        PlaylistEntry, PlaylistRecord, SkipSegment, SubtitleCollection, VideoRecord,
+        VideoStatsSnapshot, WriteBatch,
+    },
+    security::ensure_not_root,
+    transcript::Transcript,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+
+const METADATA_DB_FILE: &str = "metadata.db";
+const EXPORT_FORMAT: &str = "newtube-library";
+/// Bumped whenever the line format changes incompatibly. Imports refuse
+/// files written by a newer version.
+const EXPORT_VERSION: u32 = 1;
+/// Queued writes after which an import commits, mirroring the downloader.
+const IMPORT_BATCH_WRITES: usize = 300;
+const USAGE: &str = "Usage: library_transfer export [--media-root <path>] [--output <file>]\n       library_transfer import [--media-root <path>] [--on-conflict skip|replace] <file|->";
+
+/// What to do with an imported channel, video, playlist or blocked id that
+/// already exists locally.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum ConflictPolicy {
+    /// Keep the local row and everything attached to it.
+    #[default]
+    Skip,
+    /// Overwrite the local row with the imported one.
+    Replace,
+}
+
+impl ConflictPolicy {
+    fn parse(value: &str) -> Result<Self> {
+        match value.trim() {
+            "skip" => Ok(Self::Skip),
+            "replace" => Ok(Self::Replace),
+            other => bail!("invalid --on-conflict value: {other} (expected skip or replace)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    /// Writes to the given file, or stdout when `None`.
+    Export { output: Option<PathBuf> },
+    /// Reads from the given file; `-` reads stdin.
+    Import {
+        input: PathBuf,
+        policy: ConflictPolicy,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct TransferArgs {
+    command: Command,
+    media_root: PathBuf,
+}
+
+impl TransferArgs {
+    fn parse() -> Result<Self> {
+        Self::from_iter(env::args().skip(1))
+    }
+
+    #[cfg(test)]
+    fn from_slice(values: &[&str]) -> Result<Self> {
+        Self::from_iter(values.iter().map(|value| value.to_string()))
+    }
+
+    fn from_iter<I>(iter: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut args = iter.into_iter();
+        let Some(command) = args.next() else {
+            bail!(USAGE);
+        };
+        let export = match command.as_str() {
+            "export" => true,
+            "import" => false,
+            _ => bail!(USAGE),
+        };
+
+        let mut media_root_override: Option<PathBuf> = None;
+        let mut output: Option<PathBuf> = None;
+        let mut policy: Option<ConflictPolicy> = None;
+        let mut input: Option<PathBuf> = None;
+        while let Some(arg) = args.next() {
+            if let Some(value) = arg.strip_prefix("--media-root=") {
+                media_root_override = Some(PathBuf::from(value));
+                continue;
+            }
+            if let Some(value) = arg.strip_prefix("--output=") {
+                output = Some(PathBuf::from(value));
+                continue;
+            }
+            if let Some(value) = arg.strip_prefix("--on-conflict=") {
+                policy = Some(ConflictPolicy::parse(value)?);
+                continue;
+            }
+
+            match arg.as_str() {
+                "--media-root" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--media-root requires a value"))?;
+                    media_root_override = Some(PathBuf::from(value));
+                }
+                "--output" | "-o" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--output requires a value"))?;
+                    output = Some(PathBuf::from(value));
+                }
+                "--on-conflict" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--on-conflict requires a value"))?;
+                    policy = Some(ConflictPolicy::parse(&value)?);
+                }
+                "-" => input = Some(PathBuf::from(arg)),
+                _ if arg.starts_with("--") => bail!("unknown argument: {arg}"),
+                _ if input.is_some() => bail!("only one file can be imported at a time"),
+                _ => input = Some(PathBuf::from(arg)),
+            }
+        }
+
+        let command = if export {
+            if input.is_some() {
+                bail!("export takes no positional arguments; use --output <file>");
+            }
+            if policy.is_some() {
+                bail!("--on-conflict only applies to import");
+            }
+            Command::Export {
+                output: output.filter(|path| path.as_os_str() != "-"),
+            }
+        } else {
+            if output.is_some() {
+                bail!("--output only applies to export");
+            }
+            let Some(input) = input else {
+                bail!(USAGE);
+            };
+            Command::Import {
+                input,
+                policy: policy.unwrap_or_default(),
+            }
+        };
+        let media_root = match media_root_override {
+            Some(path) => path,
+            None => resolve_runtime_paths(RuntimeOverrides::default())?.media_root,
+        };
+
+        Ok(Self {
+            command,
+            media_root,
+        })
+    }
+}
+
+/// One line of an export file.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Entry {
+    Header {
+        format: String,
+        version: u32,
+        schema_version: i64,
+        exported_at: String,
+    },
+    Channel(ChannelRecord),
+    Video(VideoRecord),
+    Short(VideoRecord),
+    Subtitles(SubtitleCollection),
+    Comments {
+        videoid: String,
+        comments: Vec<CommentRecord>,
+    },
+    Chapters {
+        videoid: String,
+        chapters: Vec<Chapter>,
+    },
+    Transcripts {
+        videoid: String,
+        transcripts: Vec<Transcript>,
+    },
+    /// `manual` segments were edited by hand; the rest came from SponsorBlock.
+    Segments {
+        videoid: String,
+        manual: bool,
+        segments: Vec<SkipSegment>,
+    },
+    StatsHistory {
+        videoid: String,
+        snapshots: Vec<VideoStatsSnapshot>,
+    },
+    Playlist {
+        playlist: PlaylistRecord,
+        entries: Vec<PlaylistEntry>,
+    },
+    Blocked(BlockedMedia),
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct ExportStats {
+    media: usize,
+    lines: usize,
+    /// Paths outside MEDIA_ROOT, written unchanged.
+    external_paths: usize,
+}
+
+/// Serializes entries one per line and makes library paths relative.
+struct EntryWriter<'a, W: Write> {
+    out: W,
+    media_root: &'a Path,
+    stats: ExportStats,
+}
+
+impl<W: Write> EntryWriter<'_, W> {
+    fn write(&mut self, entry: &Entry) -> Result<()> {
+        serde_json::to_writer(&mut self.out, entry).context("writing export")?;
+        self.out.write_all(b"\n").context("writing export")?;
+        self.stats.lines += 1;
+        Ok(())
+    }
+
+    fn relativize(&mut self, path: &mut Option<String>) {
+        if let Some(value) = path
+            && !relativize_path(value, self.media_root)
+        {
+            self.stats.external_paths += 1;
+        }
+    }
+}
+
+/// Rewrites an absolute `path` under `root` to a `/`-separated relative one.
+/// Returns `false` for absolute paths outside `root`, which stay as they are.
+fn relativize_path(path: &mut String, root: &Path) -> bool {
+    let candidate = Path::new(path.as_str());
+    if candidate.is_relative() {
+        return true;
+    }
+    let Ok(relative) = candidate.strip_prefix(root) else {
+        return false;
+    };
+    let parts: Vec<String> = relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    *path = parts.join("/");
+    true
+}
+
+/// Anchors a relative path from an export at the local `root`. Relative
+/// paths that try to climb out of the library are dropped, which makes the
+/// backend fall back to its standard file layout.
+fn resolve_path(path: &mut Option<String>, root: &Path) {
+    let Some(value) = path.as_deref() else {
+        return;
+    };
+    let candidate = Path::new(value);
+    if candidate.is_absolute() {
+        return;
+    }
+    if candidate
+        .components()
+        .any(|component| matches!(component, Component::ParentDir))
+    {
+        *path = None;
+        return;
+    }
+    *path = Some(root.join(candidate).to_string_lossy().into_owned());
+}
+
+/// Streams every table of the metadata DB into `out`. Each video is followed
+/// by the rows that hang off it so imports can skip them together.
+async fn export_library<W: Write>(
+    reader: &MetadataReader,
+    schema_version: i64,
+    media_root: &Path,
+    out: W,
+) -> Result<ExportStats> {
+    let mut writer = EntryWriter {
+        out,
+        media_root,
+        stats: ExportStats::default(),
+    };
+    writer.write(&Entry::Header {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        schema_version,
+        exported_at: chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true),
+    })?;
+
+    for mut channel in reader.list_channels().await? {
+        writer.relativize(&mut channel.avatar_path);
+        writer.relativize(&mut channel.banner_path);
+        writer.write(&Entry::Channel(channel))?;
+    }
+
+    for short in [false, true] {
+        let records = if short {
+            reader.list_shorts().await?
+        } else {
+            reader.list_videos().await?
+        };
+        for mut record in records {
+            for source in &mut record.sources {
+                writer.relativize(&mut source.path);
+            }
+            for stream in &mut record.adaptive_streams {
+                writer.relativize(&mut stream.path);
+            }
+            let videoid = record.videoid.clone();
+            writer.write(&if short {
+                Entry::Short(record)
+            } else {
+                Entry::Video(record)
+            })?;
+            export_media_rows(reader, &mut writer, videoid).await?;
+            writer.stats.media += 1;
+        }
+    }
+
+    for playlist in reader.list_playlists().await? {
+        let entries = reader.get_playlist_entries(&playlist.playlist_id).await?;
+        writer.write(&Entry::Playlist { playlist, entries })?;
+    }
+    for blocked in reader.list_blocked_media().await? {
+        writer.write(&Entry::Blocked(blocked))?;
+    }
+    writer.out.flush().context("writing export")?;
+    Ok(writer.stats)
+}
+
+async fn export_media_rows<W: Write>(
+    reader: &MetadataReader,
+    writer: &mut EntryWriter<'_, W>,
+    videoid: String,
+) -> Result<()> {
+    if let Some(mut subtitles) = reader.get_subtitles(&videoid).await? {
+        for track in &mut subtitles.languages {
+            writer.relativize(&mut track.path);
+        }
+        writer.write(&Entry::Subtitles(subtitles))?;
+    }
+    let comments = reader.get_comments(&videoid, true).await?;
+    if !comments.is_empty() {
+        writer.write(&Entry::Comments {
+            videoid: videoid.clone(),
+            comments,
+        })?;
+    }
+    let chapters = reader.get_chapters(&videoid).await?;
+    if !chapters.is_empty() {
+        writer.write(&Entry::Chapters {
+            videoid: videoid.clone(),
+            chapters,
+        })?;
+    }
+    let transcripts = reader.get_transcripts(&videoid, None).await?;
+    if !transcripts.is_empty() {
+        writer.write(&Entry::Transcripts {
+            videoid: videoid.clone(),
+            transcripts,
+        })?;
+    }
+    let segments = reader.get_segments(&videoid).await?;
+    if !segments.is_empty() {
+        writer.write(&Entry::Segments {
+            manual: reader.has_manual_segments(&videoid).await?,
+            videoid: videoid.clone(),
+            segments,
+        })?;
+    }
+    let snapshots = reader.get_video_stats_history(&videoid).await?;
+    if !snapshots.is_empty() {
+        writer.write(&Entry::StatsHistory { videoid, snapshots })?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct ImportStats {
+    added: usize,
+    replaced: usize,
+    skipped: usize,
+    /// Batches with at least one write that failed and was rolled back.
+    failed_batches: usize,
+}
+
+/// Merges an export into the local database. Channels, media, playlists and
+/// blocked ids that already exist are handled per `policy`; rows attached to
+/// a skipped video (comments, chapters, ...) are skipped along with it.
+async fn import_library<R: BufRead>(
+    store: &MetadataStore,
+    reader: &MetadataReader,
+    media_root: &Path,
+    input: R,
+    policy: ConflictPolicy,
+) -> Result<ImportStats> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line));
+    let header = loop {
+        let Some((number, line)) = lines.next() else {
+            bail!("import file is empty");
+        };
+        let line = line.context("reading import")?;
+        if !line.trim().is_empty() {
+            break parse_entry(number, &line)?;
+        }
+    };
+    let Entry::Header {
+        format, version, ..
+    } = header
+    else {
+        bail!("import file does not start with a header line");
+    };
+    if format != EXPORT_FORMAT {
+        bail!("not a library export (format {format})");
+    }
+    if version > EXPORT_VERSION {
+        bail!(
+            "export format version {version} is newer than this binary supports ({EXPORT_VERSION})"
+        );
+    }
+
+    let mut importer = Importer {
+        store,
+        reader,
+        media_root,
+        policy,
+        batch: store.batch(),
+        media_ids: reader.list_media_ids().await?,
+        channel_ids: reader
+            .list_channels()
+            .await?
+            .into_iter()
+            .map(|channel| channel.channel_id)
+            .collect(),
+        blocked_ids: store.blocked_media_ids().await?,
+        skipped_media: HashSet::new(),
+        stats: ImportStats::default(),
+    };
+    for (number, line) in lines {
+        let line = line.context("reading import")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let entry = parse_entry(number, &line)?;
+        importer
+            .apply(entry)
+            .await
+            .with_context(|| format!("line {number}"))?;
+        if importer.batch.len() >= IMPORT_BATCH_WRITES {
+            importer.commit().await;
+        }
+    }
+    importer.commit().await;
+    Ok(importer.stats)
+}
+
+fn parse_entry(number: usize, line: &str) -> Result<Entry> {
+    serde_json::from_str(line).with_context(|| format!("line {number}: invalid entry"))
+}
+
+/// Import state: what already exists locally and which videos were skipped.
+/// Bulk rows go through a [`WriteBatch`]; the rare playlist, segment and
+/// blocklist rows are written directly.
+struct Importer<'a> {
+    store: &'a MetadataStore,
+    reader: &'a MetadataReader,
+    media_root: &'a Path,
+    policy: ConflictPolicy,
+    batch: WriteBatch<'a>,
+    media_ids: HashSet<String>,
+    channel_ids: HashSet<String>,
+    blocked_ids: HashSet<String>,
+    skipped_media: HashSet<String>,
+    stats: ImportStats,
+}
+
+impl Importer<'_> {
+    async fn apply(&mut self, entry: Entry) -> Result<()> {
+        let attached_to = match &entry {
+            Entry::Subtitles(subtitles) => Some(subtitles.videoid.as_str()),
+            Entry::Comments { videoid, .. }
+            | Entry::Chapters { videoid, .. }
+            | Entry::Transcripts { videoid, .. }
+            | Entry::Segments { videoid, .. }
+            | Entry::StatsHistory { videoid, .. } => Some(videoid.as_str()),
+            _ => None,
+        };
+        if attached_to.is_some_and(|videoid| self.skipped_media.contains(videoid)) {
+            return Ok(());
+        }
+
+        match entry {
+            Entry::Header { .. } => bail!("unexpected second header"),
+            Entry::Channel(mut channel) => {
+                let exists = !self.channel_ids.insert(channel.channel_id.clone());
+                if self.skip(exists) {
+                    return Ok(());
+                }
+                resolve_path(&mut channel.avatar_path, self.media_root);
+                resolve_path(&mut channel.banner_path, self.media_root);
+                self.batch.upsert_channel(channel);
+            }
+            Entry::Video(record) => self.media(record, false),
+            Entry::Short(record) => self.media(record, true),
+            Entry::Subtitles(mut subtitles) => {
+                for track in &mut subtitles.languages {
+                    resolve_path(&mut track.path, self.media_root);
+                }
+                self.batch.upsert_subtitles(subtitles);
+            }
+            Entry::Comments { videoid, comments } => {
+                self.batch.replace_comments(&videoid, comments);
+            }
+            Entry::Chapters { videoid, chapters } => {
+                self.batch.replace_chapters(&videoid, chapters);
+            }
+            Entry::Transcripts {
+                videoid,
+                transcripts,
+            } => self.batch.replace_transcripts(&videoid, transcripts),
+            Entry::StatsHistory { videoid, snapshots } => {
+                self.batch.replace_stats_history(&videoid, snapshots);
+            }
+            Entry::Segments {
+                videoid,
+                manual,
+                segments,
+            } => {
+                if manual {
+                    self.store.set_segments(&videoid, &segments).await?;
+                } else {
+                    self.store.import_segments(&videoid, &segments).await?;
+                }
+            }
+            Entry::Playlist { playlist, entries } => {
+                let exists = self
+                    .reader
+                    .get_playlist(&playlist.playlist_id)
+                    .await?
+                    .is_some();
+                if self.skip(exists) {
+                    return Ok(());
+                }
+                self.store.replace_playlist(&playlist, &entries).await?;
+            }
+            Entry::Blocked(entry) => {
+                let exists = !self.blocked_ids.insert(entry.videoid.clone());
+                if self.skip(exists) {
+                    return Ok(());
+                }
+                self.store.restore_blocked_media(&entry).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn media(&mut self, mut record: VideoRecord, short: bool) {
+        let exists = !self.media_ids.insert(record.videoid.clone());
+        if self.skip(exists) {
+            self.skipped_media.insert(record.videoid);
+            return;
+        }
+        for source in &mut record.sources {
+            resolve_path(&mut source.path, self.media_root);
+        }
+        for stream in &mut record.adaptive_streams {
+            resolve_path(&mut stream.path, self.media_root);
+        }
+        if short {
+            self.batch.upsert_short(record);
+        } else {
+            self.batch.upsert_video(record);
+        }
+    }
+
+    /// Applies the conflict policy to a top-level row and counts it.
+    fn skip(&mut self, exists: bool) -> bool {
+        if !exists {
+            self.stats.added += 1;
+            return false;
+        }
+        match self.policy {
+            ConflictPolicy::Skip => {
+                self.stats.skipped += 1;
+                true
+            }
+            ConflictPolicy::Replace => {
+                self.stats.replaced += 1;
+                false
+            }
+        }
+    }
+
+    /// Commits queued writes. Failed writes are rolled back individually
+    /// and reported; the import carries on with the rest.
+    async fn commit(&mut self) {
+        if let Err(err) = self.batch.commit().await {
+            eprintln!("Warning: {err:#}");
+            self.stats.failed_batches += 1;
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    ensure_not_root("library_transfer")?;
+
+    let TransferArgs {
+        command,
+        media_root,
+    } = TransferArgs::parse()?;
+
+    let metadata_path = media_root.join(METADATA_DB_FILE);
+    let store = MetadataStore::open(&metadata_path)
+        .await
+        .context("initializing metadata database")?;
+    let reader = MetadataReader::new(&metadata_path)
+        .await
+        .context("opening metadata database")?;
+
+    match command {
+        Command::Export { output } => {
+            let out: Box<dyn Write> = match &output {
+                Some(path) => Box::new(
+                    File::create(path).with_context(|| format!("creating {}", path.display()))?,
+                ),
+                None => Box::new(io::stdout().lock()),
+            };
+            let stats = export_library(
+                &reader,
+                store.schema_version().await?,
+                &media_root,
+                BufWriter::new(out),
+            )
+            .await?;
+            // Progress goes to stderr so exports can be piped.
+            eprintln!(
+                "Exported {} media items ({} lines).",
+                stats.media, stats.lines
+            );
+            if stats.external_paths > 0 {
+                eprintln!(
+                    "Warning: {} paths lie outside {} and were kept absolute.",
+                    stats.external_paths,
+                    media_root.display()
+                );
+            }
+        }
+        Command::Import { input, policy } => {
+            let source: Box<dyn BufRead> = if input.as_os_str() == "-" {
+                Box::new(io::stdin().lock())
+            } else {
+                let file =
+                    File::open(&input).with_context(|| format!("opening {}", input.display()))?;
+                Box::new(BufReader::new(file))
+            };
+            let stats = import_library(&store, &reader, &media_root, source, policy).await?;
+            println!(
+                "Imported {} new and {} replaced entries; {} existing entries were kept.",
+                stats.added, stats.replaced, stats.skipped
+            );
+            if stats.failed_batches > 0 {
+                bail!("some writes failed; see the warnings above");
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use newtube_tools::metadata::{SegmentCategory, SubtitleTrack, VideoSource};
+    use newtube_tools::transcript::TranscriptCue;
+    use tempfile::{TempDir, tempdir};
+
+    struct Library {
+        _temp: TempDir,
+        root: PathBuf,
+        store: MetadataStore,
+        reader: MetadataReader,
+    }
+
+    async fn library() -> Result<Library> {
+        let temp = tempdir()?;
+        let root = temp.path().join("media");
+        let path = root.join(METADATA_DB_FILE);
+        let store = MetadataStore::open(&path).await?;
+        let reader = MetadataReader::new(&path).await?;
+        Ok(Library {
+            _temp: temp,
+            root,
+            store,
+            reader,
+        })
+    }
+
+    fn video(id: &str, title: &str, root: &Path) -> VideoRecord {
+        VideoRecord {
+            videoid: id.into(),
+            title: title.into(),
+            description: String::new(),
+            likes: None,
+            dislikes: None,
+            views: Some(100),
+            upload_date: None,
+            author: None,
+            subscriber_count: None,
+            duration: Some(60),
+            duration_text: None,
+            channel_url: None,
+            channel_id: Some("chan".into()),
+            thumbnail_url: None,
+            tags: Vec::new(),
+            thumbnails: Vec::new(),
+            extras: serde_json::Value::Null,
+            sources: vec![VideoSource {
+                format_id: "22".into(),
+                quality_label: None,
+                width: None,
+                height: None,
+                fps: None,
+                mime_type: Some("video/mp4".into()),
+                ext: Some("mp4".into()),
+                file_size: None,
+                url: format!("/api/videos/{id}/streams/22"),
+                path: Some(
+                    root.join(format!("videos/{id}/{id}_22.mp4"))
+                        .to_string_lossy()
+                        .into_owned(),
+                ),
+                sha256: None,
+            }],
+            availability: None,
+            availability_checked_at: None,
+            adaptive_streams: Vec::new(),
+        }
+    }
+
+    fn comment(id: &str, videoid: &str, text: &str) -> CommentRecord {
+        CommentRecord {
+            id: id.into(),
+            videoid: videoid.into(),
+            author: "someone".into(),
+            text: text.into(),
+            likes: None,
+            time_posted: None,
+            parent_comment_id: None,
+            status_likedbycreator: false,
+            reply_count: None,
+            deleted_upstream_at: None,
+        }
+    }
+
+    async fn populate(source: &Library) -> Result<()> {
+        let root = &source.root;
+        let mut batch = source.store.batch();
+        batch.upsert_channel(ChannelRecord {
+            channel_id: "chan".into(),
+            name: "Channel".into(),
+            handle: None,
+            url: None,
+            subscriber_count: None,
+            avatar_path: Some(
+                root.join("channels/chan/avatar.jpg")
+                    .to_string_lossy()
+                    .into(),
+            ),
+            banner_path: Some("/elsewhere/banner.jpg".into()),
+            last_refreshed_at: None,
+        });
+        batch.upsert_video(video("alpha", "Alpha", root));
+        batch.upsert_short(video("shorty", "Shorty", root));
+        batch.upsert_subtitles(SubtitleCollection {
+            videoid: "alpha".into(),
+            languages: vec![SubtitleTrack {
+                code: "en".into(),
+                name: "English".into(),
+                url: "/api/videos/alpha/subtitles/en".into(),
+                path: Some(
+                    root.join("subtitles/alpha/alpha.en.vtt")
+                        .to_string_lossy()
+                        .into(),
+                ),
+            }],
+        });
+        batch.replace_comments("alpha", vec![comment("c1", "alpha", "exported")]);
+        batch.replace_chapters(
+            "alpha",
+            vec![Chapter {
+                start: 0.0,
+                end: None,
+                title: "Intro".into(),
+            }],
+        );
+        batch.replace_transcripts(
+            "alpha",
+            vec![Transcript {
+                language: "en".into(),
+                cues: vec![TranscriptCue {
+                    start: 1.0,
+                    end: 2.0,
+                    text: "hello there".into(),
+                }],
+            }],
+        );
+        batch.replace_stats_history(
+            "alpha",
+            vec![VideoStatsSnapshot {
+                recorded_at: "2024-01-01T00:00:00.000Z".into(),
+                views: Some(50),
+                likes: None,
+                dislikes: None,
+                comment_count: None,
+                subscriber_count: None,
+            }],
+        );
+        batch.commit().await?;
+        source
+            .store
+            .set_segments(
+                "alpha",
+                &[SkipSegment {
+                    category: SegmentCategory::Sponsor,
+                    start: 5.0,
+                    end: 9.0,
+                }],
+            )
+            .await?;
+        source
+            .store
+            .replace_playlist(
+                &PlaylistRecord {
+                    playlist_id: "PL1".into(),
+                    title: "Mix".into(),
+                    description: String::new(),
+                    owner: None,
+                    owner_channel_id: None,
+                    url: None,
+                    tracked: false,
+                    last_refreshed_at: None,
+                    entry_count: 0,
+                },
+                &[PlaylistEntry {
+                    position: 0,
+                    videoid: "alpha".into(),
+                    kind: "video".into(),
+                }],
+            )
+            .await?;
+        source
+            .store
+            .restore_blocked_media(&BlockedMedia {
+                videoid: "gone".into(),
+                blocked_at: "2023-01-01T00:00:00.000Z".into(),
+                reason: None,
+            })
+            .await?;
+        Ok(())
+    }
+
+    async fn export(source: &Library) -> Result<String> {
+        let mut out = Vec::new();
+        let stats = export_library(
+            &source.reader,
+            source.store.schema_version().await?,
+            &source.root,
+            &mut out,
+        )
+        .await?;
+        assert_eq!(stats.media, 2);
+        assert_eq!(stats.external_paths, 1);
+        Ok(String::from_utf8(out)?)
+    }
+
+    /// Paths inside the library travel as relative paths and land under the
+    /// importing instance's MEDIA_ROOT, together with every attached row.
+    #[tokio::test]
+    async fn export_then_import_relocates_library_paths() -> Result<()> {
+        let source = library().await?;
+        populate(&source).await?;
+        let dump = export(&source).await?;
+        let source_root = source.root.to_string_lossy().into_owned();
+        assert!(!dump.contains(&source_root));
+        assert!(dump.starts_with(r#"{"type":"header","format":"newtube-library","version":1"#));
+        assert!(dump.contains(r#""path":"videos/alpha/alpha_22.mp4""#));
+
+        let target = library().await?;
+        let stats = import_library(
+            &target.store,
+            &target.reader,
+            &target.root,
+            dump.as_bytes(),
+            ConflictPolicy::Skip,
+        )
+        .await?;
+        assert_eq!(
+            stats,
+            ImportStats {
+                added: 5,
+                replaced: 0,
+                skipped: 0,
+                failed_batches: 0,
+            }
+        );
+
+        let alpha = target.reader.get_video("alpha").await?.unwrap();
+        assert_eq!(
+            alpha.sources[0].path.as_deref().map(PathBuf::from),
+            Some(target.root.join("videos/alpha/alpha_22.mp4"))
+        );
+        assert!(target.reader.get_short("shorty").await?.is_some());
+        let channel = target.reader.get_channel("chan").await?.unwrap();
+        assert_eq!(
+            channel.avatar_path.map(PathBuf::from),
+            Some(target.root.join("channels/chan/avatar.jpg"))
+        );
+        assert_eq!(
+            channel.banner_path.as_deref(),
+            Some("/elsewhere/banner.jpg")
+        );
+        let subtitles = target.reader.get_subtitles("alpha").await?.unwrap();
+        assert_eq!(
+            subtitles.languages[0].path.as_deref().map(PathBuf::from),
+            Some(target.root.join("subtitles/alpha/alpha.en.vtt"))
+        );
+        assert_eq!(target.reader.get_comments("alpha", true).await?.len(), 1);
+        assert_eq!(target.reader.get_chapters("alpha").await?.len(), 1);
+        assert_eq!(
+            target.reader.get_transcripts("alpha", None).await?,
+            source.reader.get_transcripts("alpha", None).await?
+        );
+        assert_eq!(
+            target.reader.get_video_stats_history("alpha").await?,
+            source.reader.get_video_stats_history("alpha").await?
+        );
+        assert!(target.reader.has_manual_segments("alpha").await?);
+        assert_eq!(target.reader.get_playlist_entries("PL1").await?.len(), 1);
+        assert_eq!(
+            target.reader.list_blocked_media().await?,
+            source.reader.list_blocked_media().await?
+        );
+        Ok(())
+    }
+
+    /// `skip` keeps local rows and everything attached to them; `replace`
+    /// overwrites them with the imported copy.
+    #[tokio::test]
+    async fn import_applies_conflict_policy() -> Result<()> {
+        let source = library().await?;
+        populate(&source).await?;
+        let dump = export(&source).await?;
+
+        let target = library().await?;
+        target
+            .store
+            .upsert_video(&video("alpha", "Local", &target.root))
+            .await?;
+        target
+            .store
+            .replace_comments("alpha", &[comment("local", "alpha", "kept")])
+            .await?;
+
+        let stats = import_library(
+            &target.store,
+            &target.reader,
+            &target.root,
+            dump.as_bytes(),
+            ConflictPolicy::Skip,
+        )
+        .await?;
+        assert_eq!((stats.added, stats.skipped), (4, 1));
+        assert_eq!(
+            target.reader.get_video("alpha").await?.unwrap().title,
+            "Local"
+        );
+        assert_eq!(
+            target.reader.get_comments("alpha", true).await?[0].id,
+            "local"
+        );
+        assert!(target.reader.get_chapters("alpha").await?.is_empty());
+
+        let stats = import_library(
+            &target.store,
+            &target.reader,
+            &target.root,
+            dump.as_bytes(),
+            ConflictPolicy::Replace,
+        )
+        .await?;
+        assert_eq!((stats.added, stats.replaced), (0, 5));
+        assert_eq!(
+            target.reader.get_video("alpha").await?.unwrap().title,
+            "Alpha"
+        );
+        assert_eq!(target.reader.get_comments("alpha", true).await?[0].id, "c1");
+        assert_eq!(target.reader.get_chapters("alpha").await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn import_requires_a_supported_header() -> Result<()> {
+        let target = library().await?;
+        let run = |input: &'static str| {
+            import_library(
+                &target.store,
+                &target.reader,
+                &target.root,
+                input.as_bytes(),
+                ConflictPolicy::Skip,
+            )
+        };
+        assert!(run("").await.is_err());
+        assert!(
+            run(r#"{"type":"blocked","videoid":"x","blocked_at":"now"}"#)
+                .await
+                .is_err()
+        );
+        let newer = r#"{"type":"header","format":"newtube-library","version":99,"schema_version":1,"exported_at":"now"}"#;
+        let err = run(newer).await.unwrap_err();
+        assert!(err.to_string().contains("newer"));
+        assert!(target.reader.list_blocked_media().await?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn transfer_args_parse_both_commands() -> Result<()> {
+        let export =
+            TransferArgs::from_slice(&["export", "--media-root", "/srv", "-o", "lib.ndjson"])?;
+        assert_eq!(export.media_root, PathBuf::from("/srv"));
+        assert_eq!(
+            export.command,
+            Command::Export {
+                output: Some(PathBuf::from("lib.ndjson"))
+            }
+        );
+        let import = TransferArgs::from_slice(&[
+            "import",
+            "--media-root=/srv",
+            "--on-conflict=replace",
+            "-",
+        ])?;
+        assert_eq!(
+            import.command,
+            Command::Import {
+                input: PathBuf::from("-"),
+                policy: ConflictPolicy::Replace,
+            }
+        );
+        assert!(TransferArgs::from_slice(&["import", "--media-root", "/srv"]).is_err());
+        assert!(
+            TransferArgs::from_slice(&["export", "--media-root", "/srv", "--on-conflict", "skip"])
+                .is_err()
+        );
+        assert!(
+            TransferArgs::from_slice(&[
+                "import",
+                "--media-root",
+                "/srv",
+                "--on-conflict",
+                "newest",
+                "a"
+            ])
+            .is_err()
+        );
+        assert!(TransferArgs::from_slice(&["sync"]).is_err());
+        Ok(())
+    }
+}
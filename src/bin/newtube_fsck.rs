@@ -0,0 +1,1112 @@
+#![forbid(unsafe_code)]
+
+//! Checks that `metadata.db`, `download-archive.txt` and the files under
+//! MEDIA_ROOT agree with each other.
+//!
+//! Reported problems: stored source, subtitle, thumbnail and channel image
+//! paths that no longer exist, media without any playable file, per-id
+//! directories without a row, leftover `.part` downloads, and archive entries
+//! without media (or media missing from the archive). `--repair` re-derives
+//! sources from the files on disk, drops dangling subtitle and thumbnail
+//! references, deletes stale `.part` files and brings the archive in line
+//! with the database, leaving out media that still has no file so it is
+//! downloaded again. Orphan directories are only reported, never deleted.
+//! `--scrub` also re-hashes the downloaded files and reports those whose
+//! contents no longer match their recorded checksum.
+
+use anyhow::{Context, Result, bail};
+use newtube_tools::{
+    config::{RuntimeOverrides, resolve_runtime_paths},
+    library::{collect_sources_from_disk, load_archive},
+    metadata::{MetadataReader, MetadataStore, VideoRecord, VideoSource},
+    scrub::{ScrubOptions, ScrubReport, scrub_library},
+    security::ensure_not_root,
+};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use walkdir::WalkDir;
+
+const METADATA_DB_FILE: &str = "metadata.db";
+const ARCHIVE_FILE: &str = "download-archive.txt";
+const VIDEOS_SUBDIR: &str = "videos";
+const SHORTS_SUBDIR: &str = "shorts";
+const SUBTITLES_SUBDIR: &str = "subtitles";
+const THUMBNAILS_SUBDIR: &str = "thumbnails";
+const COMMENTS_SUBDIR: &str = "comments";
+/// `.part` files untouched for this long belong to abandoned downloads.
+const STALE_PART_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+const USAGE: &str = "Usage: newtube_fsck [--media-root <path>] [--repair] [--json] \
+     [--scrub [--scrub-limit <files>]]";
+
+#[derive(Debug, Clone)]
+struct FsckArgs {
+    media_root: PathBuf,
+    repair: bool,
+    json: bool,
+    /// Re-hash files; `Some(None)` hashes all of them.
+    scrub: Option<Option<usize>>,
+}
+
+impl FsckArgs {
+    fn parse() -> Result<Self> {
+        Self::from_iter(env::args().skip(1))
+    }
+
+    #[cfg(test)]
+    fn from_slice(values: &[&str]) -> Result<Self> {
+        Self::from_iter(values.iter().map(|value| value.to_string()))
+    }
+
+    fn from_iter<I>(iter: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut media_root_override: Option<PathBuf> = None;
+        let mut repair = false;
+        let mut json = false;
+        let mut scrub = false;
+        let mut scrub_limit: Option<usize> = None;
+        let mut args = iter.into_iter();
+
+        while let Some(arg) = args.next() {
+            if let Some(value) = arg.strip_prefix("--media-root=") {
+                media_root_override = Some(PathBuf::from(value));
+                continue;
+            }
+
+            match arg.as_str() {
+                "--media-root" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--media-root requires a value"))?;
+                    media_root_override = Some(PathBuf::from(value));
+                }
+                "--repair" => repair = true,
+                "--json" => json = true,
+                "--scrub" => scrub = true,
+                "--scrub-limit" => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| anyhow::anyhow!("--scrub-limit requires a value"))?;
+                    let limit = value
+                        .parse::<usize>()
+                        .ok()
+                        .filter(|limit| *limit > 0)
+                        .ok_or_else(|| anyhow::anyhow!("invalid --scrub-limit: {value}"))?;
+                    scrub_limit = Some(limit);
+                }
+                "-h" | "--help" => bail!(USAGE),
+                _ => bail!("unknown argument: {arg}\n{USAGE}"),
+            }
+        }
+
+        let media_root = match media_root_override {
+            Some(path) => path,
+            None => resolve_runtime_paths(RuntimeOverrides::default())?.media_root,
+        };
+
+        if scrub_limit.is_some() && !scrub {
+            bail!("--scrub-limit requires --scrub\n{USAGE}");
+        }
+
+        Ok(Self {
+            media_root,
+            repair,
+            json,
+            scrub: scrub.then_some(scrub_limit),
+        })
+    }
+}
+
+/// Locations under MEDIA_ROOT that the checks look at.
+struct Layout {
+    videos: PathBuf,
+    shorts: PathBuf,
+    subtitles: PathBuf,
+    thumbnails: PathBuf,
+    comments: PathBuf,
+    archive: PathBuf,
+}
+
+impl Layout {
+    fn new(media_root: &Path) -> Self {
+        Self {
+            videos: media_root.join(VIDEOS_SUBDIR),
+            shorts: media_root.join(SHORTS_SUBDIR),
+            subtitles: media_root.join(SUBTITLES_SUBDIR),
+            thumbnails: media_root.join(THUMBNAILS_SUBDIR),
+            comments: media_root.join(COMMENTS_SUBDIR),
+            archive: media_root.join(ARCHIVE_FILE),
+        }
+    }
+
+    fn media_dir(&self, short: bool) -> &Path {
+        if short { &self.shorts } else { &self.videos }
+    }
+
+    /// File a source is streamed from. Sources without a stored path use the
+    /// backend's `<id>/<id>_<format>.<ext>` fallback.
+    fn source_path(&self, short: bool, videoid: &str, source: &VideoSource) -> PathBuf {
+        match &source.path {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let format: String = source
+                    .format_id
+                    .trim()
+                    .chars()
+                    .map(|c| match c {
+                        '/' | ':' | ' ' => '_',
+                        _ => c,
+                    })
+                    .collect();
+                let ext = source.ext.as_deref().unwrap_or("mp4");
+                self.media_dir(short)
+                    .join(videoid)
+                    .join(format!("{videoid}_{format}.{ext}"))
+            }
+        }
+    }
+
+    /// Maps `/api/<slug>/<id>/thumbnails/<file>` to the file it serves.
+    /// Other URLs (remote thumbnails) are not checked.
+    fn thumbnail_path(&self, url: &str) -> Option<PathBuf> {
+        let mut parts = url.strip_prefix("/api/")?.split('/');
+        let (_slug, id, kind, file) = (parts.next()?, parts.next()?, parts.next()?, parts.next()?);
+        if kind != "thumbnails" || id.is_empty() || file.is_empty() || parts.next().is_some() {
+            return None;
+        }
+        Some(self.thumbnails.join(id).join(file))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+enum Issue {
+    MissingSource {
+        videoid: String,
+        format_id: String,
+        path: String,
+    },
+    /// None of the media's sources has a file on disk.
+    NoPlayableSource {
+        videoid: String,
+    },
+    MissingSubtitle {
+        videoid: String,
+        code: String,
+        path: String,
+    },
+    MissingThumbnail {
+        videoid: String,
+        url: String,
+    },
+    MissingChannelImage {
+        channel_id: String,
+        path: String,
+    },
+    /// Per-id directory without a matching media row.
+    OrphanDirectory {
+        path: String,
+    },
+    PartialFile {
+        path: String,
+        bytes: u64,
+        stale: bool,
+    },
+    ArchiveWithoutMedia {
+        videoid: String,
+    },
+    MediaNotInArchive {
+        videoid: String,
+    },
+    /// File contents differ from the checksum recorded at download time.
+    ChecksumMismatch {
+        videoid: String,
+        format_id: String,
+        path: String,
+    },
+    /// File exists but could not be read while scrubbing.
+    UnreadableSource {
+        videoid: String,
+        format_id: String,
+        path: String,
+    },
+}
+
+impl Issue {
+    /// Media row the issue is fixed through, if any.
+    fn media_id(&self) -> Option<&str> {
+        match self {
+            Self::MissingSource { videoid, .. }
+            | Self::NoPlayableSource { videoid }
+            | Self::MissingThumbnail { videoid, .. } => Some(videoid),
+            _ => None,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::MissingSource {
+                videoid,
+                format_id,
+                path,
+            } => format!("{videoid}: source {format_id} is missing ({path})"),
+            Self::NoPlayableSource { videoid } => format!("{videoid}: no playable source on disk"),
+            Self::MissingSubtitle {
+                videoid,
+                code,
+                path,
+            } => format!("{videoid}: subtitle {code} is missing ({path})"),
+            Self::MissingThumbnail { videoid, url } => {
+                format!("{videoid}: thumbnail is missing ({url})")
+            }
+            Self::MissingChannelImage { channel_id, path } => {
+                format!("channel {channel_id}: image is missing ({path})")
+            }
+            Self::OrphanDirectory { path } => format!("orphan directory {path}"),
+            Self::PartialFile { path, bytes, stale } => format!(
+                "{} partial download {path} ({bytes} bytes)",
+                if *stale { "stale" } else { "recent" }
+            ),
+            Self::ArchiveWithoutMedia { videoid } => {
+                format!("{videoid}: in the download archive but not in the library")
+            }
+            Self::MediaNotInArchive { videoid } => {
+                format!("{videoid}: in the library but not in the download archive")
+            }
+            Self::ChecksumMismatch {
+                videoid,
+                format_id,
+                path,
+            } => format!("{videoid}: source {format_id} does not match its checksum ({path})"),
+            Self::UnreadableSource {
+                videoid,
+                format_id,
+                path,
+            } => format!("{videoid}: source {format_id} could not be read ({path})"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Finding {
+    #[serde(flatten)]
+    issue: Issue,
+    repaired: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct FsckReport {
+    media_checked: usize,
+    findings: Vec<Finding>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scrub: Option<ScrubReport>,
+}
+
+impl FsckReport {
+    fn push(&mut self, issue: Issue) {
+        self.findings.push(Finding {
+            issue,
+            repaired: false,
+        });
+    }
+
+    fn unresolved(&self) -> usize {
+        self.findings
+            .iter()
+            .filter(|finding| !finding.repaired)
+            .count()
+    }
+
+    fn issues(&self) -> impl Iterator<Item = &Issue> {
+        self.findings.iter().map(|finding| &finding.issue)
+    }
+
+    /// Adds the files a scrub pass flagged. Files that are simply gone are
+    /// already reported as missing sources by the scan.
+    fn add_scrub(&mut self, scrub: ScrubReport) {
+        for finding in &scrub.mismatched {
+            self.push(Issue::ChecksumMismatch {
+                videoid: finding.videoid.clone(),
+                format_id: finding.format_id.clone(),
+                path: finding.path.clone(),
+            });
+        }
+        for finding in &scrub.missing {
+            if Path::new(&finding.path).exists() {
+                self.push(Issue::UnreadableSource {
+                    videoid: finding.videoid.clone(),
+                    format_id: finding.format_id.clone(),
+                    path: finding.path.clone(),
+                });
+            }
+        }
+        self.scrub = Some(scrub);
+    }
+
+    /// Marks every finding matching `predicate` as repaired.
+    fn mark_repaired(&mut self, mut predicate: impl FnMut(&Issue) -> bool) {
+        for finding in &mut self.findings {
+            if predicate(&finding.issue) {
+                finding.repaired = true;
+            }
+        }
+    }
+}
+
+/// Compares the database, the archive and the filesystem without changing
+/// anything. `now` decides which `.part` files count as stale.
+async fn scan_library(
+    reader: &MetadataReader,
+    layout: &Layout,
+    now: SystemTime,
+) -> Result<FsckReport> {
+    let mut report = FsckReport::default();
+    let archive = load_archive(&layout.archive)?;
+    let mut all_ids = HashSet::new();
+
+    for short in [false, true] {
+        let records = if short {
+            reader.list_shorts().await?
+        } else {
+            reader.list_videos().await?
+        };
+        let mut table_ids = HashSet::new();
+        for record in records {
+            report.media_checked += 1;
+            check_media(&record, short, layout, &mut report);
+            if !archive.contains(&record.videoid) {
+                report.push(Issue::MediaNotInArchive {
+                    videoid: record.videoid.clone(),
+                });
+            }
+            table_ids.insert(record.videoid);
+        }
+        find_orphan_dirs(layout.media_dir(short), &table_ids, &mut report)?;
+        all_ids.extend(table_ids);
+    }
+    for dir in [&layout.thumbnails, &layout.subtitles, &layout.comments] {
+        find_orphan_dirs(dir, &all_ids, &mut report)?;
+    }
+
+    for subtitles in reader.list_subtitles().await? {
+        for track in subtitles.languages {
+            if let Some(path) = track.path
+                && !Path::new(&path).is_file()
+            {
+                report.push(Issue::MissingSubtitle {
+                    videoid: subtitles.videoid.clone(),
+                    code: track.code,
+                    path,
+                });
+            }
+        }
+    }
+
+    for channel in reader.list_channels().await? {
+        for path in [channel.avatar_path, channel.banner_path]
+            .into_iter()
+            .flatten()
+        {
+            if !Path::new(&path).is_file() {
+                report.push(Issue::MissingChannelImage {
+                    channel_id: channel.channel_id.clone(),
+                    path,
+                });
+            }
+        }
+    }
+
+    for dir in [&layout.videos, &layout.shorts] {
+        find_partial_files(dir, now, &mut report);
+    }
+
+    let mut orphaned: Vec<&String> = archive.iter().filter(|id| !all_ids.contains(*id)).collect();
+    orphaned.sort();
+    for videoid in orphaned {
+        report.push(Issue::ArchiveWithoutMedia {
+            videoid: videoid.clone(),
+        });
+    }
+    Ok(report)
+}
+
+fn check_media(record: &VideoRecord, short: bool, layout: &Layout, report: &mut FsckReport) {
+    let mut playable = false;
+    for source in &record.sources {
+        let path = layout.source_path(short, &record.videoid, source);
+        if path.is_file() {
+            playable = true;
+        } else {
+            report.push(Issue::MissingSource {
+                videoid: record.videoid.clone(),
+                format_id: source.format_id.clone(),
+                path: path.to_string_lossy().into_owned(),
+            });
+        }
+    }
+    if !playable {
+        report.push(Issue::NoPlayableSource {
+            videoid: record.videoid.clone(),
+        });
+    }
+
+    let mut urls: BTreeSet<&str> = record.thumbnails.iter().map(String::as_str).collect();
+    urls.extend(record.thumbnail_url.as_deref());
+    for url in urls {
+        if layout
+            .thumbnail_path(url)
+            .is_some_and(|path| !path.is_file())
+        {
+            report.push(Issue::MissingThumbnail {
+                videoid: record.videoid.clone(),
+                url: url.to_string(),
+            });
+        }
+    }
+}
+
+/// Reports subdirectories of `dir` whose name is not one of `ids`.
+fn find_orphan_dirs(dir: &Path, ids: &HashSet<String>, report: &mut FsckReport) -> Result<()> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", dir.display())),
+    };
+    let mut orphans = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if !entry.path().is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if !ids.contains(&name) {
+            orphans.push(entry.path());
+        }
+    }
+    orphans.sort();
+    for path in orphans {
+        report.push(Issue::OrphanDirectory {
+            path: path.to_string_lossy().into_owned(),
+        });
+    }
+    Ok(())
+}
+
+fn find_partial_files(dir: &Path, now: SystemTime, report: &mut FsckReport) {
+    let mut partials: Vec<(PathBuf, u64, bool)> = WalkDir::new(dir)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(".part"))
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            let stale = meta
+                .modified()
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|age| age >= STALE_PART_AGE);
+            Some((entry.into_path(), meta.len(), stale))
+        })
+        .collect();
+    partials.sort();
+    for (path, bytes, stale) in partials {
+        report.push(Issue::PartialFile {
+            path: path.to_string_lossy().into_owned(),
+            bytes,
+            stale,
+        });
+    }
+}
+
+/// Fixes what can be fixed from the data on disk and marks those findings
+/// as repaired.
+async fn repair_library(
+    store: &MetadataStore,
+    reader: &MetadataReader,
+    layout: &Layout,
+    report: &mut FsckReport,
+) -> Result<()> {
+    let mut batch = store.batch();
+
+    let media_ids: BTreeSet<String> = report
+        .issues()
+        .filter_map(Issue::media_id)
+        .map(str::to_string)
+        .collect();
+    let mut playable = HashSet::new();
+    for videoid in &media_ids {
+        let (mut record, short) = match reader.get_video(videoid).await? {
+            Some(record) => (record, false),
+            None => match reader.get_short(videoid).await? {
+                Some(record) => (record, true),
+                None => continue,
+            },
+        };
+        repair_media(&mut record, short, layout)?;
+        if !record.sources.is_empty() {
+            playable.insert(videoid.clone());
+        }
+        if short {
+            batch.upsert_short(record);
+        } else {
+            batch.upsert_video(record);
+        }
+    }
+
+    let subtitle_ids: BTreeSet<String> = report
+        .issues()
+        .filter_map(|issue| match issue {
+            Issue::MissingSubtitle { videoid, .. } => Some(videoid.clone()),
+            _ => None,
+        })
+        .collect();
+    for videoid in &subtitle_ids {
+        if let Some(mut subtitles) = reader.get_subtitles(videoid).await? {
+            subtitles.languages.retain(|track| {
+                track
+                    .path
+                    .as_deref()
+                    .is_none_or(|path| Path::new(path).is_file())
+            });
+            batch.upsert_subtitles(subtitles);
+        }
+    }
+    batch.commit().await?;
+    report.mark_repaired(|issue| match issue {
+        Issue::MissingSource { videoid, .. } | Issue::MissingThumbnail { videoid, .. } => {
+            media_ids.contains(videoid)
+        }
+        Issue::MissingSubtitle { videoid, .. } => subtitle_ids.contains(videoid),
+        Issue::NoPlayableSource { videoid } => playable.contains(videoid),
+        _ => false,
+    });
+
+    let mut removed = HashSet::new();
+    for issue in report.issues() {
+        if let Issue::PartialFile {
+            path, stale: true, ..
+        } = issue
+        {
+            match fs::remove_file(path) {
+                Ok(()) => {
+                    removed.insert(path.clone());
+                }
+                Err(err) => eprintln!("Warning: could not remove {path}: {err}"),
+            }
+        }
+    }
+    report.mark_repaired(
+        |issue| matches!(issue, Issue::PartialFile { path, .. } if removed.contains(path)),
+    );
+
+    let drop_ids: HashSet<String> = report
+        .issues()
+        .filter_map(|issue| match issue {
+            Issue::ArchiveWithoutMedia { videoid } => Some(videoid.clone()),
+            _ => None,
+        })
+        .collect();
+    // An archived id is never downloaded again, so only media that ends up
+    // with a file on disk may be added.
+    let unplayable: HashSet<&str> = report
+        .issues()
+        .filter_map(|issue| match issue {
+            Issue::NoPlayableSource { videoid } if !playable.contains(videoid) => {
+                Some(videoid.as_str())
+            }
+            _ => None,
+        })
+        .collect();
+    let add_ids: Vec<String> = report
+        .issues()
+        .filter_map(|issue| match issue {
+            Issue::MediaNotInArchive { videoid } if !unplayable.contains(videoid.as_str()) => {
+                Some(videoid.clone())
+            }
+            _ => None,
+        })
+        .collect();
+    if !drop_ids.is_empty() || !add_ids.is_empty() {
+        rewrite_archive(&layout.archive, &drop_ids, &add_ids)?;
+        report.mark_repaired(|issue| match issue {
+            Issue::ArchiveWithoutMedia { .. } => true,
+            Issue::MediaNotInArchive { videoid } => add_ids.contains(videoid),
+            _ => false,
+        });
+    }
+    Ok(())
+}
+
+/// Drops sources, adaptive streams and thumbnails whose files are gone and
+/// adds every playable file found in the media directory that is not
+/// referenced yet.
+fn repair_media(record: &mut VideoRecord, short: bool, layout: &Layout) -> Result<()> {
+    let videoid = record.videoid.clone();
+    record
+        .sources
+        .retain(|source| layout.source_path(short, &videoid, source).is_file());
+    record.adaptive_streams.retain(|stream| {
+        stream
+            .path
+            .as_deref()
+            .is_some_and(|path| Path::new(path).is_file())
+    });
+
+    let base_dir = layout.media_dir(short).join(&videoid);
+    if base_dir.is_dir() {
+        // Video-only files look like playable ones on disk; the adaptive
+        // streams say which they are.
+        let known: HashSet<PathBuf> = record
+            .sources
+            .iter()
+            .map(|source| layout.source_path(short, &videoid, source))
+            .chain(
+                record
+                    .adaptive_streams
+                    .iter()
+                    .filter_map(|stream| stream.path.as_deref().map(PathBuf::from)),
+            )
+            .collect();
+        let slug = if short { SHORTS_SUBDIR } else { VIDEOS_SUBDIR };
+        for source in collect_sources_from_disk(&videoid, &base_dir, slug)? {
+            if !known.contains(&layout.source_path(short, &videoid, &source)) {
+                record.sources.push(source);
+            }
+        }
+    }
+
+    let exists = |url: &str| layout.thumbnail_path(url).is_none_or(|path| path.is_file());
+    record.thumbnails.retain(|url| exists(url));
+    if record
+        .thumbnail_url
+        .as_deref()
+        .is_some_and(|url| !exists(url))
+    {
+        record.thumbnail_url = record.thumbnails.first().cloned();
+    }
+    Ok(())
+}
+
+/// Rewrites yt-dlp's archive without `drop` and with a `youtube <id>` line
+/// for every id in `add`.
+fn rewrite_archive(archive: &Path, drop: &HashSet<String>, add: &[String]) -> Result<()> {
+    let contents = match fs::read_to_string(archive) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err).with_context(|| format!("reading {}", archive.display())),
+    };
+    let mut kept = String::with_capacity(contents.len());
+    for line in contents.lines() {
+        if line
+            .split_whitespace()
+            .last()
+            .is_some_and(|id| drop.contains(id))
+        {
+            continue;
+        }
+        kept.push_str(line);
+        kept.push('\n');
+    }
+    for id in add {
+        kept.push_str(&format!("youtube {id}\n"));
+    }
+    let tmp_path = archive.with_extension("tmp");
+    fs::write(&tmp_path, kept).with_context(|| format!("writing {}", tmp_path.display()))?;
+    fs::rename(&tmp_path, archive).with_context(|| format!("replacing {}", archive.display()))?;
+    Ok(())
+}
+
+fn print_report(report: &FsckReport) {
+    for finding in &report.findings {
+        let marker = if finding.repaired { "[repaired] " } else { "" };
+        println!("{marker}{}", finding.issue.describe());
+    }
+    println!(
+        "Checked {} media items: {} problems, {} unresolved.",
+        report.media_checked,
+        report.findings.len(),
+        report.unresolved()
+    );
+    if let Some(scrub) = &report.scrub {
+        println!(
+            "Scrubbed {} files ({} bytes): {} verified, {} new checksums, {} mismatched, {} skipped.",
+            scrub.checked,
+            scrub.bytes,
+            scrub.verified,
+            scrub.baselined,
+            scrub.mismatched.len(),
+            scrub.skipped
+        );
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    ensure_not_root("newtube_fsck")?;
+
+    let FsckArgs {
+        media_root,
+        repair,
+        json,
+        scrub,
+    } = FsckArgs::parse()?;
+
+    let metadata_path = media_root.join(METADATA_DB_FILE);
+    let store = MetadataStore::open(&metadata_path)
+        .await
+        .context("initializing metadata database")?;
+    let reader = MetadataReader::new(&metadata_path)
+        .await
+        .context("opening metadata database")?;
+    let layout = Layout::new(&media_root);
+
+    let mut report = scan_library(&reader, &layout, SystemTime::now()).await?;
+    if repair {
+        repair_library(&store, &reader, &layout, &mut report).await?;
+    }
+    if let Some(limit) = scrub {
+        let options = ScrubOptions {
+            verified_before: None,
+            limit,
+        };
+        report.add_scrub(scrub_library(&store, &reader, &options).await?);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+    let unresolved = report.unresolved();
+    if unresolved > 0 {
+        bail!("{unresolved} problems remain");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use newtube_tools::metadata::{ChannelRecord, SubtitleCollection, SubtitleTrack};
+    use tempfile::{TempDir, tempdir};
+
+    fn source(format_id: &str, path: &Path) -> VideoSource {
+        VideoSource {
+            format_id: format_id.into(),
+            quality_label: Some("720p".into()),
+            width: None,
+            height: Some(720),
+            fps: None,
+            mime_type: Some("video/mp4".into()),
+            ext: Some("mp4".into()),
+            file_size: None,
+            url: format!("/api/videos/x/streams/{format_id}"),
+            path: Some(path.to_string_lossy().into_owned()),
+            sha256: None,
+        }
+    }
+
+    fn video(id: &str, sources: Vec<VideoSource>, thumbnails: Vec<String>) -> VideoRecord {
+        VideoRecord {
+            videoid: id.into(),
+            title: id.into(),
+            description: String::new(),
+            likes: None,
+            dislikes: None,
+            views: None,
+            upload_date: None,
+            author: None,
+            subscriber_count: None,
+            duration: None,
+            duration_text: None,
+            channel_url: None,
+            channel_id: None,
+            thumbnail_url: thumbnails.last().cloned(),
+            tags: Vec::new(),
+            thumbnails,
+            extras: serde_json::Value::Null,
+            sources,
+            availability: None,
+            availability_checked_at: None,
+            adaptive_streams: Vec::new(),
+        }
+    }
+
+    fn touch(path: &Path) -> Result<()> {
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, "data")?;
+        Ok(())
+    }
+
+    /// Library with one of every problem: `alpha` lost a source, a subtitle
+    /// and a thumbnail but has an unreferenced file on disk, `beta` has no
+    /// files at all, plus orphans, a `.part` file and a skewed archive.
+    async fn broken_library() -> Result<(TempDir, Layout, MetadataStore, MetadataReader)> {
+        let temp = tempdir()?;
+        let root = temp.path();
+        let layout = Layout::new(root);
+        let db = root.join(METADATA_DB_FILE);
+        let store = MetadataStore::open(&db).await?;
+        let reader = MetadataReader::new(&db).await?;
+
+        let alpha_dir = layout.videos.join("alpha");
+        touch(&alpha_dir.join("alpha_22.mp4"))?;
+        touch(&alpha_dir.join("alpha_137.webm"))?;
+        touch(&alpha_dir.join("alpha_18.mp4.part"))?;
+        touch(&layout.thumbnails.join("alpha/cover.jpg"))?;
+        touch(&layout.subtitles.join("alpha/alpha.en.vtt"))?;
+        touch(&layout.videos.join("ghost/ghost_22.mp4"))?;
+        fs::create_dir_all(layout.thumbnails.join("ghost"))?;
+        fs::write(&layout.archive, "youtube alpha\nyoutube removed\n")?;
+
+        let mut batch = store.batch();
+        batch.upsert_video(video(
+            "alpha",
+            vec![
+                source("22", &alpha_dir.join("alpha_22.mp4")),
+                source("18", &alpha_dir.join("alpha_18.mp4")),
+            ],
+            vec![
+                "/api/videos/alpha/thumbnails/cover.jpg".into(),
+                "/api/videos/alpha/thumbnails/gone.jpg".into(),
+            ],
+        ));
+        batch.upsert_video(video(
+            "beta",
+            vec![source("22", &layout.videos.join("beta/beta_22.mp4"))],
+            Vec::new(),
+        ));
+        batch.upsert_subtitles(SubtitleCollection {
+            videoid: "alpha".into(),
+            languages: ["en", "fr"]
+                .into_iter()
+                .map(|code| SubtitleTrack {
+                    code: code.into(),
+                    name: code.into(),
+                    url: format!("/api/videos/alpha/subtitles/{code}"),
+                    path: Some(
+                        layout
+                            .subtitles
+                            .join(format!("alpha/alpha.{code}.vtt"))
+                            .to_string_lossy()
+                            .into_owned(),
+                    ),
+                })
+                .collect(),
+        });
+        batch.upsert_channel(ChannelRecord {
+            channel_id: "chan".into(),
+            name: "Channel".into(),
+            handle: None,
+            url: None,
+            subscriber_count: None,
+            avatar_path: Some(
+                root.join("channels/chan/avatar.jpg")
+                    .to_string_lossy()
+                    .into(),
+            ),
+            banner_path: None,
+            last_refreshed_at: None,
+        });
+        batch.commit().await?;
+        Ok((temp, layout, store, reader))
+    }
+
+    fn kinds(report: &FsckReport) -> Vec<String> {
+        report
+            .issues()
+            .map(|issue| {
+                let value = serde_json::to_value(issue).unwrap();
+                let kind = value["issue"].as_str().unwrap().to_string();
+                match value.get("videoid").and_then(|id| id.as_str()) {
+                    Some(id) => format!("{kind}:{id}"),
+                    None => kind,
+                }
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn scan_reports_every_inconsistency() -> Result<()> {
+        let (_temp, layout, _store, reader) = broken_library().await?;
+        let later = SystemTime::now() + 2 * STALE_PART_AGE;
+        let report = scan_library(&reader, &layout, later).await?;
+        assert_eq!(report.media_checked, 2);
+        let mut found = kinds(&report);
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                "archive_without_media:removed",
+                "media_not_in_archive:beta",
+                "missing_channel_image",
+                "missing_source:alpha",
+                "missing_source:beta",
+                "missing_subtitle:alpha",
+                "missing_thumbnail:alpha",
+                "no_playable_source:beta",
+                "orphan_directory",
+                "orphan_directory",
+                "partial_file",
+            ]
+        );
+        assert!(report.issues().any(|issue| matches!(
+            issue,
+            Issue::PartialFile {
+                stale: true,
+                bytes: 4,
+                ..
+            }
+        )));
+
+        let recent = scan_library(&reader, &layout, SystemTime::now()).await?;
+        assert!(
+            recent
+                .issues()
+                .any(|issue| matches!(issue, Issue::PartialFile { stale: false, .. }))
+        );
+        let json = serde_json::to_value(&report)?;
+        assert_eq!(json["findings"][0]["repaired"], false);
+        Ok(())
+    }
+
+    /// Repair re-derives sources from disk, drops dangling references, fixes
+    /// the archive and removes stale `.part` files; what needs a human
+    /// (orphans, media with no files at all) stays reported.
+    #[tokio::test]
+    async fn repair_fixes_what_disk_can_explain() -> Result<()> {
+        let (_temp, layout, store, reader) = broken_library().await?;
+        let later = SystemTime::now() + 2 * STALE_PART_AGE;
+        let mut report = scan_library(&reader, &layout, later).await?;
+        repair_library(&store, &reader, &layout, &mut report).await?;
+
+        let alpha = reader.get_video("alpha").await?.unwrap();
+        let formats: Vec<&str> = alpha
+            .sources
+            .iter()
+            .map(|source| source.format_id.as_str())
+            .collect();
+        assert_eq!(formats, ["22", "137"]);
+        assert_eq!(alpha.sources[0].quality_label.as_deref(), Some("720p"));
+        assert_eq!(alpha.thumbnails, ["/api/videos/alpha/thumbnails/cover.jpg"]);
+        assert_eq!(
+            alpha.thumbnail_url.as_deref(),
+            Some(alpha.thumbnails[0].as_str())
+        );
+        let subtitles = reader.get_subtitles("alpha").await?.unwrap();
+        assert_eq!(subtitles.languages.len(), 1);
+        assert!(!layout.videos.join("alpha/alpha_18.mp4.part").exists());
+        assert_eq!(
+            load_archive(&layout.archive)?,
+            HashSet::from(["alpha".to_string()])
+        );
+
+        let mut unresolved: Vec<String> = report
+            .findings
+            .iter()
+            .filter(|finding| !finding.repaired)
+            .map(|finding| serde_json::to_value(&finding.issue).unwrap()["issue"].to_string())
+            .collect();
+        unresolved.sort();
+        assert_eq!(
+            unresolved,
+            [
+                "\"media_not_in_archive\"",
+                "\"missing_channel_image\"",
+                "\"no_playable_source\"",
+                "\"orphan_directory\"",
+                "\"orphan_directory\"",
+            ]
+        );
+
+        let mut rescan = kinds(&scan_library(&reader, &layout, later).await?);
+        rescan.sort();
+        assert_eq!(
+            rescan,
+            [
+                "media_not_in_archive:beta",
+                "missing_channel_image",
+                "no_playable_source:beta",
+                "orphan_directory",
+                "orphan_directory",
+            ]
+        );
+        Ok(())
+    }
+
+    /// Media missing from the archive is only added once repair found a
+    /// file for it; otherwise archiving it would stop any future download.
+    #[tokio::test]
+    async fn repair_archives_only_playable_media() -> Result<()> {
+        let (_temp, layout, store, reader) = broken_library().await?;
+        touch(&layout.videos.join("gamma/gamma_22.mp4"))?;
+        let mut batch = store.batch();
+        batch.upsert_video(video("gamma", Vec::new(), Vec::new()));
+        batch.commit().await?;
+
+        let mut report = scan_library(&reader, &layout, SystemTime::now()).await?;
+        repair_library(&store, &reader, &layout, &mut report).await?;
+        assert_eq!(
+            load_archive(&layout.archive)?,
+            HashSet::from(["alpha".to_string(), "gamma".to_string()])
+        );
+        let mut unarchived: Vec<(&str, bool)> = report
+            .findings
+            .iter()
+            .filter_map(|finding| match &finding.issue {
+                Issue::MediaNotInArchive { videoid } => Some((videoid.as_str(), finding.repaired)),
+                _ => None,
+            })
+            .collect();
+        unarchived.sort();
+        assert_eq!(unarchived, [("beta", false), ("gamma", true)]);
+        Ok(())
+    }
+
+    /// `--scrub` reports files changed since their checksum was recorded;
+    /// files that are simply gone stay reported as missing sources only.
+    #[tokio::test]
+    async fn scrub_reports_changed_files() -> Result<()> {
+        let (_temp, layout, store, reader) = broken_library().await?;
+        let baseline = scrub_library(&store, &reader, &ScrubOptions::default()).await?;
+        assert_eq!(baseline.baselined, 1);
+
+        fs::write(layout.videos.join("alpha/alpha_22.mp4"), "changed")?;
+        let mut report = scan_library(&reader, &layout, SystemTime::now()).await?;
+        report.add_scrub(scrub_library(&store, &reader, &ScrubOptions::default()).await?);
+        let found = kinds(&report);
+        assert!(found.contains(&"checksum_mismatch:alpha".to_string()));
+        assert!(
+            !found
+                .iter()
+                .any(|kind| kind.starts_with("unreadable_source"))
+        );
+        let json = serde_json::to_value(&report)?;
+        assert_eq!(json["scrub"]["mismatched"][0]["videoid"], "alpha");
+        Ok(())
+    }
+
+    #[test]
+    fn fsck_args_parse_flags() -> Result<()> {
+        let args = FsckArgs::from_slice(&["--media-root=/srv", "--repair", "--json"])?;
+        assert_eq!(args.media_root, PathBuf::from("/srv"));
+        assert!(args.repair && args.json);
+        assert_eq!(args.scrub, None);
+        assert!(FsckArgs::from_slice(&["--media-root", "/srv", "--fix"]).is_err());
+
+        let args = FsckArgs::from_slice(&["--media-root=/srv", "--scrub"])?;
+        assert_eq!(args.scrub, Some(None));
+        let args = FsckArgs::from_slice(&["--media-root=/srv", "--scrub", "--scrub-limit", "50"])?;
+        assert_eq!(args.scrub, Some(Some(50)));
+        assert!(FsckArgs::from_slice(&["--media-root=/srv", "--scrub-limit", "50"]).is_err());
+        assert!(
+            FsckArgs::from_slice(&["--media-root=/srv", "--scrub", "--scrub-limit", "0"]).is_err()
+        );
+        Ok(())
+    }
+}
@@ -1,16 +1,17 @@
 #![forbid(unsafe_code)]
 
 //! Helper binary that re-runs the downloader for every channel already present
-//! on disk. Acts like a nightly cron job.
+//! on disk and every tracked playlist in the metadata DB. Acts like a nightly
+//! cron job.
 
 use anyhow::{Context, Result, bail};
 use newtube_tools::{
     config::{RuntimeOverrides, resolve_runtime_paths},
-    metadata::MetadataStore,
+    metadata::{MetadataReader, MetadataStore},
     security::ensure_not_root,
 };
 use serde::Deserialize;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::env;
 use std::fs::File;
 use std::io::BufReader;
@@ -147,8 +148,8 @@ struct MinimalInfo {
     uploader: Option<OneOrMany<CreatorInfo>>,
 }
 
-/// Scans on-disk metadata, identifies unique channels, and launches
-/// `download_channel` for each.
+/// Scans on-disk metadata, identifies unique channels and tracked playlists,
+/// and launches `download_channel` for each.
 #[tokio::main]
 async fn main() -> Result<()> {
     ensure_not_root("routine_update")?;
@@ -170,13 +171,20 @@ async fn main() -> Result<()> {
     let videos_dir = base_dir.join(VIDEOS_SUBDIR);
     let shorts_dir = base_dir.join(SHORTS_SUBDIR);
 
+    let reader = MetadataReader::new(&metadata_path)
+        .await
+        .context("opening metadata database")?;
+    let playlist_members = collect_playlist_members(&reader).await?;
+
     let mut channels = BTreeMap::new();
-    collect_channels(&videos_dir, &mut channels)?;
-    collect_channels(&shorts_dir, &mut channels)?;
+    collect_channels(&videos_dir, &playlist_members, &mut channels)?;
+    collect_channels(&shorts_dir, &playlist_members, &mut channels)?;
 
-    if channels.is_empty() {
+    let playlists = collect_tracked_playlists(&reader).await?;
+
+    if channels.is_empty() && playlists.is_empty() {
         println!(
-            "No previously downloaded channels found in {}.",
+            "No previously downloaded channels or tracked playlists found in {}.",
             base_dir.display()
         );
         return Ok(());
@@ -184,9 +192,14 @@ async fn main() -> Result<()> {
 
     let downloader = find_download_channel_executable()?;
 
-    let scheduled: Vec<String> = channels.values().cloned().collect();
-    println!("Found {} channel(s) to update.", scheduled.len());
-    println!("Channels queued for refresh:");
+    let mut scheduled: Vec<String> = channels.values().cloned().collect();
+    println!(
+        "Found {} channel(s) and {} tracked playlist(s) to update.",
+        channels.len(),
+        playlists.len()
+    );
+    scheduled.extend(playlists);
+    println!("Channels and playlists queued for refresh:");
     for channel in &scheduled {
         println!("  - {}", channel);
     }
@@ -194,12 +207,7 @@ async fn main() -> Result<()> {
     for (index, channel) in scheduled.iter().enumerate() {
         let current = index + 1;
         println!();
-        println!(
-            "[{}/{}] Updating channel: {}",
-            current,
-            scheduled.len(),
-            channel
-        );
+        println!("[{}/{}] Updating: {}", current, scheduled.len(), channel);
 
         match Command::new(&downloader)
             .arg("--media-root")
@@ -228,14 +236,20 @@ async fn main() -> Result<()> {
     }
 
     println!();
-    println!("All channel updates complete.");
+    println!("All channel and playlist updates complete.");
 
     Ok(())
 }
 
 /// Walks a directory tree looking for `*.info.json` files and extracts the
-/// original channel URL so we can re-run downloads later.
-fn collect_channels(root: &Path, channels: &mut BTreeMap<String, String>) -> Result<()> {
+/// original channel URL so we can re-run downloads later. Videos listed in
+/// `skip` (playlist members) are ignored, so downloading a playlist does not
+/// schedule the whole channel of every uploader in it.
+fn collect_channels(
+    root: &Path,
+    skip: &HashSet<String>,
+    channels: &mut BTreeMap<String, String>,
+) -> Result<()> {
     if !root.exists() {
         return Ok(());
     }
@@ -249,7 +263,11 @@ fn collect_channels(root: &Path, channels: &mut BTreeMap<String, String>) -> Res
             continue;
         }
 
-        if !entry.file_name().to_string_lossy().ends_with(".info.json") {
+        let file_name = entry.file_name().to_string_lossy();
+        let Some(video_id) = file_name.strip_suffix(".info.json") else {
+            continue;
+        };
+        if skip.contains(video_id) {
             continue;
         }
 
@@ -264,6 +282,43 @@ fn collect_channels(root: &Path, channels: &mut BTreeMap<String, String>) -> Res
     Ok(())
 }
 
+/// Returns the ids of every video and short stored as a playlist entry,
+/// tracked or not. Their channels are only refreshed when some other video
+/// of the channel was downloaded on its own.
+async fn collect_playlist_members(reader: &MetadataReader) -> Result<HashSet<String>> {
+    let mut members = HashSet::new();
+    for playlist in reader.list_playlists().await.context("listing playlists")? {
+        let entries = reader
+            .get_playlist_entries(&playlist.playlist_id)
+            .await
+            .with_context(|| format!("listing entries of playlist {}", playlist.playlist_id))?;
+        members.extend(entries.into_iter().map(|entry| entry.videoid));
+    }
+    Ok(members)
+}
+
+/// Returns the URL of every playlist flagged as tracked. `download_channel`
+/// replaces the stored entries on each run, so upstream removals and
+/// reordering are picked up.
+async fn collect_tracked_playlists(reader: &MetadataReader) -> Result<Vec<String>> {
+    let playlists = reader
+        .list_playlists()
+        .await
+        .context("listing tracked playlists")?;
+    Ok(playlists
+        .into_iter()
+        .filter(|playlist| playlist.tracked)
+        .map(|playlist| {
+            playlist.url.unwrap_or_else(|| {
+                format!(
+                    "https://www.youtube.com/playlist?list={}",
+                    playlist.playlist_id
+                )
+            })
+        })
+        .collect())
+}
+
 /// Reads the minimal metadata needed to figure out which channel a video
 /// belongs to.
 fn extract_channel_url(path: &Path) -> Result<Option<String>> {
@@ -387,6 +442,7 @@ fn find_download_channel_executable() -> Result<PathBuf> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use newtube_tools::metadata::{PlaylistEntry, PlaylistRecord};
     use std::collections::BTreeMap;
     use std::env;
     use std::io::Write;
@@ -457,7 +513,7 @@ mod tests {
         let info_path = videos_dir.join("sample.info.json");
         File::create(&info_path)?.write_all(br#"{"channel_url":"HTTPS://YouTube.com/@Test/"}"#)?;
         let mut map = BTreeMap::new();
-        collect_channels(&videos_dir, &mut map)?;
+        collect_channels(&videos_dir, &HashSet::new(), &mut map)?;
         assert_eq!(map.len(), 1);
         assert_eq!(map.values().next().unwrap(), "HTTPS://YouTube.com/@Test/");
         Ok(())
@@ -531,11 +587,107 @@ mod tests {
         fs::write(videos_dir.join("not_info.json"), br#"{"channel_url":"x"}"#)?;
         fs::write(videos_dir.join("bad.info.json"), br#"{bad json}"#)?;
         let mut map = BTreeMap::new();
-        collect_channels(&videos_dir, &mut map)?;
+        collect_channels(&videos_dir, &HashSet::new(), &mut map)?;
         assert!(map.is_empty());
         Ok(())
     }
 
+    /// Videos that only came in through a playlist do not schedule their
+    /// uploader's channel; other videos of the same channel still do.
+    #[tokio::test]
+    async fn collect_channels_skips_playlist_members() -> Result<()> {
+        let temp = tempdir()?;
+        let db_path = temp.path().join(METADATA_DB_FILE);
+        let store = MetadataStore::open(&db_path).await?;
+        store
+            .replace_playlist(
+                &PlaylistRecord {
+                    playlist_id: "PLa".into(),
+                    title: "Picks".into(),
+                    description: String::new(),
+                    owner: None,
+                    owner_channel_id: None,
+                    url: None,
+                    tracked: true,
+                    last_refreshed_at: None,
+                    entry_count: 1,
+                },
+                &[PlaylistEntry {
+                    position: 0,
+                    videoid: "member".into(),
+                    kind: "video".into(),
+                }],
+            )
+            .await?;
+        let reader = MetadataReader::new(&db_path).await?;
+        let members = collect_playlist_members(&reader).await?;
+
+        let videos_dir = temp.path().join("videos");
+        for (id, channel) in [
+            ("member", "https://youtube.com/@Other"),
+            ("own", "https://youtube.com/@Mine"),
+        ] {
+            let dir = videos_dir.join(id);
+            fs::create_dir_all(&dir)?;
+            fs::write(
+                dir.join(format!("{id}.info.json")),
+                format!(r#"{{"channel_url":"{channel}"}}"#),
+            )?;
+        }
+        let mut map = BTreeMap::new();
+        collect_channels(&videos_dir, &members, &mut map)?;
+        assert_eq!(
+            map.values().collect::<Vec<_>>(),
+            ["https://youtube.com/@Mine"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn collect_tracked_playlists_skips_untracked() -> Result<()> {
+        let temp = tempdir()?;
+        let db_path = temp.path().join(METADATA_DB_FILE);
+        let store = MetadataStore::open(&db_path).await?;
+        let playlist = |id: &str, tracked: bool, url: Option<&str>| PlaylistRecord {
+            playlist_id: id.into(),
+            title: id.into(),
+            description: String::new(),
+            owner: None,
+            owner_channel_id: None,
+            url: url.map(str::to_string),
+            tracked,
+            last_refreshed_at: None,
+            entry_count: 0,
+        };
+        store
+            .replace_playlist(
+                &playlist(
+                    "PLa",
+                    true,
+                    Some("https://www.youtube.com/playlist?list=PLa"),
+                ),
+                &[],
+            )
+            .await?;
+        store
+            .replace_playlist(&playlist("PLb", true, None), &[])
+            .await?;
+        store
+            .replace_playlist(&playlist("PLc", false, None), &[])
+            .await?;
+
+        let reader = MetadataReader::new(&db_path).await?;
+        let urls = collect_tracked_playlists(&reader).await?;
+        assert_eq!(
+            urls,
+            [
+                "https://www.youtube.com/playlist?list=PLa",
+                "https://www.youtube.com/playlist?list=PLb",
+            ]
+        );
+        Ok(())
+    }
+
     #[test]
     fn routine_args_rejects_unknown_flags() {
         with_env_file(
@@ -0,0 +1,450 @@
+#![forbid(unsafe_code)]
+
+//! DASH manifests for the separate video and audio downloads.
+//!
+//! YouTube's adaptive formats are single-file fragmented MP4 or WebM
+//! streams with a segment index near the start, so a manifest only needs
+//! the byte ranges of the initialization data and of that index
+//! (`SegmentBase`) for a browser player to seek and switch quality without
+//! any server-side packaging. `segment_base` finds those ranges when a file
+//! is downloaded; `manifest` renders the MPD served by the backend.
+
+use std::fs::File;
+use std::io::{BufReader, ErrorKind, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::metadata::{AdaptiveKind, AdaptiveStream, ByteRange};
+
+pub const MANIFEST_MIME: &str = "application/dash+xml";
+
+/// Top-level element ids of the WebM (Matroska) layout we walk.
+const EBML_HEADER: u64 = 0x1A45_DFA3;
+const WEBM_SEGMENT: u64 = 0x1853_8067;
+const WEBM_TRACKS: u64 = 0x1654_AE6B;
+const WEBM_CUES: u64 = 0x1C53_BB6B;
+const WEBM_CLUSTER: u64 = 0x1F43_B675;
+
+/// Initialization and segment index ranges of an on-demand DASH file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SegmentBase {
+    pub init: ByteRange,
+    pub index: ByteRange,
+}
+
+/// Locates the initialization data and segment index of a fragmented MP4
+/// or WebM file. Returns `None` for files without an index (progressive
+/// MP4s, WebMs with their cues at the end), which cannot be streamed from a
+/// `SegmentBase` manifest.
+pub fn segment_base(path: &Path) -> Result<Option<SegmentBase>> {
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let mut reader = BufReader::new(file);
+    let mut magic = [0u8; 4];
+    if let Err(err) = reader.read_exact(&mut magic) {
+        if err.kind() == ErrorKind::UnexpectedEof {
+            return Ok(None);
+        }
+        return Err(err).with_context(|| format!("reading {}", path.display()));
+    }
+    reader.seek(SeekFrom::Start(0))?;
+    let scanned = if u32::from_be_bytes(magic) as u64 == EBML_HEADER {
+        scan_webm(&mut reader)
+    } else {
+        scan_mp4(&mut reader)
+    };
+    scanned.with_context(|| format!("scanning {}", path.display()))
+}
+
+/// Walks the top-level ISO BMFF boxes: `moov` ends the initialization data
+/// and `sidx` is the index. Stops at the first media fragment.
+fn scan_mp4<R: Read + Seek>(reader: &mut R) -> std::io::Result<Option<SegmentBase>> {
+    let mut offset = 0u64;
+    let mut init_end = None;
+    loop {
+        let mut header = [0u8; 8];
+        match reader.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let kind = &header[4..8];
+        if size == 1 {
+            let mut large = [0u8; 8];
+            reader.read_exact(&mut large)?;
+            size = u64::from_be_bytes(large);
+        }
+        // A zero size runs to the end of the file; nothing useful follows.
+        if size < 8 {
+            return Ok(None);
+        }
+        let end = offset + size;
+        match kind {
+            b"moov" => init_end = Some(end - 1),
+            b"sidx" => {
+                return Ok(init_end.map(|init_end| SegmentBase {
+                    init: ByteRange {
+                        start: 0,
+                        end: init_end,
+                    },
+                    index: ByteRange {
+                        start: offset,
+                        end: end - 1,
+                    },
+                }));
+            }
+            b"moof" | b"mdat" => return Ok(None),
+            _ => {}
+        }
+        offset = reader.seek(SeekFrom::Start(end))?;
+    }
+}
+
+/// Walks the children of the WebM `Segment`: `Tracks` ends the
+/// initialization data and `Cues` is the index. YouTube writes the cues
+/// before the first cluster, so reaching one means there is no usable index.
+fn scan_webm<R: Read + Seek>(reader: &mut R) -> std::io::Result<Option<SegmentBase>> {
+    let mut offset = 0u64;
+    let mut init_end = None;
+    loop {
+        let Some((id, size, header_len)) = read_element_header(reader)? else {
+            return Ok(None);
+        };
+        let data_start = offset + header_len;
+        match id {
+            // Step into the segment instead of over it.
+            WEBM_SEGMENT => {
+                offset = data_start;
+                continue;
+            }
+            WEBM_CLUSTER => return Ok(None),
+            _ => {}
+        }
+        // Only the segment and clusters may have an unknown size.
+        let Some(size) = size else {
+            return Ok(None);
+        };
+        let end = data_start + size;
+        match id {
+            WEBM_TRACKS => init_end = Some(end - 1),
+            WEBM_CUES => {
+                return Ok(init_end.map(|init_end| SegmentBase {
+                    init: ByteRange {
+                        start: 0,
+                        end: init_end,
+                    },
+                    index: ByteRange {
+                        start: offset,
+                        end: end - 1,
+                    },
+                }));
+            }
+            _ => {}
+        }
+        offset = reader.seek(SeekFrom::Start(end))?;
+    }
+}
+
+/// Reads an EBML element id and data size. The size is `None` when the
+/// element declares an unknown size.
+fn read_element_header<R: Read>(
+    reader: &mut R,
+) -> std::io::Result<Option<(u64, Option<u64>, u64)>> {
+    let Some((id, id_len, _)) = read_vint(reader)? else {
+        return Ok(None);
+    };
+    let Some((size, size_len, unknown)) = read_vint(reader)? else {
+        return Ok(None);
+    };
+    // Ids keep their length marker bits; sizes do not.
+    let id = id | (1 << (7 * id_len));
+    Ok(Some((
+        id,
+        (!unknown).then_some(size),
+        (id_len + size_len) as u64,
+    )))
+}
+
+/// Reads an EBML variable-length integer and returns its value without the
+/// length marker, its length in bytes and whether all value bits are set
+/// (the "unknown size" marker).
+fn read_vint<R: Read>(reader: &mut R) -> std::io::Result<Option<(u64, u32, bool)>> {
+    let mut first = [0u8; 1];
+    match reader.read_exact(&mut first) {
+        Ok(()) => {}
+        Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let len = first[0].leading_zeros() + 1;
+    if len > 8 {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "invalid EBML variable-length integer",
+        ));
+    }
+    let mut value = u64::from(first[0]) & (0xFF >> len);
+    for _ in 1..len {
+        let mut byte = [0u8; 1];
+        reader.read_exact(&mut byte)?;
+        value = (value << 8) | u64::from(byte[0]);
+    }
+    let all_ones = (1u64 << (7 * len)) - 1;
+    Ok(Some((value, len, value == all_ones)))
+}
+
+/// Whether `stream` carries everything a `SegmentBase` manifest needs.
+pub fn is_streamable(stream: &AdaptiveStream) -> bool {
+    stream.init_range.is_some() && stream.index_range.is_some()
+}
+
+/// Renders a static on-demand MPD with one adaptation set per kind and
+/// container. Streams without byte ranges are left out; `duration` is the
+/// video length in seconds.
+pub fn manifest(duration: Option<i64>, streams: &[AdaptiveStream]) -> String {
+    let mut sets: Vec<(AdaptiveKind, &str, Vec<&AdaptiveStream>)> = Vec::new();
+    for stream in streams.iter().filter(|stream| is_streamable(stream)) {
+        match sets
+            .iter_mut()
+            .find(|(kind, mime, _)| *kind == stream.kind && *mime == stream.mime_type)
+        {
+            Some((_, _, members)) => members.push(stream),
+            None => sets.push((stream.kind, &stream.mime_type, vec![stream])),
+        }
+    }
+    sets.sort_by_key(|(kind, mime, _)| (*kind == AdaptiveKind::Audio, mime.to_string()));
+
+    let mut mpd = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    mpd.push_str(
+        "<MPD xmlns=\"urn:mpeg:dash:schema:mpd:2011\" \
+         profiles=\"urn:mpeg:dash:profile:isoff-on-demand:2011\" type=\"static\" \
+         minBufferTime=\"PT2S\"",
+    );
+    if let Some(seconds) = duration.filter(|seconds| *seconds > 0) {
+        mpd.push_str(&format!(" mediaPresentationDuration=\"PT{seconds}S\""));
+    }
+    mpd.push_str(">\n  <Period>\n");
+    for (index, (kind, mime, mut members)) in sets.into_iter().enumerate() {
+        members.sort_by_key(|stream| stream.bandwidth.unwrap_or_default());
+        let content_type = match kind {
+            AdaptiveKind::Video => "video",
+            AdaptiveKind::Audio => "audio",
+        };
+        mpd.push_str(&format!(
+            "    <AdaptationSet id=\"{index}\" contentType=\"{content_type}\" \
+             mimeType=\"{}\" subsegmentAlignment=\"true\">\n",
+            escape(mime)
+        ));
+        for stream in members {
+            mpd.push_str(&format!(
+                "      <Representation id=\"{}\" codecs=\"{}\" bandwidth=\"{}\"",
+                escape(&stream.format_id),
+                escape(&stream.codecs),
+                stream.bandwidth.unwrap_or_default()
+            ));
+            if let (Some(width), Some(height)) = (stream.width, stream.height) {
+                mpd.push_str(&format!(" width=\"{width}\" height=\"{height}\""));
+            }
+            if let Some(fps) = stream.fps.filter(|fps| *fps > 0.0) {
+                mpd.push_str(&format!(" frameRate=\"{}\"", frame_rate(fps)));
+            }
+            if let Some(rate) = stream.audio_sample_rate {
+                mpd.push_str(&format!(" audioSamplingRate=\"{rate}\""));
+            }
+            mpd.push_str(">\n");
+            if let Some(channels) = stream.audio_channels {
+                mpd.push_str(&format!(
+                    "        <AudioChannelConfiguration \
+                     schemeIdUri=\"urn:mpeg:dash:23003:3:audio_channel_configuration:2011\" \
+                     value=\"{channels}\"/>\n"
+                ));
+            }
+            if let (Some(init), Some(index)) = (stream.init_range, stream.index_range) {
+                mpd.push_str(&format!(
+                    "        <BaseURL>{}</BaseURL>\n        \
+                     <SegmentBase indexRange=\"{index}\">\n          \
+                     <Initialization range=\"{init}\"/>\n        \
+                     </SegmentBase>\n",
+                    escape(&stream.url)
+                ));
+            }
+            mpd.push_str("      </Representation>\n");
+        }
+        mpd.push_str("    </AdaptationSet>\n");
+    }
+    mpd.push_str("  </Period>\n</MPD>\n");
+    mpd
+}
+
+/// Formats `fps` as a DASH `FrameRateType`, which only allows integers and
+/// fractions: NTSC rates become `30000/1001`, other fractional rates are
+/// kept to the millisecond.
+fn frame_rate(fps: f64) -> String {
+    let whole = fps.round();
+    if (fps - whole).abs() < 0.01 {
+        return format!("{}", whole as i64);
+    }
+    let ntsc = (fps * 1.001).round();
+    if (fps - ntsc * 1000.0 / 1001.0).abs() < 0.01 {
+        return format!("{}/1001", ntsc as i64 * 1000);
+    }
+    format!("{}/1000", (fps * 1000.0).round() as i64)
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn mp4_box(kind: &[u8; 4], payload_len: usize) -> Vec<u8> {
+        let mut data = ((payload_len + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.resize(payload_len + 8, 0);
+        data
+    }
+
+    /// One-byte id elements would need a different marker; every id used
+    /// here is four bytes long and the sizes fit one byte.
+    fn webm_element(id: u64, payload_len: usize) -> Vec<u8> {
+        let mut data = (id as u32).to_be_bytes().to_vec();
+        data.push(0x80 | payload_len as u8);
+        data.resize(data.len() + payload_len, 0);
+        data
+    }
+
+    fn stream(format_id: &str, kind: AdaptiveKind, mime: &str, bandwidth: i64) -> AdaptiveStream {
+        AdaptiveStream {
+            format_id: format_id.into(),
+            kind,
+            mime_type: mime.into(),
+            codecs: if kind == AdaptiveKind::Video {
+                "vp9".into()
+            } else {
+                "opus".into()
+            },
+            bandwidth: Some(bandwidth),
+            quality_label: None,
+            width: (kind == AdaptiveKind::Video).then_some(3840),
+            height: (kind == AdaptiveKind::Video).then_some(2160),
+            fps: (kind == AdaptiveKind::Video).then_some(29.97),
+            audio_sample_rate: (kind == AdaptiveKind::Audio).then_some(48_000),
+            audio_channels: (kind == AdaptiveKind::Audio).then_some(2),
+            init_range: Some(ByteRange { start: 0, end: 99 }),
+            index_range: Some(ByteRange {
+                start: 100,
+                end: 199,
+            }),
+            ext: None,
+            file_size: None,
+            url: format!("/api/videos/alpha/dash/{format_id}"),
+            path: None,
+            sha256: None,
+        }
+    }
+
+    /// Ranges come from `moov`/`sidx` in MP4 and `Tracks`/`Cues` in WebM;
+    /// files without an index yield nothing.
+    #[test]
+    fn segment_base_finds_init_and_index() -> Result<()> {
+        let dir = tempdir()?;
+        let mp4 = dir.path().join("alpha_137.mp4");
+        let mut data = mp4_box(b"ftyp", 16);
+        data.extend(mp4_box(b"moov", 100));
+        data.extend(mp4_box(b"sidx", 40));
+        data.extend(mp4_box(b"moof", 10));
+        fs::write(&mp4, &data)?;
+        let found = segment_base(&mp4)?.unwrap();
+        assert_eq!(found.init, ByteRange { start: 0, end: 131 });
+        assert_eq!(
+            found.index,
+            ByteRange {
+                start: 132,
+                end: 179
+            }
+        );
+
+        let progressive = dir.path().join("alpha_18.mp4");
+        let mut data = mp4_box(b"ftyp", 16);
+        data.extend(mp4_box(b"moov", 100));
+        data.extend(mp4_box(b"mdat", 10));
+        fs::write(&progressive, &data)?;
+        assert_eq!(segment_base(&progressive)?, None);
+
+        let webm = dir.path().join("alpha_313.webm");
+        let mut data = webm_element(EBML_HEADER, 20);
+        // Segment of unknown size, as live-muxed WebMs write it.
+        data.extend([
+            0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+        ]);
+        data.extend(webm_element(0x1549_A966, 30)); // Info
+        data.extend(webm_element(WEBM_TRACKS, 50));
+        data.extend(webm_element(WEBM_CUES, 60));
+        data.extend(webm_element(WEBM_CLUSTER, 10));
+        fs::write(&webm, &data)?;
+        let found = segment_base(&webm)?.unwrap();
+        // 25 (EBML) + 12 (Segment header) + 35 (Info) + 55 (Tracks) = 127.
+        assert_eq!(found.init, ByteRange { start: 0, end: 126 });
+        assert_eq!(
+            found.index,
+            ByteRange {
+                start: 127,
+                end: 191
+            }
+        );
+
+        let empty = dir.path().join("empty.webm");
+        fs::write(&empty, "")?;
+        assert_eq!(segment_base(&empty)?, None);
+        Ok(())
+    }
+
+    /// Video sets come before audio, representations are ordered by
+    /// bandwidth and streams without ranges are skipped.
+    #[test]
+    fn manifest_groups_streams_by_kind_and_container() {
+        let mut unindexed = stream("248", AdaptiveKind::Video, "video/webm", 1);
+        unindexed.index_range = None;
+        let streams = [
+            stream("251", AdaptiveKind::Audio, "audio/webm", 160_000),
+            stream("313", AdaptiveKind::Video, "video/webm", 12_000_000),
+            stream("271", AdaptiveKind::Video, "video/webm", 6_000_000),
+            unindexed,
+        ];
+        let mpd = manifest(Some(60), &streams);
+        assert!(mpd.contains("mediaPresentationDuration=\"PT60S\""));
+        assert!(!mpd.contains("\"248\""));
+        let video = mpd.find("contentType=\"video\"").unwrap();
+        let audio = mpd.find("contentType=\"audio\"").unwrap();
+        assert!(video < audio);
+        assert!(mpd.find("id=\"271\"").unwrap() < mpd.find("id=\"313\"").unwrap());
+        assert!(mpd.contains(
+            "<Representation id=\"313\" codecs=\"vp9\" bandwidth=\"12000000\" \
+             width=\"3840\" height=\"2160\" frameRate=\"30000/1001\">"
+        ));
+        assert!(mpd.contains("<BaseURL>/api/videos/alpha/dash/313</BaseURL>"));
+        assert!(mpd.contains("<SegmentBase indexRange=\"100-199\">"));
+        assert!(mpd.contains("<Initialization range=\"0-99\"/>"));
+        assert!(mpd.contains("audioSamplingRate=\"48000\""));
+        assert!(mpd.contains("value=\"2\"/>"));
+    }
+
+    /// Frame rates stay exact: NTSC rates as `/1001` fractions, integers
+    /// as they are.
+    #[test]
+    fn frame_rate_keeps_fractional_rates() {
+        assert_eq!(frame_rate(30.0), "30");
+        assert_eq!(frame_rate(29.97), "30000/1001");
+        assert_eq!(frame_rate(59.94), "60000/1001");
+        assert_eq!(frame_rate(23.976), "24000/1001");
+        assert_eq!(frame_rate(12.5), "12500/1000");
+    }
+}
@@ -0,0 +1,234 @@
+#![forbid(unsafe_code)]
+
+//! On-the-fly HLS packaging of downloaded formats.
+//!
+//! Every muxed `VideoSource` of a video becomes one variant of its master
+//! playlist, so players can switch quality on their own. The first request
+//! for a variant remuxes the file with `ffmpeg -c copy` into fMP4 segments
+//! under `<MEDIA_ROOT>/hls/<id>/<format>/`; later requests are served from
+//! that cache until the source file is replaced.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::{Context, Result, bail};
+
+use crate::metadata::VideoSource;
+
+/// Cache directory inside `MEDIA_ROOT`.
+pub const HLS_SUBDIR: &str = "hls";
+/// Name of each variant's media playlist; segments sit next to it.
+pub const VARIANT_PLAYLIST: &str = "index.m3u8";
+pub const PLAYLIST_MIME: &str = "application/vnd.apple.mpegurl";
+
+/// Target segment length. With `-c copy` ffmpeg can only cut on keyframes,
+/// so actual segments may run longer.
+const SEGMENT_SECONDS: u32 = 6;
+/// Advertised for variants whose size or duration is unknown. `BANDWIDTH`
+/// is mandatory in a master playlist.
+const FALLBACK_BANDWIDTH: u64 = 2_000_000;
+
+/// One `#EXT-X-STREAM-INF` entry of a master playlist.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HlsVariant {
+    /// Playlist URI, relative to the master playlist.
+    pub uri: String,
+    /// Peak bits per second; estimated from file size and duration.
+    pub bandwidth: u64,
+    pub resolution: Option<(i64, i64)>,
+    pub fps: Option<f64>,
+}
+
+impl HlsVariant {
+    /// Describes `source` for a video lasting `duration` seconds.
+    pub fn for_source(source: &VideoSource, duration: Option<i64>, uri: String) -> Self {
+        let bandwidth = match (source.file_size, duration) {
+            (Some(size), Some(seconds)) if size > 0 && seconds > 0 => {
+                (size as u64).saturating_mul(8) / seconds as u64
+            }
+            _ => FALLBACK_BANDWIDTH,
+        };
+        Self {
+            uri,
+            bandwidth,
+            resolution: source.width.zip(source.height),
+            fps: source.fps.filter(|fps| *fps > 0.0),
+        }
+    }
+}
+
+/// Renders a master playlist listing `variants` from lowest to highest
+/// bandwidth.
+pub fn master_playlist(variants: &[HlsVariant]) -> String {
+    let mut sorted: Vec<&HlsVariant> = variants.iter().collect();
+    sorted.sort_by_key(|variant| variant.bandwidth);
+    let mut playlist = String::from("#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-INDEPENDENT-SEGMENTS\n");
+    for variant in sorted {
+        playlist.push_str(&format!(
+            "#EXT-X-STREAM-INF:BANDWIDTH={}",
+            variant.bandwidth
+        ));
+        if let Some((width, height)) = variant.resolution {
+            playlist.push_str(&format!(",RESOLUTION={width}x{height}"));
+        }
+        if let Some(fps) = variant.fps {
+            playlist.push_str(&format!(",FRAME-RATE={fps:.3}"));
+        }
+        playlist.push('\n');
+        playlist.push_str(&variant.uri);
+        playlist.push('\n');
+    }
+    playlist
+}
+
+/// Whether `output_dir` holds a complete packaging of `input` that is at
+/// least as new as the file itself.
+pub fn is_packaged(input: &Path, output_dir: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    match (
+        modified(&output_dir.join(VARIANT_PLAYLIST)),
+        modified(input),
+    ) {
+        (Some(packaged), Some(source)) => packaged >= source,
+        _ => false,
+    }
+}
+
+/// Remuxes `input` into fMP4 segments plus a VOD playlist in `output_dir`,
+/// replacing any earlier packaging. ffmpeg writes into a sibling temporary
+/// directory that is renamed into place, so readers never see a partial
+/// playlist. Blocks until ffmpeg exits.
+pub fn package(ffmpeg: &Path, input: &Path, output_dir: &Path) -> Result<()> {
+    let parent = output_dir
+        .parent()
+        .context("HLS output directory has no parent")?;
+    fs::create_dir_all(parent).with_context(|| format!("creating {}", parent.display()))?;
+    let staging = tempfile::Builder::new()
+        .prefix(".partial-")
+        .tempdir_in(parent)
+        .with_context(|| format!("creating staging dir in {}", parent.display()))?;
+
+    let output = Command::new(ffmpeg)
+        .arg("-nostdin")
+        .args(["-loglevel", "error", "-y", "-i"])
+        .arg(input)
+        .args(["-c", "copy", "-f", "hls"])
+        .args(["-hls_time", &SEGMENT_SECONDS.to_string()])
+        .args(["-hls_playlist_type", "vod"])
+        .args(["-hls_segment_type", "fmp4"])
+        .args(["-hls_fmp4_init_filename", "init.mp4"])
+        .arg("-hls_segment_filename")
+        .arg(staging.path().join("%d.m4s"))
+        .arg(staging.path().join(VARIANT_PLAYLIST))
+        .output()
+        .with_context(|| format!("launching {}", ffmpeg.display()))?;
+    if !output.status.success() {
+        bail!(
+            "ffmpeg failed to package {} ({}): {}",
+            input.display(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    if !staging.path().join(VARIANT_PLAYLIST).is_file() {
+        bail!("ffmpeg did not write a playlist for {}", input.display());
+    }
+
+    if output_dir.exists() {
+        fs::remove_dir_all(output_dir)
+            .with_context(|| format!("removing stale {}", output_dir.display()))?;
+    }
+    fs::rename(staging.keep(), output_dir)
+        .with_context(|| format!("moving HLS output to {}", output_dir.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    fn source(format_id: &str, height: i64, file_size: Option<i64>) -> VideoSource {
+        VideoSource {
+            format_id: format_id.into(),
+            quality_label: None,
+            width: Some(height * 16 / 9),
+            height: Some(height),
+            fps: Some(30.0),
+            mime_type: Some("video/mp4".into()),
+            ext: Some("mp4".into()),
+            file_size,
+            url: String::new(),
+            path: None,
+            sha256: None,
+        }
+    }
+
+    /// Variants are ordered by bandwidth and carry the attributes players
+    /// use to pick one; unknown sizes fall back to a fixed estimate.
+    #[test]
+    fn master_playlist_lists_variants_by_bandwidth() {
+        let high = HlsVariant::for_source(
+            &source("22", 720, Some(10_000_000)),
+            Some(10),
+            "22/index.m3u8".into(),
+        );
+        let low =
+            HlsVariant::for_source(&source("18", 360, None), Some(10), "18/index.m3u8".into());
+        assert_eq!(high.bandwidth, 8_000_000);
+        assert_eq!(low.bandwidth, FALLBACK_BANDWIDTH);
+
+        let playlist = master_playlist(&[high, low]);
+        assert_eq!(
+            playlist,
+            "#EXTM3U\n#EXT-X-VERSION:7\n#EXT-X-INDEPENDENT-SEGMENTS\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=2000000,RESOLUTION=640x360,FRAME-RATE=30.000\n\
+             18/index.m3u8\n\
+             #EXT-X-STREAM-INF:BANDWIDTH=8000000,RESOLUTION=1280x720,FRAME-RATE=30.000\n\
+             22/index.m3u8\n"
+        );
+    }
+
+    /// Output appears only after ffmpeg succeeds and is considered stale
+    /// once the source is newer.
+    #[test]
+    fn package_swaps_in_complete_output() -> Result<()> {
+        let dir = tempdir()?;
+        let input = dir.path().join("alpha_22.mp4");
+        fs::write(&input, "video")?;
+        let output_dir = dir.path().join("hls/alpha/22");
+
+        let failing = dir.path().join("ffmpeg-fail");
+        fs::write(&failing, "#!/bin/sh\necho broken >&2\nexit 1\n")?;
+        fs::set_permissions(&failing, fs::Permissions::from_mode(0o755))?;
+        let err = package(&failing, &input, &output_dir).unwrap_err();
+        assert!(err.to_string().contains("broken"));
+        assert!(!output_dir.exists());
+        assert!(!is_packaged(&input, &output_dir));
+
+        // The playlist path is the last argument; write it and a segment.
+        let ffmpeg = dir.path().join("ffmpeg");
+        fs::write(
+            &ffmpeg,
+            "#!/bin/sh\nfor last; do :; done\nout=$(dirname \"$last\")\n\
+             printf '#EXTM3U\\n' > \"$last\"\nprintf seg > \"$out/0.m4s\"\n",
+        )?;
+        fs::set_permissions(&ffmpeg, fs::Permissions::from_mode(0o755))?;
+        package(&ffmpeg, &input, &output_dir)?;
+        assert!(is_packaged(&input, &output_dir));
+        assert_eq!(fs::read_to_string(output_dir.join("0.m4s"))?, "seg");
+        let leftovers = fs::read_dir(output_dir.parent().unwrap())?.count();
+        assert_eq!(leftovers, 1);
+
+        let later = fs::metadata(output_dir.join(VARIANT_PLAYLIST))?.modified()?
+            + std::time::Duration::from_secs(60);
+        fs::File::options()
+            .write(true)
+            .open(&input)?
+            .set_modified(later)?;
+        assert!(!is_packaged(&input, &output_dir));
+        Ok(())
+    }
+}
@@ -3,8 +3,20 @@
 //! Public entry point for the reusable NewTube Rust crate.
 //!
 //! The crate is intentionally small; it mostly exposes the metadata module so
-//! binaries can share struct definitions and database helpers.
+//! binaries can share struct definitions and database helpers. The `userdata`
+//! module holds the separate per-household likes/playlists/history store and
+//! `transcript` parses subtitle files for the transcript search index.
+//! `library` knows the on-disk layout of downloaded media and `scrub` checks
+//! the downloaded files against their recorded checksums. `hls` remuxes
+//! them into HLS segments for adaptive playback, and `dash` indexes the
+//! separate video/audio downloads and describes them in DASH manifests.
 
 pub mod config;
+pub mod dash;
+pub mod hls;
+pub mod library;
 pub mod metadata;
+pub mod scrub;
 pub mod security;
+pub mod transcript;
+pub mod userdata;
@@ -0,0 +1,143 @@
+#![forbid(unsafe_code)]
+
+//! Helpers for the on-disk library layout shared by the downloader and the
+//! maintenance tools (`newtube_fsck`).
+//!
+//! Media files live at `<MEDIA_ROOT>/<videos|shorts>/<id>/<id>_<format>.<ext>`
+//! and yt-dlp records finished downloads in `download-archive.txt`.
+
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+
+use crate::metadata::VideoSource;
+
+/// Derives playable sources from the files in a media directory alone, for
+/// when yt-dlp's format list is unavailable or does not match what is on
+/// disk. Audio-only tracks, partial downloads and sidecar files are skipped.
+pub fn collect_sources_from_disk(
+    video_id: &str,
+    base_dir: &Path,
+    slug: &str,
+) -> Result<Vec<VideoSource>> {
+    let mut sources = Vec::new();
+    let prefix = format!("{video_id}_");
+    for entry in fs::read_dir(base_dir)
+        .with_context(|| format!("reading media dir {}", base_dir.display()))?
+    {
+        let entry = entry?;
+        if !entry.path().is_file() {
+            continue;
+        }
+        let file_name = entry
+            .file_name()
+            .into_string()
+            .unwrap_or_else(|os| os.to_string_lossy().into_owned());
+        if !file_name.starts_with(&prefix) {
+            continue;
+        }
+        if file_name.ends_with(".part") {
+            continue;
+        }
+        let rest = &file_name[prefix.len()..];
+        let Some((format_id, ext)) = rest.rsplit_once('.') else {
+            continue;
+        };
+        if format_id.is_empty() {
+            continue;
+        }
+        if matches!(ext, "m4a" | "mp3" | "aac" | "opus" | "flac" | "wav") {
+            continue;
+        }
+        if matches!(
+            ext,
+            "mhtml" | "json" | "txt" | "m3u8" | "mpd" | "ytdl" | "aria2"
+        ) {
+            continue;
+        }
+        let size = entry.metadata().ok().map(|meta| meta.len() as i64);
+        sources.push(VideoSource {
+            format_id: format_id.to_owned(),
+            quality_label: None,
+            width: None,
+            height: None,
+            fps: None,
+            mime_type: Some(mime_from_extension(ext)),
+            ext: Some(ext.to_owned()),
+            file_size: size,
+            url: format!("/api/{slug}/{}/streams/{}", video_id, format_id),
+            path: Some(entry.path().to_string_lossy().into_owned()),
+            sha256: None,
+        });
+    }
+
+    sources.sort_by(|a, b| a.format_id.cmp(&b.format_id));
+    Ok(sources)
+}
+
+/// Guesses the MIME type for each downloaded file based on its extension.
+pub fn mime_from_extension(ext: &str) -> String {
+    match ext {
+        "mp4" => "video/mp4".to_owned(),
+        "mkv" => "video/x-matroska".to_owned(),
+        "webm" => "video/webm".to_owned(),
+        other => format!("video/{other}"),
+    }
+}
+
+/// Parses yt-dlp's archive file to avoid duplicate downloads.
+pub fn load_archive(path: &Path) -> Result<HashSet<String>> {
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+
+    let file = File::open(path).with_context(|| format!("opening {}", path.display()))?;
+    let reader = BufReader::new(file);
+    let mut entries = HashSet::new();
+
+    for line in reader.lines() {
+        let line = line?;
+        if let Some(id) = line.split_whitespace().last()
+            && !id.is_empty()
+        {
+            entries.insert(id.to_owned());
+        }
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    /// Only complete, playable `<id>_<format>.<ext>` files become sources.
+    #[test]
+    fn collect_sources_from_disk_skips_sidecars_and_partials() -> Result<()> {
+        let dir = tempdir()?;
+        for name in [
+            "abc_22.mp4",
+            "abc_137.webm",
+            "abc_140.m4a",
+            "abc_18.mp4.part",
+            "abc.info.json",
+            "other_22.mp4",
+        ] {
+            fs::write(dir.path().join(name), "x")?;
+        }
+        let sources = collect_sources_from_disk("abc", dir.path(), "videos")?;
+        let ids: Vec<&str> = sources
+            .iter()
+            .map(|source| source.format_id.as_str())
+            .collect();
+        assert_eq!(ids, ["137", "22"]);
+        assert_eq!(sources[1].url, "/api/videos/abc/streams/22");
+        assert_eq!(sources[0].mime_type.as_deref(), Some("video/webm"));
+        assert_eq!(sources[1].file_size, Some(1));
+        Ok(())
+    }
+}
@@ -5,11 +5,19 @@
 //! All structs in this module mirror how metadata is serialized to disk and
 //! exposed to the API.
 
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::ops::Deref;
 use std::path::Path;
+use std::sync::Arc;
 
-use anyhow::{Context, Result};
-use libsql::{Builder, Connection, Row, params};
+use anyhow::{Context, Result, bail};
+use libsql::params::IntoParams;
+use libsql::{Builder, Connection, Database, Row, Rows, Statement, TransactionBehavior, params};
+use parking_lot::Mutex;
 use serde::{Deserialize, Serialize};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::transcript::{Transcript, TranscriptCue};
 
 /// Description of a single downloadable media source (e.g. 1080p mp4).
 ///
@@ -36,6 +44,76 @@ pub struct VideoSource {
     pub url: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub path: Option<String>,
+    /// Hex SHA-256 of the file at `path`, recorded when it was downloaded
+    /// (or first scrubbed) and re-checked by the scrub job.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sha256: Option<String>,
+}
+
+/// Whether an adaptive stream carries the picture or the sound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AdaptiveKind {
+    Video,
+    Audio,
+}
+
+/// Inclusive byte range inside a file, written `start-end` in manifests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl std::fmt::Display for ByteRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+/// Video-only or audio-only download that is only playable next to a
+/// matching track of the other kind, through a DASH manifest.
+///
+/// YouTube serves its high resolutions (1440p and up, VP9/AV1) only this
+/// way. `init_range` and `index_range` locate the initialization data and
+/// the segment index inside the file; without both the stream cannot be
+/// listed in a manifest.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AdaptiveStream {
+    pub format_id: String,
+    pub kind: AdaptiveKind,
+    pub mime_type: String,
+    /// RFC 6381 codec string as reported by yt-dlp, e.g. `vp09.00.50.08`.
+    pub codecs: String,
+    /// Average bits per second.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bandwidth: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quality_label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fps: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_sample_rate: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_channels: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub init_range: Option<ByteRange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_range: Option<ByteRange>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ext: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file_size: Option<i64>,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// Hex SHA-256 of the file at `path`, like [`VideoSource::sha256`].
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub sha256: Option<String>,
 }
 
 /// Rows stored in the `videos` and `shorts` tables.
@@ -65,6 +143,9 @@ pub struct VideoRecord {
     pub duration_text: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub channel_url: Option<String>,
+    /// Primary channel id; joins against the `channels` table.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub thumbnail_url: Option<String>,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
@@ -75,6 +156,60 @@ pub struct VideoRecord {
     pub extras: serde_json::Value,
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub sources: Vec<VideoSource>,
+    /// Whether YouTube still serves the video, as of the last refresh.
+    /// `None` until a refresh has checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub availability: Option<Availability>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub availability_checked_at: Option<String>,
+    /// Separate video and audio tracks served through the DASH manifest.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub adaptive_streams: Vec<AdaptiveStream>,
+}
+
+/// Upstream state of a video. Anything but `Available` means our copy may be
+/// the only one left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Availability {
+    Available,
+    /// Deleted by the uploader, or the account was terminated.
+    Removed,
+    Private,
+    /// Taken down after a copyright claim.
+    Copyright,
+    MembersOnly,
+    /// Needs a signed-in, age-verified account.
+    AgeRestricted,
+    /// Unavailable for a reason yt-dlp did not spell out.
+    Unavailable,
+}
+
+impl Availability {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "available" => Some(Self::Available),
+            "removed" => Some(Self::Removed),
+            "private" => Some(Self::Private),
+            "copyright" => Some(Self::Copyright),
+            "members_only" => Some(Self::MembersOnly),
+            "age_restricted" => Some(Self::AgeRestricted),
+            "unavailable" => Some(Self::Unavailable),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Available => "available",
+            Self::Removed => "removed",
+            Self::Private => "private",
+            Self::Copyright => "copyright",
+            Self::MembersOnly => "members_only",
+            Self::AgeRestricted => "age_restricted",
+            Self::Unavailable => "unavailable",
+        }
+    }
 }
 
 /// Subtitle manifest for a single video.
@@ -114,1078 +249,5549 @@ pub struct CommentRecord {
     pub status_likedbycreator: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub reply_count: Option<i64>,
+    /// When a merge first noticed the comment was no longer returned by
+    /// YouTube. Such comments are kept for the archive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_upstream_at: Option<String>,
 }
 
-async fn configure_connection(conn: &Connection) -> Result<()> {
-    conn.execute_batch(
-        r#"
-        PRAGMA journal_mode=WAL;
-        PRAGMA synchronous=NORMAL;
-        PRAGMA foreign_keys=ON;
-        "#,
-    )
-    .await?;
-    Ok(())
+/// Normalized channel metadata keyed by the YouTube channel id.
+///
+/// Optional fields are merged on upsert, so partial updates (e.g. derived from
+/// a single video's info JSON) never wipe avatar/banner data gathered by a full
+/// channel refresh.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChannelRecord {
+    pub channel_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub handle: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscriber_count: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub banner_path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_refreshed_at: Option<String>,
 }
 
-async fn ensure_schema(conn: &Connection) -> Result<()> {
-    conn.execute_batch(
-        r#"
-        CREATE TABLE IF NOT EXISTS videos (
-            videoid TEXT PRIMARY KEY,
-            title TEXT NOT NULL,
-            description TEXT DEFAULT '',
-            likes INTEGER,
-            dislikes INTEGER,
-            views INTEGER,
-            upload_date TEXT,
-            author TEXT,
-            subscriber_count INTEGER,
-            duration INTEGER,
-            duration_text TEXT,
-            channel_url TEXT,
-            thumbnail_url TEXT,
-            tags_json TEXT DEFAULT '[]',
-            thumbnails_json TEXT DEFAULT '[]',
-            extras_json TEXT DEFAULT 'null',
-            sources_json TEXT DEFAULT '[]'
-        );
+/// Playlist metadata. `entry_count` is computed when reading and ignored on
+/// writes; the ordered members live in `PlaylistEntry` rows.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaylistRecord {
+    pub playlist_id: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub owner_channel_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// Tracked playlists are re-synced by `routine_update`.
+    #[serde(default)]
+    pub tracked: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_refreshed_at: Option<String>,
+    #[serde(default)]
+    pub entry_count: i64,
+}
 
-        CREATE TABLE IF NOT EXISTS shorts (
-            videoid TEXT PRIMARY KEY,
-            title TEXT NOT NULL,
-            description TEXT DEFAULT '',
-            likes INTEGER,
-            dislikes INTEGER,
-            views INTEGER,
-            upload_date TEXT,
-            author TEXT,
-            subscriber_count INTEGER,
-            duration INTEGER,
-            duration_text TEXT,
-            channel_url TEXT,
-            thumbnail_url TEXT,
-            tags_json TEXT DEFAULT '[]',
-            thumbnails_json TEXT DEFAULT '[]',
-            extras_json TEXT DEFAULT 'null',
-            sources_json TEXT DEFAULT '[]'
-        );
+/// Single ordered playlist member. `kind` is `video` or `short` and tells the
+/// client which table/API slug holds the record.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlaylistEntry {
+    pub position: i64,
+    pub videoid: String,
+    pub kind: String,
+}
 
-        CREATE TABLE IF NOT EXISTS subtitles (
-            videoid TEXT PRIMARY KEY,
-            languages_json TEXT NOT NULL DEFAULT '[]'
-        );
+/// Point-in-time copy of a video's counters, recorded whenever a refresh sees
+/// different values than the previous snapshot.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VideoStatsSnapshot {
+    pub recorded_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub views: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub likes: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dislikes: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment_count: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscriber_count: Option<i64>,
+}
 
-        CREATE TABLE IF NOT EXISTS comments (
-            id TEXT PRIMARY KEY,
-            videoid TEXT NOT NULL,
-            author TEXT DEFAULT '',
-            text TEXT DEFAULT '',
-            likes INTEGER,
-            time_posted TEXT,
-            parent_comment_id TEXT,
-            status_likedbycreator INTEGER NOT NULL DEFAULT 0,
-            reply_count INTEGER
-        );
+/// Channel totals for one day: the sum of every video's latest snapshot as of
+/// that day, plus the highest subscriber count seen.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChannelStatsPoint {
+    pub day: String,
+    pub video_count: i64,
+    pub views: i64,
+    pub likes: i64,
+    pub comment_count: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subscriber_count: Option<i64>,
+}
 
-        CREATE INDEX IF NOT EXISTS idx_comments_videoid ON comments(videoid);
-        CREATE INDEX IF NOT EXISTS idx_comments_parent ON comments(parent_comment_id);
-        "#,
-    )
-    .await?;
+/// Entry of the "never re-download" list consulted by `download_channel`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BlockedMedia {
+    pub videoid: String,
+    pub blocked_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
 
-    migrate_comments_schema(conn).await?;
+/// Chapter marker inside a video, in seconds from the start. Chapters come
+/// from yt-dlp's `chapters` list or, failing that, from timestamps in the
+/// description.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Chapter {
+    pub start: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<f64>,
+    pub title: String,
+}
 
-    Ok(())
+/// Kind of skippable span. Names match SponsorBlock's categories so dump
+/// imports map one to one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SegmentCategory {
+    Sponsor,
+    Intro,
+    Outro,
+    Selfpromo,
 }
 
-async fn migrate_comments_schema(conn: &Connection) -> Result<()> {
-    let mut rows = conn
-        .query("PRAGMA foreign_key_list(comments)", params![])
-        .await?;
-    let mut has_video_fk = false;
-    while let Some(row) = rows.next().await? {
-        let table: String = row.get(2)?;
-        if table == "videos" {
-            has_video_fk = true;
-            break;
+impl SegmentCategory {
+    /// Parses a SponsorBlock category name. Categories we do not skip
+    /// (`music_offtopic`, `filler`, ...) return `None`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "sponsor" => Some(Self::Sponsor),
+            "intro" => Some(Self::Intro),
+            "outro" => Some(Self::Outro),
+            "selfpromo" => Some(Self::Selfpromo),
+            _ => None,
         }
     }
 
-    if !has_video_fk {
-        return Ok(());
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Sponsor => "sponsor",
+            Self::Intro => "intro",
+            Self::Outro => "outro",
+            Self::Selfpromo => "selfpromo",
+        }
     }
+}
 
-    conn.execute_batch(
-        r#"
-        BEGIN;
-        CREATE TABLE IF NOT EXISTS comments_new (
-            id TEXT PRIMARY KEY,
-            videoid TEXT NOT NULL,
-            author TEXT DEFAULT '',
-            text TEXT DEFAULT '',
-            likes INTEGER,
-            time_posted TEXT,
-            parent_comment_id TEXT,
-            status_likedbycreator INTEGER NOT NULL DEFAULT 0,
-            reply_count INTEGER
-        );
-        INSERT INTO comments_new (
-            id, videoid, author, text, likes, time_posted,
-            parent_comment_id, status_likedbycreator, reply_count
-        )
-        SELECT
-            id, videoid, author, text, likes, time_posted,
-            parent_comment_id, status_likedbycreator, reply_count
-        FROM comments;
-        DROP TABLE comments;
-        ALTER TABLE comments_new RENAME TO comments;
-        CREATE INDEX IF NOT EXISTS idx_comments_videoid ON comments(videoid);
-        CREATE INDEX IF NOT EXISTS idx_comments_parent ON comments(parent_comment_id);
-        COMMIT;
-        "#,
-    )
-    .await?;
+/// Outcome of the last scrub pass over a downloaded file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumStatus {
+    /// The file hashed to the recorded checksum.
+    Ok,
+    /// The file is readable but its contents changed.
+    Mismatch,
+    /// The file is gone or could not be read.
+    Missing,
+}
 
-    Ok(())
+impl ChecksumStatus {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "ok" => Some(Self::Ok),
+            "mismatch" => Some(Self::Mismatch),
+            "missing" => Some(Self::Missing),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Ok => "ok",
+            Self::Mismatch => "mismatch",
+            Self::Missing => "missing",
+        }
+    }
 }
 
-/// Wrapper around the SQLite-compatible connection that performs read/write operations.
-#[derive(Debug)]
-pub struct MetadataStore {
-    conn: Connection,
+/// Row of `source_checksums`: the recorded hash of one downloaded file and
+/// what the last scrub found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceChecksum {
+    pub path: String,
+    pub videoid: String,
+    pub sha256: String,
+    pub hashed_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verified_at: Option<String>,
+    pub status: ChecksumStatus,
+    /// Hash seen by the last scrub when it differs from `sha256`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observed_sha256: Option<String>,
 }
 
-impl MetadataStore {
-    /// Opens (and if necessary creates) the SQLite DB and ensures the expected
-    /// schema exists.
-    pub async fn open(path: &Path) -> Result<Self> {
-        if let Some(parent) = path.parent() {
-            std::fs::create_dir_all(parent)
-                .with_context(|| format!("creating metadata directory {}", parent.display()))?;
-        }
+/// Span of a video the player may skip, in seconds from the start.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkipSegment {
+    pub category: SegmentCategory,
+    pub start: f64,
+    pub end: f64,
+}
 
-        let db = Builder::new_local(path)
-            .build()
-            .await
-            .with_context(|| format!("opening metadata DB {}", path.display()))?;
+/// Single ranked result returned by the full-text search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub videoid: String,
+    /// `video` or `short`, depending on which table the hit lives in.
+    pub kind: String,
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Excerpt around the best matching column with matches wrapped in
+    /// `<mark>`/`</mark>`. The surrounding text is not HTML-escaped.
+    pub snippet: String,
+    /// BM25 score; lower is more relevant.
+    pub score: f64,
+}
 
-        let conn = db.connect()?;
-        configure_connection(&conn).await?;
+/// Single cue matched by the transcript search, pointing at the moment in the
+/// video where the words are spoken.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TranscriptHit {
+    pub videoid: String,
+    /// `video` or `short`, depending on which table the hit lives in.
+    pub kind: String,
+    pub title: String,
+    pub language: String,
+    /// Cue start/end in seconds from the start of the video.
+    pub start: f64,
+    pub end: f64,
+    /// Cue text with matches wrapped in `<mark>`/`</mark>`.
+    pub snippet: String,
+    /// BM25 score; lower is more relevant.
+    pub score: f64,
+}
 
-        let store = Self { conn };
-        store.ensure_tables().await?;
-        Ok(store)
+/// Optional filters applied on top of a full-text query.
+#[derive(Debug, Clone, Default)]
+pub struct SearchOptions {
+    /// Restrict hits to `video` or `short`.
+    pub kind: Option<String>,
+    /// Channel id or channel URL the hit must belong to.
+    pub channel: Option<String>,
+    pub limit: Option<u32>,
+}
+
+/// Sort keys offered by the paginated media listing. Every sort is backed by
+/// a SQL expression and ties are broken by `rowid`, which keeps keyset
+/// cursors stable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MediaSort {
+    #[default]
+    Date,
+    Views,
+    Likes,
+    Duration,
+    Title,
+    /// Order in which records first reached the library.
+    Downloaded,
+}
+
+impl MediaSort {
+    /// Parses the `sort` query value. Unknown names return `None`.
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "date" | "upload_date" => Some(Self::Date),
+            "views" => Some(Self::Views),
+            "likes" => Some(Self::Likes),
+            "duration" => Some(Self::Duration),
+            "title" => Some(Self::Title),
+            "downloaded" | "recent" => Some(Self::Downloaded),
+            _ => None,
+        }
     }
 
-    /// Runs the SQL required to create the tables if they do not already exist.
-    async fn ensure_tables(&self) -> Result<()> {
-        ensure_schema(&self.conn).await
+    fn name(self) -> &'static str {
+        match self {
+            Self::Date => "date",
+            Self::Views => "views",
+            Self::Likes => "likes",
+            Self::Duration => "duration",
+            Self::Title => "title",
+            Self::Downloaded => "downloaded",
+        }
     }
 
-    /// Inserts or updates a long-form video entry.
-    pub async fn upsert_video(&self, record: &VideoRecord) -> Result<()> {
-        self.upsert("videos", record).await
+    /// Expression used both in `ORDER BY` and in the keyset comparison.
+    /// NULLs are coalesced so comparisons never fall through.
+    fn expression(self) -> &'static str {
+        match self {
+            Self::Date => "COALESCE(upload_date, '')",
+            Self::Views => "COALESCE(views, -1)",
+            Self::Likes => "COALESCE(likes, -1)",
+            Self::Duration => "COALESCE(duration, -1)",
+            Self::Title => "title COLLATE NOCASE",
+            Self::Downloaded => "COALESCE(downloaded_at, '')",
+        }
     }
 
-    pub async fn upsert_short(&self, record: &VideoRecord) -> Result<()> {
-        self.upsert("shorts", record).await
+    fn is_numeric(self) -> bool {
+        matches!(self, Self::Views | Self::Likes | Self::Duration)
     }
 
-    /// Shared helper used by both `videos` and `shorts` tables.
-    async fn upsert(&self, table: &str, record: &VideoRecord) -> Result<()> {
-        let tags_json = serde_json::to_string(&record.tags).context("serializing tags")?;
-        let thumbnails_json =
-            serde_json::to_string(&record.thumbnails).context("serializing thumbnails")?;
-        let extras_json =
-            serde_json::to_string(&record.extras).context("serializing extra metadata")?;
-        let sources_json = serde_json::to_string(&record.sources).context("serializing sources")?;
+    /// Titles read naturally A→Z; everything else defaults to newest/largest first.
+    fn default_descending(self) -> bool {
+        !matches!(self, Self::Title)
+    }
+}
 
-        self.conn
-            .execute(
-                &format!(
-                    r#"
-                INSERT INTO {table} (
-                    videoid, title, description, likes, dislikes, views,
-                    upload_date, author, subscriber_count, duration, duration_text,
-                    channel_url, thumbnail_url, tags_json, thumbnails_json,
-                    extras_json, sources_json
-                ) VALUES (
-                    :videoid, :title, :description, :likes, :dislikes, :views,
-                    :upload_date, :author, :subscriber_count, :duration, :duration_text,
-                    :channel_url, :thumbnail_url, :tags_json, :thumbnails_json,
-                    :extras_json, :sources_json
-                )
-                ON CONFLICT(videoid) DO UPDATE SET
-                    title = excluded.title,
-                    description = excluded.description,
-                    likes = excluded.likes,
-                    dislikes = excluded.dislikes,
-                    views = excluded.views,
-                    upload_date = excluded.upload_date,
-                    author = excluded.author,
-                    subscriber_count = excluded.subscriber_count,
-                    duration = excluded.duration,
-                    duration_text = excluded.duration_text,
-                    channel_url = excluded.channel_url,
-                    thumbnail_url = excluded.thumbnail_url,
-                    tags_json = excluded.tags_json,
-                    thumbnails_json = excluded.thumbnails_json,
-                    extras_json = excluded.extras_json,
-                    sources_json = excluded.sources_json
-                "#,
-                ),
-                params![
-                    record.videoid.as_str(),
-                    record.title.as_str(),
-                    record.description.as_str(),
-                    record.likes,
-                    record.dislikes,
-                    record.views,
-                    record.upload_date.as_deref(),
-                    record.author.as_deref(),
-                    record.subscriber_count,
-                    record.duration,
-                    record.duration_text.as_deref(),
-                    record.channel_url.as_deref(),
-                    record.thumbnail_url.as_deref(),
-                    tags_json,
-                    thumbnails_json,
-                    extras_json,
-                    sources_json,
-                ],
-            )
-            .await?;
+/// Parameters for `MetadataReader::list_videos_page`/`list_shorts_page`.
+#[derive(Debug, Clone, Default)]
+pub struct MediaListQuery {
+    pub sort: MediaSort,
+    /// Overrides the natural direction of `sort`.
+    pub descending: Option<bool>,
+    /// Opaque value from a previous page's `next_cursor`.
+    pub cursor: Option<String>,
+    pub limit: Option<u32>,
+    /// Channel id or channel URL.
+    pub channel: Option<String>,
+    /// Inclusive lower bound on `upload_date` (ISO-8601 prefix).
+    pub uploaded_after: Option<String>,
+    /// Exclusive upper bound on `upload_date` (ISO-8601 prefix).
+    pub uploaded_before: Option<String>,
+    /// Inclusive duration bounds in seconds.
+    pub min_duration: Option<i64>,
+    pub max_duration: Option<i64>,
+    pub has_subtitles: Option<bool>,
+    /// Only media whose availability is one of these; empty means any.
+    /// Media never checked count as available.
+    pub availability: Vec<Availability>,
+}
 
-        Ok(())
+/// Returned (wrapped in `anyhow::Error`) when a listing cursor is malformed or
+/// was minted for a different sort, so callers can answer with a client error.
+#[derive(Debug)]
+pub struct InvalidCursor;
+
+impl std::fmt::Display for InvalidCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("invalid or mismatched cursor")
     }
+}
 
-    /// Stores subtitle metadata in the DB.
-    pub async fn upsert_subtitles(&self, subtitles: &SubtitleCollection) -> Result<()> {
-        let languages_json =
-            serde_json::to_string(&subtitles.languages).context("serializing subtitles")?;
-
-        self.conn
-            .execute(
-                r#"
-            INSERT INTO subtitles (videoid, languages_json)
-            VALUES (:videoid, :languages_json)
-            ON CONFLICT(videoid) DO UPDATE SET
-                languages_json = excluded.languages_json
-            "#,
-                params![subtitles.videoid.as_str(), languages_json],
-            )
-            .await?;
+impl std::error::Error for InvalidCursor {}
 
-        Ok(())
-    }
+/// One page of a media listing. `next_cursor` is `None` on the last page.
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaPage {
+    pub items: Vec<VideoRecord>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
 
-    /// Replaces every stored comment for `videoid` in one transaction so we do
-    /// not mix old and new comment trees.
-    pub async fn replace_comments(&self, videoid: &str, comments: &[CommentRecord]) -> Result<()> {
-        let tx = self.conn.transaction().await?;
-        tx.execute("DELETE FROM comments WHERE videoid = ?1", params![videoid])
-            .await?;
+/// Kind of row tracked by the `changes` log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeEntity {
+    Video,
+    Short,
+    /// A video's subtitle collection, keyed by videoid.
+    Subtitles,
+    /// A single comment, keyed by comment id.
+    Comment,
+}
 
-        for comment in comments {
-            tx.execute(
-                r#"
-                INSERT INTO comments (
-                    id, videoid, author, text, likes, time_posted,
-                    parent_comment_id, status_likedbycreator, reply_count
-                ) VALUES (
-                    :id, :videoid, :author, :text, :likes, :time_posted,
-                    :parent_comment_id, :status_likedbycreator, :reply_count
-                )
-                "#,
-                params![
-                    comment.id.as_str(),
-                    comment.videoid.as_str(),
-                    comment.author.as_str(),
-                    comment.text.as_str(),
-                    comment.likes,
-                    comment.time_posted.as_deref(),
-                    comment.parent_comment_id.as_deref(),
-                    comment.status_likedbycreator as i64,
-                    comment.reply_count,
-                ],
-            )
-            .await?;
+impl ChangeEntity {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "video" => Some(Self::Video),
+            "short" => Some(Self::Short),
+            "subtitles" => Some(Self::Subtitles),
+            "comment" => Some(Self::Comment),
+            _ => None,
         }
+    }
 
-        tx.commit().await?;
-        Ok(())
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Video => "video",
+            Self::Short => "short",
+            Self::Subtitles => "subtitles",
+            Self::Comment => "comment",
+        }
     }
 }
 
-/// Lightweight cloneable reader that opens short‑lived connections for each
-/// query. This avoids keeping a single connection open across threads/tasks.
-#[derive(Clone)]
-pub struct MetadataReader {
-    conn: Connection,
+/// Row removed since the client's last sync. Comments deleted upstream are
+/// reported this way too, since the default listings leave them out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tombstone {
+    pub seq: i64,
+    pub entity: ChangeEntity,
+    pub key: String,
 }
 
-impl MetadataReader {
-    /// Creates a new reader that lazily opens the DB whenever a query runs.
-    pub async fn new(path: impl AsRef<Path>) -> Result<Self> {
-        let db = Builder::new_local(path.as_ref())
-            .build()
-            .await
-            .with_context(|| format!("opening metadata DB {}", path.as_ref().display()))?;
-        let conn = db.connect()?;
-        configure_connection(&conn).await?;
-        ensure_schema(&conn).await?;
-        Ok(Self { conn })
-    }
+/// Rows changed after a given sequence number, as returned by
+/// [`MetadataReader::changes_since`].
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ChangeSet {
+    /// Highest sequence number covered; pass it back as `since` next time.
+    pub seq: i64,
+    /// More changes are waiting after `seq`.
+    pub has_more: bool,
+    /// `since` is ahead of the log, e.g. because `metadata.db` was rebuilt;
+    /// the client has to start over from a full bootstrap.
+    pub reset: bool,
+    pub videos: Vec<VideoRecord>,
+    pub shorts: Vec<VideoRecord>,
+    pub subtitles: Vec<SubtitleCollection>,
+    pub comments: Vec<CommentRecord>,
+    pub deleted: Vec<Tombstone>,
+}
 
-    pub async fn list_videos(&self) -> Result<Vec<VideoRecord>> {
-        self.fetch_videos_from("videos").await
-    }
+pub(crate) async fn configure_connection(conn: &Connection) -> Result<()> {
+    // The backend and the downloaders write to the same file; wait for the
+    // other side's write lock instead of failing straight away.
+    conn.execute_batch(
+        r#"
+        PRAGMA journal_mode=WAL;
+        PRAGMA synchronous=NORMAL;
+        PRAGMA foreign_keys=ON;
+        PRAGMA busy_timeout=5000;
+        "#,
+    )
+    .await?;
+    Ok(())
+}
 
-    pub async fn list_shorts(&self) -> Result<Vec<VideoRecord>> {
-        self.fetch_videos_from("shorts").await
-    }
+/// Latest schema version understood by this binary. Databases stamped with a
+/// higher version were written by a newer NewTube build and are refused.
+pub const SCHEMA_VERSION: i64 = MIGRATIONS[MIGRATIONS.len() - 1].version;
 
-    pub async fn get_video(&self, videoid: &str) -> Result<Option<VideoRecord>> {
-        self.fetch_single("videos", videoid).await
-    }
+/// Single forward-only schema change. Versions must be strictly increasing and
+/// are never renumbered once released.
+pub(crate) struct Migration {
+    pub(crate) version: i64,
+    pub(crate) description: &'static str,
+    pub(crate) step: MigrationStep,
+}
 
-    pub async fn get_short(&self, videoid: &str) -> Result<Option<VideoRecord>> {
-        self.fetch_single("shorts", videoid).await
-    }
+/// What a migration does. Most are plain SQL; a few need to inspect the
+/// existing schema first and are implemented as dedicated functions.
+pub(crate) enum MigrationStep {
+    Sql(&'static str),
+    DropLegacyCommentsForeignKey,
+    /// Creates the change log with triggers comparing the given columns.
+    CreateChangeLog(ChangeLogColumns),
+    /// Plain SQL that changes the columns of a change-tracked table. The
+    /// change log triggers compare those columns, so they are rebuilt
+    /// afterwards from the snapshot that goes with the SQL.
+    SqlRebuildingChangeTriggers(&'static str, ChangeLogColumns),
+}
 
-    pub async fn get_subtitles(&self, videoid: &str) -> Result<Option<SubtitleCollection>> {
-        let conn = &self.conn;
-        let stmt = conn
-            .prepare(
-                r#"
-                SELECT languages_json
-                FROM subtitles
-                WHERE videoid = ?1
-                "#,
+/// Columns the change log triggers compare to skip no-op updates, per table
+/// in [`CHANGE_TRACKED_TABLES`]. Each migration that touches them carries its
+/// own frozen copy, so adding a column later never changes what an earlier
+/// schema version created.
+pub(crate) type ChangeLogColumns = &'static [(&'static str, &'static str)];
+
+/// Ordered list of every migration. Each one runs exactly once, inside its own
+/// transaction, and records its version in `schema_version` on success.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "baseline videos/shorts/subtitles/comments tables",
+        step: MigrationStep::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS videos (
+                videoid TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                description TEXT DEFAULT '',
+                likes INTEGER,
+                dislikes INTEGER,
+                views INTEGER,
+                upload_date TEXT,
+                author TEXT,
+                subscriber_count INTEGER,
+                duration INTEGER,
+                duration_text TEXT,
+                channel_url TEXT,
+                thumbnail_url TEXT,
+                tags_json TEXT DEFAULT '[]',
+                thumbnails_json TEXT DEFAULT '[]',
+                extras_json TEXT DEFAULT 'null',
+                sources_json TEXT DEFAULT '[]'
+            );
+
+            CREATE TABLE IF NOT EXISTS shorts (
+                videoid TEXT PRIMARY KEY,
+                title TEXT NOT NULL,
+                description TEXT DEFAULT '',
+                likes INTEGER,
+                dislikes INTEGER,
+                views INTEGER,
+                upload_date TEXT,
+                author TEXT,
+                subscriber_count INTEGER,
+                duration INTEGER,
+                duration_text TEXT,
+                channel_url TEXT,
+                thumbnail_url TEXT,
+                tags_json TEXT DEFAULT '[]',
+                thumbnails_json TEXT DEFAULT '[]',
+                extras_json TEXT DEFAULT 'null',
+                sources_json TEXT DEFAULT '[]'
+            );
+
+            CREATE TABLE IF NOT EXISTS subtitles (
+                videoid TEXT PRIMARY KEY,
+                languages_json TEXT NOT NULL DEFAULT '[]'
+            );
+
+            CREATE TABLE IF NOT EXISTS comments (
+                id TEXT PRIMARY KEY,
+                videoid TEXT NOT NULL,
+                author TEXT DEFAULT '',
+                text TEXT DEFAULT '',
+                likes INTEGER,
+                time_posted TEXT,
+                parent_comment_id TEXT,
+                status_likedbycreator INTEGER NOT NULL DEFAULT 0,
+                reply_count INTEGER
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_comments_videoid ON comments(videoid);
+            CREATE INDEX IF NOT EXISTS idx_comments_parent ON comments(parent_comment_id);
+            "#,
+        ),
+    },
+    Migration {
+        version: 2,
+        description: "drop legacy comments -> videos foreign key",
+        step: MigrationStep::DropLegacyCommentsForeignKey,
+    },
+    Migration {
+        version: 3,
+        description: "FTS5 search index over videos, shorts and comments",
+        step: MigrationStep::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS search_documents (
+                docid INTEGER PRIMARY KEY,
+                videoid TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                UNIQUE(videoid, kind)
+            );
+
+            CREATE VIRTUAL TABLE IF NOT EXISTS search_index USING fts5(
+                title,
+                description,
+                tags,
+                author,
+                comments,
+                tokenize = 'unicode61 remove_diacritics 2'
+            );
+
+            INSERT OR IGNORE INTO search_documents (videoid, kind)
+            SELECT videoid, 'video' FROM videos;
+            INSERT OR IGNORE INTO search_documents (videoid, kind)
+            SELECT videoid, 'short' FROM shorts;
+
+            INSERT INTO search_index (rowid, title, description, tags, author, comments)
+            SELECT d.docid, m.title, m.description, m.tags_json, m.author,
+                   (SELECT group_concat(c.text, ' ') FROM comments c WHERE c.videoid = m.videoid)
+            FROM videos m
+            JOIN search_documents d ON d.videoid = m.videoid AND d.kind = 'video';
+            INSERT INTO search_index (rowid, title, description, tags, author, comments)
+            SELECT d.docid, m.title, m.description, m.tags_json, m.author,
+                   (SELECT group_concat(c.text, ' ') FROM comments c WHERE c.videoid = m.videoid)
+            FROM shorts m
+            JOIN search_documents d ON d.videoid = m.videoid AND d.kind = 'short';
+            "#,
+        ),
+    },
+    Migration {
+        version: 4,
+        description: "channels table and per-record channel_id",
+        step: MigrationStep::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS channels (
+                channel_id TEXT PRIMARY KEY,
+                name TEXT NOT NULL DEFAULT '',
+                handle TEXT,
+                url TEXT,
+                subscriber_count INTEGER,
+                avatar_path TEXT,
+                banner_path TEXT,
+                last_refreshed_at TEXT
+            );
+
+            ALTER TABLE videos ADD COLUMN channel_id TEXT;
+            ALTER TABLE shorts ADD COLUMN channel_id TEXT;
+            UPDATE videos SET channel_id = json_extract(extras_json, '$.channelId');
+            UPDATE shorts SET channel_id = json_extract(extras_json, '$.channelId');
+            CREATE INDEX IF NOT EXISTS idx_videos_channel ON videos(channel_id);
+            CREATE INDEX IF NOT EXISTS idx_shorts_channel ON shorts(channel_id);
+
+            INSERT OR IGNORE INTO channels (channel_id, name, url, subscriber_count)
+            SELECT channel_id,
+                   COALESCE(json_extract(extras_json, '$.channelFieldNames[0]'), author, ''),
+                   channel_url,
+                   subscriber_count
+            FROM (
+                SELECT channel_id, extras_json, author, channel_url, subscriber_count, upload_date
+                FROM videos
+                UNION ALL
+                SELECT channel_id, extras_json, author, channel_url, subscriber_count, upload_date
+                FROM shorts
             )
-            .await?;
+            WHERE channel_id IS NOT NULL
+            ORDER BY upload_date DESC;
+            "#,
+        ),
+    },
+    Migration {
+        version: 5,
+        description: "playlists with ordered entries",
+        step: MigrationStep::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS playlists (
+                playlist_id TEXT PRIMARY KEY,
+                title TEXT NOT NULL DEFAULT '',
+                description TEXT NOT NULL DEFAULT '',
+                owner TEXT,
+                owner_channel_id TEXT,
+                url TEXT,
+                tracked INTEGER NOT NULL DEFAULT 1,
+                last_refreshed_at TEXT
+            );
 
-        let mut rows = stmt.query([videoid]).await?;
-        let Some(row) = rows.next().await? else {
-            return Ok(None);
-        };
-        let languages_json: String = row.get(0)?;
-        let languages: Vec<SubtitleTrack> =
-            serde_json::from_str(&languages_json).context("parsing subtitle tracks")?;
-        Ok(Some(SubtitleCollection {
-            videoid: videoid.to_owned(),
-            languages,
-        }))
-    }
+            CREATE TABLE IF NOT EXISTS playlist_entries (
+                playlist_id TEXT NOT NULL REFERENCES playlists(playlist_id) ON DELETE CASCADE,
+                position INTEGER NOT NULL,
+                videoid TEXT NOT NULL,
+                kind TEXT NOT NULL DEFAULT 'video',
+                PRIMARY KEY (playlist_id, position)
+            );
 
-    pub async fn list_subtitles(&self) -> Result<Vec<SubtitleCollection>> {
-        let conn = &self.conn;
-        let stmt = conn
-            .prepare(
-                r#"
-                SELECT videoid, languages_json
-                FROM subtitles
-                "#,
+            CREATE INDEX IF NOT EXISTS idx_playlist_entries_videoid ON playlist_entries(videoid);
+            "#,
+        ),
+    },
+    Migration {
+        version: 6,
+        description: "downloaded_at on media for listing sorts",
+        step: MigrationStep::Sql(
+            r#"
+            ALTER TABLE videos ADD COLUMN downloaded_at TEXT;
+            ALTER TABLE shorts ADD COLUMN downloaded_at TEXT;
+            CREATE INDEX IF NOT EXISTS idx_videos_upload_date ON videos(upload_date);
+            CREATE INDEX IF NOT EXISTS idx_shorts_upload_date ON shorts(upload_date);
+            "#,
+        ),
+    },
+    Migration {
+        version: 7,
+        description: "video_stats_history snapshots",
+        step: MigrationStep::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS video_stats_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                videoid TEXT NOT NULL,
+                recorded_at TEXT NOT NULL,
+                views INTEGER,
+                likes INTEGER,
+                dislikes INTEGER,
+                comment_count INTEGER,
+                subscriber_count INTEGER
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_video_stats_history_videoid
+                ON video_stats_history(videoid, id);
+
+            INSERT INTO video_stats_history (
+                videoid, recorded_at, views, likes, dislikes, comment_count, subscriber_count
             )
-            .await?;
+            SELECT videoid,
+                   strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                   views, likes, dislikes,
+                   json_extract(extras_json, '$.commentCount'),
+                   subscriber_count
+            FROM (
+                SELECT videoid, views, likes, dislikes, subscriber_count, extras_json
+                FROM videos
+                UNION ALL
+                SELECT videoid, views, likes, dislikes, subscriber_count, extras_json
+                FROM shorts
+            )
+            WHERE views IS NOT NULL OR likes IS NOT NULL OR dislikes IS NOT NULL
+               OR subscriber_count IS NOT NULL
+               OR json_extract(extras_json, '$.commentCount') IS NOT NULL;
+            "#,
+        ),
+    },
+    Migration {
+        version: 8,
+        description: "blocked_media never re-download list",
+        step: MigrationStep::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS blocked_media (
+                videoid TEXT PRIMARY KEY,
+                blocked_at TEXT NOT NULL,
+                reason TEXT
+            );
+            "#,
+        ),
+    },
+    Migration {
+        version: 9,
+        description: "chapters table",
+        step: MigrationStep::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS chapters (
+                videoid TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                start_time REAL NOT NULL,
+                end_time REAL,
+                title TEXT NOT NULL,
+                PRIMARY KEY (videoid, position)
+            );
+            "#,
+        ),
+    },
+    Migration {
+        version: 10,
+        description: "transcript cues with FTS5 index",
+        step: MigrationStep::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS transcript_cues (
+                cue_id INTEGER PRIMARY KEY,
+                videoid TEXT NOT NULL,
+                language TEXT NOT NULL,
+                start_time REAL NOT NULL,
+                end_time REAL NOT NULL,
+                text TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_transcript_cues_video
+                ON transcript_cues(videoid, language, start_time);
 
-        let mut rows = stmt.query(params![]).await?;
-        let mut results = Vec::new();
-        while let Some(row) = rows.next().await? {
-            let videoid: String = row.get(0)?;
-            let languages_json: String = row.get(1)?;
-            let languages: Vec<SubtitleTrack> =
-                serde_json::from_str(&languages_json).context("parsing subtitle tracks")?;
-            results.push(SubtitleCollection { videoid, languages });
-        }
-        Ok(results)
+            CREATE VIRTUAL TABLE IF NOT EXISTS transcript_index USING fts5(
+                text,
+                tokenize = 'unicode61 remove_diacritics 2'
+            );
+            "#,
+        ),
+    },
+    Migration {
+        version: 11,
+        description: "skip segments table",
+        step: MigrationStep::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS skip_segments (
+                videoid TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                category TEXT NOT NULL,
+                start_time REAL NOT NULL,
+                end_time REAL NOT NULL,
+                source TEXT NOT NULL,
+                PRIMARY KEY (videoid, position)
+            );
+            "#,
+        ),
+    },
+    Migration {
+        version: 12,
+        description: "source checksums table",
+        step: MigrationStep::Sql(
+            r#"
+            CREATE TABLE IF NOT EXISTS source_checksums (
+                path TEXT PRIMARY KEY,
+                videoid TEXT NOT NULL,
+                sha256 TEXT NOT NULL,
+                hashed_at TEXT NOT NULL,
+                verified_at TEXT,
+                status TEXT NOT NULL DEFAULT 'ok',
+                observed_sha256 TEXT
+            );
+            CREATE INDEX IF NOT EXISTS idx_source_checksums_videoid
+                ON source_checksums(videoid);
+            "#,
+        ),
+    },
+    Migration {
+        version: 13,
+        description: "comments deleted upstream",
+        step: MigrationStep::Sql(
+            r#"
+            ALTER TABLE comments ADD COLUMN deleted_upstream_at TEXT;
+            "#,
+        ),
+    },
+    Migration {
+        version: 14,
+        description: "upstream availability of videos and shorts",
+        step: MigrationStep::Sql(
+            r#"
+            ALTER TABLE videos ADD COLUMN availability TEXT;
+            ALTER TABLE videos ADD COLUMN availability_checked_at TEXT;
+            ALTER TABLE shorts ADD COLUMN availability TEXT;
+            ALTER TABLE shorts ADD COLUMN availability_checked_at TEXT;
+            "#,
+        ),
+    },
+    Migration {
+        version: 15,
+        description: "change log for delta sync",
+        step: MigrationStep::CreateChangeLog(&[
+            ("videos", CHANGE_LOG_V15_MEDIA_COLUMNS),
+            ("shorts", CHANGE_LOG_V15_MEDIA_COLUMNS),
+            ("subtitles", "videoid, languages_json"),
+            ("comments", CHANGE_LOG_V15_COMMENT_COLUMNS),
+        ]),
+    },
+    Migration {
+        version: 16,
+        description: "adaptive streams for DASH playback",
+        step: MigrationStep::SqlRebuildingChangeTriggers(
+            r#"
+            ALTER TABLE videos ADD COLUMN adaptive_streams_json TEXT DEFAULT '[]';
+            ALTER TABLE shorts ADD COLUMN adaptive_streams_json TEXT DEFAULT '[]';
+            "#,
+            &[
+                ("videos", CHANGE_LOG_V16_MEDIA_COLUMNS),
+                ("shorts", CHANGE_LOG_V16_MEDIA_COLUMNS),
+                ("subtitles", "videoid, languages_json"),
+                ("comments", CHANGE_LOG_V15_COMMENT_COLUMNS),
+            ],
+        ),
+    },
+];
+
+/// Media columns compared by the change log triggers of schema version 15.
+const CHANGE_LOG_V15_MEDIA_COLUMNS: &str = "videoid, title, description, likes, dislikes, \
+     views, upload_date, author, subscriber_count, duration, duration_text, \
+     channel_url, thumbnail_url, tags_json, thumbnails_json, extras_json, sources_json, \
+     channel_id, availability, availability_checked_at";
+/// Comment columns compared by the change log triggers since version 15.
+const CHANGE_LOG_V15_COMMENT_COLUMNS: &str = "id, videoid, author, text, likes, time_posted, \
+     parent_comment_id, status_likedbycreator, reply_count, deleted_upstream_at";
+/// Version 16 adds `adaptive_streams_json` to the media tables.
+const CHANGE_LOG_V16_MEDIA_COLUMNS: &str = "videoid, title, description, likes, dislikes, \
+     views, upload_date, author, subscriber_count, duration, duration_text, \
+     channel_url, thumbnail_url, tags_json, thumbnails_json, extras_json, sources_json, \
+     channel_id, availability, availability_checked_at, adaptive_streams_json";
+
+/// Column list shared by every query that feeds `row_to_video_record`.
+const VIDEO_COLUMNS: &str = "videoid, title, description, likes, dislikes, views, \
+     upload_date, author, subscriber_count, duration, duration_text, \
+     channel_url, thumbnail_url, tags_json, thumbnails_json, \
+     extras_json, sources_json, channel_id, availability, availability_checked_at, \
+     adaptive_streams_json";
+/// Number of columns in `VIDEO_COLUMNS`; extra selected columns follow them.
+const VIDEO_COLUMN_COUNT: i32 = 21;
+
+/// Column list shared by every query that feeds `row_to_playlist`.
+const PLAYLIST_COLUMNS: &str = "p.playlist_id, p.title, p.description, p.owner, \
+     p.owner_channel_id, p.url, p.tracked, p.last_refreshed_at, \
+     (SELECT COUNT(*) FROM playlist_entries e WHERE e.playlist_id = p.playlist_id)";
+
+/// Column list shared by every query that feeds `row_to_channel`.
+const CHANNEL_COLUMNS: &str = "channel_id, name, handle, url, subscriber_count, \
+     avatar_path, banner_path, last_refreshed_at";
+
+/// Column list shared by every query that feeds `row_to_comment`.
+const COMMENT_COLUMNS: &str = "id, videoid, author, text, likes, time_posted, \
+     parent_comment_id, status_likedbycreator, reply_count, deleted_upstream_at";
+
+/// Tables mirrored into the `changes` log: table, entity, key column and the
+/// column whose presence turns a row into a tombstone. The columns compared
+/// to skip no-op updates come from the migrations; see [`ChangeLogColumns`].
+const CHANGE_TRACKED_TABLES: [(&str, ChangeEntity, &str, Option<&str>); 4] = [
+    ("videos", ChangeEntity::Video, "videoid", None),
+    ("shorts", ChangeEntity::Short, "videoid", None),
+    ("subtitles", ChangeEntity::Subtitles, "videoid", None),
+    (
+        "comments",
+        ChangeEntity::Comment,
+        "id",
+        Some("deleted_upstream_at"),
+    ),
+];
+
+/// Default and maximum number of log entries per `changes_since` call.
+const DEFAULT_CHANGES_LIMIT: u32 = 1000;
+const MAX_CHANGES_LIMIT: u32 = 10_000;
+
+/// Media tables paired with the `kind` label used by the search index.
+const MEDIA_TABLES: [(&str, &str); 2] = [("videos", "video"), ("shorts", "short")];
+
+/// Default and maximum page size for the paginated media listings.
+const DEFAULT_PAGE_LIMIT: u32 = 50;
+const MAX_PAGE_LIMIT: u32 = 500;
+
+/// `skip_segments.source` values. Hand-edited segments win over imports.
+const SEGMENT_SOURCE_MANUAL: &str = "manual";
+const SEGMENT_SOURCE_SPONSORBLOCK: &str = "sponsorblock";
+
+/// Query connections a [`MetadataReader`] opens unless told otherwise.
+pub const DEFAULT_READER_POOL_SIZE: usize = 4;
+
+/// Default and maximum number of hits returned by `MetadataReader::search`.
+const DEFAULT_SEARCH_LIMIT: u32 = 50;
+const MAX_SEARCH_LIMIT: u32 = 200;
+
+/// Brings the database up to `SCHEMA_VERSION`, applying every pending
+/// migration in order. Fails if the DB was created by a newer binary.
+async fn ensure_schema(conn: &Connection) -> Result<()> {
+    ensure_schema_with(conn, "metadata", MIGRATIONS).await
+}
+
+/// Shared migration runner; `label` names the database in error messages.
+/// Also used by the user data store, which keeps its own `schema_version`.
+pub(crate) async fn ensure_schema_with(
+    conn: &Connection,
+    label: &str,
+    migrations: &[Migration],
+) -> Result<()> {
+    let latest = migrations.last().map_or(0, |migration| migration.version);
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            description TEXT NOT NULL DEFAULT '',
+            applied_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        "#,
+    )
+    .await?;
+
+    let current = read_schema_version(conn).await?;
+    if current > latest {
+        bail!(
+            "{label} DB schema version {current} is newer than this binary supports \
+             ({latest}); upgrade NewTube before opening it"
+        );
+    }
+    if current == latest {
+        return Ok(());
     }
 
-    pub async fn get_comments(&self, videoid: &str) -> Result<Vec<CommentRecord>> {
-        let conn = &self.conn;
-        let stmt = conn
-            .prepare(
-                r#"
-                SELECT id, videoid, author, text, likes, time_posted,
-                       parent_comment_id, status_likedbycreator, reply_count
-                FROM comments
-                WHERE videoid = ?1
-                  AND (
-                    EXISTS (SELECT 1 FROM videos WHERE videoid = ?1)
-                    OR EXISTS (SELECT 1 FROM shorts WHERE videoid = ?1)
-                  )
-                ORDER BY datetime(time_posted) IS NULL, datetime(time_posted) ASC, rowid ASC
-                "#,
+    for migration in migrations {
+        apply_migration(conn, migration).await.with_context(|| {
+            format!(
+                "applying {label} migration {} ({})",
+                migration.version, migration.description
             )
-            .await?;
+        })?;
+    }
 
-        let mut comments = Vec::new();
-        let mut rows = stmt.query([videoid]).await?;
-        while let Some(row) = rows.next().await? {
-            comments.push(row_to_comment(&row)?);
+    Ok(())
+}
+
+/// Returns the highest applied migration version, or 0 for a fresh DB.
+pub(crate) async fn read_schema_version(conn: &Connection) -> Result<i64> {
+    let mut rows = conn
+        .query(
+            "SELECT COALESCE(MAX(version), 0) FROM schema_version",
+            params![],
+        )
+        .await?;
+    let row = rows.next().await?.context("missing schema_version row")?;
+    Ok(row.get(0)?)
+}
+
+/// Runs a single migration inside an IMMEDIATE transaction. The version is
+/// re-checked after taking the write lock so concurrent openers (backend and
+/// downloader) never apply the same step twice.
+async fn apply_migration(conn: &Connection, migration: &Migration) -> Result<()> {
+    let tx = conn
+        .transaction_with_behavior(TransactionBehavior::Immediate)
+        .await?;
+    if read_schema_version(&tx).await? >= migration.version {
+        return Ok(());
+    }
+
+    match migration.step {
+        MigrationStep::Sql(sql) => tx.execute_batch(sql).await.map(|_| ())?,
+        MigrationStep::DropLegacyCommentsForeignKey => drop_legacy_comments_fk(&tx).await?,
+        MigrationStep::CreateChangeLog(columns) => create_change_log(&tx, columns).await?,
+        MigrationStep::SqlRebuildingChangeTriggers(sql, columns) => {
+            tx.execute_batch(sql).await?;
+            rebuild_change_triggers(&tx, columns).await?;
         }
-        Ok(comments)
     }
 
-    pub async fn list_all_comments(&self) -> Result<Vec<CommentRecord>> {
-        let conn = &self.conn;
-        let stmt = conn
-            .prepare(
-                r#"
-                SELECT id, videoid, author, text, likes, time_posted,
-                       parent_comment_id, status_likedbycreator, reply_count
-                FROM comments
-                WHERE videoid IN (
-                    SELECT videoid FROM videos
-                    UNION
-                    SELECT videoid FROM shorts
-                )
-                ORDER BY datetime(time_posted) IS NULL, datetime(time_posted) ASC, rowid ASC
-                "#,
-            )
-            .await?;
+    tx.execute(
+        "INSERT INTO schema_version (version, description) VALUES (?1, ?2)",
+        params![migration.version, migration.description],
+    )
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
 
-        let mut rows = stmt.query(params![]).await?;
-        let mut comments = Vec::new();
-        while let Some(row) = rows.next().await? {
-            comments.push(row_to_comment(&row)?);
+/// Creates the `changes` log behind delta sync. Triggers keep one row per
+/// tracked key and move it to a fresh `seq` on every insert, real update or
+/// delete, leaving a tombstone (`deleted = 1`) for deletions. Existing rows
+/// are seeded so a client syncing from 0 receives the whole library.
+async fn create_change_log(conn: &Connection, columns: ChangeLogColumns) -> Result<()> {
+    let mut sql = String::from(
+        r#"
+        CREATE TABLE IF NOT EXISTS changes (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            entity TEXT NOT NULL,
+            key TEXT NOT NULL,
+            deleted INTEGER NOT NULL DEFAULT 0,
+            changed_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+            UNIQUE (entity, key)
+        );
+        "#,
+    );
+    for (table, entity, key, tombstone_column) in CHANGE_TRACKED_TABLES {
+        let seed_deleted =
+            tombstone_column.map_or("0".to_owned(), |column| format!("{column} IS NOT NULL"));
+        sql.push_str(&format!(
+            r#"
+            INSERT INTO changes (entity, key, deleted)
+            SELECT '{entity}', {key}, {seed_deleted} FROM {table} ORDER BY rowid;
+            "#,
+            entity = entity.as_str(),
+        ));
+    }
+    sql.push_str(&change_triggers_sql(columns)?);
+    conn.execute_batch(&sql).await?;
+    Ok(())
+}
+
+/// Drops and recreates the change log triggers so they compare `columns`.
+async fn rebuild_change_triggers(conn: &Connection, columns: ChangeLogColumns) -> Result<()> {
+    let mut sql = String::new();
+    for (table, ..) in CHANGE_TRACKED_TABLES {
+        for event in ["insert", "update", "delete"] {
+            sql.push_str(&format!(
+                "DROP TRIGGER IF EXISTS changes_{table}_{event};\n"
+            ));
         }
-        Ok(comments)
     }
+    sql.push_str(&change_triggers_sql(columns)?);
+    conn.execute_batch(&sql).await?;
+    Ok(())
+}
 
-    pub async fn data_version(&self) -> Result<i64> {
-        let conn = &self.conn;
-        let mut rows = conn.query("PRAGMA data_version", params![]).await?;
-        let row = rows.next().await?.context("missing data_version row")?;
-        Ok(row.get(0)?)
+/// Triggers that keep one `changes` row per tracked row, bumping its `seq`
+/// on every insert, real update (of `columns`) and delete.
+fn change_triggers_sql(columns: ChangeLogColumns) -> Result<String> {
+    let mut sql = String::new();
+    for (table, entity, key, tombstone_column) in CHANGE_TRACKED_TABLES {
+        let entity = entity.as_str();
+        let columns = columns
+            .iter()
+            .find_map(|(name, columns)| (*name == table).then_some(*columns))
+            .with_context(|| format!("no change log columns for {table}"))?;
+        let deleted = |prefix: &str| {
+            tombstone_column.map_or("0".to_owned(), |column| {
+                format!("{prefix}{column} IS NOT NULL")
+            })
+        };
+        let qualified = |prefix: &str| {
+            columns
+                .split(',')
+                .map(|column| format!("{prefix}{}", column.trim()))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let record = |row: &str, deleted: &str| {
+            format!(
+                "DELETE FROM changes WHERE entity = '{entity}' AND key = {row}.{key};
+                INSERT INTO changes (entity, key, deleted) VALUES ('{entity}', {row}.{key}, {deleted});"
+            )
+        };
+        sql.push_str(&format!(
+            r#"
+            CREATE TRIGGER IF NOT EXISTS changes_{table}_insert AFTER INSERT ON {table}
+            BEGIN
+                {on_insert}
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS changes_{table}_update AFTER UPDATE ON {table}
+            WHEN ({old_columns}) IS NOT ({new_columns})
+            BEGIN
+                {on_update}
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS changes_{table}_delete AFTER DELETE ON {table}
+            BEGIN
+                {on_delete}
+            END;
+            "#,
+            on_insert = record("NEW", &deleted("NEW.")),
+            old_columns = qualified("OLD."),
+            new_columns = qualified("NEW."),
+            on_update = record("NEW", &deleted("NEW.")),
+            on_delete = record("OLD", "1"),
+        ));
     }
+    Ok(sql)
+}
 
-    async fn fetch_videos_from(&self, table: &str) -> Result<Vec<VideoRecord>> {
-        let conn = &self.conn;
-        let stmt = conn
-            .prepare(&format!(
-                r#"
-                SELECT videoid, title, description, likes, dislikes, views,
-                       upload_date, author, subscriber_count, duration, duration_text,
-                       channel_url, thumbnail_url, tags_json, thumbnails_json,
-                       extras_json, sources_json
-                FROM {table}
-                ORDER BY upload_date DESC, rowid DESC
-                "#
-            ))
-            .await?;
-
-        let mut rows = stmt.query(params![]).await?;
-        let mut records = Vec::new();
-        while let Some(row) = rows.next().await? {
-            records.push(row_to_video_record(&row)?);
+/// Early releases declared `comments.videoid` as a foreign key to `videos`,
+/// which rejected comments for shorts. Rebuild the table without it.
+async fn drop_legacy_comments_fk(conn: &Connection) -> Result<()> {
+    let mut rows = conn
+        .query("PRAGMA foreign_key_list(comments)", params![])
+        .await?;
+    let mut has_video_fk = false;
+    while let Some(row) = rows.next().await? {
+        let table: String = row.get(2)?;
+        if table == "videos" {
+            has_video_fk = true;
+            break;
         }
-        Ok(records)
     }
+    // Release the pragma cursor before rebuilding the table it reads from.
+    drop(rows);
 
-    async fn fetch_single(&self, table: &str, videoid: &str) -> Result<Option<VideoRecord>> {
-        let conn = &self.conn;
-        let stmt = conn
-            .prepare(&format!(
+    if !has_video_fk {
+        return Ok(());
+    }
+
+    conn.execute_batch(
+        r#"
+        CREATE TABLE IF NOT EXISTS comments_new (
+            id TEXT PRIMARY KEY,
+            videoid TEXT NOT NULL,
+            author TEXT DEFAULT '',
+            text TEXT DEFAULT '',
+            likes INTEGER,
+            time_posted TEXT,
+            parent_comment_id TEXT,
+            status_likedbycreator INTEGER NOT NULL DEFAULT 0,
+            reply_count INTEGER
+        );
+        INSERT INTO comments_new (
+            id, videoid, author, text, likes, time_posted,
+            parent_comment_id, status_likedbycreator, reply_count
+        )
+        SELECT
+            id, videoid, author, text, likes, time_posted,
+            parent_comment_id, status_likedbycreator, reply_count
+        FROM comments;
+        DROP TABLE comments;
+        ALTER TABLE comments_new RENAME TO comments;
+        CREATE INDEX IF NOT EXISTS idx_comments_videoid ON comments(videoid);
+        CREATE INDEX IF NOT EXISTS idx_comments_parent ON comments(parent_comment_id);
+        "#,
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Rebuilds the full-text entries for `videoid` from the current contents of
+/// `videos`/`shorts` and `comments`. Called inside the write transaction of
+/// every mutation that touches indexed columns.
+async fn refresh_search_entry(statements: &mut StatementCache<'_>, videoid: &str) -> Result<()> {
+    statements
+        .execute(
+        "DELETE FROM search_index WHERE rowid IN (SELECT docid FROM search_documents WHERE videoid = ?1)",
+        params![videoid],
+    )
+    .await?;
+
+    for (table, kind) in MEDIA_TABLES {
+        statements
+            .execute(
+                &format!(
+                    "INSERT OR IGNORE INTO search_documents (videoid, kind) \
+                 SELECT videoid, '{kind}' FROM {table} WHERE videoid = ?1"
+                ),
+                params![videoid],
+            )
+            .await?;
+        statements
+            .execute(
+            &format!(
                 r#"
-                SELECT videoid, title, description, likes, dislikes, views,
-                       upload_date, author, subscriber_count, duration, duration_text,
-                       channel_url, thumbnail_url, tags_json, thumbnails_json,
-                       extras_json, sources_json
-                FROM {table}
-                WHERE videoid = ?1
+                INSERT INTO search_index (rowid, title, description, tags, author, comments)
+                SELECT d.docid, m.title, m.description, m.tags_json, m.author,
+                       (SELECT group_concat(c.text, ' ') FROM comments c WHERE c.videoid = m.videoid)
+                FROM {table} m
+                JOIN search_documents d ON d.videoid = m.videoid AND d.kind = '{kind}'
+                WHERE m.videoid = ?1
                 "#
-            ))
-            .await?;
+            ),
+            params![videoid],
+        )
+        .await?;
+    }
 
-        let mut rows = stmt.query([videoid]).await?;
-        if let Some(row) = rows.next().await? {
-            Ok(Some(row_to_video_record(&row)?))
-        } else {
-            Ok(None)
-        }
+    Ok(())
+}
+
+/// Serializes the last row's sort key into an opaque, URL-safe cursor. The sort
+/// and direction are embedded so a cursor cannot be replayed against a
+/// different ordering.
+fn encode_cursor(sort: MediaSort, descending: bool, value: &libsql::Value, rowid: i64) -> String {
+    let key = match value {
+        libsql::Value::Integer(number) => number.to_string(),
+        libsql::Value::Real(number) => (*number as i64).to_string(),
+        libsql::Value::Text(text) => text.clone(),
+        _ => String::new(),
+    };
+    let direction = if descending { "d" } else { "a" };
+    let raw = format!("{}:{direction}:{rowid}:{key}", sort.name());
+    raw.bytes().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Reverses `encode_cursor`, rejecting cursors minted for another sort.
+fn decode_cursor(cursor: &str, sort: MediaSort, descending: bool) -> Result<(libsql::Value, i64)> {
+    let invalid = || anyhow::Error::new(InvalidCursor);
+    if !cursor.len().is_multiple_of(2) || !cursor.is_ascii() {
+        return Err(invalid());
+    }
+    let bytes = (0..cursor.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&cursor[index..index + 2], 16))
+        .collect::<std::result::Result<Vec<u8>, _>>()
+        .map_err(|_| invalid())?;
+    let raw = String::from_utf8(bytes).map_err(|_| invalid())?;
+
+    let mut parts = raw.splitn(4, ':');
+    let (Some(name), Some(direction), Some(rowid), Some(key)) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid());
+    };
+    let expected_direction = if descending { "d" } else { "a" };
+    if name != sort.name() || direction != expected_direction {
+        return Err(invalid());
     }
+    let rowid: i64 = rowid.parse().map_err(|_| invalid())?;
+    let value = if sort.is_numeric() {
+        libsql::Value::Integer(key.parse().map_err(|_| invalid())?)
+    } else {
+        libsql::Value::Text(key.to_string())
+    };
+    Ok((value, rowid))
 }
 
-/// Converts a SQL row into a `VideoRecord`, deserializing the Vec/JSON fields.
-fn row_to_video_record(row: &Row) -> Result<VideoRecord> {
-    // Column order must match the SELECT statements in fetch_videos_from/fetch_single.
-    let tags_json: String = row.get(13)?;
-    let thumbnails_json: String = row.get(14)?;
-    let extras_json: String = row.get(15)?;
-    let sources_json: String = row.get(16)?;
+/// Appends a `video_stats_history` row when the counters differ from the most
+/// recent snapshot. Records without any counters are skipped.
+async fn record_stats_snapshot(
+    statements: &mut StatementCache<'_>,
+    record: &VideoRecord,
+) -> Result<()> {
+    let comment_count = record
+        .extras
+        .get("commentCount")
+        .and_then(serde_json::Value::as_i64);
+    if record.views.is_none()
+        && record.likes.is_none()
+        && record.dislikes.is_none()
+        && comment_count.is_none()
+        && record.subscriber_count.is_none()
+    {
+        return Ok(());
+    }
 
-    let tags: Vec<String> = serde_json::from_str(&tags_json).context("parsing stored tags JSON")?;
-    let thumbnails: Vec<String> =
-        serde_json::from_str(&thumbnails_json).context("parsing stored thumbnails JSON")?;
-    let extras: serde_json::Value =
-        serde_json::from_str(&extras_json).context("parsing stored extras JSON")?;
-    let sources: Vec<VideoSource> =
-        serde_json::from_str(&sources_json).context("parsing stored sources JSON")?;
+    statements
+        .execute(
+            r#"
+        INSERT INTO video_stats_history (
+            videoid, recorded_at, views, likes, dislikes, comment_count, subscriber_count
+        )
+        SELECT ?1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), ?2, ?3, ?4, ?5, ?6
+        WHERE NOT EXISTS (
+            SELECT 1 FROM (
+                SELECT views, likes, dislikes, comment_count, subscriber_count
+                FROM video_stats_history
+                WHERE videoid = ?1
+                ORDER BY id DESC
+                LIMIT 1
+            ) last
+            WHERE last.views IS ?2
+              AND last.likes IS ?3
+              AND last.dislikes IS ?4
+              AND last.comment_count IS ?5
+              AND last.subscriber_count IS ?6
+        )
+        "#,
+            params![
+                record.videoid.as_str(),
+                record.views,
+                record.likes,
+                record.dislikes,
+                comment_count,
+                record.subscriber_count,
+            ],
+        )
+        .await?;
+    Ok(())
+}
 
-    Ok(VideoRecord {
-        videoid: row.get(0)?,
-        title: row.get(1)?,
-        description: row.get(2)?,
-        likes: row.get(3)?,
-        dislikes: row.get(4)?,
-        views: row.get(5)?,
-        upload_date: row.get(6)?,
-        author: row.get(7)?,
-        subscriber_count: row.get(8)?,
-        duration: row.get(9)?,
-        duration_text: row.get(10)?,
-        channel_url: row.get(11)?,
-        thumbnail_url: row.get(12)?,
-        tags,
-        thumbnails,
-        extras,
-        sources,
-    })
+/// Turns free-form user input into a safe FTS5 MATCH expression. Every word is
+/// quoted so operators/punctuation cannot break the query, and the last word
+/// is treated as a prefix so search-as-you-type works.
+fn fts_match_expression(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .filter(|term| term.chars().any(char::is_alphanumeric))
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect();
+    let (last, rest) = terms.split_last()?;
+    let mut expression = rest.join(" ");
+    if !expression.is_empty() {
+        expression.push(' ');
+    }
+    expression.push_str(last);
+    expression.push('*');
+    Some(expression)
 }
 
-/// Converts a SQL row into a `CommentRecord` while normalizing the boolean flag
-/// stored as an INTEGER in SQLite.
-fn row_to_comment(row: &Row) -> Result<CommentRecord> {
-    Ok(CommentRecord {
-        id: row.get(0)?,
-        videoid: row.get(1)?,
-        author: row.get(2)?,
-        text: row.get(3)?,
-        likes: row.get(4)?,
-        time_posted: row.get(5)?,
-        parent_comment_id: row.get(6)?,
-        status_likedbycreator: row.get::<i64>(7).map(|value| value != 0)?,
-        reply_count: row.get(8)?,
-    })
+/// Wrapper around the SQLite-compatible connection that performs read/write operations.
+#[derive(Debug)]
+pub struct MetadataStore {
+    conn: Connection,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serde_json::Value;
-    use std::path::PathBuf;
-    use tempfile::tempdir;
+impl MetadataStore {
+    /// Opens (and if necessary creates) the SQLite DB and ensures the expected
+    /// schema exists.
+    pub async fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating metadata directory {}", parent.display()))?;
+        }
 
-    /// Utility builder so every test can generate a fully populated video row
-    /// without repeating dozens of assignments. Individual tests tweak the
-    /// resulting struct when they need to exercise specific fields.
-    fn sample_video(id: &str) -> VideoRecord {
-        VideoRecord {
-            videoid: id.to_owned(),
-            title: format!("Video {id}"),
-            description: "desc".into(),
-            likes: Some(1),
-            dislikes: Some(0),
-            views: Some(42),
-            upload_date: Some("2024-01-01".into()),
-            author: Some("Author".into()),
-            subscriber_count: Some(1000),
-            duration: Some(120),
-            duration_text: Some("2:00".into()),
-            channel_url: Some("https://example.com".into()),
-            thumbnail_url: Some("thumb.jpg".into()),
-            tags: vec!["tech".into()],
-            thumbnails: vec!["thumb.jpg".into()],
-            extras: serde_json::json!({"kind": "demo"}),
-            sources: vec![VideoSource {
-                format_id: "1080p".into(),
-                quality_label: Some("1080p".into()),
-                width: Some(1920),
-                height: Some(1080),
-                fps: Some(30.0),
-                mime_type: Some("video/mp4".into()),
-                ext: Some("mp4".into()),
-                file_size: Some(1_000_000),
-                url: "https://cdn.example/video.mp4".into(),
-                path: Some("/videos/video.mp4".into()),
-            }],
+        let db = Builder::new_local(path)
+            .build()
+            .await
+            .with_context(|| format!("opening metadata DB {}", path.display()))?;
+
+        let conn = db.connect()?;
+        configure_connection(&conn).await?;
+
+        let store = Self { conn };
+        store.ensure_tables().await?;
+        Ok(store)
+    }
+
+    /// Applies any pending schema migrations.
+    async fn ensure_tables(&self) -> Result<()> {
+        ensure_schema(&self.conn).await
+    }
+
+    /// Returns the schema version currently recorded in the DB.
+    pub async fn schema_version(&self) -> Result<i64> {
+        read_schema_version(&self.conn).await
+    }
+
+    /// Inserts or updates a long-form video entry.
+    pub async fn upsert_video(&self, record: &VideoRecord) -> Result<()> {
+        let mut batch = self.batch();
+        batch.upsert_video(record.clone());
+        batch.commit().await
+    }
+
+    pub async fn upsert_short(&self, record: &VideoRecord) -> Result<()> {
+        let mut batch = self.batch();
+        batch.upsert_short(record.clone());
+        batch.commit().await
+    }
+
+    /// Inserts or merges a channel row. Empty names and `None` fields keep
+    /// whatever was stored previously.
+    pub async fn upsert_channel(&self, channel: &ChannelRecord) -> Result<()> {
+        let mut batch = self.batch();
+        batch.upsert_channel(channel.clone());
+        batch.commit().await
+    }
+
+    /// Starts an empty [`WriteBatch`]. Nothing touches the database until
+    /// [`WriteBatch::commit`] runs.
+    pub fn batch(&self) -> WriteBatch<'_> {
+        WriteBatch {
+            store: self,
+            writes: Vec::new(),
+            failed_commits: 0,
         }
     }
 
-    /// Helper that produces deterministic comment rows; individual tests can
-    /// tweak author/text/timestamps without redefining the entire struct.
-    fn sample_comment(id: &str, videoid: &str) -> CommentRecord {
-        CommentRecord {
-            id: id.into(),
-            videoid: videoid.into(),
-            author: format!("author-{id}"),
-            text: format!("text-{id}"),
-            likes: Some(0),
-            time_posted: Some("2024-01-01T00:00:00Z".into()),
-            parent_comment_id: None,
-            status_likedbycreator: false,
-            reply_count: Some(0),
+    /// Removes a video or short together with its subtitles, comments, stats
+    /// history, chapters, transcripts, skip segments and search rows. Playlist
+    /// entries are kept because they mirror upstream order. Returns whether a
+    /// media row existed.
+    pub async fn delete_media(&self, videoid: &str) -> Result<bool> {
+        let tx = self.conn.transaction().await?;
+        let mut removed = 0;
+        for (table, _) in MEDIA_TABLES {
+            removed += tx
+                .execute(
+                    &format!("DELETE FROM {table} WHERE videoid = ?1"),
+                    [videoid],
+                )
+                .await?;
+        }
+        let mut statements = StatementCache::new(&tx);
+        clear_transcripts(&mut statements, videoid).await?;
+        for table in [
+            "subtitles",
+            "comments",
+            "video_stats_history",
+            "chapters",
+            "skip_segments",
+            "source_checksums",
+        ] {
+            statements
+                .execute(
+                    &format!("DELETE FROM {table} WHERE videoid = ?1"),
+                    [videoid],
+                )
+                .await?;
         }
+        refresh_search_entry(&mut statements, videoid).await?;
+        drop(statements);
+        tx.commit().await?;
+        Ok(removed > 0)
+    }
+
+    /// Adds an id to the "never re-download" list. Re-blocking keeps the
+    /// original timestamp but updates the reason.
+    pub async fn block_media(&self, videoid: &str, reason: Option<&str>) -> Result<()> {
+        self.conn
+            .execute(
+                r#"
+                INSERT INTO blocked_media (videoid, blocked_at, reason)
+                VALUES (?1, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), ?2)
+                ON CONFLICT(videoid) DO UPDATE SET
+                    reason = COALESCE(excluded.reason, blocked_media.reason)
+                "#,
+                params![videoid, reason],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Puts an entry back on the "never re-download" list with its original
+    /// timestamp and reason, replacing any existing entry for the id.
+    pub async fn restore_blocked_media(&self, entry: &BlockedMedia) -> Result<()> {
+        self.conn
+            .execute(
+                r#"
+                INSERT OR REPLACE INTO blocked_media (videoid, blocked_at, reason)
+                VALUES (?1, ?2, ?3)
+                "#,
+                params![
+                    entry.videoid.as_str(),
+                    entry.blocked_at.as_str(),
+                    entry.reason.as_deref(),
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Records the outcome of re-hashing the file at `path`. `observed` is
+    /// the hash the scrub computed when it differs from the recorded one.
+    pub async fn record_scrub_result(
+        &self,
+        path: &str,
+        status: ChecksumStatus,
+        observed: Option<&str>,
+    ) -> Result<bool> {
+        let updated = self
+            .conn
+            .execute(
+                r#"
+                UPDATE source_checksums
+                SET verified_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                    status = ?2,
+                    observed_sha256 = ?3
+                WHERE path = ?1
+                "#,
+                params![path, status.as_str(), observed],
+            )
+            .await?;
+        Ok(updated > 0)
+    }
+
+    /// Removes an id from the "never re-download" list.
+    pub async fn unblock_media(&self, videoid: &str) -> Result<bool> {
+        let removed = self
+            .conn
+            .execute("DELETE FROM blocked_media WHERE videoid = ?1", [videoid])
+            .await?;
+        Ok(removed > 0)
+    }
+
+    /// Returns every blocked id so the downloader can skip them up front.
+    pub async fn blocked_media_ids(&self) -> Result<HashSet<String>> {
+        let mut rows = self
+            .conn
+            .query("SELECT videoid FROM blocked_media", params![])
+            .await?;
+        let mut ids = HashSet::new();
+        while let Some(row) = rows.next().await? {
+            ids.insert(row.get(0)?);
+        }
+        Ok(ids)
+    }
+
+    /// Stores a playlist and replaces its entries in one transaction, so
+    /// removals and reordering upstream are mirrored exactly.
+    pub async fn replace_playlist(
+        &self,
+        playlist: &PlaylistRecord,
+        entries: &[PlaylistEntry],
+    ) -> Result<()> {
+        let tx = self.conn.transaction().await?;
+        tx.execute(
+            r#"
+            INSERT INTO playlists (
+                playlist_id, title, description, owner, owner_channel_id,
+                url, tracked, last_refreshed_at
+            ) VALUES (
+                :playlist_id, :title, :description, :owner, :owner_channel_id,
+                :url, :tracked, :last_refreshed_at
+            )
+            ON CONFLICT(playlist_id) DO UPDATE SET
+                title = excluded.title,
+                description = excluded.description,
+                owner = excluded.owner,
+                owner_channel_id = excluded.owner_channel_id,
+                url = excluded.url,
+                tracked = excluded.tracked,
+                last_refreshed_at = excluded.last_refreshed_at
+            "#,
+            params![
+                playlist.playlist_id.as_str(),
+                playlist.title.as_str(),
+                playlist.description.as_str(),
+                playlist.owner.as_deref(),
+                playlist.owner_channel_id.as_deref(),
+                playlist.url.as_deref(),
+                playlist.tracked as i64,
+                playlist.last_refreshed_at.as_deref(),
+            ],
+        )
+        .await?;
+
+        tx.execute(
+            "DELETE FROM playlist_entries WHERE playlist_id = ?1",
+            params![playlist.playlist_id.as_str()],
+        )
+        .await?;
+        for entry in entries {
+            tx.execute(
+                r#"
+                INSERT INTO playlist_entries (playlist_id, position, videoid, kind)
+                VALUES (?1, ?2, ?3, ?4)
+                "#,
+                params![
+                    playlist.playlist_id.as_str(),
+                    entry.position,
+                    entry.videoid.as_str(),
+                    entry.kind.as_str(),
+                ],
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Stores subtitle metadata in the DB.
+    pub async fn upsert_subtitles(&self, subtitles: &SubtitleCollection) -> Result<()> {
+        let mut batch = self.batch();
+        batch.upsert_subtitles(subtitles.clone());
+        batch.commit().await
+    }
+
+    /// Replaces every stored comment for `videoid` in one transaction so we do
+    /// not mix old and new comment trees.
+    pub async fn replace_comments(&self, videoid: &str, comments: &[CommentRecord]) -> Result<()> {
+        let mut batch = self.batch();
+        batch.replace_comments(videoid, comments.to_vec());
+        batch.commit().await
+    }
+
+    /// Merges a fresh comment fetch for `videoid` into the stored ones; see
+    /// [`WriteBatch::merge_comments`].
+    pub async fn merge_comments(&self, videoid: &str, comments: &[CommentRecord]) -> Result<()> {
+        let mut batch = self.batch();
+        batch.merge_comments(videoid, comments.to_vec());
+        batch.commit().await
+    }
+
+    /// Replaces the chapter list for `videoid`. An empty slice clears it, so a
+    /// re-edited description without timestamps drops stale chapters.
+    pub async fn replace_chapters(&self, videoid: &str, chapters: &[Chapter]) -> Result<()> {
+        let mut batch = self.batch();
+        batch.replace_chapters(videoid, chapters.to_vec());
+        batch.commit().await
+    }
+
+    /// Replaces every transcript of `videoid` with `transcripts` and keeps the
+    /// cue search index in step. Languages missing from the slice are dropped.
+    pub async fn replace_transcripts(
+        &self,
+        videoid: &str,
+        transcripts: &[Transcript],
+    ) -> Result<()> {
+        let mut batch = self.batch();
+        batch.replace_transcripts(videoid, transcripts.to_vec());
+        batch.commit().await
+    }
+
+    /// Stores hand-edited skip segments for `videoid`, replacing whatever was
+    /// there. Later dump imports leave these videos alone; an empty slice
+    /// clears the list and lets the next import fill it again.
+    pub async fn set_segments(&self, videoid: &str, segments: &[SkipSegment]) -> Result<()> {
+        let tx = self.conn.transaction().await?;
+        write_segments(&tx, videoid, segments, SEGMENT_SOURCE_MANUAL).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Replaces the imported (SponsorBlock) segments of `videoid`. Returns
+    /// `false` without touching anything when the video has manual edits.
+    pub async fn import_segments(&self, videoid: &str, segments: &[SkipSegment]) -> Result<bool> {
+        let tx = self.conn.transaction().await?;
+        let mut rows = tx
+            .query(
+                "SELECT 1 FROM skip_segments WHERE videoid = ?1 AND source = ?2 LIMIT 1",
+                params![videoid, SEGMENT_SOURCE_MANUAL],
+            )
+            .await?;
+        let edited = rows.next().await?.is_some();
+        drop(rows);
+        if edited {
+            return Ok(false);
+        }
+        write_segments(&tx, videoid, segments, SEGMENT_SOURCE_SPONSORBLOCK).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+}
+
+/// Queue of media, channel, subtitle, comment, chapter, transcript, stats
+/// history and availability writes that [`WriteBatch::commit`] applies in a
+/// single transaction.
+///
+/// Writes are buffered in memory, so the write lock is only held while the
+/// batch is applied and not while callers fetch data from yt-dlp. Each SQL
+/// statement is prepared once per commit and reused for every row, and the
+/// search index is rebuilt once per touched video instead of once per write.
+/// Every queued write runs inside its own savepoint, so one bad row (say a
+/// duplicate comment id) only loses that write, not the whole batch.
+pub struct WriteBatch<'a> {
+    store: &'a MetadataStore,
+    writes: Vec<PendingWrite>,
+    failed_commits: u32,
+}
+
+enum PendingWrite {
+    Media {
+        table: &'static str,
+        record: Box<VideoRecord>,
+    },
+    Channel(ChannelRecord),
+    Subtitles(SubtitleCollection),
+    Comments {
+        videoid: String,
+        comments: Vec<CommentRecord>,
+        mode: CommentWrite,
+    },
+    Chapters {
+        videoid: String,
+        chapters: Vec<Chapter>,
+    },
+    Transcripts {
+        videoid: String,
+        transcripts: Vec<Transcript>,
+    },
+    StatsHistory {
+        videoid: String,
+        snapshots: Vec<VideoStatsSnapshot>,
+    },
+    Availability {
+        videoid: String,
+        availability: Availability,
+    },
+}
+
+/// How a queued comment list is applied to the stored comments of a video.
+enum CommentWrite {
+    /// Drop the stored comments and insert the list.
+    Replace,
+    /// Upsert the list and tombstone stored comments missing from it.
+    Merge,
+    /// Upsert the list and leave every other stored comment alone.
+    Upsert,
+}
+
+impl WriteBatch<'_> {
+    pub fn upsert_video(&mut self, record: VideoRecord) {
+        self.writes.push(PendingWrite::Media {
+            table: "videos",
+            record: Box::new(record),
+        });
+    }
+
+    pub fn upsert_short(&mut self, record: VideoRecord) {
+        self.writes.push(PendingWrite::Media {
+            table: "shorts",
+            record: Box::new(record),
+        });
+    }
+
+    pub fn upsert_channel(&mut self, channel: ChannelRecord) {
+        self.writes.push(PendingWrite::Channel(channel));
+    }
+
+    pub fn upsert_subtitles(&mut self, subtitles: SubtitleCollection) {
+        self.writes.push(PendingWrite::Subtitles(subtitles));
+    }
+
+    pub fn replace_comments(&mut self, videoid: &str, comments: Vec<CommentRecord>) {
+        self.writes.push(PendingWrite::Comments {
+            videoid: videoid.to_string(),
+            comments,
+            mode: CommentWrite::Replace,
+        });
+    }
+
+    /// Upserts `comments` by id, refreshing text, likes and reply counts, and
+    /// stamps `deleted_upstream_at` on stored comments of `videoid` that the
+    /// fetch no longer returned instead of deleting them. A comment that
+    /// shows up again loses the stamp. Only queue this for the result of a
+    /// complete, successful fetch; anything less would tombstone comments
+    /// that are still up.
+    pub fn merge_comments(&mut self, videoid: &str, comments: Vec<CommentRecord>) {
+        self.writes.push(PendingWrite::Comments {
+            videoid: videoid.to_string(),
+            comments,
+            mode: CommentWrite::Merge,
+        });
+    }
+
+    /// Upserts `comments` by id like [`WriteBatch::merge_comments`] but never
+    /// tombstones the stored comments that are missing from the list, for
+    /// lists that may be stale or partial (such as a dump read from disk).
+    pub fn upsert_comments(&mut self, videoid: &str, comments: Vec<CommentRecord>) {
+        self.writes.push(PendingWrite::Comments {
+            videoid: videoid.to_string(),
+            comments,
+            mode: CommentWrite::Upsert,
+        });
+    }
+
+    pub fn replace_chapters(&mut self, videoid: &str, chapters: Vec<Chapter>) {
+        self.writes.push(PendingWrite::Chapters {
+            videoid: videoid.to_string(),
+            chapters,
+        });
+    }
+
+    pub fn replace_transcripts(&mut self, videoid: &str, transcripts: Vec<Transcript>) {
+        self.writes.push(PendingWrite::Transcripts {
+            videoid: videoid.to_string(),
+            transcripts,
+        });
+    }
+
+    /// Replaces the whole counter history of `videoid`, e.g. when restoring
+    /// an export. Queue it after the media upsert, which records a snapshot
+    /// of its own.
+    pub fn replace_stats_history(&mut self, videoid: &str, snapshots: Vec<VideoStatsSnapshot>) {
+        self.writes.push(PendingWrite::StatsHistory {
+            videoid: videoid.to_string(),
+            snapshots,
+        });
+    }
+
+    /// Records what a refresh learned about `videoid` upstream without
+    /// touching the rest of its metadata, so a video that went private keeps
+    /// its last good title, description and counters.
+    pub fn set_availability(&mut self, videoid: &str, availability: Availability) {
+        self.writes.push(PendingWrite::Availability {
+            videoid: videoid.to_string(),
+            availability,
+        });
+    }
+
+    /// Number of queued writes.
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// Commits in a row that failed at the database level and left the
+    /// writes queued. Reset by the next commit that goes through.
+    pub fn failed_commits(&self) -> u32 {
+        self.failed_commits
+    }
+
+    /// Applies every queued write in one transaction and empties the batch so
+    /// it can be reused. Writes that fail are rolled back individually while
+    /// the rest are committed; the first failure is then returned as the
+    /// error. Database-level failures (such as a lock that outlasts the busy
+    /// timeout) roll back the whole batch and keep it queued for a retry.
+    pub async fn commit(&mut self) -> Result<()> {
+        if self.writes.is_empty() {
+            return Ok(());
+        }
+        let failures = match self.apply_queued().await {
+            Ok(failures) => failures,
+            Err(err) => {
+                self.failed_commits += 1;
+                return Err(err);
+            }
+        };
+        self.failed_commits = 0;
+        let writes = std::mem::take(&mut self.writes);
+
+        let failed = failures.len();
+        match failures.into_iter().next() {
+            None => Ok(()),
+            Some(err) => {
+                Err(err.context(format!("{failed} of {} queued writes failed", writes.len())))
+            }
+        }
+    }
+
+    /// Runs the queued writes in one IMMEDIATE transaction and returns the
+    /// errors of the writes that were rolled back individually.
+    async fn apply_queued(&self) -> Result<Vec<anyhow::Error>> {
+        let tx = self
+            .store
+            .conn
+            .transaction_with_behavior(TransactionBehavior::Immediate)
+            .await?;
+        let failures = match apply_writes(&tx, &self.writes).await {
+            Ok(failures) => failures,
+            Err(err) => {
+                tx.rollback().await?;
+                return Err(err);
+            }
+        };
+        tx.commit().await?;
+        Ok(failures)
+    }
+}
+
+/// Runs each write inside a savepoint and returns the errors of the writes
+/// that had to be rolled back.
+async fn apply_writes(conn: &Connection, writes: &[PendingWrite]) -> Result<Vec<anyhow::Error>> {
+    let mut statements = StatementCache::new(conn);
+    let mut search_updates: BTreeSet<&str> = BTreeSet::new();
+    let mut failures = Vec::new();
+    for write in writes {
+        statements
+            .execute("SAVEPOINT batch_write", params![])
+            .await?;
+        match apply_write(&mut statements, write).await {
+            Ok(touched) => {
+                search_updates.extend(touched);
+            }
+            Err(err) => {
+                statements
+                    .execute("ROLLBACK TO batch_write", params![])
+                    .await?;
+                failures.push(err);
+            }
+        }
+        statements.execute("RELEASE batch_write", params![]).await?;
+    }
+    for videoid in search_updates {
+        refresh_search_entry(&mut statements, videoid).await?;
+    }
+    Ok(failures)
+}
+
+/// Applies one queued write. Returns the video whose search entry needs a
+/// refresh, if any.
+async fn apply_write<'w>(
+    statements: &mut StatementCache<'_>,
+    write: &'w PendingWrite,
+) -> Result<Option<&'w str>> {
+    match write {
+        PendingWrite::Media { table, record } => {
+            write_media(statements, table, record).await?;
+            record_stats_snapshot(statements, record).await?;
+            Ok(Some(&record.videoid))
+        }
+        PendingWrite::Channel(channel) => {
+            write_channel(statements, channel).await?;
+            Ok(None)
+        }
+        PendingWrite::Subtitles(subtitles) => {
+            write_subtitles(statements, subtitles).await?;
+            Ok(None)
+        }
+        PendingWrite::Comments {
+            videoid,
+            comments,
+            mode,
+        } => {
+            match mode {
+                CommentWrite::Replace => write_comments(statements, videoid, comments).await?,
+                CommentWrite::Merge => merge_comments(statements, videoid, comments, true).await?,
+                CommentWrite::Upsert => {
+                    merge_comments(statements, videoid, comments, false).await?
+                }
+            }
+            Ok(Some(videoid))
+        }
+        PendingWrite::Chapters { videoid, chapters } => {
+            write_chapters(statements, videoid, chapters).await?;
+            Ok(None)
+        }
+        PendingWrite::Transcripts {
+            videoid,
+            transcripts,
+        } => {
+            write_transcripts(statements, videoid, transcripts).await?;
+            Ok(None)
+        }
+        PendingWrite::StatsHistory { videoid, snapshots } => {
+            write_stats_history(statements, videoid, snapshots).await?;
+            Ok(None)
+        }
+        PendingWrite::Availability {
+            videoid,
+            availability,
+        } => {
+            for (table, _) in MEDIA_TABLES {
+                statements
+                    .execute(
+                        &format!(
+                            "UPDATE {table} SET availability = ?2, \
+                             availability_checked_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') \
+                             WHERE videoid = ?1"
+                        ),
+                        params![videoid.as_str(), availability.as_str()],
+                    )
+                    .await?;
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// Prepared statements keyed by their SQL text, reused for every row written
+/// inside one transaction.
+struct StatementCache<'c> {
+    conn: &'c Connection,
+    statements: HashMap<String, Statement>,
+}
+
+impl<'c> StatementCache<'c> {
+    fn new(conn: &'c Connection) -> Self {
+        Self {
+            conn,
+            statements: HashMap::new(),
+        }
+    }
+
+    async fn prepared(&mut self, sql: &str) -> Result<&Statement> {
+        if !self.statements.contains_key(sql) {
+            let statement = self.conn.prepare(sql).await?;
+            self.statements.insert(sql.to_string(), statement);
+        }
+        let statement = &self.statements[sql];
+        statement.reset();
+        Ok(statement)
+    }
+
+    async fn execute(&mut self, sql: &str, params: impl IntoParams) -> Result<usize> {
+        Ok(self.prepared(sql).await?.execute(params).await?)
+    }
+
+    async fn query(&mut self, sql: &str, params: impl IntoParams) -> Result<Rows> {
+        Ok(self.prepared(sql).await?.query(params).await?)
+    }
+
+    fn last_insert_rowid(&self) -> i64 {
+        self.conn.last_insert_rowid()
+    }
+}
+
+/// Shared upsert used by both the `videos` and `shorts` tables.
+async fn write_media(
+    statements: &mut StatementCache<'_>,
+    table: &str,
+    record: &VideoRecord,
+) -> Result<()> {
+    let tags_json = serde_json::to_string(&record.tags).context("serializing tags")?;
+    let thumbnails_json =
+        serde_json::to_string(&record.thumbnails).context("serializing thumbnails")?;
+    let extras_json =
+        serde_json::to_string(&record.extras).context("serializing extra metadata")?;
+    let (sources, adaptive_streams) = write_source_checksums(statements, record).await?;
+    let sources_json = serde_json::to_string(&sources).context("serializing sources")?;
+    let adaptive_streams_json =
+        serde_json::to_string(&adaptive_streams).context("serializing adaptive streams")?;
+
+    statements
+        .execute(
+            &format!(
+                r#"
+                INSERT INTO {table} (
+                    videoid, title, description, likes, dislikes, views,
+                    upload_date, author, subscriber_count, duration, duration_text,
+                    channel_url, thumbnail_url, tags_json, thumbnails_json,
+                    extras_json, sources_json, channel_id, availability,
+                    availability_checked_at, adaptive_streams_json, downloaded_at
+                ) VALUES (
+                    :videoid, :title, :description, :likes, :dislikes, :views,
+                    :upload_date, :author, :subscriber_count, :duration, :duration_text,
+                    :channel_url, :thumbnail_url, :tags_json, :thumbnails_json,
+                    :extras_json, :sources_json, :channel_id, :availability,
+                    :availability_checked_at, :adaptive_streams_json,
+                    strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                )
+                ON CONFLICT(videoid) DO UPDATE SET
+                    title = excluded.title,
+                    description = excluded.description,
+                    likes = excluded.likes,
+                    dislikes = excluded.dislikes,
+                    views = excluded.views,
+                    upload_date = excluded.upload_date,
+                    author = excluded.author,
+                    subscriber_count = excluded.subscriber_count,
+                    duration = excluded.duration,
+                    duration_text = excluded.duration_text,
+                    channel_url = excluded.channel_url,
+                    thumbnail_url = excluded.thumbnail_url,
+                    tags_json = excluded.tags_json,
+                    thumbnails_json = excluded.thumbnails_json,
+                    extras_json = excluded.extras_json,
+                    sources_json = excluded.sources_json,
+                    adaptive_streams_json = excluded.adaptive_streams_json,
+                    channel_id = excluded.channel_id,
+                    availability = COALESCE(excluded.availability, {table}.availability),
+                    availability_checked_at = COALESCE(
+                        excluded.availability_checked_at,
+                        {table}.availability_checked_at
+                    )
+                "#,
+            ),
+            params![
+                record.videoid.as_str(),
+                record.title.as_str(),
+                record.description.as_str(),
+                record.likes,
+                record.dislikes,
+                record.views,
+                record.upload_date.as_deref(),
+                record.author.as_deref(),
+                record.subscriber_count,
+                record.duration,
+                record.duration_text.as_deref(),
+                record.channel_url.as_deref(),
+                record.thumbnail_url.as_deref(),
+                tags_json,
+                thumbnails_json,
+                extras_json,
+                sources_json,
+                record.channel_id.as_deref(),
+                record.availability.map(Availability::as_str),
+                record.availability_checked_at.as_deref(),
+                adaptive_streams_json,
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+/// Keeps `source_checksums` in step with the record's sources and adaptive
+/// streams. Files that carry a hash are recorded (a changed hash resets the
+/// scrub status); files without one pick up the hash already recorded for
+/// their path, so metadata refreshes that rebuild the lists do not lose it.
+async fn write_source_checksums(
+    statements: &mut StatementCache<'_>,
+    record: &VideoRecord,
+) -> Result<(Vec<VideoSource>, Vec<AdaptiveStream>)> {
+    let mut sources = record.sources.clone();
+    let mut adaptive_streams = record.adaptive_streams.clone();
+    let files = sources
+        .iter_mut()
+        .map(|source| (source.path.as_deref(), &mut source.sha256))
+        .chain(
+            adaptive_streams
+                .iter_mut()
+                .map(|stream| (stream.path.as_deref(), &mut stream.sha256)),
+        );
+    for (path, sha256) in files {
+        let Some(path) = path else {
+            continue;
+        };
+        match sha256.as_deref() {
+            Some(sha256) => {
+                statements
+                    .execute(
+                        r#"
+                        INSERT INTO source_checksums (
+                            path, videoid, sha256, hashed_at, verified_at, status
+                        ) VALUES (
+                            ?1, ?2, ?3,
+                            strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                            strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                            'ok'
+                        )
+                        ON CONFLICT(path) DO UPDATE SET
+                            videoid = excluded.videoid,
+                            sha256 = excluded.sha256,
+                            hashed_at = excluded.hashed_at,
+                            verified_at = excluded.verified_at,
+                            status = excluded.status,
+                            observed_sha256 = NULL
+                        WHERE source_checksums.sha256 <> excluded.sha256
+                            OR source_checksums.videoid <> excluded.videoid
+                        "#,
+                        params![path, record.videoid.as_str(), sha256],
+                    )
+                    .await?;
+            }
+            None => {
+                let mut rows = statements
+                    .query(
+                        "SELECT sha256 FROM source_checksums WHERE path = ?1 AND videoid = ?2",
+                        params![path, record.videoid.as_str()],
+                    )
+                    .await?;
+                if let Some(row) = rows.next().await? {
+                    *sha256 = Some(row.get(0)?);
+                }
+            }
+        }
+    }
+    Ok((sources, adaptive_streams))
+}
+
+async fn write_channel(statements: &mut StatementCache<'_>, channel: &ChannelRecord) -> Result<()> {
+    statements
+        .execute(
+            r#"
+            INSERT INTO channels (
+                channel_id, name, handle, url, subscriber_count,
+                avatar_path, banner_path, last_refreshed_at
+            ) VALUES (
+                :channel_id, :name, :handle, :url, :subscriber_count,
+                :avatar_path, :banner_path, :last_refreshed_at
+            )
+            ON CONFLICT(channel_id) DO UPDATE SET
+                name = CASE WHEN excluded.name <> '' THEN excluded.name ELSE channels.name END,
+                handle = COALESCE(excluded.handle, channels.handle),
+                url = COALESCE(excluded.url, channels.url),
+                subscriber_count = COALESCE(excluded.subscriber_count, channels.subscriber_count),
+                avatar_path = COALESCE(excluded.avatar_path, channels.avatar_path),
+                banner_path = COALESCE(excluded.banner_path, channels.banner_path),
+                last_refreshed_at = COALESCE(excluded.last_refreshed_at, channels.last_refreshed_at)
+            "#,
+            params![
+                channel.channel_id.as_str(),
+                channel.name.as_str(),
+                channel.handle.as_deref(),
+                channel.url.as_deref(),
+                channel.subscriber_count,
+                channel.avatar_path.as_deref(),
+                channel.banner_path.as_deref(),
+                channel.last_refreshed_at.as_deref(),
+            ],
+        )
+        .await?;
+    Ok(())
+}
+
+async fn write_subtitles(
+    statements: &mut StatementCache<'_>,
+    subtitles: &SubtitleCollection,
+) -> Result<()> {
+    let languages_json =
+        serde_json::to_string(&subtitles.languages).context("serializing subtitles")?;
+    statements
+        .execute(
+            r#"
+            INSERT INTO subtitles (videoid, languages_json)
+            VALUES (:videoid, :languages_json)
+            ON CONFLICT(videoid) DO UPDATE SET
+                languages_json = excluded.languages_json
+            "#,
+            params![subtitles.videoid.as_str(), languages_json],
+        )
+        .await?;
+    Ok(())
+}
+
+async fn write_comments(
+    statements: &mut StatementCache<'_>,
+    videoid: &str,
+    comments: &[CommentRecord],
+) -> Result<()> {
+    statements
+        .execute("DELETE FROM comments WHERE videoid = ?1", params![videoid])
+        .await?;
+    for comment in comments {
+        statements
+            .execute(
+                r#"
+                INSERT INTO comments (
+                    id, videoid, author, text, likes, time_posted,
+                    parent_comment_id, status_likedbycreator, reply_count,
+                    deleted_upstream_at
+                ) VALUES (
+                    :id, :videoid, :author, :text, :likes, :time_posted,
+                    :parent_comment_id, :status_likedbycreator, :reply_count,
+                    :deleted_upstream_at
+                )
+                "#,
+                params![
+                    comment.id.as_str(),
+                    comment.videoid.as_str(),
+                    comment.author.as_str(),
+                    comment.text.as_str(),
+                    comment.likes,
+                    comment.time_posted.as_deref(),
+                    comment.parent_comment_id.as_deref(),
+                    comment.status_likedbycreator as i64,
+                    comment.reply_count,
+                    comment.deleted_upstream_at.as_deref(),
+                ],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+/// Upserts `comments` and, with `tombstone` set, stamps `deleted_upstream_at`
+/// on the stored comments of `videoid` that are not in the list.
+async fn merge_comments(
+    statements: &mut StatementCache<'_>,
+    videoid: &str,
+    comments: &[CommentRecord],
+    tombstone: bool,
+) -> Result<()> {
+    for comment in comments {
+        statements
+            .execute(
+                r#"
+                INSERT INTO comments (
+                    id, videoid, author, text, likes, time_posted,
+                    parent_comment_id, status_likedbycreator, reply_count
+                ) VALUES (
+                    :id, :videoid, :author, :text, :likes, :time_posted,
+                    :parent_comment_id, :status_likedbycreator, :reply_count
+                )
+                ON CONFLICT(id) DO UPDATE SET
+                    videoid = excluded.videoid,
+                    author = excluded.author,
+                    text = excluded.text,
+                    likes = excluded.likes,
+                    time_posted = excluded.time_posted,
+                    parent_comment_id = excluded.parent_comment_id,
+                    status_likedbycreator = excluded.status_likedbycreator,
+                    reply_count = excluded.reply_count,
+                    deleted_upstream_at = NULL
+                "#,
+                params![
+                    comment.id.as_str(),
+                    comment.videoid.as_str(),
+                    comment.author.as_str(),
+                    comment.text.as_str(),
+                    comment.likes,
+                    comment.time_posted.as_deref(),
+                    comment.parent_comment_id.as_deref(),
+                    comment.status_likedbycreator as i64,
+                    comment.reply_count,
+                ],
+            )
+            .await?;
+    }
+    if !tombstone {
+        return Ok(());
+    }
+
+    let ids = serde_json::to_string(
+        &comments
+            .iter()
+            .map(|comment| comment.id.as_str())
+            .collect::<Vec<_>>(),
+    )
+    .context("serializing comment ids")?;
+    statements
+        .execute(
+            r#"
+            UPDATE comments
+            SET deleted_upstream_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+            WHERE videoid = ?1
+              AND deleted_upstream_at IS NULL
+              AND id NOT IN (SELECT value FROM json_each(?2))
+            "#,
+            params![videoid, ids],
+        )
+        .await?;
+    Ok(())
+}
+
+async fn write_chapters(
+    statements: &mut StatementCache<'_>,
+    videoid: &str,
+    chapters: &[Chapter],
+) -> Result<()> {
+    statements
+        .execute("DELETE FROM chapters WHERE videoid = ?1", [videoid])
+        .await?;
+    for (position, chapter) in chapters.iter().enumerate() {
+        statements
+            .execute(
+                r#"
+                INSERT INTO chapters (videoid, position, start_time, end_time, title)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                "#,
+                params![
+                    videoid,
+                    position as i64,
+                    chapter.start,
+                    chapter.end,
+                    chapter.title.as_str(),
+                ],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+async fn write_stats_history(
+    statements: &mut StatementCache<'_>,
+    videoid: &str,
+    snapshots: &[VideoStatsSnapshot],
+) -> Result<()> {
+    statements
+        .execute(
+            "DELETE FROM video_stats_history WHERE videoid = ?1",
+            [videoid],
+        )
+        .await?;
+    for snapshot in snapshots {
+        statements
+            .execute(
+                r#"
+                INSERT INTO video_stats_history (
+                    videoid, recorded_at, views, likes, dislikes, comment_count, subscriber_count
+                )
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                "#,
+                params![
+                    videoid,
+                    snapshot.recorded_at.as_str(),
+                    snapshot.views,
+                    snapshot.likes,
+                    snapshot.dislikes,
+                    snapshot.comment_count,
+                    snapshot.subscriber_count,
+                ],
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+async fn write_transcripts(
+    statements: &mut StatementCache<'_>,
+    videoid: &str,
+    transcripts: &[Transcript],
+) -> Result<()> {
+    clear_transcripts(statements, videoid).await?;
+    for transcript in transcripts {
+        for cue in &transcript.cues {
+            statements
+                .execute(
+                    r#"
+                    INSERT INTO transcript_cues (videoid, language, start_time, end_time, text)
+                    VALUES (?1, ?2, ?3, ?4, ?5)
+                    "#,
+                    params![
+                        videoid,
+                        transcript.language.as_str(),
+                        cue.start,
+                        cue.end,
+                        cue.text.as_str(),
+                    ],
+                )
+                .await?;
+            let cue_id = statements.last_insert_rowid();
+            statements
+                .execute(
+                    "INSERT INTO transcript_index (rowid, text) VALUES (?1, ?2)",
+                    params![cue_id, cue.text.as_str()],
+                )
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+async fn write_segments(
+    conn: &Connection,
+    videoid: &str,
+    segments: &[SkipSegment],
+    source: &str,
+) -> Result<()> {
+    conn.execute("DELETE FROM skip_segments WHERE videoid = ?1", [videoid])
+        .await?;
+    let mut ordered: Vec<&SkipSegment> = segments.iter().collect();
+    ordered.sort_by(|a, b| a.start.total_cmp(&b.start).then(a.end.total_cmp(&b.end)));
+    for (position, segment) in ordered.into_iter().enumerate() {
+        conn.execute(
+            r#"
+            INSERT INTO skip_segments (videoid, position, category, start_time, end_time, source)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            "#,
+            params![
+                videoid,
+                position as i64,
+                segment.category.as_str(),
+                segment.start,
+                segment.end,
+                source,
+            ],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Removes the cues of `videoid` together with their search index rows.
+async fn clear_transcripts(statements: &mut StatementCache<'_>, videoid: &str) -> Result<()> {
+    statements
+        .execute(
+            r#"
+            DELETE FROM transcript_index
+            WHERE rowid IN (SELECT cue_id FROM transcript_cues WHERE videoid = ?1)
+            "#,
+            [videoid],
+        )
+        .await?;
+    statements
+        .execute("DELETE FROM transcript_cues WHERE videoid = ?1", [videoid])
+        .await?;
+    Ok(())
+}
+
+/// Cloneable read handle backed by a bounded pool of connections, so
+/// concurrent API requests run side by side instead of queueing behind one
+/// slow query. `PRAGMA data_version` is only comparable on the same
+/// connection, so version polling gets a dedicated connection of its own.
+#[derive(Clone)]
+pub struct MetadataReader {
+    pool: Arc<ReaderPool>,
+}
+
+struct ReaderPool {
+    db: Database,
+    size: usize,
+    /// Connections opened so far and currently unused. New ones are opened
+    /// lazily until every permit is taken.
+    idle: Mutex<Vec<Connection>>,
+    permits: Semaphore,
+    version: Connection,
+}
+
+/// Connection borrowed from the pool; handed back on drop.
+struct PooledConnection<'a> {
+    pool: &'a ReaderPool,
+    conn: Option<Connection>,
+    _permit: SemaphorePermit<'a>,
+}
+
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn
+            .as_ref()
+            .expect("pooled connection already returned")
+    }
+}
+
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool.idle.lock().push(conn);
+        }
+    }
+}
+
+impl MetadataReader {
+    /// Creates a reader with [`DEFAULT_READER_POOL_SIZE`] pooled connections.
+    pub async fn new(path: impl AsRef<Path>) -> Result<Self> {
+        Self::with_pool_size(path, DEFAULT_READER_POOL_SIZE).await
+    }
+
+    /// Creates a reader that opens at most `pool_size` query connections
+    /// (at least one) plus the dedicated `data_version` connection.
+    pub async fn with_pool_size(path: impl AsRef<Path>, pool_size: usize) -> Result<Self> {
+        let db = Builder::new_local(path.as_ref())
+            .build()
+            .await
+            .with_context(|| format!("opening metadata DB {}", path.as_ref().display()))?;
+        let version = db.connect()?;
+        configure_connection(&version).await?;
+        ensure_schema(&version).await?;
+        let size = pool_size.max(1);
+        Ok(Self {
+            pool: Arc::new(ReaderPool {
+                db,
+                size,
+                idle: Mutex::new(Vec::with_capacity(size)),
+                permits: Semaphore::new(size),
+                version,
+            }),
+        })
+    }
+
+    /// Maximum number of queries that run concurrently.
+    pub fn pool_size(&self) -> usize {
+        self.pool.size
+    }
+
+    /// Waits for a free slot and hands out an idle connection, opening a new
+    /// one if this slot has not been used yet.
+    async fn connection(&self) -> Result<PooledConnection<'_>> {
+        let permit = self
+            .pool
+            .permits
+            .acquire()
+            .await
+            .context("reader pool closed")?;
+        let idle = self.pool.idle.lock().pop();
+        let conn = match idle {
+            Some(conn) => conn,
+            None => {
+                let conn = self.pool.db.connect()?;
+                configure_connection(&conn).await?;
+                conn
+            }
+        };
+        Ok(PooledConnection {
+            pool: &self.pool,
+            conn: Some(conn),
+            _permit: permit,
+        })
+    }
+
+    pub async fn list_videos(&self) -> Result<Vec<VideoRecord>> {
+        self.fetch_videos_from("videos").await
+    }
+
+    pub async fn list_shorts(&self) -> Result<Vec<VideoRecord>> {
+        self.fetch_videos_from("shorts").await
+    }
+
+    /// Returns one page of videos using keyset pagination. Sorting and
+    /// filtering run in SQL so only `limit` rows are ever materialized.
+    pub async fn list_videos_page(&self, query: &MediaListQuery) -> Result<MediaPage> {
+        self.fetch_media_page("videos", query).await
+    }
+
+    pub async fn list_shorts_page(&self, query: &MediaListQuery) -> Result<MediaPage> {
+        self.fetch_media_page("shorts", query).await
+    }
+
+    pub async fn get_video(&self, videoid: &str) -> Result<Option<VideoRecord>> {
+        self.fetch_single("videos", videoid).await
+    }
+
+    pub async fn get_short(&self, videoid: &str) -> Result<Option<VideoRecord>> {
+        self.fetch_single("shorts", videoid).await
+    }
+
+    /// Lists the "never re-download" list, most recently blocked first.
+    pub async fn list_blocked_media(&self) -> Result<Vec<BlockedMedia>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(
+                "SELECT videoid, blocked_at, reason FROM blocked_media \
+                 ORDER BY blocked_at DESC, videoid",
+            )
+            .await?;
+
+        let mut rows = stmt.query(params![]).await?;
+        let mut blocked = Vec::new();
+        while let Some(row) = rows.next().await? {
+            blocked.push(BlockedMedia {
+                videoid: row.get(0)?,
+                blocked_at: row.get(1)?,
+                reason: row.get(2)?,
+            });
+        }
+        Ok(blocked)
+    }
+
+    /// Returns every recorded stats snapshot for a video, oldest first.
+    pub async fn get_video_stats_history(&self, videoid: &str) -> Result<Vec<VideoStatsSnapshot>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(
+                r#"
+                SELECT recorded_at, views, likes, dislikes, comment_count, subscriber_count
+                FROM video_stats_history
+                WHERE videoid = ?1
+                ORDER BY id ASC
+                "#,
+            )
+            .await?;
+
+        let mut rows = stmt.query([videoid]).await?;
+        let mut snapshots = Vec::new();
+        while let Some(row) = rows.next().await? {
+            snapshots.push(VideoStatsSnapshot {
+                recorded_at: row.get(0)?,
+                views: row.get(1)?,
+                likes: row.get(2)?,
+                dislikes: row.get(3)?,
+                comment_count: row.get(4)?,
+                subscriber_count: row.get(5)?,
+            });
+        }
+        Ok(snapshots)
+    }
+
+    /// Builds a daily growth series for a channel. For each day with at least
+    /// one snapshot, every video contributes its latest snapshot up to and
+    /// including that day, so videos that did not change still count.
+    pub async fn get_channel_stats_history(
+        &self,
+        channel_id: &str,
+    ) -> Result<Vec<ChannelStatsPoint>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(
+                r#"
+                WITH media AS (
+                    SELECT videoid FROM videos WHERE channel_id = ?1
+                    UNION
+                    SELECT videoid FROM shorts WHERE channel_id = ?1
+                ),
+                days AS (
+                    SELECT DISTINCT substr(h.recorded_at, 1, 10) AS day
+                    FROM video_stats_history h
+                    JOIN media m ON m.videoid = h.videoid
+                )
+                SELECT d.day,
+                       COUNT(h.id),
+                       COALESCE(SUM(h.views), 0),
+                       COALESCE(SUM(h.likes), 0),
+                       COALESCE(SUM(h.comment_count), 0),
+                       MAX(h.subscriber_count)
+                FROM days d
+                CROSS JOIN media m
+                JOIN video_stats_history h ON h.id = (
+                    SELECT latest.id FROM video_stats_history latest
+                    WHERE latest.videoid = m.videoid
+                      AND substr(latest.recorded_at, 1, 10) <= d.day
+                    ORDER BY latest.id DESC
+                    LIMIT 1
+                )
+                GROUP BY d.day
+                ORDER BY d.day ASC
+                "#,
+            )
+            .await?;
+
+        let mut rows = stmt.query([channel_id]).await?;
+        let mut points = Vec::new();
+        while let Some(row) = rows.next().await? {
+            points.push(ChannelStatsPoint {
+                day: row.get(0)?,
+                video_count: row.get(1)?,
+                views: row.get(2)?,
+                likes: row.get(3)?,
+                comment_count: row.get(4)?,
+                subscriber_count: row.get(5)?,
+            });
+        }
+        Ok(points)
+    }
+
+    /// Lists every known channel alphabetically.
+    pub async fn list_channels(&self) -> Result<Vec<ChannelRecord>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(&format!(
+                "SELECT {CHANNEL_COLUMNS} FROM channels ORDER BY name COLLATE NOCASE, channel_id"
+            ))
+            .await?;
+
+        let mut rows = stmt.query(params![]).await?;
+        let mut channels = Vec::new();
+        while let Some(row) = rows.next().await? {
+            channels.push(row_to_channel(&row)?);
+        }
+        Ok(channels)
+    }
+
+    pub async fn get_channel(&self, channel_id: &str) -> Result<Option<ChannelRecord>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(&format!(
+                "SELECT {CHANNEL_COLUMNS} FROM channels WHERE channel_id = ?1"
+            ))
+            .await?;
+
+        let mut rows = stmt.query([channel_id]).await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(row_to_channel(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub async fn list_channel_videos(&self, channel_id: &str) -> Result<Vec<VideoRecord>> {
+        self.fetch_channel_media("videos", channel_id).await
+    }
+
+    pub async fn list_channel_shorts(&self, channel_id: &str) -> Result<Vec<VideoRecord>> {
+        self.fetch_channel_media("shorts", channel_id).await
+    }
+
+    /// Lists every stored playlist alphabetically with its entry count.
+    pub async fn list_playlists(&self) -> Result<Vec<PlaylistRecord>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(&format!(
+                "SELECT {PLAYLIST_COLUMNS} FROM playlists p \
+                 ORDER BY p.title COLLATE NOCASE, p.playlist_id"
+            ))
+            .await?;
+
+        let mut rows = stmt.query(params![]).await?;
+        let mut playlists = Vec::new();
+        while let Some(row) = rows.next().await? {
+            playlists.push(row_to_playlist(&row)?);
+        }
+        Ok(playlists)
+    }
+
+    pub async fn get_playlist(&self, playlist_id: &str) -> Result<Option<PlaylistRecord>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(&format!(
+                "SELECT {PLAYLIST_COLUMNS} FROM playlists p WHERE p.playlist_id = ?1"
+            ))
+            .await?;
+
+        let mut rows = stmt.query([playlist_id]).await?;
+        match rows.next().await? {
+            Some(row) => Ok(Some(row_to_playlist(&row)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the members of a playlist in playlist order.
+    pub async fn get_playlist_entries(&self, playlist_id: &str) -> Result<Vec<PlaylistEntry>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(
+                r#"
+                SELECT position, videoid, kind
+                FROM playlist_entries
+                WHERE playlist_id = ?1
+                ORDER BY position ASC
+                "#,
+            )
+            .await?;
+
+        let mut rows = stmt.query([playlist_id]).await?;
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next().await? {
+            entries.push(PlaylistEntry {
+                position: row.get(0)?,
+                videoid: row.get(1)?,
+                kind: row.get(2)?,
+            });
+        }
+        Ok(entries)
+    }
+
+    pub async fn get_subtitles(&self, videoid: &str) -> Result<Option<SubtitleCollection>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(
+                r#"
+                SELECT languages_json
+                FROM subtitles
+                WHERE videoid = ?1
+                "#,
+            )
+            .await?;
+
+        let mut rows = stmt.query([videoid]).await?;
+        let Some(row) = rows.next().await? else {
+            return Ok(None);
+        };
+        let languages_json: String = row.get(0)?;
+        let languages: Vec<SubtitleTrack> =
+            serde_json::from_str(&languages_json).context("parsing subtitle tracks")?;
+        Ok(Some(SubtitleCollection {
+            videoid: videoid.to_owned(),
+            languages,
+        }))
+    }
+
+    pub async fn list_subtitles(&self) -> Result<Vec<SubtitleCollection>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(
+                r#"
+                SELECT videoid, languages_json
+                FROM subtitles
+                "#,
+            )
+            .await?;
+
+        let mut rows = stmt.query(params![]).await?;
+        let mut results = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let videoid: String = row.get(0)?;
+            let languages_json: String = row.get(1)?;
+            let languages: Vec<SubtitleTrack> =
+                serde_json::from_str(&languages_json).context("parsing subtitle tracks")?;
+            results.push(SubtitleCollection { videoid, languages });
+        }
+        Ok(results)
+    }
+
+    /// Chapters for `videoid` in playback order.
+    pub async fn get_chapters(&self, videoid: &str) -> Result<Vec<Chapter>> {
+        let conn = self.connection().await?;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT start_time, end_time, title
+                FROM chapters
+                WHERE videoid = ?1
+                ORDER BY position
+                "#,
+                [videoid],
+            )
+            .await?;
+        let mut chapters = Vec::new();
+        while let Some(row) = rows.next().await? {
+            chapters.push(Chapter {
+                start: row.get(0)?,
+                end: row.get(1)?,
+                title: row.get(2)?,
+            });
+        }
+        Ok(chapters)
+    }
+
+    /// Skip segments for `videoid` ordered by start time.
+    pub async fn get_segments(&self, videoid: &str) -> Result<Vec<SkipSegment>> {
+        let conn = self.connection().await?;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT category, start_time, end_time
+                FROM skip_segments
+                WHERE videoid = ?1
+                ORDER BY position
+                "#,
+                [videoid],
+            )
+            .await?;
+        let mut segments = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let category: String = row.get(0)?;
+            let Some(category) = SegmentCategory::parse(&category) else {
+                continue;
+            };
+            segments.push(SkipSegment {
+                category,
+                start: row.get(1)?,
+                end: row.get(2)?,
+            });
+        }
+        Ok(segments)
+    }
+
+    /// Whether the segments of `videoid` were edited by hand rather than
+    /// imported from SponsorBlock.
+    pub async fn has_manual_segments(&self, videoid: &str) -> Result<bool> {
+        let conn = self.connection().await?;
+        let mut rows = conn
+            .query(
+                "SELECT 1 FROM skip_segments WHERE videoid = ?1 AND source = ?2 LIMIT 1",
+                params![videoid, SEGMENT_SOURCE_MANUAL],
+            )
+            .await?;
+        Ok(rows.next().await?.is_some())
+    }
+
+    /// Recorded file checksums ordered by path, optionally restricted to one
+    /// scrub status.
+    pub async fn list_source_checksums(
+        &self,
+        status: Option<ChecksumStatus>,
+    ) -> Result<Vec<SourceChecksum>> {
+        let conn = self.connection().await?;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT path, videoid, sha256, hashed_at, verified_at, status, observed_sha256
+                FROM source_checksums
+                WHERE ?1 IS NULL OR status = ?1
+                ORDER BY path
+                "#,
+                params![status.map(ChecksumStatus::as_str)],
+            )
+            .await?;
+        let mut checksums = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let status: String = row.get(5)?;
+            checksums.push(SourceChecksum {
+                path: row.get(0)?,
+                videoid: row.get(1)?,
+                sha256: row.get(2)?,
+                hashed_at: row.get(3)?,
+                verified_at: row.get(4)?,
+                status: ChecksumStatus::parse(&status).unwrap_or(ChecksumStatus::Mismatch),
+                observed_sha256: row.get(6)?,
+            });
+        }
+        Ok(checksums)
+    }
+
+    /// Every video and short id in the library.
+    pub async fn list_media_ids(&self) -> Result<HashSet<String>> {
+        let conn = self.connection().await?;
+        let mut rows = conn
+            .query(
+                "SELECT videoid FROM videos UNION SELECT videoid FROM shorts",
+                params![],
+            )
+            .await?;
+        let mut ids = HashSet::new();
+        while let Some(row) = rows.next().await? {
+            ids.insert(row.get(0)?);
+        }
+        Ok(ids)
+    }
+
+    /// Transcripts for `videoid`, one per language, optionally restricted to
+    /// `language`. Cues are in playback order.
+    pub async fn get_transcripts(
+        &self,
+        videoid: &str,
+        language: Option<&str>,
+    ) -> Result<Vec<Transcript>> {
+        let conn = self.connection().await?;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT language, start_time, end_time, text
+                FROM transcript_cues
+                WHERE videoid = ?1
+                  AND (?2 IS NULL OR language = ?2)
+                ORDER BY language, start_time, cue_id
+                "#,
+                params![videoid, language],
+            )
+            .await?;
+        let mut transcripts: Vec<Transcript> = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let language: String = row.get(0)?;
+            let cue = TranscriptCue {
+                start: row.get(1)?,
+                end: row.get(2)?,
+                text: row.get(3)?,
+            };
+            match transcripts.last_mut() {
+                Some(last) if last.language == language => last.cues.push(cue),
+                _ => transcripts.push(Transcript {
+                    language,
+                    cues: vec![cue],
+                }),
+            }
+        }
+        Ok(transcripts)
+    }
+
+    /// Comments of `videoid` in posting order. Comments deleted upstream are
+    /// only returned with `include_deleted`.
+    pub async fn get_comments(
+        &self,
+        videoid: &str,
+        include_deleted: bool,
+    ) -> Result<Vec<CommentRecord>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(
+                r#"
+                SELECT id, videoid, author, text, likes, time_posted,
+                       parent_comment_id, status_likedbycreator, reply_count,
+                       deleted_upstream_at
+                FROM comments
+                WHERE videoid = ?1
+                  AND (?2 OR deleted_upstream_at IS NULL)
+                  AND (
+                    EXISTS (SELECT 1 FROM videos WHERE videoid = ?1)
+                    OR EXISTS (SELECT 1 FROM shorts WHERE videoid = ?1)
+                  )
+                ORDER BY datetime(time_posted) IS NULL, datetime(time_posted) ASC, rowid ASC
+                "#,
+            )
+            .await?;
+
+        let mut comments = Vec::new();
+        let mut rows = stmt.query(params![videoid, include_deleted]).await?;
+        while let Some(row) = rows.next().await? {
+            comments.push(row_to_comment(&row)?);
+        }
+        Ok(comments)
+    }
+
+    /// Every comment in the library; see [`MetadataReader::get_comments`].
+    pub async fn list_all_comments(&self, include_deleted: bool) -> Result<Vec<CommentRecord>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(
+                r#"
+                SELECT id, videoid, author, text, likes, time_posted,
+                       parent_comment_id, status_likedbycreator, reply_count,
+                       deleted_upstream_at
+                FROM comments
+                WHERE videoid IN (
+                    SELECT videoid FROM videos
+                    UNION
+                    SELECT videoid FROM shorts
+                )
+                  AND (?1 OR deleted_upstream_at IS NULL)
+                ORDER BY datetime(time_posted) IS NULL, datetime(time_posted) ASC, rowid ASC
+                "#,
+            )
+            .await?;
+
+        let mut rows = stmt.query(params![include_deleted]).await?;
+        let mut comments = Vec::new();
+        while let Some(row) = rows.next().await? {
+            comments.push(row_to_comment(&row)?);
+        }
+        Ok(comments)
+    }
+
+    /// Runs a ranked full-text query over titles, descriptions, tags, authors
+    /// and comment text. Blank queries return no hits.
+    pub async fn search(&self, query: &str, options: &SearchOptions) -> Result<Vec<SearchHit>> {
+        let Some(expression) = fts_match_expression(query) else {
+            return Ok(Vec::new());
+        };
+        let limit = options
+            .limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT);
+
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(
+                r#"
+                SELECT d.videoid, d.kind, m.title, m.author,
+                       snippet(search_index, -1, '<mark>', '</mark>', '…', 16),
+                       bm25(search_index, 10.0, 2.0, 4.0, 3.0, 1.0) AS score
+                FROM search_index
+                JOIN search_documents d ON d.docid = search_index.rowid
+                JOIN (
+                    SELECT videoid, 'video' AS kind, title, author, channel_id, channel_url,
+                           extras_json
+                    FROM videos
+                    UNION ALL
+                    SELECT videoid, 'short' AS kind, title, author, channel_id, channel_url,
+                           extras_json
+                    FROM shorts
+                ) m ON m.videoid = d.videoid AND m.kind = d.kind
+                WHERE search_index MATCH ?1
+                  AND (?2 IS NULL OR d.kind = ?2)
+                  AND (
+                    ?3 IS NULL
+                    OR m.channel_id = ?3
+                    OR m.channel_url = ?3
+                    OR json_extract(m.extras_json, '$.channelId') = ?3
+                    OR EXISTS (
+                        SELECT 1 FROM json_each(m.extras_json, '$.channelIds') WHERE value = ?3
+                    )
+                  )
+                ORDER BY score ASC, d.docid ASC
+                LIMIT ?4
+                "#,
+            )
+            .await?;
+
+        let mut rows = stmt
+            .query(params![
+                expression,
+                options.kind.as_deref(),
+                options.channel.as_deref(),
+                limit
+            ])
+            .await?;
+        let mut hits = Vec::new();
+        while let Some(row) = rows.next().await? {
+            hits.push(SearchHit {
+                videoid: row.get(0)?,
+                kind: row.get(1)?,
+                title: row.get(2)?,
+                author: row.get(3)?,
+                snippet: row.get(4)?,
+                score: row.get(5)?,
+            });
+        }
+        Ok(hits)
+    }
+
+    /// Ranked full-text query over transcript cues. Each hit carries the cue
+    /// timing so clients can jump straight to the spoken words. `options.kind`
+    /// and `options.channel` filter like [`MetadataReader::search`].
+    pub async fn search_transcripts(
+        &self,
+        query: &str,
+        language: Option<&str>,
+        options: &SearchOptions,
+    ) -> Result<Vec<TranscriptHit>> {
+        let Some(expression) = fts_match_expression(query) else {
+            return Ok(Vec::new());
+        };
+        let limit = options
+            .limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT);
+
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(
+                r#"
+                SELECT c.videoid, m.kind, m.title, c.language, c.start_time, c.end_time,
+                       highlight(transcript_index, 0, '<mark>', '</mark>'),
+                       bm25(transcript_index) AS score
+                FROM transcript_index
+                JOIN transcript_cues c ON c.cue_id = transcript_index.rowid
+                JOIN (
+                    SELECT videoid, 'video' AS kind, title, channel_id, channel_url, extras_json
+                    FROM videos
+                    UNION ALL
+                    SELECT videoid, 'short' AS kind, title, channel_id, channel_url, extras_json
+                    FROM shorts
+                ) m ON m.videoid = c.videoid
+                WHERE transcript_index MATCH ?1
+                  AND (?2 IS NULL OR m.kind = ?2)
+                  AND (
+                    ?3 IS NULL
+                    OR m.channel_id = ?3
+                    OR m.channel_url = ?3
+                    OR json_extract(m.extras_json, '$.channelId') = ?3
+                    OR EXISTS (
+                        SELECT 1 FROM json_each(m.extras_json, '$.channelIds') WHERE value = ?3
+                    )
+                  )
+                  AND (?4 IS NULL OR c.language = ?4)
+                ORDER BY score ASC, c.videoid ASC, c.start_time ASC
+                LIMIT ?5
+                "#,
+            )
+            .await?;
+
+        let mut rows = stmt
+            .query(params![
+                expression,
+                options.kind.as_deref(),
+                options.channel.as_deref(),
+                language,
+                limit
+            ])
+            .await?;
+        let mut hits = Vec::new();
+        while let Some(row) = rows.next().await? {
+            hits.push(TranscriptHit {
+                videoid: row.get(0)?,
+                kind: row.get(1)?,
+                title: row.get(2)?,
+                language: row.get(3)?,
+                start: row.get(4)?,
+                end: row.get(5)?,
+                snippet: row.get(6)?,
+                score: row.get(7)?,
+            });
+        }
+        Ok(hits)
+    }
+
+    /// Highest sequence number in the change log, or 0 for an empty library.
+    /// Read it before a full listing so later deltas cover anything that
+    /// changed while the listing ran.
+    pub async fn latest_change_seq(&self) -> Result<i64> {
+        let conn = self.connection().await?;
+        let mut rows = conn
+            .query("SELECT COALESCE(MAX(seq), 0) FROM changes", params![])
+            .await?;
+        let row = rows.next().await?.context("missing change seq row")?;
+        Ok(row.get(0)?)
+    }
+
+    /// Rows inserted, updated or deleted after `since`, at most `limit` log
+    /// entries (default 1000) per call. A row that changed several times is
+    /// only reported once, at its latest state.
+    pub async fn changes_since(&self, since: i64, limit: Option<u32>) -> Result<ChangeSet> {
+        let limit = limit
+            .unwrap_or(DEFAULT_CHANGES_LIMIT)
+            .clamp(1, MAX_CHANGES_LIMIT);
+        let latest = self.latest_change_seq().await?;
+        if since > latest {
+            return Ok(ChangeSet {
+                seq: latest,
+                reset: true,
+                ..ChangeSet::default()
+            });
+        }
+
+        let conn = self.connection().await?;
+        let mut rows = conn
+            .query(
+                r#"
+                SELECT seq, entity, key, deleted
+                FROM changes
+                WHERE seq > ?1
+                ORDER BY seq
+                LIMIT ?2
+                "#,
+                params![since, i64::from(limit) + 1],
+            )
+            .await?;
+        let mut changes = ChangeSet {
+            seq: since,
+            ..ChangeSet::default()
+        };
+        let mut seen = 0;
+        while let Some(row) = rows.next().await? {
+            seen += 1;
+            if seen > limit {
+                changes.has_more = true;
+                break;
+            }
+            let seq: i64 = row.get(0)?;
+            changes.seq = seq;
+            if row.get::<i64>(3)? != 0 {
+                let entity: String = row.get(1)?;
+                changes.deleted.push(Tombstone {
+                    seq,
+                    entity: ChangeEntity::parse(&entity)
+                        .with_context(|| format!("unknown change entity {entity}"))?,
+                    key: row.get(2)?,
+                });
+            }
+        }
+        drop(rows);
+        drop(conn);
+
+        // Rows are looked up by their log position, so a row changing again
+        // meanwhile moves past `changes.seq` and shows up in the next batch.
+        let window = (since, changes.seq);
+        let changed = |entity: ChangeEntity| {
+            format!(
+                "SELECT key FROM changes WHERE entity = '{}' AND deleted = 0 \
+                 AND seq > ?1 AND seq <= ?2",
+                entity.as_str()
+            )
+        };
+        let conn = self.connection().await?;
+        for (table, entity) in [
+            ("videos", ChangeEntity::Video),
+            ("shorts", ChangeEntity::Short),
+        ] {
+            let mut rows = conn
+                .query(
+                    &format!(
+                        "SELECT {VIDEO_COLUMNS} FROM {table} WHERE videoid IN ({})",
+                        changed(entity)
+                    ),
+                    window,
+                )
+                .await?;
+            while let Some(row) = rows.next().await? {
+                let record = row_to_video_record(&row)?;
+                match entity {
+                    ChangeEntity::Short => changes.shorts.push(record),
+                    _ => changes.videos.push(record),
+                }
+            }
+        }
+
+        let mut rows = conn
+            .query(
+                &format!(
+                    "SELECT videoid, languages_json FROM subtitles WHERE videoid IN ({})",
+                    changed(ChangeEntity::Subtitles)
+                ),
+                window,
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            let languages_json: String = row.get(1)?;
+            changes.subtitles.push(SubtitleCollection {
+                videoid: row.get(0)?,
+                languages: serde_json::from_str(&languages_json)
+                    .context("parsing subtitle tracks")?,
+            });
+        }
+        drop(rows);
+
+        let mut rows = conn
+            .query(
+                &format!(
+                    r#"
+                    SELECT {COMMENT_COLUMNS}
+                    FROM comments
+                    WHERE id IN ({})
+                      AND videoid IN (
+                        SELECT videoid FROM videos
+                        UNION
+                        SELECT videoid FROM shorts
+                      )
+                    ORDER BY datetime(time_posted) IS NULL, datetime(time_posted) ASC, rowid ASC
+                    "#,
+                    changed(ChangeEntity::Comment)
+                ),
+                window,
+            )
+            .await?;
+        while let Some(row) = rows.next().await? {
+            changes.comments.push(row_to_comment(&row)?);
+        }
+        Ok(changes)
+    }
+
+    /// `PRAGMA data_version` as seen by the dedicated polling connection; it
+    /// moves whenever another connection commits.
+    pub async fn data_version(&self) -> Result<i64> {
+        let conn = &self.pool.version;
+        let mut rows = conn.query("PRAGMA data_version", params![]).await?;
+        let row = rows.next().await?.context("missing data_version row")?;
+        Ok(row.get(0)?)
+    }
+
+    async fn fetch_media_page(&self, table: &str, query: &MediaListQuery) -> Result<MediaPage> {
+        let sort = query.sort;
+        let descending = query.descending.unwrap_or(sort.default_descending());
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_PAGE_LIMIT)
+            .clamp(1, MAX_PAGE_LIMIT);
+        let expression = sort.expression();
+
+        let mut conditions: Vec<String> = Vec::new();
+        let mut values: Vec<libsql::Value> = Vec::new();
+        let mut bind = |conditions: &mut Vec<String>, clause: &str, value: libsql::Value| {
+            values.push(value);
+            conditions.push(clause.replace("?", &format!("?{}", values.len())));
+        };
+
+        if let Some(channel) = &query.channel {
+            bind(
+                &mut conditions,
+                "(channel_id = ? OR channel_url = ?)",
+                channel.clone().into(),
+            );
+        }
+        if let Some(after) = &query.uploaded_after {
+            bind(&mut conditions, "upload_date >= ?", after.clone().into());
+        }
+        if let Some(before) = &query.uploaded_before {
+            bind(&mut conditions, "upload_date < ?", before.clone().into());
+        }
+        if let Some(min) = query.min_duration {
+            bind(&mut conditions, "duration >= ?", min.into());
+        }
+        if let Some(max) = query.max_duration {
+            bind(&mut conditions, "duration <= ?", max.into());
+        }
+        if let Some(has_subtitles) = query.has_subtitles {
+            let exists = format!(
+                "EXISTS (SELECT 1 FROM subtitles s WHERE s.videoid = {table}.videoid \
+                 AND s.languages_json NOT IN ('', '[]'))"
+            );
+            conditions.push(if has_subtitles {
+                exists
+            } else {
+                format!("NOT {exists}")
+            });
+        }
+        if !query.availability.is_empty() {
+            let placeholders: Vec<String> = query
+                .availability
+                .iter()
+                .map(|availability| {
+                    values.push(availability.as_str().into());
+                    format!("?{}", values.len())
+                })
+                .collect();
+            conditions.push(format!(
+                "COALESCE(availability, 'available') IN ({})",
+                placeholders.join(", ")
+            ));
+        }
+        if let Some(cursor) = &query.cursor {
+            let (value, rowid) = decode_cursor(cursor, sort, descending)?;
+            let op = if descending { "<" } else { ">" };
+            values.push(value);
+            let value_index = values.len();
+            values.push(rowid.into());
+            let rowid_index = values.len();
+            conditions.push(format!(
+                "({expression} {op} ?{value_index} OR \
+                 ({expression} = ?{value_index} AND rowid {op} ?{rowid_index}))"
+            ));
+        }
+
+        let where_clause = if conditions.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conditions.join(" AND "))
+        };
+        let direction = if descending { "DESC" } else { "ASC" };
+        let sql = format!(
+            r#"
+            SELECT {VIDEO_COLUMNS}, rowid, {expression}
+            FROM {table}
+            {where_clause}
+            ORDER BY {expression} {direction}, rowid {direction}
+            LIMIT {}
+            "#,
+            limit + 1
+        );
+
+        let conn = self.connection().await?;
+        let stmt = conn.prepare(&sql).await?;
+        let mut rows = stmt.query(values).await?;
+        let mut items = Vec::new();
+        let mut last_key: Option<(libsql::Value, i64)> = None;
+        let mut has_more = false;
+        while let Some(row) = rows.next().await? {
+            if items.len() == limit as usize {
+                has_more = true;
+                break;
+            }
+            items.push(row_to_video_record(&row)?);
+            last_key = Some((
+                row.get_value(VIDEO_COLUMN_COUNT + 1)?,
+                row.get(VIDEO_COLUMN_COUNT)?,
+            ));
+        }
+
+        let next_cursor = match (has_more, last_key) {
+            (true, Some((value, rowid))) => Some(encode_cursor(sort, descending, &value, rowid)),
+            _ => None,
+        };
+        Ok(MediaPage { items, next_cursor })
+    }
+
+    async fn fetch_videos_from(&self, table: &str) -> Result<Vec<VideoRecord>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(&format!(
+                r#"
+                SELECT {VIDEO_COLUMNS}
+                FROM {table}
+                ORDER BY upload_date DESC, rowid DESC
+                "#
+            ))
+            .await?;
+
+        let mut rows = stmt.query(params![]).await?;
+        let mut records = Vec::new();
+        while let Some(row) = rows.next().await? {
+            records.push(row_to_video_record(&row)?);
+        }
+        Ok(records)
+    }
+
+    async fn fetch_channel_media(&self, table: &str, channel_id: &str) -> Result<Vec<VideoRecord>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(&format!(
+                r#"
+                SELECT {VIDEO_COLUMNS}
+                FROM {table}
+                WHERE channel_id = ?1
+                ORDER BY upload_date DESC, rowid DESC
+                "#
+            ))
+            .await?;
+
+        let mut rows = stmt.query([channel_id]).await?;
+        let mut records = Vec::new();
+        while let Some(row) = rows.next().await? {
+            records.push(row_to_video_record(&row)?);
+        }
+        Ok(records)
+    }
+
+    async fn fetch_single(&self, table: &str, videoid: &str) -> Result<Option<VideoRecord>> {
+        let conn = self.connection().await?;
+        let stmt = conn
+            .prepare(&format!(
+                r#"
+                SELECT {VIDEO_COLUMNS}
+                FROM {table}
+                WHERE videoid = ?1
+                "#
+            ))
+            .await?;
+
+        let mut rows = stmt.query([videoid]).await?;
+        if let Some(row) = rows.next().await? {
+            Ok(Some(row_to_video_record(&row)?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// Converts a SQL row into a `VideoRecord`, deserializing the Vec/JSON fields.
+fn row_to_video_record(row: &Row) -> Result<VideoRecord> {
+    // Column order must match `VIDEO_COLUMNS`.
+    let tags_json: String = row.get(13)?;
+    let thumbnails_json: String = row.get(14)?;
+    let extras_json: String = row.get(15)?;
+    let sources_json: String = row.get(16)?;
+
+    let tags: Vec<String> = serde_json::from_str(&tags_json).context("parsing stored tags JSON")?;
+    let thumbnails: Vec<String> =
+        serde_json::from_str(&thumbnails_json).context("parsing stored thumbnails JSON")?;
+    let extras: serde_json::Value =
+        serde_json::from_str(&extras_json).context("parsing stored extras JSON")?;
+    let sources: Vec<VideoSource> =
+        serde_json::from_str(&sources_json).context("parsing stored sources JSON")?;
+    let adaptive_streams_json: Option<String> = row.get(20)?;
+    let adaptive_streams: Vec<AdaptiveStream> = match adaptive_streams_json.as_deref() {
+        Some(json) => serde_json::from_str(json).context("parsing stored adaptive streams JSON")?,
+        None => Vec::new(),
+    };
+
+    Ok(VideoRecord {
+        videoid: row.get(0)?,
+        title: row.get(1)?,
+        description: row.get(2)?,
+        likes: row.get(3)?,
+        dislikes: row.get(4)?,
+        views: row.get(5)?,
+        upload_date: row.get(6)?,
+        author: row.get(7)?,
+        subscriber_count: row.get(8)?,
+        duration: row.get(9)?,
+        duration_text: row.get(10)?,
+        channel_url: row.get(11)?,
+        channel_id: row.get(17)?,
+        thumbnail_url: row.get(12)?,
+        tags,
+        thumbnails,
+        extras,
+        sources,
+        availability: row
+            .get::<Option<String>>(18)?
+            .as_deref()
+            .and_then(Availability::parse),
+        availability_checked_at: row.get(19)?,
+        adaptive_streams,
+    })
+}
+
+/// Converts a SQL row selected with `CHANNEL_COLUMNS` into a `ChannelRecord`.
+fn row_to_channel(row: &Row) -> Result<ChannelRecord> {
+    Ok(ChannelRecord {
+        channel_id: row.get(0)?,
+        name: row.get(1)?,
+        handle: row.get(2)?,
+        url: row.get(3)?,
+        subscriber_count: row.get(4)?,
+        avatar_path: row.get(5)?,
+        banner_path: row.get(6)?,
+        last_refreshed_at: row.get(7)?,
+    })
+}
+
+/// Converts a SQL row selected with `PLAYLIST_COLUMNS` into a `PlaylistRecord`.
+fn row_to_playlist(row: &Row) -> Result<PlaylistRecord> {
+    Ok(PlaylistRecord {
+        playlist_id: row.get(0)?,
+        title: row.get(1)?,
+        description: row.get(2)?,
+        owner: row.get(3)?,
+        owner_channel_id: row.get(4)?,
+        url: row.get(5)?,
+        tracked: row.get::<i64>(6).map(|value| value != 0)?,
+        last_refreshed_at: row.get(7)?,
+        entry_count: row.get(8)?,
+    })
+}
+
+/// Converts a SQL row into a `CommentRecord` while normalizing the boolean flag
+/// stored as an INTEGER in SQLite.
+fn row_to_comment(row: &Row) -> Result<CommentRecord> {
+    Ok(CommentRecord {
+        id: row.get(0)?,
+        videoid: row.get(1)?,
+        author: row.get(2)?,
+        text: row.get(3)?,
+        likes: row.get(4)?,
+        time_posted: row.get(5)?,
+        parent_comment_id: row.get(6)?,
+        status_likedbycreator: row.get::<i64>(7).map(|value| value != 0)?,
+        reply_count: row.get(8)?,
+        deleted_upstream_at: row.get(9)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use std::path::PathBuf;
+    use tempfile::tempdir;
+
+    /// Utility builder so every test can generate a fully populated video row
+    /// without repeating dozens of assignments. Individual tests tweak the
+    /// resulting struct when they need to exercise specific fields.
+    fn sample_video(id: &str) -> VideoRecord {
+        VideoRecord {
+            videoid: id.to_owned(),
+            title: format!("Video {id}"),
+            description: "desc".into(),
+            likes: Some(1),
+            dislikes: Some(0),
+            views: Some(42),
+            upload_date: Some("2024-01-01".into()),
+            author: Some("Author".into()),
+            subscriber_count: Some(1000),
+            duration: Some(120),
+            duration_text: Some("2:00".into()),
+            channel_url: Some("https://example.com".into()),
+            channel_id: Some("UC_sample".into()),
+            thumbnail_url: Some("thumb.jpg".into()),
+            tags: vec!["tech".into()],
+            thumbnails: vec!["thumb.jpg".into()],
+            extras: serde_json::json!({"kind": "demo"}),
+            sources: vec![VideoSource {
+                format_id: "1080p".into(),
+                quality_label: Some("1080p".into()),
+                width: Some(1920),
+                height: Some(1080),
+                fps: Some(30.0),
+                mime_type: Some("video/mp4".into()),
+                ext: Some("mp4".into()),
+                file_size: Some(1_000_000),
+                url: "https://cdn.example/video.mp4".into(),
+                path: Some("/videos/video.mp4".into()),
+                sha256: None,
+            }],
+            availability: None,
+            availability_checked_at: None,
+            adaptive_streams: Vec::new(),
+        }
+    }
+
+    /// Helper that produces deterministic comment rows; individual tests can
+    /// tweak author/text/timestamps without redefining the entire struct.
+    fn sample_comment(id: &str, videoid: &str) -> CommentRecord {
+        CommentRecord {
+            id: id.into(),
+            videoid: videoid.into(),
+            author: format!("author-{id}"),
+            text: format!("text-{id}"),
+            likes: Some(0),
+            time_posted: Some("2024-01-01T00:00:00Z".into()),
+            parent_comment_id: None,
+            status_likedbycreator: false,
+            reply_count: Some(0),
+            deleted_upstream_at: None,
+        }
+    }
+
+    /// Opens a brand‑new temporary SQLite store and returns both the writable
+    /// `MetadataStore` and read-only `MetadataReader`. Using a temp directory
+    /// keeps tests isolated and mirrors how the binaries interact with the DB.
+    async fn create_store() -> Result<(tempfile::TempDir, MetadataStore, MetadataReader, PathBuf)> {
+        let dir = tempdir()?;
+        let path = dir.path().join("metadata/test.db");
+        let store = MetadataStore::open(&path).await?;
+        let reader = MetadataReader::new(&path).await?;
+        Ok((dir, store, reader, path))
+    }
+
+    /// Validates that opening a store creates the DB file, turns on WAL mode and
+    /// provisions every expected table/index. This guards against regressions in
+    /// the bootstrap SQL.
+    #[tokio::test]
+    async fn opens_store_and_creates_schema() -> Result<()> {
+        let (_temp, _store, _reader, path) = create_store().await?;
+        assert!(path.exists(), "database file should be created");
+
+        let db = Builder::new_local(&path).build().await?;
+        let conn = db.connect()?;
+        configure_connection(&conn).await?;
+        let mut rows = conn.query("PRAGMA journal_mode", params![]).await?;
+        let journal_row = rows.next().await?.context("missing journal_mode row")?;
+        let journal: String = journal_row.get(0)?;
+        assert_eq!(journal.to_lowercase(), "wal");
+        let mut rows = conn.query("PRAGMA synchronous", params![]).await?;
+        let sync_row = rows.next().await?.context("missing synchronous row")?;
+        let synchronous: i64 = sync_row.get(0)?;
+        assert!(
+            synchronous >= 1,
+            "synchronous should be NORMAL or stricter but was {synchronous}"
+        );
+
+        for table in ["videos", "shorts", "subtitles", "comments"] {
+            let mut rows = conn
+                .query(
+                    "SELECT name FROM sqlite_master WHERE type='table' AND name=?1",
+                    [table],
+                )
+                .await?;
+            let exists: Option<String> = rows
+                .next()
+                .await?
+                .map(|row| row.get::<String>(0))
+                .transpose()?;
+            assert_eq!(exists.as_deref(), Some(table));
+        }
+
+        for index in ["idx_comments_videoid", "idx_comments_parent"] {
+            let mut rows = conn
+                .query(
+                    "SELECT name FROM sqlite_master WHERE type='index' AND name=?1",
+                    [index],
+                )
+                .await?;
+            let exists: Option<String> = rows
+                .next()
+                .await?
+                .map(|row| row.get::<String>(0))
+                .transpose()?;
+            assert_eq!(exists.as_deref(), Some(index));
+        }
+        Ok(())
+    }
+
+    /// Ensures that short-lived connections keep foreign_keys enforcement
+    /// enabled so cascades behave consistently across helpers.
+    #[tokio::test]
+    async fn reader_enforces_foreign_keys() -> Result<()> {
+        let (_temp, _store, reader, _path) = create_store().await?;
+        let conn = reader.connection().await?;
+        let mut rows = conn.query("PRAGMA foreign_keys", params![]).await?;
+        let row = rows.next().await?.context("missing foreign_keys row")?;
+        let flag: i64 = row.get(0)?;
+        assert_eq!(flag, 1);
+        Ok(())
+    }
+
+    /// Covers the insert/update path for long-form videos, ensuring JSON fields
+    /// survive a round trip and updates override previous values as intended.
+    #[tokio::test]
+    async fn upsert_video_roundtrip() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+
+        let mut record = sample_video("alpha");
+        // First insertion should persist all provided metadata as-is.
+        store.upsert_video(&record).await?;
+
+        let fetched = reader.get_video("alpha").await?.expect("video fetched");
+        assert_eq!(fetched.title, record.title);
+        assert_eq!(fetched.tags, record.tags);
+        assert_eq!(fetched.sources[0].format_id, "1080p");
+
+        // Update a couple of fields and verify that ON CONFLICT rewrites them.
+        record.title = "Updated".into();
+        record.tags.push("review".into());
+        store.upsert_video(&record).await?;
+        let updated = reader
+            .get_video("alpha")
+            .await?
+            .expect("video fetched after update");
+        assert_eq!(updated.title, "Updated");
+        assert!(updated.tags.contains(&"review".into()));
+        Ok(())
+    }
+
+    /// Mirrors the previous test but against the `shorts` table to guarantee
+    /// feature parity between both content types.
+    #[tokio::test]
+    async fn upsert_short_roundtrip() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+
+        let record = sample_video("shorty");
+        // Short content uses the dedicated table but otherwise mirrors videos.
+        store.upsert_short(&record).await?;
+
+        let shorts = reader.list_shorts().await?;
+        assert_eq!(shorts.len(), 1);
+        assert_eq!(shorts[0].videoid, "shorty");
+        Ok(())
+    }
+
+    /// Ensures subtitle collections get serialized to JSON and can be retrieved
+    /// verbatim by the reader API.
+    #[tokio::test]
+    async fn upsert_and_list_subtitles() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_video(&sample_video("vid")).await?;
+
+        let subtitles = SubtitleCollection {
+            videoid: "vid".into(),
+            languages: vec![SubtitleTrack {
+                code: "en".into(),
+                name: "English".into(),
+                url: "https://cdn/subs.vtt".into(),
+                path: Some("/subs/en.vtt".into()),
+            }],
+        };
+        // Writing a collection should replace any prior row for the video.
+        store.upsert_subtitles(&subtitles).await?;
+
+        let listed = reader.list_subtitles().await?;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].languages[0].code, "en");
+        Ok(())
+    }
+
+    /// Exercises the transactional comment replacement flow so we never keep
+    /// stale comment trees after a new download cycle.
+    #[tokio::test]
+    async fn replace_comments_resets_previous_entries() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_video(&sample_video("vid")).await?;
+
+        let first = vec![CommentRecord {
+            id: "1".into(),
+            videoid: "vid".into(),
+            author: "a".into(),
+            text: "hello".into(),
+            likes: Some(1),
+            time_posted: Some("2024-01-01".into()),
+            parent_comment_id: None,
+            status_likedbycreator: true,
+            reply_count: Some(0),
+            deleted_upstream_at: None,
+        }];
+        // Seed the DB with a first batch of comments.
+        store.replace_comments("vid", &first).await?;
+
+        let second = vec![CommentRecord {
+            id: "2".into(),
+            videoid: "vid".into(),
+            author: "b".into(),
+            text: "world".into(),
+            likes: Some(2),
+            time_posted: Some("2024-01-02".into()),
+            parent_comment_id: None,
+            status_likedbycreator: false,
+            reply_count: Some(1),
+            deleted_upstream_at: None,
+        }];
+        // Second replacement should wipe the previous entries before inserting.
+        store.replace_comments("vid", &second).await?;
+
+        let fetched = reader.get_comments("vid", true).await?;
+        assert_eq!(fetched.len(), 1);
+        assert_eq!(fetched[0].id, "2");
+        assert!(!fetched[0].status_likedbycreator);
+        Ok(())
+    }
+
+    /// Verifies that listing videos applies the desired ordering (newest first)
+    /// even when dates differ, which is critical for deterministic feeds.
+    #[tokio::test]
+    async fn list_videos_returns_sorted_records() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+
+        let mut old = sample_video("old");
+        old.upload_date = Some("2023-01-01".into());
+        store.upsert_video(&old).await?;
+
+        let mut new = sample_video("new");
+        new.upload_date = Some("2024-05-01".into());
+        store.upsert_video(&new).await?;
+
+        let videos = reader.list_videos().await?;
+        assert_eq!(videos.len(), 2);
+        assert_eq!(videos[0].videoid, "new");
+        assert_eq!(videos[1].videoid, "old");
+        Ok(())
+    }
+
+    /// Reader helpers should gracefully return `None` when a record is missing.
+    #[tokio::test]
+    async fn reader_returns_none_for_missing_entries() -> Result<()> {
+        let (_temp, _store, reader, _path) = create_store().await?;
+        assert!(reader.get_video("ghost").await?.is_none());
+        assert!(reader.get_short("ghost").await?.is_none());
+        assert!(reader.get_subtitles("ghost").await?.is_none());
+        Ok(())
+    }
+
+    /// Listing shorts mirrors videos but must respect upload_date ordering.
+    #[tokio::test]
+    async fn list_shorts_sorted_by_upload_date() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        let mut older = sample_video("short-old");
+        older.upload_date = Some("2023-05-01".into());
+        store.upsert_short(&older).await?;
+
+        let mut newer = sample_video("short-new");
+        newer.upload_date = Some("2024-06-01".into());
+        store.upsert_short(&newer).await?;
+
+        let shorts = reader.list_shorts().await?;
+        assert_eq!(shorts.len(), 2);
+        assert_eq!(shorts[0].videoid, "short-new");
+        assert_eq!(shorts[1].videoid, "short-old");
+        Ok(())
+    }
+
+    /// Subtitle upserts should overwrite existing rows rather than append.
+    #[tokio::test]
+    async fn upsert_subtitles_overwrites_existing_languages() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_video(&sample_video("vid-sub")).await?;
+
+        let initial = SubtitleCollection {
+            videoid: "vid-sub".into(),
+            languages: vec![SubtitleTrack {
+                code: "en".into(),
+                name: "English".into(),
+                url: "https://cdn/en.vtt".into(),
+                path: None,
+            }],
+        };
+        store.upsert_subtitles(&initial).await?;
+
+        let updated = SubtitleCollection {
+            videoid: "vid-sub".into(),
+            languages: vec![SubtitleTrack {
+                code: "fr".into(),
+                name: "Français".into(),
+                url: "https://cdn/fr.vtt".into(),
+                path: Some("/subs/fr.vtt".into()),
+            }],
+        };
+        store.upsert_subtitles(&updated).await?;
+
+        let fetched = reader
+            .get_subtitles("vid-sub")
+            .await?
+            .expect("subtitles exist");
+        assert_eq!(fetched.languages.len(), 1);
+        assert_eq!(fetched.languages[0].code, "fr");
+        Ok(())
+    }
+
+    /// Comments containing replies and flags should persist verbatim.
+    #[tokio::test]
+    async fn replace_comments_preserves_replies_and_flags() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_video(&sample_video("with-comments")).await?;
+
+        let mut parent = sample_comment("parent", "with-comments");
+        parent.status_likedbycreator = true;
+        let mut reply = sample_comment("child", "with-comments");
+        reply.parent_comment_id = Some("parent".into());
+
+        store
+            .replace_comments("with-comments", &[parent.clone(), reply.clone()])
+            .await?;
+
+        let comments = reader.get_comments("with-comments", true).await?;
+        assert_eq!(comments.len(), 2);
+        assert_eq!(comments[0].id, "parent");
+        assert!(comments[0].status_likedbycreator);
+        assert_eq!(comments[1].parent_comment_id.as_deref(), Some("parent"));
+        Ok(())
+    }
+
+    /// list_all_comments should merge comments across videos ordered by timestamp.
+    #[tokio::test]
+    async fn list_all_comments_orders_by_time() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_video(&sample_video("video-one")).await?;
+        store.upsert_video(&sample_video("video-two")).await?;
+
+        let mut first = sample_comment("1", "video-one");
+        first.time_posted = Some("2024-01-01T00:00:00Z".into());
+        let mut second = sample_comment("2", "video-two");
+        second.time_posted = Some("2024-01-01T00:05:00Z".into());
+        let mut third = sample_comment("3", "video-one");
+        third.time_posted = Some("2024-01-01T00:10:00Z".into());
+
+        store
+            .replace_comments("video-one", &[first.clone(), third.clone()])
+            .await?;
+        store
+            .replace_comments("video-two", &[second.clone()])
+            .await?;
+
+        let all = reader.list_all_comments(true).await?;
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[0].id, "1");
+        assert_eq!(all[1].id, "2");
+        assert_eq!(all[2].id, "3");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn open_creates_parent_dirs_and_reopens() -> Result<()> {
+        let temp = tempdir()?;
+        let path = temp.path().join("nested/metadata/db.sqlite");
+        let _store = MetadataStore::open(&path).await?;
+        assert!(path.exists());
+        assert!(path.parent().unwrap().exists());
+        let _reopened = MetadataStore::open(&path).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_videos_orders_by_date_then_rowid() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+
+        let mut first = sample_video("first");
+        first.upload_date = Some("2024-01-01".into());
+        store.upsert_video(&first).await?;
+
+        let mut second = sample_video("second");
+        second.upload_date = Some("2024-01-01".into());
+        store.upsert_video(&second).await?;
+
+        let mut no_date = sample_video("no-date");
+        no_date.upload_date = None;
+        store.upsert_video(&no_date).await?;
+
+        let videos = reader.list_videos().await?;
+        assert_eq!(videos.len(), 3);
+        assert_eq!(videos[0].videoid, "second");
+        assert_eq!(videos[1].videoid, "first");
+        assert_eq!(videos[2].videoid, "no-date");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replace_comments_with_empty_slice_clears_entries() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_video(&sample_video("vid")).await?;
+        store
+            .replace_comments("vid", &[sample_comment("c1", "vid")])
+            .await?;
+        assert_eq!(reader.get_comments("vid", true).await?.len(), 1);
+
+        store.replace_comments("vid", &[]).await?;
+        assert!(reader.get_comments("vid", true).await?.is_empty());
+        Ok(())
+    }
+
+    /// A batch applies writes for several videos at once, keeps the search
+    /// index current and is reusable. A failing write is rolled back on its
+    /// own and reported, while the rest of the batch still lands.
+    #[tokio::test]
+    async fn write_batch_commits_and_isolates_failed_writes() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        let mut batch = store.batch();
+        for id in ["alpha", "beta"] {
+            let mut record = sample_video(id);
+            record.title = format!("{id} batched");
+            batch.upsert_video(record);
+            batch.replace_comments(id, vec![sample_comment(&format!("{id}-c"), id)]);
+            batch.replace_chapters(
+                id,
+                vec![Chapter {
+                    start: 0.0,
+                    end: None,
+                    title: "Start".into(),
+                }],
+            );
+        }
+        assert_eq!(batch.len(), 6);
+        assert!(reader.get_video("alpha").await?.is_none());
+        batch.commit().await?;
+        assert!(batch.is_empty());
+
+        assert_eq!(reader.list_videos().await?.len(), 2);
+        assert_eq!(reader.get_comments("beta", true).await?.len(), 1);
+        assert_eq!(reader.get_chapters("alpha").await?.len(), 1);
+        let hits = reader.search("batched", &SearchOptions::default()).await?;
+        assert_eq!(hits.len(), 2);
+
+        batch.upsert_short(sample_video("gamma"));
+        batch.replace_comments(
+            "alpha",
+            vec![
+                sample_comment("dup", "alpha"),
+                sample_comment("dup", "alpha"),
+            ],
+        );
+        let err = batch.commit().await.unwrap_err();
+        assert!(err.to_string().contains("1 of 2 queued writes failed"));
+        assert!(batch.is_empty());
+        assert!(reader.get_short("gamma").await?.is_some());
+        let comments = reader.get_comments("alpha", true).await?;
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].id, "alpha-c");
+        Ok(())
+    }
+
+    /// A batch that cannot start its transaction stays queued, so the next
+    /// commit still writes everything.
+    #[tokio::test]
+    async fn write_batch_survives_a_locked_database() -> Result<()> {
+        let (_temp, store, reader, path) = create_store().await?;
+        store.conn.execute_batch("PRAGMA busy_timeout=0;").await?;
+        let other = MetadataStore::open(&path).await?;
+        other.conn.execute_batch("BEGIN IMMEDIATE;").await?;
+
+        let mut batch = store.batch();
+        batch.upsert_video(sample_video("alpha"));
+        assert!(batch.commit().await.is_err());
+        assert_eq!(batch.len(), 1);
+        assert!(batch.commit().await.is_err());
+        assert_eq!(batch.failed_commits(), 2);
+
+        other.conn.execute_batch("ROLLBACK;").await?;
+        batch.commit().await?;
+        assert!(batch.is_empty());
+        assert_eq!(batch.failed_commits(), 0);
+        assert!(reader.get_video("alpha").await?.is_some());
+        Ok(())
+    }
+
+    /// Restoring an export keeps the original history and block timestamps
+    /// instead of stamping everything with the import time.
+    #[tokio::test]
+    async fn restores_stats_history_and_blocked_entries() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        let snapshots = vec![
+            VideoStatsSnapshot {
+                recorded_at: "2024-01-01T00:00:00.000Z".into(),
+                views: Some(10),
+                likes: None,
+                dislikes: None,
+                comment_count: None,
+                subscriber_count: None,
+            },
+            VideoStatsSnapshot {
+                recorded_at: "2024-02-01T00:00:00.000Z".into(),
+                views: Some(20),
+                likes: Some(2),
+                dislikes: None,
+                comment_count: None,
+                subscriber_count: None,
+            },
+        ];
+        let mut batch = store.batch();
+        batch.upsert_video(sample_video("alpha"));
+        batch.replace_stats_history("alpha", snapshots.clone());
+        batch.commit().await?;
+        assert_eq!(reader.get_video_stats_history("alpha").await?, snapshots);
+
+        let entry = BlockedMedia {
+            videoid: "gone".into(),
+            blocked_at: "2023-05-05T10:00:00.000Z".into(),
+            reason: Some("spam".into()),
+        };
+        store.block_media("gone", None).await?;
+        store.restore_blocked_media(&entry).await?;
+        assert_eq!(reader.list_blocked_media().await?, vec![entry]);
+
+        assert!(!reader.has_manual_segments("alpha").await?);
+        let segment = SkipSegment {
+            category: SegmentCategory::Sponsor,
+            start: 1.0,
+            end: 2.0,
+        };
+        store
+            .import_segments("alpha", std::slice::from_ref(&segment))
+            .await?;
+        assert!(!reader.has_manual_segments("alpha").await?);
+        store.set_segments("alpha", &[segment]).await?;
+        assert!(reader.has_manual_segments("alpha").await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn replace_comments_rolls_back_on_duplicate_ids() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_video(&sample_video("vid")).await?;
+        store
+            .replace_comments("vid", &[sample_comment("keep", "vid")])
+            .await?;
+
+        let mut dup1 = sample_comment("dup", "vid");
+        let dup2 = sample_comment("dup", "vid");
+        dup1.text = "first".into();
+        let err = store.replace_comments("vid", &[dup1, dup2]).await;
+        assert!(err.is_err());
+
+        let comments = reader.get_comments("vid", true).await?;
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].id, "keep");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn comments_queries_ignore_orphaned_video_ids() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store
+            .replace_comments("ghost", &[sample_comment("c1", "ghost")])
+            .await?;
+
+        assert!(reader.get_comments("ghost", true).await?.is_empty());
+        assert!(reader.list_all_comments(true).await?.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upsert_video_allows_empty_collections_and_null_extras() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+
+        let record = VideoRecord {
+            videoid: "empty".into(),
+            title: "Empty".into(),
+            description: String::new(),
+            likes: None,
+            dislikes: None,
+            views: None,
+            upload_date: None,
+            author: None,
+            subscriber_count: None,
+            duration: None,
+            duration_text: None,
+            channel_url: None,
+            channel_id: None,
+            thumbnail_url: None,
+            tags: Vec::new(),
+            thumbnails: Vec::new(),
+            extras: Value::Null,
+            sources: Vec::new(),
+            availability: None,
+            availability_checked_at: None,
+            adaptive_streams: Vec::new(),
+        };
+
+        store.upsert_video(&record).await?;
+        let fetched = reader.get_video("empty").await?.expect("video exists");
+        assert!(fetched.tags.is_empty());
+        assert!(fetched.thumbnails.is_empty());
+        assert!(fetched.sources.is_empty());
+        assert_eq!(fetched.extras, Value::Null);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn upsert_subtitles_allows_empty_languages() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_video(&sample_video("vid")).await?;
+
+        let subtitles = SubtitleCollection {
+            videoid: "vid".into(),
+            languages: Vec::new(),
+        };
+        store.upsert_subtitles(&subtitles).await?;
+
+        let fetched = reader.get_subtitles("vid").await?.expect("subtitles exist");
+        assert!(fetched.languages.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn data_version_changes_after_writes() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        let before = reader.data_version().await?;
+        store.upsert_video(&sample_video("alpha")).await?;
+        let after = reader.data_version().await?;
+        assert_ne!(before, after);
+        Ok(())
+    }
+
+    /// The pool never hands out more connections than its size, reuses
+    /// returned ones, and version polling does not take a pool slot.
+    #[tokio::test]
+    async fn reader_pool_is_bounded_and_reuses_connections() -> Result<()> {
+        let (_temp, store, _reader, path) = create_store().await?;
+        let reader = MetadataReader::with_pool_size(&path, 2).await?;
+        assert_eq!(reader.pool_size(), 2);
+        store.upsert_video(&sample_video("alpha")).await?;
+
+        let first = reader.connection().await?;
+        let second = reader.connection().await?;
+        assert_eq!(reader.pool.permits.available_permits(), 0);
+        let before = reader.data_version().await?;
+        store.upsert_video(&sample_video("beta")).await?;
+        assert_ne!(reader.data_version().await?, before);
+
+        drop(first);
+        assert_eq!(reader.pool.idle.lock().len(), 1);
+        assert_eq!(reader.list_videos().await?.len(), 2);
+        assert_eq!(reader.pool.idle.lock().len(), 1);
+        drop(second);
+        assert_eq!(reader.pool.idle.lock().len(), 2);
+
+        let mut queries = tokio::task::JoinSet::new();
+        for _ in 0..8 {
+            let reader = reader.clone();
+            queries.spawn(async move { reader.list_videos().await });
+        }
+        while let Some(videos) = queries.join_next().await {
+            assert_eq!(videos??.len(), 2);
+        }
+        assert_eq!(reader.pool.idle.lock().len(), 2);
+        assert_eq!(
+            MetadataReader::with_pool_size(&path, 0).await?.pool_size(),
+            1
+        );
+        Ok(())
+    }
+
+    /// Fresh databases get every migration applied exactly once, and reopening
+    /// the store must not re-run or re-record any of them.
+    #[tokio::test]
+    async fn migrations_apply_once_and_record_version() -> Result<()> {
+        let (_temp, store, _reader, path) = create_store().await?;
+        assert_eq!(store.schema_version().await?, SCHEMA_VERSION);
+        drop(store);
+
+        let reopened = MetadataStore::open(&path).await?;
+        assert_eq!(reopened.schema_version().await?, SCHEMA_VERSION);
+
+        let mut rows = reopened
+            .conn
+            .query("SELECT COUNT(*) FROM schema_version", params![])
+            .await?;
+        let row = rows.next().await?.context("missing count row")?;
+        let applied: i64 = row.get(0)?;
+        assert_eq!(applied, MIGRATIONS.len() as i64);
+        Ok(())
+    }
+
+    /// A DB stamped by a newer binary must be refused rather than silently
+    /// read or written with an outdated understanding of the schema.
+    #[tokio::test]
+    async fn refuses_database_newer_than_binary() -> Result<()> {
+        let (_temp, store, _reader, path) = create_store().await?;
+        store
+            .conn
+            .execute(
+                "INSERT INTO schema_version (version, description) VALUES (?1, 'future')",
+                params![SCHEMA_VERSION + 1],
+            )
+            .await?;
+        drop(store);
+
+        let err = MetadataStore::open(&path)
+            .await
+            .expect_err("newer schema should be rejected");
+        assert!(err.to_string().contains("newer than this binary"));
+        assert!(MetadataReader::new(&path).await.is_err());
+        Ok(())
+    }
+
+    /// Databases created before versioning existed (no `schema_version` table,
+    /// legacy comments foreign key) are upgraded in place without data loss.
+    #[tokio::test]
+    async fn upgrades_unversioned_legacy_database() -> Result<()> {
+        let temp = tempdir()?;
+        let path = temp.path().join("legacy.db");
+        {
+            let db = Builder::new_local(&path).build().await?;
+            let conn = db.connect()?;
+            conn.execute_batch(
+                r#"
+                CREATE TABLE videos (
+                    videoid TEXT PRIMARY KEY,
+                    title TEXT NOT NULL,
+                    description TEXT DEFAULT '',
+                    likes INTEGER,
+                    dislikes INTEGER,
+                    views INTEGER,
+                    upload_date TEXT,
+                    author TEXT,
+                    subscriber_count INTEGER,
+                    duration INTEGER,
+                    duration_text TEXT,
+                    channel_url TEXT,
+                    thumbnail_url TEXT,
+                    tags_json TEXT DEFAULT '[]',
+                    thumbnails_json TEXT DEFAULT '[]',
+                    extras_json TEXT DEFAULT 'null',
+                    sources_json TEXT DEFAULT '[]'
+                );
+                CREATE TABLE comments (
+                    id TEXT PRIMARY KEY,
+                    videoid TEXT NOT NULL REFERENCES videos(videoid),
+                    author TEXT DEFAULT '',
+                    text TEXT DEFAULT '',
+                    likes INTEGER,
+                    time_posted TEXT,
+                    parent_comment_id TEXT,
+                    status_likedbycreator INTEGER NOT NULL DEFAULT 0,
+                    reply_count INTEGER
+                );
+                INSERT INTO videos (videoid, title, views) VALUES ('vid', 'Legacy', 42);
+                INSERT INTO comments (id, videoid, text) VALUES ('c1', 'vid', 'kept');
+                "#,
+            )
+            .await?;
+        }
+
+        let store = MetadataStore::open(&path).await?;
+        assert_eq!(store.schema_version().await?, SCHEMA_VERSION);
+
+        let mut rows = store
+            .conn
+            .query("PRAGMA foreign_key_list(comments)", params![])
+            .await?;
+        assert!(rows.next().await?.is_none(), "legacy FK should be dropped");
+
+        let mut rows = store
+            .conn
+            .query("SELECT text FROM comments WHERE id = 'c1'", params![])
+            .await?;
+        let row = rows.next().await?.context("legacy comment missing")?;
+        assert_eq!(row.get::<String>(0)?, "kept");
+        drop(rows);
+
+        // Existing rows are backfilled into the search index.
+        let reader = MetadataReader::new(&path).await?;
+        let hits = reader.search("legacy", &SearchOptions::default()).await?;
+        assert_eq!(hits.len(), 1);
+
+        // Known stats seed the history as observed at upgrade time.
+        let history = reader.get_video_stats_history("vid").await?;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].views, Some(42));
+        Ok(())
+    }
+
+    /// Titles, tags and comment text are all searchable, and the index follows
+    /// later upserts/comment replacements instead of returning stale hits.
+    #[tokio::test]
+    async fn search_indexes_records_and_comments() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        let mut record = sample_video("alpha");
+        record.title = "Building a tube amplifier".into();
+        store.upsert_video(&record).await?;
+        store
+            .replace_comments(
+                "alpha",
+                &[CommentRecord {
+                    text: "Great soldering technique".into(),
+                    ..sample_comment("c1", "alpha")
+                }],
+            )
+            .await?;
+
+        let hits = reader
+            .search("amplifier", &SearchOptions::default())
+            .await?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].videoid, "alpha");
+        assert_eq!(hits[0].kind, "video");
+        assert!(hits[0].snippet.contains("<mark>"));
+
+        assert_eq!(
+            reader
+                .search("solder", &SearchOptions::default())
+                .await?
+                .len(),
+            1
+        );
+        assert_eq!(
+            reader
+                .search("tech", &SearchOptions::default())
+                .await?
+                .len(),
+            1
+        );
+
+        record.title = "Renamed".into();
+        store.upsert_video(&record).await?;
+        store.replace_comments("alpha", &[]).await?;
+        assert!(
+            reader
+                .search("amplifier", &SearchOptions::default())
+                .await?
+                .is_empty()
+        );
+        assert!(
+            reader
+                .search("soldering", &SearchOptions::default())
+                .await?
+                .is_empty()
+        );
+        assert_eq!(
+            reader
+                .search("renamed", &SearchOptions::default())
+                .await?
+                .len(),
+            1
+        );
+        Ok(())
     }
 
-    /// Opens a brand‑new temporary SQLite store and returns both the writable
-    /// `MetadataStore` and read-only `MetadataReader`. Using a temp directory
-    /// keeps tests isolated and mirrors how the binaries interact with the DB.
-    async fn create_store() -> Result<(tempfile::TempDir, MetadataStore, MetadataReader, PathBuf)> {
-        let dir = tempdir()?;
-        let path = dir.path().join("metadata/test.db");
-        let store = MetadataStore::open(&path).await?;
-        let reader = MetadataReader::new(&path).await?;
-        Ok((dir, store, reader, path))
+    /// Kind/channel filters narrow the hits and title matches outrank matches
+    /// that only appear in the description.
+    #[tokio::test]
+    async fn search_applies_filters_and_ranking() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        let mut titled = sample_video("titled");
+        titled.title = "Synthesizer basics".into();
+        titled.extras = serde_json::json!({"channelId": "UC_one"});
+        store.upsert_video(&titled).await?;
+
+        let mut described = sample_video("described");
+        described.description = "We briefly mention a synthesizer here".into();
+        described.channel_url = Some("https://example.com/@two".into());
+        store.upsert_video(&described).await?;
+
+        let mut short = sample_video("short");
+        short.title = "Synthesizer in 30s".into();
+        store.upsert_short(&short).await?;
+
+        let all = reader
+            .search("synthesizer", &SearchOptions::default())
+            .await?;
+        assert_eq!(all.len(), 3);
+        assert_eq!(
+            all.last().map(|hit| hit.videoid.as_str()),
+            Some("described")
+        );
+
+        let shorts_only = SearchOptions {
+            kind: Some("short".into()),
+            ..SearchOptions::default()
+        };
+        let hits = reader.search("synthesizer", &shorts_only).await?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].videoid, "short");
+
+        let by_id = SearchOptions {
+            channel: Some("UC_one".into()),
+            ..SearchOptions::default()
+        };
+        let hits = reader.search("synthesizer", &by_id).await?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].videoid, "titled");
+
+        let by_url = SearchOptions {
+            channel: Some("https://example.com/@two".into()),
+            ..SearchOptions::default()
+        };
+        let hits = reader.search("synthesizer", &by_url).await?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].videoid, "described");
+        Ok(())
     }
 
-    /// Validates that opening a store creates the DB file, turns on WAL mode and
-    /// provisions every expected table/index. This guards against regressions in
-    /// the bootstrap SQL.
+    /// Raw user input containing FTS5 operators must not produce SQL errors.
     #[tokio::test]
-    async fn opens_store_and_creates_schema() -> Result<()> {
-        let (_temp, _store, _reader, path) = create_store().await?;
-        assert!(path.exists(), "database file should be created");
+    async fn search_tolerates_fts_syntax_in_queries() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_video(&sample_video("alpha")).await?;
 
-        let db = Builder::new_local(&path).build().await?;
-        let conn = db.connect()?;
-        configure_connection(&conn).await?;
-        let mut rows = conn.query("PRAGMA journal_mode", params![]).await?;
-        let journal_row = rows.next().await?.context("missing journal_mode row")?;
-        let journal: String = journal_row.get(0)?;
-        assert_eq!(journal.to_lowercase(), "wal");
-        let mut rows = conn.query("PRAGMA synchronous", params![]).await?;
-        let sync_row = rows.next().await?.context("missing synchronous row")?;
-        let synchronous: i64 = sync_row.get(0)?;
+        for query in ["\"unbalanced", "NEAR(", "a OR", "-", "*", "   "] {
+            reader.search(query, &SearchOptions::default()).await?;
+        }
         assert!(
-            synchronous >= 1,
-            "synchronous should be NORMAL or stricter but was {synchronous}"
+            reader
+                .search("", &SearchOptions::default())
+                .await?
+                .is_empty()
         );
+        Ok(())
+    }
 
-        for table in ["videos", "shorts", "subtitles", "comments"] {
-            let mut rows = conn
-                .query(
-                    "SELECT name FROM sqlite_master WHERE type='table' AND name=?1",
-                    [table],
-                )
-                .await?;
-            let exists: Option<String> = rows
-                .next()
-                .await?
-                .map(|row| row.get::<String>(0))
-                .transpose()?;
-            assert_eq!(exists.as_deref(), Some(table));
+    fn sample_channel(id: &str) -> ChannelRecord {
+        ChannelRecord {
+            channel_id: id.into(),
+            name: format!("Channel {id}"),
+            handle: Some(format!("@{id}")),
+            url: Some(format!("https://www.youtube.com/channel/{id}")),
+            subscriber_count: Some(10),
+            avatar_path: Some(format!("/yt/channels/{id}/avatar.jpg")),
+            banner_path: None,
+            last_refreshed_at: Some("2024-01-01T00:00:00Z".into()),
         }
+    }
 
-        for index in ["idx_comments_videoid", "idx_comments_parent"] {
-            let mut rows = conn
-                .query(
-                    "SELECT name FROM sqlite_master WHERE type='index' AND name=?1",
-                    [index],
-                )
-                .await?;
-            let exists: Option<String> = rows
-                .next()
-                .await?
-                .map(|row| row.get::<String>(0))
-                .transpose()?;
-            assert_eq!(exists.as_deref(), Some(index));
-        }
+    /// Partial channel updates (as produced from a single video's metadata)
+    /// must refresh counts without discarding avatar/handle data.
+    #[tokio::test]
+    async fn upsert_channel_merges_partial_updates() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_channel(&sample_channel("UC_a")).await?;
+
+        store
+            .upsert_channel(&ChannelRecord {
+                channel_id: "UC_a".into(),
+                name: String::new(),
+                handle: None,
+                url: None,
+                subscriber_count: Some(25),
+                avatar_path: None,
+                banner_path: Some("/yt/channels/UC_a/banner.jpg".into()),
+                last_refreshed_at: None,
+            })
+            .await?;
+
+        let channel = reader.get_channel("UC_a").await?.expect("channel stored");
+        assert_eq!(channel.name, "Channel UC_a");
+        assert_eq!(channel.handle.as_deref(), Some("@UC_a"));
+        assert_eq!(channel.subscriber_count, Some(25));
+        assert!(channel.avatar_path.is_some());
+        assert!(channel.banner_path.is_some());
+        assert_eq!(
+            channel.last_refreshed_at.as_deref(),
+            Some("2024-01-01T00:00:00Z")
+        );
+        assert!(reader.get_channel("UC_missing").await?.is_none());
         Ok(())
     }
 
-    /// Ensures that short-lived connections keep foreign_keys enforcement
-    /// enabled so cascades behave consistently across helpers.
+    /// Channel listings only return rows whose `channel_id` matches, per table.
     #[tokio::test]
-    async fn reader_enforces_foreign_keys() -> Result<()> {
-        let (_temp, _store, reader, _path) = create_store().await?;
-        let conn = &reader.conn;
-        let mut rows = conn.query("PRAGMA foreign_keys", params![]).await?;
-        let row = rows.next().await?.context("missing foreign_keys row")?;
-        let flag: i64 = row.get(0)?;
-        assert_eq!(flag, 1);
+    async fn channel_media_listing_filters_by_channel() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_channel(&sample_channel("UC_b")).await?;
+        store.upsert_channel(&sample_channel("UC_a")).await?;
+
+        let mut mine = sample_video("mine");
+        mine.channel_id = Some("UC_a".into());
+        store.upsert_video(&mine).await?;
+        let mut other = sample_video("other");
+        other.channel_id = Some("UC_b".into());
+        store.upsert_video(&other).await?;
+        let mut short = sample_video("mine-short");
+        short.channel_id = Some("UC_a".into());
+        store.upsert_short(&short).await?;
+
+        let channels = reader.list_channels().await?;
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0].channel_id, "UC_a");
+
+        let videos = reader.list_channel_videos("UC_a").await?;
+        assert_eq!(videos.len(), 1);
+        assert_eq!(videos[0].videoid, "mine");
+        assert_eq!(videos[0].channel_id.as_deref(), Some("UC_a"));
+
+        let shorts = reader.list_channel_shorts("UC_a").await?;
+        assert_eq!(shorts.len(), 1);
+        assert_eq!(shorts[0].videoid, "mine-short");
+        assert!(reader.list_channel_shorts("UC_b").await?.is_empty());
         Ok(())
     }
 
-    /// Covers the insert/update path for long-form videos, ensuring JSON fields
-    /// survive a round trip and updates override previous values as intended.
+    fn sample_playlist(id: &str) -> PlaylistRecord {
+        PlaylistRecord {
+            playlist_id: id.into(),
+            title: format!("Playlist {id}"),
+            description: "curated".into(),
+            owner: Some("Author".into()),
+            owner_channel_id: Some("UC_sample".into()),
+            url: Some(format!("https://www.youtube.com/playlist?list={id}")),
+            tracked: true,
+            last_refreshed_at: None,
+            entry_count: 0,
+        }
+    }
+
+    fn playlist_entry(position: i64, videoid: &str) -> PlaylistEntry {
+        PlaylistEntry {
+            position,
+            videoid: videoid.into(),
+            kind: "video".into(),
+        }
+    }
+
+    /// Re-syncing a playlist must mirror upstream removals and reordering
+    /// rather than appending to the previous entry list.
     #[tokio::test]
-    async fn upsert_video_roundtrip() -> Result<()> {
+    async fn replace_playlist_mirrors_removals_and_reordering() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
+        let playlist = sample_playlist("PL1");
+        store
+            .replace_playlist(
+                &playlist,
+                &[
+                    playlist_entry(0, "a"),
+                    playlist_entry(1, "b"),
+                    playlist_entry(2, "c"),
+                ],
+            )
+            .await?;
+        assert_eq!(reader.get_playlist_entries("PL1").await?.len(), 3);
 
-        let mut record = sample_video("alpha");
-        // First insertion should persist all provided metadata as-is.
-        store.upsert_video(&record).await?;
+        store
+            .replace_playlist(&playlist, &[playlist_entry(0, "c"), playlist_entry(1, "a")])
+            .await?;
+        let entries = reader.get_playlist_entries("PL1").await?;
+        let ids: Vec<&str> = entries.iter().map(|entry| entry.videoid.as_str()).collect();
+        assert_eq!(ids, ["c", "a"]);
 
-        let fetched = reader.get_video("alpha").await?.expect("video fetched");
-        assert_eq!(fetched.title, record.title);
-        assert_eq!(fetched.tags, record.tags);
-        assert_eq!(fetched.sources[0].format_id, "1080p");
+        let stored = reader.get_playlist("PL1").await?.expect("playlist stored");
+        assert_eq!(stored.entry_count, 2);
+        assert!(stored.tracked);
+        assert_eq!(stored.owner.as_deref(), Some("Author"));
+        assert!(reader.get_playlist("missing").await?.is_none());
+        Ok(())
+    }
 
-        // Update a couple of fields and verify that ON CONFLICT rewrites them.
-        record.title = "Updated".into();
-        record.tags.push("review".into());
-        store.upsert_video(&record).await?;
-        let updated = reader
-            .get_video("alpha")
-            .await?
-            .expect("video fetched after update");
-        assert_eq!(updated.title, "Updated");
-        assert!(updated.tags.contains(&"review".into()));
+    /// Playlists are listed alphabetically by title.
+    #[tokio::test]
+    async fn list_playlists_sorted_by_title() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        let mut zulu = sample_playlist("PLz");
+        zulu.title = "Zulu".into();
+        store.replace_playlist(&zulu, &[]).await?;
+        let mut alpha = sample_playlist("PLa");
+        alpha.title = "alpha".into();
+        store
+            .replace_playlist(&alpha, &[playlist_entry(0, "x")])
+            .await?;
+
+        let playlists = reader.list_playlists().await?;
+        assert_eq!(playlists.len(), 2);
+        assert_eq!(playlists[0].playlist_id, "PLa");
+        assert_eq!(playlists[0].entry_count, 1);
+        assert_eq!(playlists[1].playlist_id, "PLz");
         Ok(())
     }
 
-    /// Mirrors the previous test but against the `shorts` table to guarantee
-    /// feature parity between both content types.
+    /// Walks every page with the given query and returns the video ids in
+    /// the order the pages produced them.
+    async fn collect_pages(
+        reader: &MetadataReader,
+        mut query: MediaListQuery,
+    ) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        loop {
+            let page = reader.list_videos_page(&query).await?;
+            ids.extend(page.items.into_iter().map(|record| record.videoid));
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => return Ok(ids),
+            }
+        }
+    }
+
+    /// Keyset pages must cover every row exactly once, including rows that
+    /// tie on the sort key or have no value for it.
     #[tokio::test]
-    async fn upsert_short_roundtrip() -> Result<()> {
+    async fn media_pages_walk_every_row_once() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
+        for (id, views) in [
+            ("a", Some(5)),
+            ("b", Some(9)),
+            ("c", Some(5)),
+            ("d", None),
+            ("e", Some(1)),
+        ] {
+            let mut record = sample_video(id);
+            record.views = views;
+            store.upsert_video(&record).await?;
+        }
 
-        let record = sample_video("shorty");
-        // Short content uses the dedicated table but otherwise mirrors videos.
-        store.upsert_short(&record).await?;
+        let query = MediaListQuery {
+            sort: MediaSort::Views,
+            limit: Some(2),
+            ..MediaListQuery::default()
+        };
+        let first = reader.list_videos_page(&query).await?;
+        assert_eq!(first.items.len(), 2);
+        assert!(first.next_cursor.is_some());
+        assert_eq!(
+            collect_pages(&reader, query).await?,
+            ["b", "c", "a", "e", "d"]
+        );
 
-        let shorts = reader.list_shorts().await?;
-        assert_eq!(shorts.len(), 1);
-        assert_eq!(shorts[0].videoid, "shorty");
+        let ascending = MediaListQuery {
+            sort: MediaSort::Views,
+            descending: Some(false),
+            limit: Some(3),
+            ..MediaListQuery::default()
+        };
+        assert_eq!(
+            collect_pages(&reader, ascending).await?,
+            ["d", "e", "a", "c", "b"]
+        );
         Ok(())
     }
 
-    /// Ensures subtitle collections get serialized to JSON and can be retrieved
-    /// verbatim by the reader API.
     #[tokio::test]
-    async fn upsert_and_list_subtitles() -> Result<()> {
+    async fn media_pages_sort_by_title_and_reject_foreign_cursors() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
-        store.upsert_video(&sample_video("vid")).await?;
+        for (id, title) in [("x", "beta"), ("y", "Alpha"), ("z", "gamma")] {
+            let mut record = sample_video(id);
+            record.title = title.into();
+            store.upsert_video(&record).await?;
+        }
 
-        let subtitles = SubtitleCollection {
-            videoid: "vid".into(),
-            languages: vec![SubtitleTrack {
-                code: "en".into(),
-                name: "English".into(),
-                url: "https://cdn/subs.vtt".into(),
-                path: Some("/subs/en.vtt".into()),
-            }],
+        let by_title = MediaListQuery {
+            sort: MediaSort::Title,
+            limit: Some(1),
+            ..MediaListQuery::default()
         };
-        // Writing a collection should replace any prior row for the video.
-        store.upsert_subtitles(&subtitles).await?;
+        assert_eq!(
+            collect_pages(&reader, by_title.clone()).await?,
+            ["y", "x", "z"]
+        );
 
-        let listed = reader.list_subtitles().await?;
-        assert_eq!(listed.len(), 1);
-        assert_eq!(listed[0].languages[0].code, "en");
+        let page = reader.list_videos_page(&by_title).await?;
+        let foreign = MediaListQuery {
+            sort: MediaSort::Views,
+            cursor: page.next_cursor,
+            ..MediaListQuery::default()
+        };
+        let err = reader.list_videos_page(&foreign).await.unwrap_err();
+        assert!(err.downcast_ref::<InvalidCursor>().is_some());
+        let garbage = MediaListQuery {
+            cursor: Some("zz".into()),
+            ..MediaListQuery::default()
+        };
+        assert!(reader.list_videos_page(&garbage).await.is_err());
         Ok(())
     }
 
-    /// Exercises the transactional comment replacement flow so we never keep
-    /// stale comment trees after a new download cycle.
     #[tokio::test]
-    async fn replace_comments_resets_previous_entries() -> Result<()> {
+    async fn media_pages_apply_filters() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
-        store.upsert_video(&sample_video("vid")).await?;
-
-        let first = vec![CommentRecord {
-            id: "1".into(),
-            videoid: "vid".into(),
-            author: "a".into(),
-            text: "hello".into(),
-            likes: Some(1),
-            time_posted: Some("2024-01-01".into()),
-            parent_comment_id: None,
-            status_likedbycreator: true,
-            reply_count: Some(0),
-        }];
-        // Seed the DB with a first batch of comments.
-        store.replace_comments("vid", &first).await?;
+        let mut early = sample_video("early");
+        early.upload_date = Some("2023-06-01T00:00:00Z".into());
+        early.duration = Some(30);
+        store.upsert_video(&early).await?;
+        let mut late = sample_video("late");
+        late.upload_date = Some("2024-06-01T00:00:00Z".into());
+        late.duration = Some(600);
+        late.channel_id = Some("UC_other".into());
+        store.upsert_video(&late).await?;
+        store
+            .upsert_subtitles(&SubtitleCollection {
+                videoid: "late".into(),
+                languages: vec![SubtitleTrack {
+                    code: "en".into(),
+                    name: "English".into(),
+                    url: "/subs/late.en.vtt".into(),
+                    path: None,
+                }],
+            })
+            .await?;
 
-        let second = vec![CommentRecord {
-            id: "2".into(),
-            videoid: "vid".into(),
-            author: "b".into(),
-            text: "world".into(),
-            likes: Some(2),
-            time_posted: Some("2024-01-02".into()),
-            parent_comment_id: None,
-            status_likedbycreator: false,
-            reply_count: Some(1),
-        }];
-        // Second replacement should wipe the previous entries before inserting.
-        store.replace_comments("vid", &second).await?;
+        let ids = |page: MediaPage| -> Vec<String> {
+            page.items
+                .into_iter()
+                .map(|record| record.videoid)
+                .collect()
+        };
+        let query = |build: fn(&mut MediaListQuery)| {
+            let mut query = MediaListQuery::default();
+            build(&mut query);
+            query
+        };
 
-        let fetched = reader.get_comments("vid").await?;
-        assert_eq!(fetched.len(), 1);
-        assert_eq!(fetched[0].id, "2");
-        assert!(!fetched[0].status_likedbycreator);
+        let page = reader
+            .list_videos_page(&query(|q| q.channel = Some("UC_other".into())))
+            .await?;
+        assert_eq!(ids(page), ["late"]);
+        let page = reader
+            .list_videos_page(&query(|q| {
+                q.uploaded_after = Some("2023-01-01".into());
+                q.uploaded_before = Some("2024-01-01".into());
+            }))
+            .await?;
+        assert_eq!(ids(page), ["early"]);
+        let page = reader
+            .list_videos_page(&query(|q| q.min_duration = Some(60)))
+            .await?;
+        assert_eq!(ids(page), ["late"]);
+        let page = reader
+            .list_videos_page(&query(|q| q.max_duration = Some(60)))
+            .await?;
+        assert_eq!(ids(page), ["early"]);
+        let page = reader
+            .list_videos_page(&query(|q| q.has_subtitles = Some(true)))
+            .await?;
+        assert_eq!(ids(page), ["late"]);
+        let page = reader
+            .list_videos_page(&query(|q| q.has_subtitles = Some(false)))
+            .await?;
+        assert_eq!(ids(page), ["early"]);
         Ok(())
     }
 
-    /// Verifies that listing videos applies the desired ordering (newest first)
-    /// even when dates differ, which is critical for deterministic feeds.
+    /// Availability updates leave the rest of the record alone, upserts
+    /// without a verdict keep the stored one, and listings filter on it with
+    /// unchecked media counting as available.
+    /// The change log reports each row once at its latest state, skips
+    /// no-op rewrites, pages by sequence number and leaves tombstones for
+    /// deleted media and comments deleted upstream.
     #[tokio::test]
-    async fn list_videos_returns_sorted_records() -> Result<()> {
-        let (_temp, store, reader, _path) = create_store().await?;
+    async fn change_log_tracks_rows_and_tombstones() -> Result<()> {
+        let (_dir, store, reader, _) = create_store().await?;
+        store.upsert_video(&sample_video("alpha")).await?;
+        store.upsert_video(&sample_video("beta")).await?;
+        store.upsert_short(&sample_video("gamma")).await?;
+        store
+            .replace_comments(
+                "alpha",
+                &[sample_comment("c1", "alpha"), sample_comment("c2", "alpha")],
+            )
+            .await?;
 
-        let mut old = sample_video("old");
-        old.upload_date = Some("2023-01-01".into());
-        store.upsert_video(&old).await?;
+        let full = reader.changes_since(0, None).await?;
+        assert_eq!(full.seq, reader.latest_change_seq().await?);
+        assert_eq!((full.videos.len(), full.shorts.len()), (2, 1));
+        assert_eq!(full.comments.len(), 2);
+        assert!(full.deleted.is_empty() && !full.has_more && !full.reset);
 
-        let mut new = sample_video("new");
-        new.upload_date = Some("2024-05-01".into());
-        store.upsert_video(&new).await?;
+        store.upsert_video(&sample_video("alpha")).await?;
+        assert_eq!(reader.latest_change_seq().await?, full.seq);
+
+        let mut renamed = sample_video("alpha");
+        renamed.title = "Renamed".into();
+        store.upsert_video(&renamed).await?;
+        store.upsert_video(&renamed.clone()).await?;
+        store.delete_media("beta").await?;
+        store
+            .merge_comments("alpha", &[sample_comment("c1", "alpha")])
+            .await?;
+
+        let delta = reader.changes_since(full.seq, None).await?;
+        assert_eq!(delta.videos.len(), 1);
+        assert_eq!(delta.videos[0].title, "Renamed");
+        assert!(delta.shorts.is_empty() && delta.comments.is_empty());
+        let deleted: Vec<(ChangeEntity, &str)> = delta
+            .deleted
+            .iter()
+            .map(|tombstone| (tombstone.entity, tombstone.key.as_str()))
+            .collect();
+        assert_eq!(
+            deleted,
+            [(ChangeEntity::Video, "beta"), (ChangeEntity::Comment, "c2")]
+        );
+
+        let first = reader.changes_since(full.seq, Some(1)).await?;
+        assert!(first.has_more);
+        let rest = reader.changes_since(first.seq, None).await?;
+        assert_eq!(rest.seq, delta.seq);
+        assert!(!rest.has_more);
+
+        let ahead = reader.changes_since(delta.seq + 100, None).await?;
+        assert!(ahead.reset);
+        assert_eq!(ahead.seq, delta.seq);
+        Ok(())
+    }
+
+    /// Each schema version's change log triggers only compare columns that
+    /// exist at that version, and the newest snapshot matches the columns
+    /// the reads return.
+    #[tokio::test]
+    async fn change_log_columns_are_frozen_per_version() -> Result<()> {
+        let temp = tempdir()?;
+        let path = temp.path().join("v15.db");
+        let conn = Builder::new_local(&path).build().await?.connect()?;
+        configure_connection(&conn).await?;
+        let v15 = MIGRATIONS
+            .iter()
+            .position(|migration| migration.version == 15)
+            .unwrap();
+        ensure_schema_with(&conn, "metadata", &MIGRATIONS[..=v15]).await?;
+        conn.execute(
+            "INSERT INTO videos (videoid, title) VALUES ('alpha', 'Alpha')",
+            params![],
+        )
+        .await?;
+        conn.execute(
+            "UPDATE videos SET title = 'Renamed' WHERE videoid = 'alpha'",
+            params![],
+        )
+        .await?;
+        let mut rows = conn
+            .query("SELECT entity, key FROM changes", params![])
+            .await?;
+        let row = rows.next().await?.context("missing change row")?;
+        assert_eq!(
+            (row.get::<String>(0)?, row.get::<String>(1)?),
+            ("video".to_string(), "alpha".to_string())
+        );
 
-        let videos = reader.list_videos().await?;
-        assert_eq!(videos.len(), 2);
-        assert_eq!(videos[0].videoid, "new");
-        assert_eq!(videos[1].videoid, "old");
+        let latest = MIGRATIONS
+            .iter()
+            .rev()
+            .find_map(|migration| match migration.step {
+                MigrationStep::CreateChangeLog(columns)
+                | MigrationStep::SqlRebuildingChangeTriggers(_, columns) => Some(columns),
+                _ => None,
+            })
+            .unwrap();
+        let split = |columns: &str| {
+            columns
+                .split(',')
+                .map(|column| column.trim().to_string())
+                .collect::<Vec<_>>()
+        };
+        let snapshot = |table: &str| {
+            let (_, columns) = latest.iter().find(|(name, _)| *name == table).unwrap();
+            split(columns)
+        };
+        assert_eq!(snapshot("videos"), split(VIDEO_COLUMNS));
+        assert_eq!(snapshot("shorts"), split(VIDEO_COLUMNS));
+        assert_eq!(snapshot("comments"), split(COMMENT_COLUMNS));
         Ok(())
     }
 
-    /// Reader helpers should gracefully return `None` when a record is missing.
+    /// Adaptive streams survive a round trip, and changing only them still
+    /// lands in the change log through the rebuilt triggers.
     #[tokio::test]
-    async fn reader_returns_none_for_missing_entries() -> Result<()> {
-        let (_temp, _store, reader, _path) = create_store().await?;
-        assert!(reader.get_video("ghost").await?.is_none());
-        assert!(reader.get_short("ghost").await?.is_none());
-        assert!(reader.get_subtitles("ghost").await?.is_none());
+    async fn adaptive_streams_round_trip_and_are_tracked() -> Result<()> {
+        let (_dir, store, reader, _) = create_store().await?;
+        store.upsert_video(&sample_video("alpha")).await?;
+        let before = reader.latest_change_seq().await?;
+
+        let mut video = sample_video("alpha");
+        video.adaptive_streams.push(AdaptiveStream {
+            format_id: "313".into(),
+            kind: AdaptiveKind::Video,
+            mime_type: "video/webm".into(),
+            codecs: "vp9".into(),
+            bandwidth: Some(12_000_000),
+            quality_label: Some("2160p".into()),
+            width: Some(3840),
+            height: Some(2160),
+            fps: Some(30.0),
+            audio_sample_rate: None,
+            audio_channels: None,
+            init_range: Some(ByteRange { start: 0, end: 219 }),
+            index_range: Some(ByteRange {
+                start: 220,
+                end: 1000,
+            }),
+            ext: Some("webm".into()),
+            file_size: Some(1_000_000),
+            url: "/api/videos/alpha/dash/313".into(),
+            path: Some("/videos/alpha/alpha_313.webm".into()),
+            sha256: Some("cafe".into()),
+        });
+        store.upsert_video(&video).await?;
+
+        let stored = reader.get_video("alpha").await?.unwrap();
+        assert_eq!(stored.adaptive_streams, video.adaptive_streams);
+        assert_eq!(
+            stored.adaptive_streams[0].index_range.unwrap().to_string(),
+            "220-1000"
+        );
+        let delta = reader.changes_since(before, None).await?;
+        assert_eq!(delta.videos.len(), 1);
+
+        // Their checksums are tracked like those of sources and survive a
+        // refresh that does not hash again.
+        let checksums = reader.list_source_checksums(None).await?;
+        assert_eq!(checksums.len(), 1);
+        assert_eq!(checksums[0].path, "/videos/alpha/alpha_313.webm");
+        video.adaptive_streams[0].sha256 = None;
+        store.upsert_video(&video).await?;
+        let stored = reader.get_video("alpha").await?.unwrap();
+        assert_eq!(stored.adaptive_streams[0].sha256.as_deref(), Some("cafe"));
         Ok(())
     }
 
-    /// Listing shorts mirrors videos but must respect upload_date ordering.
     #[tokio::test]
-    async fn list_shorts_sorted_by_upload_date() -> Result<()> {
+    async fn availability_is_kept_and_filterable() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
-        let mut older = sample_video("short-old");
-        older.upload_date = Some("2023-05-01".into());
-        store.upsert_short(&older).await?;
+        store.upsert_video(&sample_video("gone")).await?;
+        store.upsert_video(&sample_video("fresh")).await?;
+        let mut batch = store.batch();
+        batch.set_availability("gone", Availability::Removed);
+        batch.commit().await?;
 
-        let mut newer = sample_video("short-new");
-        newer.upload_date = Some("2024-06-01".into());
-        store.upsert_short(&newer).await?;
+        let mut rewritten = sample_video("gone");
+        rewritten.title = "Edited offline".into();
+        store.upsert_video(&rewritten).await?;
+        let gone = reader.get_video("gone").await?.unwrap();
+        assert_eq!(gone.title, "Edited offline");
+        assert_eq!(gone.availability, Some(Availability::Removed));
+        assert!(gone.availability_checked_at.is_some());
 
-        let shorts = reader.list_shorts().await?;
-        assert_eq!(shorts.len(), 2);
-        assert_eq!(shorts[0].videoid, "short-new");
-        assert_eq!(shorts[1].videoid, "short-old");
+        let ids = |page: MediaPage| -> Vec<String> {
+            page.items
+                .into_iter()
+                .map(|record| record.videoid)
+                .collect()
+        };
+        let filtered = |availability: Vec<Availability>| MediaListQuery {
+            availability,
+            ..MediaListQuery::default()
+        };
+        let page = reader
+            .list_videos_page(&filtered(vec![
+                Availability::Removed,
+                Availability::Private,
+            ]))
+            .await?;
+        assert_eq!(ids(page), ["gone"]);
+        let page = reader
+            .list_videos_page(&filtered(vec![Availability::Available]))
+            .await?;
+        assert_eq!(ids(page), ["fresh"]);
         Ok(())
     }
 
-    /// Subtitle upserts should overwrite existing rows rather than append.
+    /// `downloaded_at` is set on first insert only, so refreshing metadata
+    /// does not bump an old video to the top of "recently downloaded".
     #[tokio::test]
-    async fn upsert_subtitles_overwrites_existing_languages() -> Result<()> {
+    async fn downloaded_sort_keeps_first_insert_time() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
-        store.upsert_video(&sample_video("vid-sub")).await?;
-
-        let initial = SubtitleCollection {
-            videoid: "vid-sub".into(),
-            languages: vec![SubtitleTrack {
-                code: "en".into(),
-                name: "English".into(),
-                url: "https://cdn/en.vtt".into(),
-                path: None,
-            }],
-        };
-        store.upsert_subtitles(&initial).await?;
+        store.upsert_video(&sample_video("old")).await?;
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        store.upsert_video(&sample_video("new")).await?;
+        tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+        store.upsert_video(&sample_video("old")).await?;
 
-        let updated = SubtitleCollection {
-            videoid: "vid-sub".into(),
-            languages: vec![SubtitleTrack {
-                code: "fr".into(),
-                name: "Français".into(),
-                url: "https://cdn/fr.vtt".into(),
-                path: Some("/subs/fr.vtt".into()),
-            }],
+        let query = MediaListQuery {
+            sort: MediaSort::Downloaded,
+            ..MediaListQuery::default()
         };
-        store.upsert_subtitles(&updated).await?;
-
-        let fetched = reader
-            .get_subtitles("vid-sub")
-            .await?
-            .expect("subtitles exist");
-        assert_eq!(fetched.languages.len(), 1);
-        assert_eq!(fetched.languages[0].code, "fr");
+        assert_eq!(collect_pages(&reader, query).await?, ["new", "old"]);
         Ok(())
     }
 
-    /// Comments containing replies and flags should persist verbatim.
+    /// Refreshes only append a snapshot when a counter actually changed.
     #[tokio::test]
-    async fn replace_comments_preserves_replies_and_flags() -> Result<()> {
+    async fn stats_history_records_only_changes() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
-        store.upsert_video(&sample_video("with-comments")).await?;
+        let mut record = sample_video("alpha");
+        record.extras = serde_json::json!({"commentCount": 3});
+        store.upsert_video(&record).await?;
+        store.upsert_video(&record).await?;
+        assert_eq!(reader.get_video_stats_history("alpha").await?.len(), 1);
 
-        let mut parent = sample_comment("parent", "with-comments");
-        parent.status_likedbycreator = true;
-        let mut reply = sample_comment("child", "with-comments");
-        reply.parent_comment_id = Some("parent".into());
+        record.views = Some(100);
+        store.upsert_video(&record).await?;
+        record.extras = serde_json::json!({"commentCount": 4});
+        store.upsert_video(&record).await?;
+        store.upsert_video(&record).await?;
 
-        store
-            .replace_comments("with-comments", &[parent.clone(), reply.clone()])
-            .await?;
+        let history = reader.get_video_stats_history("alpha").await?;
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[0].views, Some(42));
+        assert_eq!(history[0].comment_count, Some(3));
+        assert_eq!(history[1].views, Some(100));
+        assert_eq!(history[2].comment_count, Some(4));
 
-        let comments = reader.get_comments("with-comments").await?;
-        assert_eq!(comments.len(), 2);
-        assert_eq!(comments[0].id, "parent");
-        assert!(comments[0].status_likedbycreator);
-        assert_eq!(comments[1].parent_comment_id.as_deref(), Some("parent"));
+        let mut empty = sample_video("bare");
+        empty.views = None;
+        empty.likes = None;
+        empty.dislikes = None;
+        empty.subscriber_count = None;
+        store.upsert_video(&empty).await?;
+        assert!(reader.get_video_stats_history("bare").await?.is_empty());
         Ok(())
     }
 
-    /// list_all_comments should merge comments across videos ordered by timestamp.
+    /// Channel aggregates carry each video's latest snapshot forward into
+    /// later days, and only include media from the requested channel.
     #[tokio::test]
-    async fn list_all_comments_orders_by_time() -> Result<()> {
-        let (_temp, store, reader, _path) = create_store().await?;
-        store.upsert_video(&sample_video("video-one")).await?;
-        store.upsert_video(&sample_video("video-two")).await?;
+    async fn channel_stats_history_aggregates_latest_snapshots() -> Result<()> {
+        let (_temp, store, reader, path) = create_store().await?;
+        store.upsert_video(&sample_video("a")).await?;
+        store.upsert_short(&sample_video("b")).await?;
+        let mut other = sample_video("c");
+        other.channel_id = Some("UC_other".into());
+        store.upsert_video(&other).await?;
 
-        let mut first = sample_comment("1", "video-one");
-        first.time_posted = Some("2024-01-01T00:00:00Z".into());
-        let mut second = sample_comment("2", "video-two");
-        second.time_posted = Some("2024-01-01T00:05:00Z".into());
-        let mut third = sample_comment("3", "video-one");
-        third.time_posted = Some("2024-01-01T00:10:00Z".into());
+        let conn = Builder::new_local(&path).build().await?.connect()?;
+        conn.execute(
+            "UPDATE video_stats_history SET recorded_at = '2024-01-01T00:00:00Z'",
+            params![],
+        )
+        .await?;
+        conn.execute(
+            r#"
+            INSERT INTO video_stats_history (videoid, recorded_at, views, likes, subscriber_count)
+            VALUES ('a', '2024-01-02T08:00:00Z', 100, 5, 2000)
+            "#,
+            params![],
+        )
+        .await?;
+
+        let points = reader.get_channel_stats_history("UC_sample").await?;
+        assert_eq!(points.len(), 2);
+        assert_eq!(points[0].day, "2024-01-01");
+        assert_eq!(points[0].video_count, 2);
+        assert_eq!(points[0].views, 84);
+        assert_eq!(points[1].day, "2024-01-02");
+        assert_eq!(points[1].views, 142);
+        assert_eq!(points[1].likes, 6);
+        assert_eq!(points[1].subscriber_count, Some(2000));
+        assert!(
+            reader
+                .get_channel_stats_history("UC_none")
+                .await?
+                .is_empty()
+        );
+        Ok(())
+    }
 
+    /// Deleting a record removes every row keyed by its id, including the
+    /// search index, but leaves unrelated media alone.
+    #[tokio::test]
+    async fn delete_media_cascades_to_related_rows() -> Result<()> {
+        let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_video(&sample_video("alpha")).await?;
+        store.upsert_video(&sample_video("beta")).await?;
         store
-            .replace_comments("video-one", &[first.clone(), third.clone()])
+            .upsert_subtitles(&SubtitleCollection {
+                videoid: "alpha".into(),
+                languages: vec![],
+            })
             .await?;
         store
-            .replace_comments("video-two", &[second.clone()])
+            .replace_comments("alpha", &[sample_comment("c1", "alpha")])
             .await?;
 
-        let all = reader.list_all_comments().await?;
-        assert_eq!(all.len(), 3);
-        assert_eq!(all[0].id, "1");
-        assert_eq!(all[1].id, "2");
-        assert_eq!(all[2].id, "3");
-        Ok(())
-    }
+        assert!(store.delete_media("alpha").await?);
+        assert!(!store.delete_media("alpha").await?);
 
-    #[tokio::test]
-    async fn open_creates_parent_dirs_and_reopens() -> Result<()> {
-        let temp = tempdir()?;
-        let path = temp.path().join("nested/metadata/db.sqlite");
-        let _store = MetadataStore::open(&path).await?;
-        assert!(path.exists());
-        assert!(path.parent().unwrap().exists());
-        let _reopened = MetadataStore::open(&path).await?;
+        assert!(reader.get_video("alpha").await?.is_none());
+        assert!(reader.get_comments("alpha", true).await?.is_empty());
+        assert!(reader.get_subtitles("alpha").await?.is_none());
+        assert!(reader.get_video_stats_history("alpha").await?.is_empty());
+        let hits = reader.search("Video", &SearchOptions::default()).await?;
+        let ids: Vec<&str> = hits.iter().map(|hit| hit.videoid.as_str()).collect();
+        assert_eq!(ids, ["beta"]);
         Ok(())
     }
 
+    /// Source checksums land in `source_checksums`, survive refreshes that
+    /// rebuild the source list without them, and a new hash resets the
+    /// scrub status.
     #[tokio::test]
-    async fn list_videos_orders_by_date_then_rowid() -> Result<()> {
+    async fn source_checksums_survive_refreshes() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
+        let mut video = sample_video("alpha");
+        video.sources[0].sha256 = Some("aa".into());
+        store.upsert_video(&video).await?;
+        store
+            .record_scrub_result("/videos/video.mp4", ChecksumStatus::Mismatch, Some("bb"))
+            .await?;
 
-        let mut first = sample_video("first");
-        first.upload_date = Some("2024-01-01".into());
-        store.upsert_video(&first).await?;
-
-        let mut second = sample_video("second");
-        second.upload_date = Some("2024-01-01".into());
-        store.upsert_video(&second).await?;
+        store.upsert_video(&sample_video("alpha")).await?;
+        let stored = reader.get_video("alpha").await?.unwrap();
+        assert_eq!(stored.sources[0].sha256.as_deref(), Some("aa"));
+        let flagged = reader
+            .list_source_checksums(Some(ChecksumStatus::Mismatch))
+            .await?;
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].observed_sha256.as_deref(), Some("bb"));
 
-        let mut no_date = sample_video("no-date");
-        no_date.upload_date = None;
-        store.upsert_video(&no_date).await?;
+        video.sources[0].sha256 = Some("cc".into());
+        store.upsert_video(&video).await?;
+        let all = reader.list_source_checksums(None).await?;
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0].sha256, "cc");
+        assert_eq!(all[0].status, ChecksumStatus::Ok);
+        assert!(all[0].observed_sha256.is_none());
 
-        let videos = reader.list_videos().await?;
-        assert_eq!(videos.len(), 3);
-        assert_eq!(videos[0].videoid, "second");
-        assert_eq!(videos[1].videoid, "first");
-        assert_eq!(videos[2].videoid, "no-date");
+        store.delete_media("alpha").await?;
+        assert!(reader.list_source_checksums(None).await?.is_empty());
         Ok(())
     }
 
+    /// Merging keeps comments that disappeared upstream, stamping them
+    /// instead, refreshes counts of the ones still returned and clears the
+    /// stamp when a comment comes back.
     #[tokio::test]
-    async fn replace_comments_with_empty_slice_clears_entries() -> Result<()> {
+    async fn merge_comments_marks_deleted_upstream() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
-        store.upsert_video(&sample_video("vid")).await?;
+        store.upsert_video(&sample_video("alpha")).await?;
         store
-            .replace_comments("vid", &[sample_comment("c1", "vid")])
+            .merge_comments(
+                "alpha",
+                &[sample_comment("c1", "alpha"), sample_comment("c2", "alpha")],
+            )
             .await?;
-        assert_eq!(reader.get_comments("vid").await?.len(), 1);
 
-        store.replace_comments("vid", &[]).await?;
-        assert!(reader.get_comments("vid").await?.is_empty());
+        let mut liked = sample_comment("c1", "alpha");
+        liked.likes = Some(99);
+        liked.reply_count = Some(4);
+        store.merge_comments("alpha", &[liked.clone()]).await?;
+
+        let visible = reader.get_comments("alpha", false).await?;
+        assert_eq!(visible.len(), 1);
+        assert_eq!(visible[0].likes, Some(99));
+        assert_eq!(visible[0].reply_count, Some(4));
+        let all = reader.get_comments("alpha", true).await?;
+        let gone = all.iter().find(|comment| comment.id == "c2").unwrap();
+        let stamp = gone.deleted_upstream_at.clone().expect("stamped");
+        assert_eq!(reader.list_all_comments(false).await?.len(), 1);
+        assert_eq!(reader.list_all_comments(true).await?.len(), 2);
+
+        store.merge_comments("alpha", &[liked.clone()]).await?;
+        let all = reader.get_comments("alpha", true).await?;
+        let gone = all.iter().find(|comment| comment.id == "c2").unwrap();
+        assert_eq!(gone.deleted_upstream_at.as_deref(), Some(stamp.as_str()));
+
+        store
+            .merge_comments("alpha", &[liked, sample_comment("c2", "alpha")])
+            .await?;
+        assert_eq!(reader.get_comments("alpha", false).await?.len(), 2);
         Ok(())
     }
 
+    /// Upserting a partial list refreshes the listed comments and leaves the
+    /// others untombstoned.
     #[tokio::test]
-    async fn replace_comments_rolls_back_on_duplicate_ids() -> Result<()> {
+    async fn upsert_comments_never_tombstones() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
-        store.upsert_video(&sample_video("vid")).await?;
+        store.upsert_video(&sample_video("alpha")).await?;
         store
-            .replace_comments("vid", &[sample_comment("keep", "vid")])
+            .merge_comments(
+                "alpha",
+                &[sample_comment("c1", "alpha"), sample_comment("c2", "alpha")],
+            )
             .await?;
 
-        let mut dup1 = sample_comment("dup", "vid");
-        let dup2 = sample_comment("dup", "vid");
-        dup1.text = "first".into();
-        let err = store.replace_comments("vid", &[dup1, dup2]).await;
-        assert!(err.is_err());
+        let mut liked = sample_comment("c1", "alpha");
+        liked.likes = Some(7);
+        let mut batch = store.batch();
+        batch.upsert_comments("alpha", vec![liked]);
+        batch.upsert_comments("alpha", Vec::new());
+        batch.commit().await?;
 
-        let comments = reader.get_comments("vid").await?;
-        assert_eq!(comments.len(), 1);
-        assert_eq!(comments[0].id, "keep");
+        let visible = reader.get_comments("alpha", false).await?;
+        assert_eq!(visible.len(), 2);
+        assert_eq!(
+            visible.iter().find(|c| c.id == "c1").unwrap().likes,
+            Some(7)
+        );
         Ok(())
     }
 
     #[tokio::test]
-    async fn comments_queries_ignore_orphaned_video_ids() -> Result<()> {
+    async fn blocked_media_roundtrip() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
-        store
-            .replace_comments("ghost", &[sample_comment("c1", "ghost")])
-            .await?;
+        store.block_media("alpha", Some("spam")).await?;
+        store.block_media("alpha", None).await?;
+        store.block_media("beta", None).await?;
 
-        assert!(reader.get_comments("ghost").await?.is_empty());
-        assert!(reader.list_all_comments().await?.is_empty());
+        let ids = store.blocked_media_ids().await?;
+        assert!(ids.contains("alpha") && ids.contains("beta"));
+        let listed = reader.list_blocked_media().await?;
+        let alpha = listed
+            .iter()
+            .find(|entry| entry.videoid == "alpha")
+            .unwrap();
+        assert_eq!(alpha.reason.as_deref(), Some("spam"));
+
+        assert!(store.unblock_media("alpha").await?);
+        assert!(!store.unblock_media("alpha").await?);
+        assert_eq!(store.blocked_media_ids().await?.len(), 1);
         Ok(())
     }
 
+    /// Transcript cues are searchable with their timing, filtered by language
+    /// and kind, and disappear when replaced or when the media is deleted.
     #[tokio::test]
-    async fn upsert_video_allows_empty_collections_and_null_extras() -> Result<()> {
+    async fn transcripts_are_indexed_and_replaced() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
+        store.upsert_video(&sample_video("alpha")).await?;
+        let cue = |start: f64, text: &str| TranscriptCue {
+            start,
+            end: start + 2.0,
+            text: text.into(),
+        };
+        store
+            .replace_transcripts(
+                "alpha",
+                &[
+                    Transcript {
+                        language: "de".into(),
+                        cues: vec![cue(1.0, "Café im Wald")],
+                    },
+                    Transcript {
+                        language: "en".into(),
+                        cues: vec![cue(5.0, "greetings from the forest"), cue(1.0, "hello")],
+                    },
+                ],
+            )
+            .await?;
 
-        let record = VideoRecord {
-            videoid: "empty".into(),
-            title: "Empty".into(),
-            description: String::new(),
-            likes: None,
-            dislikes: None,
-            views: None,
-            upload_date: None,
-            author: None,
-            subscriber_count: None,
-            duration: None,
-            duration_text: None,
-            channel_url: None,
-            thumbnail_url: None,
-            tags: Vec::new(),
-            thumbnails: Vec::new(),
-            extras: Value::Null,
-            sources: Vec::new(),
+        let transcripts = reader.get_transcripts("alpha", None).await?;
+        let languages: Vec<&str> = transcripts.iter().map(|t| t.language.as_str()).collect();
+        assert_eq!(languages, ["de", "en"]);
+        assert_eq!(transcripts[1].cues[0].text, "hello");
+        assert_eq!(reader.get_transcripts("alpha", Some("en")).await?.len(), 1);
+
+        let hits = reader
+            .search_transcripts("forest", None, &SearchOptions::default())
+            .await?;
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].videoid, "alpha");
+        assert_eq!(hits[0].kind, "video");
+        assert_eq!((hits[0].start, hits[0].end), (5.0, 7.0));
+        assert!(hits[0].snippet.contains("<mark>forest</mark>"));
+        assert_eq!(
+            reader
+                .search_transcripts("cafe", None, &SearchOptions::default())
+                .await?
+                .len(),
+            1
+        );
+        assert!(
+            reader
+                .search_transcripts("forest", Some("de"), &SearchOptions::default())
+                .await?
+                .is_empty()
+        );
+        let shorts_only = SearchOptions {
+            kind: Some("short".into()),
+            ..SearchOptions::default()
         };
+        assert!(
+            reader
+                .search_transcripts("forest", None, &shorts_only)
+                .await?
+                .is_empty()
+        );
 
-        store.upsert_video(&record).await?;
-        let fetched = reader.get_video("empty").await?.expect("video exists");
-        assert!(fetched.tags.is_empty());
-        assert!(fetched.thumbnails.is_empty());
-        assert!(fetched.sources.is_empty());
-        assert_eq!(fetched.extras, Value::Null);
+        store
+            .replace_transcripts(
+                "alpha",
+                &[Transcript {
+                    language: "en".into(),
+                    cues: vec![cue(0.0, "rewritten")],
+                }],
+            )
+            .await?;
+        assert!(
+            reader
+                .search_transcripts("forest", None, &SearchOptions::default())
+                .await?
+                .is_empty()
+        );
+        assert_eq!(reader.get_transcripts("alpha", None).await?.len(), 1);
+
+        store.delete_media("alpha").await?;
+        assert!(reader.get_transcripts("alpha", None).await?.is_empty());
+        assert!(
+            reader
+                .search_transcripts("rewritten", None, &SearchOptions::default())
+                .await?
+                .is_empty()
+        );
         Ok(())
     }
 
+    /// Manual edits replace imported segments and are protected from later
+    /// imports until cleared.
     #[tokio::test]
-    async fn upsert_subtitles_allows_empty_languages() -> Result<()> {
+    async fn skip_segments_prefer_manual_edits() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
-        store.upsert_video(&sample_video("vid")).await?;
-
-        let subtitles = SubtitleCollection {
-            videoid: "vid".into(),
-            languages: Vec::new(),
+        store.upsert_video(&sample_video("alpha")).await?;
+        let segment = |category, start: f64, end: f64| SkipSegment {
+            category,
+            start,
+            end,
         };
-        store.upsert_subtitles(&subtitles).await?;
+        let imported = [
+            segment(SegmentCategory::Outro, 50.0, 60.0),
+            segment(SegmentCategory::Sponsor, 5.0, 20.0),
+        ];
+        assert!(store.import_segments("alpha", &imported).await?);
+        let segments = reader.get_segments("alpha").await?;
+        assert_eq!(segments[0].category, SegmentCategory::Sponsor);
+        assert_eq!(segments[1].start, 50.0);
 
-        let fetched = reader.get_subtitles("vid").await?.expect("subtitles exist");
-        assert!(fetched.languages.is_empty());
+        store
+            .set_segments("alpha", &[segment(SegmentCategory::Intro, 0.0, 8.0)])
+            .await?;
+        assert!(!store.import_segments("alpha", &imported).await?);
+        assert_eq!(
+            reader.get_segments("alpha").await?,
+            [segment(SegmentCategory::Intro, 0.0, 8.0)]
+        );
+
+        store.set_segments("alpha", &[]).await?;
+        assert!(store.import_segments("alpha", &imported).await?);
+        assert_eq!(reader.get_segments("alpha").await?.len(), 2);
+        assert!(reader.list_media_ids().await?.contains("alpha"));
+
+        store.delete_media("alpha").await?;
+        assert!(reader.get_segments("alpha").await?.is_empty());
         Ok(())
     }
 
     #[tokio::test]
-    async fn data_version_changes_after_writes() -> Result<()> {
+    async fn replace_chapters_overwrites_and_clears() -> Result<()> {
         let (_temp, store, reader, _path) = create_store().await?;
-        let before = reader.data_version().await?;
         store.upsert_video(&sample_video("alpha")).await?;
-        let after = reader.data_version().await?;
-        assert_ne!(before, after);
+        let chapter = |start: f64, end: Option<f64>, title: &str| Chapter {
+            start,
+            end,
+            title: title.into(),
+        };
+        store
+            .replace_chapters(
+                "alpha",
+                &[
+                    chapter(0.0, Some(30.0), "Intro"),
+                    chapter(30.0, None, "Main"),
+                ],
+            )
+            .await?;
+        store
+            .replace_chapters(
+                "alpha",
+                &[
+                    chapter(0.0, Some(10.0), "Cold open"),
+                    chapter(10.0, Some(60.0), "Intro"),
+                    chapter(60.0, Some(120.0), "Main"),
+                ],
+            )
+            .await?;
+        let chapters = reader.get_chapters("alpha").await?;
+        let titles: Vec<&str> = chapters.iter().map(|c| c.title.as_str()).collect();
+        assert_eq!(titles, ["Cold open", "Intro", "Main"]);
+        assert_eq!(chapters[2].end, Some(120.0));
+
+        store.replace_chapters("alpha", &[]).await?;
+        assert!(reader.get_chapters("alpha").await?.is_empty());
+
+        store
+            .replace_chapters("alpha", &[chapter(0.0, None, "Only")])
+            .await?;
+        store.delete_media("alpha").await?;
+        assert!(reader.get_chapters("alpha").await?.is_empty());
         Ok(())
     }
 }
@@ -0,0 +1,449 @@
+#![forbid(unsafe_code)]
+
+//! File checksums for downloaded media and the scrub job that re-checks
+//! them.
+//!
+//! The downloader records the SHA-256 of every file it fetches on the
+//! matching `VideoSource` or `AdaptiveStream`; `write_media` mirrors it into
+//! `source_checksums`. A scrub pass re-hashes the files, records a baseline
+//! for files that do not have one yet and flags files whose contents changed
+//! or disappeared.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::metadata::{ChecksumStatus, MetadataReader, MetadataStore, SourceChecksum, VideoRecord};
+
+const HASH_BUFFER_SIZE: usize = 1 << 20;
+
+/// Streams `path` through SHA-256 and returns the hex digest together with
+/// the number of bytes read.
+pub fn sha256_file(path: &Path) -> std::io::Result<(String, u64)> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; HASH_BUFFER_SIZE];
+    let mut total = 0u64;
+    loop {
+        let read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+        hasher.update(&buffer[..read]);
+        total += read as u64;
+    }
+    Ok((format!("{:x}", hasher.finalize()), total))
+}
+
+/// Hashes every source and adaptive stream of `record` that has a file on
+/// disk but no checksum yet.
+pub fn fill_checksums(record: &mut VideoRecord) -> Result<()> {
+    for (path, sha256) in record_files(record) {
+        let Some(path) = path.filter(|_| sha256.is_none()) else {
+            continue;
+        };
+        let (hash, _) = sha256_file(Path::new(path)).with_context(|| format!("hashing {path}"))?;
+        *sha256 = Some(hash);
+    }
+    Ok(())
+}
+
+/// Path and checksum slot of every downloaded file of `record`.
+fn record_files(
+    record: &mut VideoRecord,
+) -> impl Iterator<Item = (Option<&str>, &mut Option<String>)> {
+    let sources = record
+        .sources
+        .iter_mut()
+        .map(|source| (source.path.as_deref(), &mut source.sha256));
+    let streams = record
+        .adaptive_streams
+        .iter_mut()
+        .map(|stream| (stream.path.as_deref(), &mut stream.sha256));
+    sources.chain(streams)
+}
+
+/// Which files a scrub pass re-hashes.
+#[derive(Debug, Clone, Default)]
+pub struct ScrubOptions {
+    /// Skip files verified at or after this RFC 3339 timestamp, so scheduled
+    /// runs only revisit what has not been checked recently.
+    pub verified_before: Option<String>,
+    /// Hash at most this many files, least recently verified first.
+    pub limit: Option<usize>,
+}
+
+/// File flagged by a scrub pass.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrubFinding {
+    pub videoid: String,
+    pub format_id: String,
+    pub path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observed: Option<String>,
+}
+
+/// Summary of one scrub pass.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrubReport {
+    /// Files hashed during this pass.
+    pub checked: usize,
+    /// Files that had no checksum and got one recorded.
+    pub baselined: usize,
+    /// Files that still match their recorded checksum.
+    pub verified: usize,
+    /// Files left alone because they were verified recently or the limit
+    /// was reached.
+    pub skipped: usize,
+    pub bytes: u64,
+    pub mismatched: Vec<ScrubFinding>,
+    pub missing: Vec<ScrubFinding>,
+}
+
+impl ScrubReport {
+    /// Whether the pass found any file that no longer matches.
+    pub fn has_problems(&self) -> bool {
+        !self.mismatched.is_empty() || !self.missing.is_empty()
+    }
+}
+
+struct Candidate {
+    videoid: String,
+    format_id: String,
+    path: String,
+    expected: Option<String>,
+    verified_at: Option<String>,
+}
+
+/// Re-hashes the library's downloaded files. Mismatches and missing files
+/// are recorded in `source_checksums` and returned; sources without a
+/// checksum get their first one.
+pub async fn scrub_library(
+    store: &MetadataStore,
+    reader: &MetadataReader,
+    options: &ScrubOptions,
+) -> Result<ScrubReport> {
+    let known: HashMap<String, SourceChecksum> = reader
+        .list_source_checksums(None)
+        .await?
+        .into_iter()
+        .map(|checksum| (checksum.path.clone(), checksum))
+        .collect();
+
+    let mut records: HashMap<String, (bool, VideoRecord)> = HashMap::new();
+    for (short, list) in [
+        (false, reader.list_videos().await?),
+        (true, reader.list_shorts().await?),
+    ] {
+        for record in list {
+            records.insert(record.videoid.clone(), (short, record));
+        }
+    }
+
+    let mut report = ScrubReport::default();
+    let mut candidates = Vec::new();
+    for (_, record) in records.values() {
+        let files = record
+            .sources
+            .iter()
+            .map(|source| (&source.format_id, &source.path, &source.sha256))
+            .chain(
+                record
+                    .adaptive_streams
+                    .iter()
+                    .map(|stream| (&stream.format_id, &stream.path, &stream.sha256)),
+            );
+        for (format_id, path, sha256) in files {
+            let Some(path) = path.clone() else {
+                continue;
+            };
+            let verified_at = known
+                .get(&path)
+                .and_then(|checksum| checksum.verified_at.clone());
+            if let (Some(cutoff), Some(verified_at)) = (&options.verified_before, &verified_at)
+                && verified_at >= cutoff
+            {
+                report.skipped += 1;
+                continue;
+            }
+            candidates.push(Candidate {
+                videoid: record.videoid.clone(),
+                format_id: format_id.clone(),
+                expected: sha256.clone(),
+                path,
+                verified_at,
+            });
+        }
+    }
+    candidates.sort_by(|a, b| {
+        a.verified_at
+            .cmp(&b.verified_at)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    if let Some(limit) = options.limit
+        && candidates.len() > limit
+    {
+        report.skipped += candidates.len() - limit;
+        candidates.truncate(limit);
+    }
+
+    let mut baselines: HashMap<String, Vec<(String, String)>> = HashMap::new();
+    for candidate in candidates {
+        report.checked += 1;
+        let path = candidate.path.clone();
+        let hashed = tokio::task::spawn_blocking(move || sha256_file(Path::new(&path)))
+            .await
+            .context("joining hash task")?;
+        let finding = |observed: Option<String>| ScrubFinding {
+            videoid: candidate.videoid.clone(),
+            format_id: candidate.format_id.clone(),
+            path: candidate.path.clone(),
+            expected: candidate.expected.clone(),
+            observed,
+        };
+        let (sha256, bytes) = match hashed {
+            Ok(hashed) => hashed,
+            Err(err) => {
+                if err.kind() != ErrorKind::NotFound {
+                    eprintln!("Warning: failed to read {}: {err}", candidate.path);
+                }
+                store
+                    .record_scrub_result(&candidate.path, ChecksumStatus::Missing, None)
+                    .await?;
+                report.missing.push(finding(None));
+                continue;
+            }
+        };
+        report.bytes += bytes;
+        match candidate.expected.as_deref() {
+            None => {
+                report.baselined += 1;
+                baselines
+                    .entry(candidate.videoid.clone())
+                    .or_default()
+                    .push((candidate.path.clone(), sha256));
+            }
+            Some(expected) if expected == sha256 => {
+                report.verified += 1;
+                store
+                    .record_scrub_result(&candidate.path, ChecksumStatus::Ok, None)
+                    .await?;
+            }
+            Some(_) => {
+                store
+                    .record_scrub_result(&candidate.path, ChecksumStatus::Mismatch, Some(&sha256))
+                    .await?;
+                report.mismatched.push(finding(Some(sha256)));
+            }
+        }
+    }
+
+    for (videoid, hashes) in baselines {
+        let Some((short, mut record)) = records.remove(&videoid) else {
+            continue;
+        };
+        for (path, sha256) in record_files(&mut record) {
+            if let Some((_, hash)) = hashes
+                .iter()
+                .find(|(hashed, _)| path == Some(hashed.as_str()))
+            {
+                *sha256 = Some(hash.clone());
+            }
+        }
+        if short {
+            store.upsert_short(&record).await?;
+        } else {
+            store.upsert_video(&record).await?;
+        }
+    }
+
+    report.mismatched.sort_by(|a, b| a.path.cmp(&b.path));
+    report.missing.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metadata::{AdaptiveKind, AdaptiveStream, VideoSource};
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn sample_record(id: &str, path: &Path) -> VideoRecord {
+        VideoRecord {
+            videoid: id.into(),
+            title: format!("Video {id}"),
+            description: String::new(),
+            likes: None,
+            dislikes: None,
+            views: None,
+            upload_date: None,
+            author: None,
+            subscriber_count: None,
+            duration: None,
+            duration_text: None,
+            channel_url: None,
+            channel_id: None,
+            thumbnail_url: None,
+            tags: Vec::new(),
+            thumbnails: Vec::new(),
+            extras: serde_json::Value::Null,
+            sources: vec![VideoSource {
+                format_id: "22".into(),
+                quality_label: None,
+                width: None,
+                height: None,
+                fps: None,
+                mime_type: Some("video/mp4".into()),
+                ext: Some("mp4".into()),
+                file_size: None,
+                url: format!("/api/videos/{id}/streams/22"),
+                path: Some(path.to_string_lossy().into_owned()),
+                sha256: None,
+            }],
+            availability: None,
+            availability_checked_at: None,
+            adaptive_streams: Vec::new(),
+        }
+    }
+
+    /// Digest of a known input, streamed through the chunked reader.
+    #[test]
+    fn sha256_file_matches_known_digest() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("abc.txt");
+        fs::write(&path, "abc")?;
+        let (digest, bytes) = sha256_file(&path)?;
+        assert_eq!(
+            digest,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(bytes, 3);
+        Ok(())
+    }
+
+    /// A first pass records baselines; later passes verify them, flag
+    /// changed and missing files, and honour the recency cutoff.
+    #[tokio::test]
+    async fn scrub_baselines_then_flags_changes() -> Result<()> {
+        let dir = tempdir()?;
+        let db = dir.path().join("metadata.db");
+        let store = MetadataStore::open(&db).await?;
+        let reader = MetadataReader::new(&db).await?;
+        let alpha = dir.path().join("alpha_22.mp4");
+        let beta = dir.path().join("beta_22.mp4");
+        let gamma = dir.path().join("gamma_22.mp4");
+        for path in [&alpha, &beta, &gamma] {
+            fs::write(path, "original")?;
+        }
+        store.upsert_video(&sample_record("alpha", &alpha)).await?;
+        store.upsert_video(&sample_record("beta", &beta)).await?;
+        store.upsert_short(&sample_record("gamma", &gamma)).await?;
+
+        let first = scrub_library(&store, &reader, &ScrubOptions::default()).await?;
+        assert_eq!((first.checked, first.baselined), (3, 3));
+        assert!(!first.has_problems());
+        let gamma_record = reader.get_short("gamma").await?.unwrap();
+        assert!(gamma_record.sources[0].sha256.is_some());
+
+        fs::write(&alpha, "bit rot")?;
+        fs::remove_file(&beta)?;
+        let second = scrub_library(&store, &reader, &ScrubOptions::default()).await?;
+        assert_eq!((second.checked, second.verified), (3, 1));
+        assert_eq!(second.mismatched.len(), 1);
+        assert_eq!(second.mismatched[0].videoid, "alpha");
+        assert_ne!(second.mismatched[0].observed, second.mismatched[0].expected);
+        assert_eq!(second.missing[0].videoid, "beta");
+
+        let flagged = reader
+            .list_source_checksums(Some(ChecksumStatus::Mismatch))
+            .await?;
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].videoid, "alpha");
+        let missing = reader
+            .list_source_checksums(Some(ChecksumStatus::Missing))
+            .await?;
+        assert_eq!(missing[0].videoid, "beta");
+
+        let recent = ScrubOptions {
+            verified_before: Some("2000-01-01T00:00:00.000Z".into()),
+            limit: None,
+        };
+        let third = scrub_library(&store, &reader, &recent).await?;
+        assert_eq!((third.checked, third.skipped), (0, 3));
+        let limited = ScrubOptions {
+            verified_before: None,
+            limit: Some(1),
+        };
+        let fourth = scrub_library(&store, &reader, &limited).await?;
+        assert_eq!((fourth.checked, fourth.skipped), (1, 2));
+        Ok(())
+    }
+
+    /// Video-only and audio-only downloads are hashed and scrubbed like the
+    /// muxed sources.
+    #[tokio::test]
+    async fn adaptive_streams_are_hashed_and_scrubbed() -> Result<()> {
+        let dir = tempdir()?;
+        let db = dir.path().join("metadata.db");
+        let store = MetadataStore::open(&db).await?;
+        let reader = MetadataReader::new(&db).await?;
+        let muxed = dir.path().join("alpha_22.mp4");
+        let video_only = dir.path().join("alpha_137.mp4");
+        fs::write(&muxed, "muxed")?;
+        fs::write(&video_only, "video")?;
+        let mut record = sample_record("alpha", &muxed);
+        record.adaptive_streams.push(AdaptiveStream {
+            format_id: "137".into(),
+            kind: AdaptiveKind::Video,
+            mime_type: "video/mp4".into(),
+            codecs: "avc1".into(),
+            bandwidth: None,
+            quality_label: None,
+            width: None,
+            height: None,
+            fps: None,
+            audio_sample_rate: None,
+            audio_channels: None,
+            init_range: None,
+            index_range: None,
+            ext: Some("mp4".into()),
+            file_size: None,
+            url: "/api/videos/alpha/dash/137".into(),
+            path: Some(video_only.to_string_lossy().into_owned()),
+            sha256: None,
+        });
+
+        let mut hashed = record.clone();
+        fill_checksums(&mut hashed)?;
+        assert_eq!(
+            hashed.adaptive_streams[0].sha256,
+            Some(sha256_file(&video_only)?.0)
+        );
+
+        store.upsert_video(&record).await?;
+        let first = scrub_library(&store, &reader, &ScrubOptions::default()).await?;
+        assert_eq!((first.checked, first.baselined), (2, 2));
+        let stored = reader.get_video("alpha").await?.unwrap();
+        assert_eq!(
+            stored.adaptive_streams[0].sha256,
+            hashed.adaptive_streams[0].sha256
+        );
+
+        fs::write(&video_only, "bit rot")?;
+        let second = scrub_library(&store, &reader, &ScrubOptions::default()).await?;
+        assert_eq!(second.verified, 1);
+        assert_eq!(second.mismatched.len(), 1);
+        assert_eq!(second.mismatched[0].format_id, "137");
+        Ok(())
+    }
+}
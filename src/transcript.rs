@@ -0,0 +1,502 @@
+#![forbid(unsafe_code)]
+
+//! Subtitle parsing for the transcript index.
+//!
+//! yt-dlp leaves subtitle files as `subtitles/<id>/<id>.<lang>.<ext>`. The
+//! backend serves them untouched; this module turns them into plain
+//! start/end/text cues so `MetadataStore::replace_transcripts` can index what
+//! was said and when. Only the formats YouTube hands out are understood
+//! (WebVTT, srv1/2/3, SRT and TTML); the parsers are lenient and simply drop
+//! cues they cannot make sense of.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// One timed line of a transcript, in seconds from the start of the video.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TranscriptCue {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Every cue of one subtitle language for a video.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Transcript {
+    pub language: String,
+    pub cues: Vec<TranscriptCue>,
+}
+
+/// Preference order between subtitle formats of the same language; lower is
+/// better and 10+ means "never pick". Shared with the backend's subtitle
+/// endpoint so the served track and the indexed one agree.
+pub fn subtitle_extension_rank(ext: &str) -> usize {
+    match ext.to_ascii_lowercase().as_str() {
+        "vtt" => 0,
+        "srv3" => 1,
+        "srv2" => 2,
+        "srv1" => 3,
+        "srt" => 4,
+        "ttml" => 5,
+        "xml" => 6,
+        "ass" => 7,
+        _ => 10,
+    }
+}
+
+/// Parses subtitle `contents` based on the file extension. Returns `None` for
+/// formats we do not understand (e.g. `ass`).
+pub fn parse_subtitle(ext: &str, contents: &str) -> Option<Vec<TranscriptCue>> {
+    let contents = contents.trim_start_matches('\u{feff}');
+    let cues = match ext.to_ascii_lowercase().as_str() {
+        "vtt" => parse_vtt(contents),
+        "srt" => parse_srt(contents),
+        "srv3" => parse_timedtext(contents, "p", "t", "d", 0.001),
+        "srv2" => parse_timedtext(contents, "text", "t", "d", 0.001),
+        "srv1" => parse_timedtext(contents, "text", "start", "dur", 1.0),
+        "ttml" | "xml" => parse_ttml(contents),
+        _ => return None,
+    };
+    Some(cues)
+}
+
+/// Reads the best-ranked parseable file for every language in
+/// `subtitles_dir` (named `<videoid>.<lang>.<ext>`). Languages whose files
+/// contain no usable cues are left out.
+pub fn read_transcripts(subtitles_dir: &Path, videoid: &str) -> Result<Vec<Transcript>> {
+    if !subtitles_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let prefix = format!("{videoid}.");
+    let mut best: HashMap<String, (usize, PathBuf, String)> = HashMap::new();
+    for entry in fs::read_dir(subtitles_dir)
+        .with_context(|| format!("reading subtitles dir {}", subtitles_dir.display()))?
+    {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some((stem, ext)) = name.rsplit_once('.') else {
+            continue;
+        };
+        let Some(language) = stem.strip_prefix(&prefix) else {
+            continue;
+        };
+        let rank = subtitle_extension_rank(ext);
+        if rank >= 10 || parse_subtitle(ext, "").is_none() {
+            continue;
+        }
+        match best.get(language) {
+            Some((best_rank, _, _)) if *best_rank <= rank => {}
+            _ => {
+                best.insert(language.to_string(), (rank, path.clone(), ext.to_string()));
+            }
+        }
+    }
+
+    let mut transcripts = Vec::new();
+    for (language, (_, path, ext)) in best {
+        let bytes = fs::read(&path).with_context(|| format!("reading {}", path.display()))?;
+        let contents = String::from_utf8_lossy(&bytes);
+        let cues = parse_subtitle(&ext, &contents).unwrap_or_default();
+        if !cues.is_empty() {
+            transcripts.push(Transcript { language, cues });
+        }
+    }
+    transcripts.sort_by(|a, b| a.language.cmp(&b.language));
+    Ok(transcripts)
+}
+
+/// WebVTT. YouTube's auto-generated captions repeat the previous line at the
+/// top of each cue (the "rolling" display), so lines already emitted by the
+/// previous cue are dropped.
+fn parse_vtt(contents: &str) -> Vec<TranscriptCue> {
+    let mut cues = Vec::new();
+    let mut previous_lines: Vec<String> = Vec::new();
+    for block in split_blocks(contents) {
+        let mut lines = block.iter().copied();
+        let Some((start, end)) = lines.by_ref().find_map(parse_cue_timing) else {
+            continue;
+        };
+        let cue_lines: Vec<String> = lines
+            .map(clean_markup)
+            .filter(|line| !line.is_empty())
+            .collect();
+        let fresh: Vec<&String> = cue_lines
+            .iter()
+            .filter(|line| !previous_lines.contains(line))
+            .collect();
+        let text = collapse_whitespace(
+            &fresh
+                .iter()
+                .map(|line| line.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+        );
+        if !cue_lines.is_empty() {
+            previous_lines = cue_lines.clone();
+        }
+        if !text.is_empty() && end > start {
+            cues.push(TranscriptCue { start, end, text });
+        }
+    }
+    cues
+}
+
+fn parse_srt(contents: &str) -> Vec<TranscriptCue> {
+    let mut cues = Vec::new();
+    for block in split_blocks(contents) {
+        let mut lines = block.iter().copied();
+        let Some((start, end)) = lines.by_ref().find_map(parse_cue_timing) else {
+            continue;
+        };
+        let text = collapse_whitespace(&lines.map(clean_markup).collect::<Vec<_>>().join(" "));
+        if !text.is_empty() && end >= start {
+            cues.push(TranscriptCue { start, end, text });
+        }
+    }
+    cues
+}
+
+/// YouTube's XML formats: `<p t="ms" d="ms">` (srv3), `<text t d>` (srv2) and
+/// `<text start dur>` in seconds (srv1).
+fn parse_timedtext(
+    contents: &str,
+    tag: &str,
+    start_attr: &str,
+    duration_attr: &str,
+    scale: f64,
+) -> Vec<TranscriptCue> {
+    let mut cues = Vec::new();
+    for (attrs, inner) in xml_elements(contents, tag) {
+        let Some(start) = xml_attr(attrs, start_attr).and_then(|value| value.parse::<f64>().ok())
+        else {
+            continue;
+        };
+        let duration = xml_attr(attrs, duration_attr)
+            .and_then(|value| value.parse::<f64>().ok())
+            .unwrap_or(0.0);
+        let text = clean_markup(inner);
+        if text.is_empty() {
+            continue;
+        }
+        cues.push(TranscriptCue {
+            start: start * scale,
+            end: (start + duration) * scale,
+            text,
+        });
+    }
+    cues
+}
+
+fn parse_ttml(contents: &str) -> Vec<TranscriptCue> {
+    let mut cues = Vec::new();
+    for (attrs, inner) in xml_elements(contents, "p") {
+        let Some(start) = xml_attr(attrs, "begin").and_then(|value| parse_ttml_time(&value)) else {
+            continue;
+        };
+        let end = xml_attr(attrs, "end")
+            .and_then(|value| parse_ttml_time(&value))
+            .or_else(|| {
+                xml_attr(attrs, "dur")
+                    .and_then(|value| parse_ttml_time(&value))
+                    .map(|duration| start + duration)
+            })
+            .unwrap_or(start);
+        let text = clean_markup(inner);
+        if !text.is_empty() {
+            cues.push(TranscriptCue { start, end, text });
+        }
+    }
+    cues
+}
+
+/// Splits a text subtitle file into blank-line separated blocks.
+fn split_blocks(contents: &str) -> Vec<Vec<&str>> {
+    let mut blocks = Vec::new();
+    let mut current = Vec::new();
+    for line in contents.lines() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            if !current.is_empty() {
+                blocks.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+    blocks
+}
+
+/// Parses `00:00:01.000 --> 00:00:04.000 align:start` (VTT) or the SRT
+/// variant with a comma before the milliseconds.
+fn parse_cue_timing(line: &str) -> Option<(f64, f64)> {
+    let (start, rest) = line.split_once("-->")?;
+    let end = rest.split_whitespace().next()?;
+    Some((parse_clock(start.trim())?, parse_clock(end)?))
+}
+
+/// `HH:MM:SS.mmm`, `MM:SS.mmm` or `HH:MM:SS,mmm`.
+fn parse_clock(value: &str) -> Option<f64> {
+    let value = value.replace(',', ".");
+    let parts: Vec<&str> = value.split(':').collect();
+    if !(2..=3).contains(&parts.len()) {
+        return None;
+    }
+    let mut seconds = 0.0;
+    for (index, part) in parts.iter().enumerate() {
+        let last = index == parts.len() - 1;
+        let number: f64 = if last {
+            part.parse().ok()?
+        } else {
+            part.parse::<u64>().ok()? as f64
+        };
+        if number < 0.0 || (index > 0 && number >= 60.0) {
+            return None;
+        }
+        seconds = seconds * 60.0 + number;
+    }
+    Some(seconds)
+}
+
+/// TTML time expressions: clock time or offsets like `1.5s` / `1500ms`.
+fn parse_ttml_time(value: &str) -> Option<f64> {
+    let value = value.trim();
+    if let Some(ms) = value.strip_suffix("ms") {
+        return ms.parse::<f64>().ok().map(|ms| ms / 1000.0);
+    }
+    if let Some(seconds) = value.strip_suffix('s') {
+        return seconds.parse().ok();
+    }
+    if let Some(minutes) = value.strip_suffix('m') {
+        return minutes.parse::<f64>().ok().map(|minutes| minutes * 60.0);
+    }
+    if let Some(hours) = value.strip_suffix('h') {
+        return hours.parse::<f64>().ok().map(|hours| hours * 3600.0);
+    }
+    parse_clock(value)
+}
+
+/// Yields `(attributes, inner)` for every `<tag ...>inner</tag>` element.
+/// Self-closing elements carry no text and are skipped.
+fn xml_elements<'a>(contents: &'a str, tag: &str) -> Vec<(&'a str, &'a str)> {
+    let open = format!("<{tag}");
+    let close = format!("</{tag}>");
+    let mut elements = Vec::new();
+    let mut rest = contents;
+    while let Some(index) = rest.find(&open) {
+        rest = &rest[index + open.len()..];
+        // Make sure we matched `<p` and not `<param`.
+        if !rest.starts_with(|ch: char| ch.is_whitespace() || ch == '>' || ch == '/') {
+            continue;
+        }
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let attrs = &rest[..tag_end];
+        rest = &rest[tag_end + 1..];
+        if attrs.ends_with('/') {
+            continue;
+        }
+        let Some(close_index) = rest.find(&close) else {
+            break;
+        };
+        elements.push((attrs, &rest[..close_index]));
+        rest = &rest[close_index + close.len()..];
+    }
+    elements
+}
+
+fn xml_attr(attrs: &str, name: &str) -> Option<String> {
+    let mut rest = attrs;
+    while let Some(index) = rest.find(name) {
+        let before = rest[..index].chars().last();
+        let after = &rest[index + name.len()..];
+        rest = after;
+        if before.is_some_and(|ch| !ch.is_whitespace()) {
+            continue;
+        }
+        let Some(after_eq) = after.trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let after_eq = after_eq.trim_start();
+        let quote = after_eq.chars().next()?;
+        if quote != '"' && quote != '\'' {
+            continue;
+        }
+        let value = &after_eq[1..];
+        let end = value.find(quote)?;
+        return Some(decode_entities(&value[..end]));
+    }
+    None
+}
+
+/// Removes tags (`<c>`, `<b>`, `<00:00:01.500>`, `<br/>`, ...) and decodes
+/// entities, collapsing whitespace so cues index cleanly.
+fn clean_markup(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut in_tag = false;
+    for ch in text.chars() {
+        match ch {
+            '<' => {
+                in_tag = true;
+                // Tags such as <br/> separate words.
+                plain.push(' ');
+            }
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => plain.push(ch),
+            _ => {}
+        }
+    }
+    collapse_whitespace(&decode_entities(&plain))
+}
+
+fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(index) = rest.find('&') {
+        decoded.push_str(&rest[..index]);
+        rest = &rest[index..];
+        let Some(end) = rest.find(';').filter(|end| *end <= 10) else {
+            decoded.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let replacement = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match replacement {
+            Some(ch) => {
+                decoded.push(ch);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn texts(cues: &[TranscriptCue]) -> Vec<&str> {
+        cues.iter().map(|cue| cue.text.as_str()).collect()
+    }
+
+    /// Rolling auto-caption cues only contribute their new line, and inline
+    /// word timestamps/class tags are stripped.
+    #[test]
+    fn vtt_drops_rolling_duplicates_and_markup() {
+        let vtt = "WEBVTT\nKind: captions\nLanguage: en\n\n\
+            NOTE generated\n\n\
+            00:00:00.000 --> 00:00:02.000 align:start position:0%\n\
+            hello<00:00:00.500><c> world</c>\n\n\
+            00:00:02.000 --> 00:00:02.010\n\
+            hello world\n\n\
+            00:00:02.010 --> 00:00:04.500\n\
+            hello world\n\
+            this is &amp; new\n\n\
+            01:02.000 --> 01:03.000\n\
+            <b>Bold</b> claim\n";
+        let cues = parse_subtitle("vtt", vtt).unwrap();
+        assert_eq!(texts(&cues), ["hello world", "this is & new", "Bold claim"]);
+        assert_eq!(cues[1].start, 2.01);
+        assert_eq!(cues[1].end, 4.5);
+        assert_eq!(cues[2].start, 62.0);
+    }
+
+    #[test]
+    fn srt_and_ttml_parse_timings() {
+        let srt = "1\r\n00:00:01,500 --> 00:00:03,000\r\nFirst line\r\nsecond line\r\n\r\n\
+            2\r\n01:00:00,000 --> 01:00:01,000\r\n<i>Later</i>\r\n";
+        let cues = parse_subtitle("srt", srt).unwrap();
+        assert_eq!(texts(&cues), ["First line second line", "Later"]);
+        assert_eq!((cues[0].start, cues[0].end), (1.5, 3.0));
+        assert_eq!(cues[1].start, 3600.0);
+
+        let ttml = r#"<?xml version="1.0"?><tt xmlns="http://www.w3.org/ns/ttml"><body><div>
+            <p begin="00:00:01.000" end="00:00:02.500">Hi<br/>there</p>
+            <p begin="3s" dur="1500ms">Offset &#39;times&#39;</p>
+            <p begin="bogus">skipped</p>
+        </div></body></tt>"#;
+        let cues = parse_subtitle("ttml", ttml).unwrap();
+        assert_eq!(texts(&cues), ["Hi there", "Offset 'times'"]);
+        assert_eq!((cues[1].start, cues[1].end), (3.0, 4.5));
+    }
+
+    #[test]
+    fn youtube_timedtext_formats() {
+        let srv3 = r#"<?xml version="1.0" encoding="utf-8" ?><timedtext format="3"><body>
+            <p t="1200" d="2300" w="1"><s ac="0">never</s><s t="400"> gonna</s></p>
+            <p t="3500" d="10" w="1" a="1">
+            </p>
+            <p t="4000" d="1000">give &quot;you&quot; up</p>
+        </body></timedtext>"#;
+        let cues = parse_subtitle("srv3", srv3).unwrap();
+        assert_eq!(texts(&cues), ["never gonna", "give \"you\" up"]);
+        assert_eq!((cues[0].start, cues[0].end), (1.2, 3.5));
+
+        let srv1 = r#"<transcript><text start="0.5" dur="1.25">one</text><text dur="1">no start</text></transcript>"#;
+        let cues = parse_subtitle("srv1", srv1).unwrap();
+        assert_eq!(texts(&cues), ["one"]);
+        assert_eq!(cues[0].end, 1.75);
+
+        assert!(parse_subtitle("ass", "[Script Info]").is_none());
+    }
+
+    /// Each language uses its best-ranked format; other videos' files and
+    /// unsupported formats are ignored.
+    #[test]
+    fn read_transcripts_picks_preferred_format_per_language() -> Result<()> {
+        let dir = tempdir()?;
+        let vtt = "WEBVTT\n\n00:00:00.000 --> 00:00:01.000\nfrom vtt\n";
+        let srt = "1\n00:00:00,000 --> 00:00:01,000\nfrom srt\n";
+        fs::write(dir.path().join("abc.en.vtt"), vtt)?;
+        fs::write(dir.path().join("abc.en.srt"), srt)?;
+        fs::write(dir.path().join("abc.de.srt"), srt)?;
+        fs::write(dir.path().join("abc.fr.ass"), "[Script Info]")?;
+        fs::write(dir.path().join("abc.live_chat.json"), "{}")?;
+        fs::write(dir.path().join("other.en.vtt"), vtt)?;
+
+        let transcripts = read_transcripts(dir.path(), "abc")?;
+        let summary: Vec<(&str, &str)> = transcripts
+            .iter()
+            .map(|t| (t.language.as_str(), t.cues[0].text.as_str()))
+            .collect();
+        assert_eq!(summary, [("de", "from srt"), ("en", "from vtt")]);
+        assert!(read_transcripts(&dir.path().join("missing"), "abc")?.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,1757 @@
+#![forbid(unsafe_code)]
+
+//! Server-side copy of the per-household user data that the frontend keeps in
+//! `localStorage` (`UserDataStore` in `userData.js`): likes/dislikes,
+//! playlists, subscriptions and watch history, plus the instance-wide play
+//! counts the backend records while streaming.
+//!
+//! The data lives in its own `userdata.db` next to `metadata.db` because the
+//! metadata DB is served verbatim to clients at `/metadata.db`. Timestamps are
+//! epoch milliseconds, matching `Date.now()` on the JS side, so snapshots can
+//! round-trip through the existing JSON export format unchanged.
+//!
+//! Every entity is scoped to a [`Profile`]; [`UserDataStore::profile`] hands
+//! out a [`ProfileData`] view so callers cannot accidentally read or write
+//! another profile's rows. Passwords are hashed with argon2.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+use argon2::Argon2;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use chrono::Utc;
+use libsql::{Builder, Connection, Row, params};
+use serde::{Deserialize, Serialize};
+
+use crate::metadata::{Migration, MigrationStep, configure_connection, ensure_schema_with};
+
+/// File name used for the user data DB inside `MEDIA_ROOT`.
+pub const USER_DATA_DB_FILE: &str = "userdata.db";
+
+/// Version stamped into snapshots; mirrors `createEmptyState().version`.
+const SNAPSHOT_VERSION: i64 = 1;
+
+/// Profile created by the migration that introduced profiles. It owns data
+/// written before then and starts out as the only admin.
+pub const DEFAULT_PROFILE_ID: i64 = 1;
+
+/// Sessions stay valid for 30 days; a new login issues a fresh token.
+const SESSION_TTL_MILLIS: i64 = 30 * 24 * 60 * 60 * 1000;
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "reactions, playlists, subscriptions and watch history",
+        step: MigrationStep::Sql(
+            r#"
+        CREATE TABLE IF NOT EXISTS reactions (
+            videoid TEXT PRIMARY KEY,
+            reaction TEXT NOT NULL CHECK (reaction IN ('like', 'dislike')),
+            title TEXT,
+            author TEXT,
+            channel_id TEXT,
+            thumbnail TEXT,
+            saved_at INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS user_playlists (
+            playlist_id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            last_entry_json TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS user_playlist_items (
+            playlist_id TEXT NOT NULL REFERENCES user_playlists(playlist_id) ON DELETE CASCADE,
+            videoid TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            PRIMARY KEY (playlist_id, videoid)
+        );
+
+        CREATE TABLE IF NOT EXISTS subscriptions (
+            channel_id TEXT PRIMARY KEY,
+            name TEXT,
+            channel_url TEXT,
+            subscribed_at INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS watch_history (
+            videoid TEXT PRIMARY KEY,
+            progress REAL NOT NULL DEFAULT 0,
+            watched INTEGER NOT NULL DEFAULT 0,
+            title TEXT,
+            author TEXT,
+            thumbnail TEXT,
+            updated_at INTEGER NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_watch_history_updated ON watch_history(updated_at);
+        "#,
+        ),
+    },
+    Migration {
+        version: 2,
+        description: "profiles, sessions and per-profile user data",
+        step: MigrationStep::Sql(
+            r#"
+            CREATE TABLE profiles (
+                profile_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL UNIQUE COLLATE NOCASE,
+                password_hash TEXT,
+                is_admin INTEGER NOT NULL DEFAULT 0,
+                settings_json TEXT NOT NULL DEFAULT '{}',
+                created_at INTEGER NOT NULL
+            );
+
+            -- Data written before profiles existed belongs to this admin.
+            INSERT INTO profiles (profile_id, name, is_admin, created_at)
+            VALUES (1, 'Default', 1, CAST(strftime('%s', 'now') AS INTEGER) * 1000);
+
+            CREATE TABLE sessions (
+                token TEXT PRIMARY KEY,
+                profile_id INTEGER NOT NULL REFERENCES profiles(profile_id) ON DELETE CASCADE,
+                created_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL
+            );
+            CREATE INDEX idx_sessions_profile ON sessions(profile_id);
+
+            CREATE TABLE reactions_v2 (
+                profile_id INTEGER NOT NULL REFERENCES profiles(profile_id) ON DELETE CASCADE,
+                videoid TEXT NOT NULL,
+                reaction TEXT NOT NULL CHECK (reaction IN ('like', 'dislike')),
+                title TEXT,
+                author TEXT,
+                channel_id TEXT,
+                thumbnail TEXT,
+                saved_at INTEGER NOT NULL,
+                PRIMARY KEY (profile_id, videoid)
+            );
+            INSERT INTO reactions_v2
+                (profile_id, videoid, reaction, title, author, channel_id, thumbnail, saved_at)
+            SELECT 1, videoid, reaction, title, author, channel_id, thumbnail, saved_at
+            FROM reactions;
+
+            CREATE TABLE user_playlists_v2 (
+                profile_id INTEGER NOT NULL REFERENCES profiles(profile_id) ON DELETE CASCADE,
+                playlist_id TEXT NOT NULL,
+                name TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL,
+                last_entry_json TEXT,
+                PRIMARY KEY (profile_id, playlist_id)
+            );
+            INSERT INTO user_playlists_v2
+                (profile_id, playlist_id, name, created_at, updated_at, last_entry_json)
+            SELECT 1, playlist_id, name, created_at, updated_at, last_entry_json
+            FROM user_playlists;
+
+            CREATE TABLE user_playlist_items_v2 (
+                profile_id INTEGER NOT NULL,
+                playlist_id TEXT NOT NULL,
+                videoid TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                PRIMARY KEY (profile_id, playlist_id, videoid),
+                FOREIGN KEY (profile_id, playlist_id)
+                    REFERENCES user_playlists_v2(profile_id, playlist_id) ON DELETE CASCADE
+            );
+            INSERT INTO user_playlist_items_v2 (profile_id, playlist_id, videoid, position)
+            SELECT 1, playlist_id, videoid, position FROM user_playlist_items;
+
+            CREATE TABLE subscriptions_v2 (
+                profile_id INTEGER NOT NULL REFERENCES profiles(profile_id) ON DELETE CASCADE,
+                channel_id TEXT NOT NULL,
+                name TEXT,
+                channel_url TEXT,
+                subscribed_at INTEGER NOT NULL,
+                PRIMARY KEY (profile_id, channel_id)
+            );
+            INSERT INTO subscriptions_v2 (profile_id, channel_id, name, channel_url, subscribed_at)
+            SELECT 1, channel_id, name, channel_url, subscribed_at FROM subscriptions;
+
+            CREATE TABLE watch_history_v2 (
+                profile_id INTEGER NOT NULL REFERENCES profiles(profile_id) ON DELETE CASCADE,
+                videoid TEXT NOT NULL,
+                progress REAL NOT NULL DEFAULT 0,
+                watched INTEGER NOT NULL DEFAULT 0,
+                title TEXT,
+                author TEXT,
+                thumbnail TEXT,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (profile_id, videoid)
+            );
+            INSERT INTO watch_history_v2
+                (profile_id, videoid, progress, watched, title, author, thumbnail, updated_at)
+            SELECT 1, videoid, progress, watched, title, author, thumbnail, updated_at
+            FROM watch_history;
+
+            DROP TABLE user_playlist_items;
+            DROP TABLE user_playlists;
+            DROP TABLE reactions;
+            DROP TABLE subscriptions;
+            DROP TABLE watch_history;
+
+            ALTER TABLE reactions_v2 RENAME TO reactions;
+            ALTER TABLE user_playlists_v2 RENAME TO user_playlists;
+            ALTER TABLE user_playlist_items_v2 RENAME TO user_playlist_items;
+            ALTER TABLE subscriptions_v2 RENAME TO subscriptions;
+            ALTER TABLE watch_history_v2 RENAME TO watch_history;
+
+            CREATE INDEX idx_watch_history_updated ON watch_history(profile_id, updated_at);
+            "#,
+        ),
+    },
+    Migration {
+        version: 3,
+        description: "local play stats",
+        step: MigrationStep::Sql(
+            r#"
+            CREATE TABLE play_stats (
+                videoid TEXT PRIMARY KEY,
+                plays INTEGER NOT NULL DEFAULT 0,
+                watched_bytes INTEGER NOT NULL DEFAULT 0,
+                last_played_at INTEGER NOT NULL
+            );
+            CREATE INDEX idx_play_stats_plays ON play_stats(plays, watched_bytes);
+            "#,
+        ),
+    },
+];
+
+/// Like or dislike; a video has at most one of the two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reaction {
+    Like,
+    Dislike,
+}
+
+impl Reaction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Reaction::Like => "like",
+            Reaction::Dislike => "dislike",
+        }
+    }
+}
+
+/// Video summary saved with likes and playlist entries
+/// (`buildVideoMetadata` in `userData.js`).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedVideo {
+    #[serde(default)]
+    pub videoid: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    #[serde(default)]
+    pub saved_at: Option<i64>,
+}
+
+/// User-created playlist. Unrelated to the archived YouTube playlists in
+/// `metadata.db`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserPlaylist {
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub video_ids: Vec<String>,
+    #[serde(default)]
+    pub created_at: Option<i64>,
+    #[serde(default)]
+    pub updated_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_entry: Option<SavedVideo>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Subscription {
+    #[serde(default)]
+    pub channel_id: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub channel_url: Option<String>,
+    #[serde(default)]
+    pub subscribed_at: Option<i64>,
+}
+
+/// Playback progress for one video. `progress` is a 0..=1 fraction and
+/// `watched` is sticky once set, as in `setWatchProgress`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchEntry {
+    #[serde(default)]
+    pub progress: Option<f64>,
+    #[serde(default)]
+    pub watched: bool,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub author: Option<String>,
+    #[serde(default)]
+    pub thumbnail: Option<String>,
+    #[serde(default)]
+    pub updated_at: Option<i64>,
+}
+
+/// Full user state in the shape of the frontend's `data` object. Maps are
+/// keyed the same way as in `localStorage`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserSnapshot {
+    #[serde(default)]
+    pub version: i64,
+    #[serde(default)]
+    pub likes: BTreeMap<String, SavedVideo>,
+    #[serde(default)]
+    pub dislikes: BTreeMap<String, SavedVideo>,
+    #[serde(default)]
+    pub playlists: BTreeMap<String, UserPlaylist>,
+    #[serde(default)]
+    pub subscriptions: BTreeMap<String, Subscription>,
+    #[serde(default)]
+    pub watch_history: BTreeMap<String, WatchEntry>,
+}
+
+/// Accepts both `exportToString()` output (`{exportedAt, data}`) and a bare
+/// `data` object, like `importFromString`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum UserDataImport {
+    Export {
+        #[serde(rename = "exportedAt", default)]
+        exported_at: Option<String>,
+        data: UserSnapshot,
+    },
+    Bare(UserSnapshot),
+}
+
+impl UserDataImport {
+    pub fn into_snapshot(self) -> UserSnapshot {
+        match self {
+            UserDataImport::Export { data, .. } => data,
+            UserDataImport::Bare(data) => data,
+        }
+    }
+}
+
+/// Mirrors `normalizeKey` in `userData.js` so server-created playlists get the
+/// same ids the browser would assign.
+pub fn normalize_playlist_key(value: &str) -> String {
+    let mut key = String::new();
+    let mut pending_dash = false;
+    for ch in value.trim().to_lowercase().chars() {
+        if ch.is_whitespace() {
+            pending_dash = true;
+            continue;
+        }
+        if pending_dash {
+            key.push('-');
+            pending_dash = false;
+        }
+        key.push(ch);
+    }
+    let key: String = key.chars().take(64).collect();
+    if key.is_empty() {
+        "default".to_string()
+    } else {
+        key
+    }
+}
+
+fn now_millis() -> i64 {
+    Utc::now().timestamp_millis()
+}
+
+/// A household member. Profiles without a password can be picked by anyone,
+/// like a streaming service's profile screen; a password or PIN is optional.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub id: i64,
+    pub name: String,
+    pub is_admin: bool,
+    pub has_password: bool,
+    pub created_at: i64,
+}
+
+/// Local playback totals for one video, across all profiles. A play is
+/// counted by the backend once a streaming session has sent enough of the
+/// file; `watched_bytes` sums every byte streamed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlayStats {
+    pub videoid: String,
+    pub plays: i64,
+    pub watched_bytes: i64,
+    pub last_played_at: Option<i64>,
+}
+
+/// Freshly issued login session. Only the opaque token is handed to clients.
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub token: String,
+    pub profile: Profile,
+    pub expires_at: i64,
+}
+
+const PROFILE_COLUMNS: &str = r#"
+    profiles.profile_id, profiles.name, profiles.is_admin,
+    profiles.password_hash IS NOT NULL, profiles.created_at
+"#;
+
+/// Read/write handle for `userdata.db`.
+pub struct UserDataStore {
+    conn: Connection,
+}
+
+impl UserDataStore {
+    /// Opens (creating if needed) the DB and applies pending migrations.
+    pub async fn open(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("creating user data directory {}", parent.display()))?;
+        }
+        let db = Builder::new_local(path)
+            .build()
+            .await
+            .with_context(|| format!("opening user data DB {}", path.display()))?;
+        let conn = db.connect()?;
+        configure_connection(&conn).await?;
+        ensure_schema_with(&conn, "user data", MIGRATIONS).await?;
+        Ok(Self { conn })
+    }
+
+    /// Scopes likes, playlists, subscriptions, history and settings to one
+    /// profile. Every query issued through the handle filters on its id.
+    pub fn profile(&self, profile_id: i64) -> ProfileData<'_> {
+        ProfileData {
+            conn: &self.conn,
+            profile_id,
+        }
+    }
+
+    /// Lists profiles alphabetically, for the profile picker.
+    pub async fn list_profiles(&self) -> Result<Vec<Profile>> {
+        let mut rows = self
+            .conn
+            .query(
+                &format!(
+                    "SELECT {PROFILE_COLUMNS} FROM profiles ORDER BY name COLLATE NOCASE, profile_id"
+                ),
+                params![],
+            )
+            .await?;
+        let mut profiles = Vec::new();
+        while let Some(row) = rows.next().await? {
+            profiles.push(row_to_profile(&row)?);
+        }
+        Ok(profiles)
+    }
+
+    pub async fn get_profile(&self, profile_id: i64) -> Result<Option<Profile>> {
+        let mut rows = self
+            .conn
+            .query(
+                &format!("SELECT {PROFILE_COLUMNS} FROM profiles WHERE profile_id = ?1"),
+                [profile_id],
+            )
+            .await?;
+        rows.next()
+            .await?
+            .map(|row| row_to_profile(&row))
+            .transpose()
+    }
+
+    /// Looks a profile up by name, ignoring case.
+    pub async fn find_profile(&self, name: &str) -> Result<Option<Profile>> {
+        let mut rows = self
+            .conn
+            .query(
+                &format!("SELECT {PROFILE_COLUMNS} FROM profiles WHERE name = ?1"),
+                [name],
+            )
+            .await?;
+        rows.next()
+            .await?
+            .map(|row| row_to_profile(&row))
+            .transpose()
+    }
+
+    /// Creates a profile. Names are unique regardless of case.
+    pub async fn create_profile(
+        &self,
+        name: &str,
+        password: Option<&str>,
+        is_admin: bool,
+    ) -> Result<Profile> {
+        let name = name.trim();
+        if name.is_empty() {
+            bail!("profile name must not be empty");
+        }
+        if self.find_profile(name).await?.is_some() {
+            bail!("profile {name} already exists");
+        }
+        let password_hash = match password {
+            Some(password) => Some(hash_password(password.to_string()).await?),
+            None => None,
+        };
+        self.conn
+            .execute(
+                r#"
+                INSERT INTO profiles (name, password_hash, is_admin, created_at)
+                VALUES (?1, ?2, ?3, ?4)
+                "#,
+                params![name, password_hash, is_admin as i64, now_millis()],
+            )
+            .await?;
+        let profile_id = self.conn.last_insert_rowid();
+        self.get_profile(profile_id)
+            .await?
+            .context("profile missing after insert")
+    }
+
+    /// Deletes a profile together with its data and sessions.
+    pub async fn delete_profile(&self, profile_id: i64) -> Result<bool> {
+        let removed = self
+            .conn
+            .execute("DELETE FROM profiles WHERE profile_id = ?1", [profile_id])
+            .await?;
+        Ok(removed > 0)
+    }
+
+    /// Sets or (with `None`) clears a profile's password. Existing sessions
+    /// are revoked so a changed password locks out other devices.
+    pub async fn set_password(&self, profile_id: i64, password: Option<&str>) -> Result<bool> {
+        let password_hash = match password {
+            Some(password) => Some(hash_password(password.to_string()).await?),
+            None => None,
+        };
+        let updated = self
+            .conn
+            .execute(
+                "UPDATE profiles SET password_hash = ?2 WHERE profile_id = ?1",
+                params![profile_id, password_hash],
+            )
+            .await?;
+        self.conn
+            .execute("DELETE FROM sessions WHERE profile_id = ?1", [profile_id])
+            .await?;
+        Ok(updated > 0)
+    }
+
+    /// Checks a login attempt. Profiles without a password accept any input.
+    pub async fn verify_password(&self, profile_id: i64, password: Option<&str>) -> Result<bool> {
+        let mut rows = self
+            .conn
+            .query(
+                "SELECT password_hash FROM profiles WHERE profile_id = ?1",
+                [profile_id],
+            )
+            .await?;
+        let Some(row) = rows.next().await? else {
+            return Ok(false);
+        };
+        let Some(hash) = row.get::<Option<String>>(0)? else {
+            return Ok(true);
+        };
+        let Some(password) = password else {
+            return Ok(false);
+        };
+        let password = password.to_string();
+        tokio::task::spawn_blocking(move || verify_password_hash(&hash, &password))
+            .await
+            .context("password verification task failed")
+    }
+
+    /// Issues a new session token for `profile_id`, pruning expired ones.
+    pub async fn create_session(&self, profile_id: i64) -> Result<Session> {
+        let profile = self
+            .get_profile(profile_id)
+            .await?
+            .with_context(|| format!("profile {profile_id} does not exist"))?;
+        let now = now_millis();
+        self.conn
+            .execute("DELETE FROM sessions WHERE expires_at <= ?1", [now])
+            .await?;
+
+        let mut bytes = [0u8; 32];
+        OsRng.fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+        let expires_at = now + SESSION_TTL_MILLIS;
+        self.conn
+            .execute(
+                r#"
+                INSERT INTO sessions (token, profile_id, created_at, expires_at)
+                VALUES (?1, ?2, ?3, ?4)
+                "#,
+                params![token.as_str(), profile_id, now, expires_at],
+            )
+            .await?;
+        Ok(Session {
+            token,
+            profile,
+            expires_at,
+        })
+    }
+
+    /// Resolves a session token to its profile unless it expired.
+    pub async fn session_profile(&self, token: &str) -> Result<Option<Profile>> {
+        let mut rows = self
+            .conn
+            .query(
+                &format!(
+                    r#"
+                    SELECT {PROFILE_COLUMNS}
+                    FROM sessions JOIN profiles USING (profile_id)
+                    WHERE token = ?1 AND expires_at > ?2
+                    "#
+                ),
+                params![token, now_millis()],
+            )
+            .await?;
+        rows.next()
+            .await?
+            .map(|row| row_to_profile(&row))
+            .transpose()
+    }
+
+    pub async fn delete_session(&self, token: &str) -> Result<bool> {
+        let removed = self
+            .conn
+            .execute("DELETE FROM sessions WHERE token = ?1", [token])
+            .await?;
+        Ok(removed > 0)
+    }
+
+    /// Profile that requests without a session act as. This is only the case
+    /// while the instance has a single, password-less profile, so existing
+    /// single-user setups keep working until a second profile is added.
+    pub async fn anonymous_profile(&self) -> Result<Option<Profile>> {
+        let mut profiles = self.list_profiles().await?;
+        if profiles.len() == 1 && !profiles[0].has_password {
+            Ok(profiles.pop())
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Adds streamed bytes to a video's totals and bumps its play count when
+    /// the session just crossed the play threshold.
+    pub async fn record_play(&self, videoid: &str, bytes: u64, counted: bool) -> Result<()> {
+        self.conn
+            .execute(
+                r#"
+                INSERT INTO play_stats (videoid, plays, watched_bytes, last_played_at)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT(videoid) DO UPDATE SET
+                    plays = plays + excluded.plays,
+                    watched_bytes = watched_bytes + excluded.watched_bytes,
+                    last_played_at = excluded.last_played_at
+                "#,
+                params![
+                    videoid,
+                    i64::from(counted),
+                    i64::try_from(bytes).unwrap_or(i64::MAX),
+                    now_millis()
+                ],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Totals for one video; all zero when it was never streamed.
+    pub async fn play_stats(&self, videoid: &str) -> Result<PlayStats> {
+        let mut rows = self
+            .conn
+            .query(
+                r#"
+                SELECT videoid, plays, watched_bytes, last_played_at
+                FROM play_stats
+                WHERE videoid = ?1
+                "#,
+                [videoid],
+            )
+            .await?;
+        match rows.next().await? {
+            Some(row) => row_to_play_stats(&row),
+            None => Ok(PlayStats {
+                videoid: videoid.to_owned(),
+                ..PlayStats::default()
+            }),
+        }
+    }
+
+    /// Lists the most played videos, breaking ties by watched bytes and then
+    /// by how recently they were played. Videos streamed without reaching a
+    /// play are left out.
+    pub async fn most_played(&self, limit: usize) -> Result<Vec<PlayStats>> {
+        let mut rows = self
+            .conn
+            .query(
+                r#"
+                SELECT videoid, plays, watched_bytes, last_played_at
+                FROM play_stats
+                WHERE plays > 0
+                ORDER BY plays DESC, watched_bytes DESC, last_played_at DESC, videoid
+                LIMIT ?1
+                "#,
+                [i64::try_from(limit).unwrap_or(i64::MAX)],
+            )
+            .await?;
+        let mut stats = Vec::new();
+        while let Some(row) = rows.next().await? {
+            stats.push(row_to_play_stats(&row)?);
+        }
+        Ok(stats)
+    }
+}
+
+fn row_to_play_stats(row: &Row) -> Result<PlayStats> {
+    Ok(PlayStats {
+        videoid: row.get(0)?,
+        plays: row.get(1)?,
+        watched_bytes: row.get(2)?,
+        last_played_at: row.get(3)?,
+    })
+}
+
+/// User data belonging to one profile; see [`UserDataStore::profile`].
+pub struct ProfileData<'a> {
+    conn: &'a Connection,
+    profile_id: i64,
+}
+
+impl ProfileData<'_> {
+    /// Returns every entity in the frontend's snapshot shape.
+    pub async fn snapshot(&self) -> Result<UserSnapshot> {
+        let mut snapshot = UserSnapshot {
+            version: SNAPSHOT_VERSION,
+            ..UserSnapshot::default()
+        };
+        for (videoid, saved) in self.list_reactions(Reaction::Like).await? {
+            snapshot.likes.insert(videoid, saved);
+        }
+        for (videoid, saved) in self.list_reactions(Reaction::Dislike).await? {
+            snapshot.dislikes.insert(videoid, saved);
+        }
+        for playlist in self.list_playlists().await? {
+            snapshot.playlists.insert(playlist.id.clone(), playlist);
+        }
+        for subscription in self.list_subscriptions().await? {
+            snapshot
+                .subscriptions
+                .insert(subscription.channel_id.clone(), subscription);
+        }
+        for (videoid, entry) in self.list_history().await? {
+            snapshot.watch_history.insert(videoid, entry);
+        }
+        Ok(snapshot)
+    }
+
+    /// Free-form frontend preferences (theme, autoplay, ...) as a JSON object.
+    pub async fn settings(&self) -> Result<serde_json::Value> {
+        let mut rows = self
+            .conn
+            .query(
+                "SELECT settings_json FROM profiles WHERE profile_id = ?1",
+                [self.profile_id],
+            )
+            .await?;
+        let Some(row) = rows.next().await? else {
+            bail!("profile {} does not exist", self.profile_id);
+        };
+        let json: String = row.get(0)?;
+        serde_json::from_str(&json).context("parsing profile settings")
+    }
+
+    pub async fn set_settings(&self, settings: &serde_json::Value) -> Result<()> {
+        if !settings.is_object() {
+            bail!("profile settings must be a JSON object");
+        }
+        self.conn
+            .execute(
+                "UPDATE profiles SET settings_json = ?2 WHERE profile_id = ?1",
+                params![self.profile_id, settings.to_string()],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Lists likes or dislikes, newest first.
+    pub async fn list_reactions(&self, reaction: Reaction) -> Result<Vec<(String, SavedVideo)>> {
+        let mut rows = self
+            .conn
+            .query(
+                r#"
+                SELECT videoid, title, author, channel_id, thumbnail, saved_at
+                FROM reactions
+                WHERE profile_id = ?1 AND reaction = ?2
+                ORDER BY saved_at DESC, videoid
+                "#,
+                params![self.profile_id, reaction.as_str()],
+            )
+            .await?;
+        let mut reactions = Vec::new();
+        while let Some(row) = rows.next().await? {
+            let videoid: String = row.get(0)?;
+            reactions.push((
+                videoid.clone(),
+                SavedVideo {
+                    videoid: Some(videoid),
+                    title: row.get(1)?,
+                    author: row.get(2)?,
+                    channel_id: row.get(3)?,
+                    thumbnail: row.get(4)?,
+                    saved_at: row.get(5)?,
+                },
+            ));
+        }
+        Ok(reactions)
+    }
+
+    /// Sets the reaction for a video, replacing the opposite one.
+    pub async fn set_reaction(
+        &self,
+        videoid: &str,
+        reaction: Reaction,
+        saved: &SavedVideo,
+    ) -> Result<()> {
+        upsert_reaction(self.conn, self.profile_id, videoid, reaction, saved, false).await
+    }
+
+    /// Removes a like/dislike. Returns whether that reaction was present.
+    pub async fn clear_reaction(&self, videoid: &str, reaction: Reaction) -> Result<bool> {
+        let removed = self
+            .conn
+            .execute(
+                "DELETE FROM reactions WHERE profile_id = ?1 AND videoid = ?2 AND reaction = ?3",
+                params![self.profile_id, videoid, reaction.as_str()],
+            )
+            .await?;
+        Ok(removed > 0)
+    }
+
+    /// Lists playlists (with ordered video ids) alphabetically by name.
+    pub async fn list_playlists(&self) -> Result<Vec<UserPlaylist>> {
+        let mut rows = self
+            .conn
+            .query(
+                r#"
+                SELECT playlist_id, name, created_at, updated_at, last_entry_json
+                FROM user_playlists
+                WHERE profile_id = ?1
+                ORDER BY name COLLATE NOCASE, playlist_id
+                "#,
+                [self.profile_id],
+            )
+            .await?;
+        let mut playlists = Vec::new();
+        while let Some(row) = rows.next().await? {
+            playlists.push(row_to_playlist(&row)?);
+        }
+        drop(rows);
+
+        for playlist in &mut playlists {
+            playlist.video_ids = self.playlist_video_ids(&playlist.id).await?;
+        }
+        Ok(playlists)
+    }
+
+    pub async fn get_playlist(&self, playlist_id: &str) -> Result<Option<UserPlaylist>> {
+        let mut rows = self
+            .conn
+            .query(
+                r#"
+                SELECT playlist_id, name, created_at, updated_at, last_entry_json
+                FROM user_playlists
+                WHERE profile_id = ?1 AND playlist_id = ?2
+                "#,
+                params![self.profile_id, playlist_id],
+            )
+            .await?;
+        let Some(row) = rows.next().await? else {
+            return Ok(None);
+        };
+        let mut playlist = row_to_playlist(&row)?;
+        drop(rows);
+        playlist.video_ids = self.playlist_video_ids(playlist_id).await?;
+        Ok(Some(playlist))
+    }
+
+    async fn playlist_video_ids(&self, playlist_id: &str) -> Result<Vec<String>> {
+        let mut rows = self
+            .conn
+            .query(
+                r#"
+                SELECT videoid FROM user_playlist_items
+                WHERE profile_id = ?1 AND playlist_id = ?2
+                ORDER BY position
+                "#,
+                params![self.profile_id, playlist_id],
+            )
+            .await?;
+        let mut ids = Vec::new();
+        while let Some(row) = rows.next().await? {
+            ids.push(row.get(0)?);
+        }
+        Ok(ids)
+    }
+
+    /// Creates a playlist or renames an existing one.
+    pub async fn upsert_playlist(&self, playlist_id: &str, name: &str) -> Result<UserPlaylist> {
+        let now = now_millis();
+        self.conn
+            .execute(
+                r#"
+                INSERT INTO user_playlists (profile_id, playlist_id, name, created_at, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?4)
+                ON CONFLICT(profile_id, playlist_id) DO UPDATE SET
+                    name = excluded.name,
+                    updated_at = excluded.updated_at
+                "#,
+                params![self.profile_id, playlist_id, name, now],
+            )
+            .await?;
+        self.get_playlist(playlist_id)
+            .await?
+            .context("playlist missing after upsert")
+    }
+
+    pub async fn delete_playlist(&self, playlist_id: &str) -> Result<bool> {
+        let removed = self
+            .conn
+            .execute(
+                "DELETE FROM user_playlists WHERE profile_id = ?1 AND playlist_id = ?2",
+                params![self.profile_id, playlist_id],
+            )
+            .await?;
+        Ok(removed > 0)
+    }
+
+    /// Appends a video to a playlist; already-present videos keep their spot.
+    pub async fn add_to_playlist(
+        &self,
+        playlist_id: &str,
+        videoid: &str,
+        saved: &SavedVideo,
+    ) -> Result<UserPlaylist> {
+        let tx = self.conn.transaction().await?;
+        let mut exists = tx
+            .query(
+                "SELECT 1 FROM user_playlists WHERE profile_id = ?1 AND playlist_id = ?2",
+                params![self.profile_id, playlist_id],
+            )
+            .await?;
+        if exists.next().await?.is_none() {
+            bail!("playlist {playlist_id} does not exist");
+        }
+        drop(exists);
+
+        let added = append_playlist_item(&tx, self.profile_id, playlist_id, videoid).await?;
+        if added {
+            let mut entry = saved.clone();
+            entry.videoid = Some(videoid.to_string());
+            entry.saved_at.get_or_insert_with(now_millis);
+            tx.execute(
+                r#"
+                UPDATE user_playlists
+                SET updated_at = ?3, last_entry_json = ?4
+                WHERE profile_id = ?1 AND playlist_id = ?2
+                "#,
+                params![
+                    self.profile_id,
+                    playlist_id,
+                    now_millis(),
+                    serde_json::to_string(&entry).context("serializing playlist entry")?,
+                ],
+            )
+            .await?;
+        }
+        tx.commit().await?;
+        self.get_playlist(playlist_id)
+            .await?
+            .context("playlist missing after insert")
+    }
+
+    pub async fn remove_from_playlist(&self, playlist_id: &str, videoid: &str) -> Result<bool> {
+        let removed = self
+            .conn
+            .execute(
+                r#"
+                DELETE FROM user_playlist_items
+                WHERE profile_id = ?1 AND playlist_id = ?2 AND videoid = ?3
+                "#,
+                params![self.profile_id, playlist_id, videoid],
+            )
+            .await?;
+        if removed > 0 {
+            self.conn
+                .execute(
+                    r#"
+                    UPDATE user_playlists SET updated_at = ?3
+                    WHERE profile_id = ?1 AND playlist_id = ?2
+                    "#,
+                    params![self.profile_id, playlist_id, now_millis()],
+                )
+                .await?;
+        }
+        Ok(removed > 0)
+    }
+
+    /// Lists subscriptions, oldest first.
+    pub async fn list_subscriptions(&self) -> Result<Vec<Subscription>> {
+        let mut rows = self
+            .conn
+            .query(
+                r#"
+                SELECT channel_id, name, channel_url, subscribed_at
+                FROM subscriptions
+                WHERE profile_id = ?1
+                ORDER BY subscribed_at, channel_id
+                "#,
+                [self.profile_id],
+            )
+            .await?;
+        let mut subscriptions = Vec::new();
+        while let Some(row) = rows.next().await? {
+            subscriptions.push(Subscription {
+                channel_id: row.get(0)?,
+                name: row.get(1)?,
+                channel_url: row.get(2)?,
+                subscribed_at: row.get(3)?,
+            });
+        }
+        Ok(subscriptions)
+    }
+
+    pub async fn subscribe(&self, subscription: &Subscription) -> Result<()> {
+        upsert_subscription(self.conn, self.profile_id, subscription).await
+    }
+
+    pub async fn unsubscribe(&self, channel_id: &str) -> Result<bool> {
+        let removed = self
+            .conn
+            .execute(
+                "DELETE FROM subscriptions WHERE profile_id = ?1 AND channel_id = ?2",
+                params![self.profile_id, channel_id],
+            )
+            .await?;
+        Ok(removed > 0)
+    }
+
+    /// Lists watch history, most recently updated first.
+    pub async fn list_history(&self) -> Result<Vec<(String, WatchEntry)>> {
+        let mut rows = self
+            .conn
+            .query(
+                r#"
+                SELECT videoid, progress, watched, title, author, thumbnail, updated_at
+                FROM watch_history
+                WHERE profile_id = ?1
+                ORDER BY updated_at DESC, videoid
+                "#,
+                [self.profile_id],
+            )
+            .await?;
+        let mut history = Vec::new();
+        while let Some(row) = rows.next().await? {
+            history.push((
+                row.get(0)?,
+                WatchEntry {
+                    progress: row.get(1)?,
+                    watched: row.get::<i64>(2)? != 0,
+                    title: row.get(3)?,
+                    author: row.get(4)?,
+                    thumbnail: row.get(5)?,
+                    updated_at: row.get(6)?,
+                },
+            ));
+        }
+        Ok(history)
+    }
+
+    /// Records playback progress. Progress is clamped to 0..=1 and a video
+    /// counts as watched once it reaches 90%, like the frontend.
+    pub async fn record_watch(&self, videoid: &str, entry: &WatchEntry) -> Result<()> {
+        let mut entry = entry.clone();
+        let progress = entry.progress.unwrap_or(0.0).clamp(0.0, 1.0);
+        entry.progress = Some((progress * 100.0).round() / 100.0);
+        entry.watched |= progress >= 0.9;
+        entry.updated_at = Some(now_millis());
+        merge_watch_entry(self.conn, self.profile_id, videoid, &entry).await
+    }
+
+    pub async fn delete_history_entry(&self, videoid: &str) -> Result<bool> {
+        let removed = self
+            .conn
+            .execute(
+                "DELETE FROM watch_history WHERE profile_id = ?1 AND videoid = ?2",
+                params![self.profile_id, videoid],
+            )
+            .await?;
+        Ok(removed > 0)
+    }
+
+    /// Merges a device's snapshot into the server copy and returns the merged
+    /// state. Exports carry no deletions, so merging is a union:
+    ///
+    /// * reactions and watch progress: the newer timestamp wins; `watched`
+    ///   stays set once any device set it;
+    /// * playlists: server order is kept and unseen videos are appended;
+    /// * subscriptions: union, keeping the earliest `subscribedAt`.
+    pub async fn merge(&self, incoming: &UserSnapshot) -> Result<UserSnapshot> {
+        let profile_id = self.profile_id;
+        let tx = self.conn.transaction().await?;
+
+        for (reaction, entries) in [
+            (Reaction::Like, &incoming.likes),
+            (Reaction::Dislike, &incoming.dislikes),
+        ] {
+            for (videoid, saved) in entries {
+                upsert_reaction(&tx, profile_id, videoid, reaction, saved, true).await?;
+            }
+        }
+
+        for (key, playlist) in &incoming.playlists {
+            merge_playlist(&tx, profile_id, key, playlist).await?;
+        }
+
+        for (channel_id, subscription) in &incoming.subscriptions {
+            let mut subscription = subscription.clone();
+            if subscription.channel_id.is_empty() {
+                subscription.channel_id = channel_id.clone();
+            }
+            upsert_subscription(&tx, profile_id, &subscription).await?;
+        }
+
+        for (videoid, entry) in &incoming.watch_history {
+            merge_watch_entry(&tx, profile_id, videoid, entry).await?;
+        }
+
+        tx.commit().await?;
+        self.snapshot().await
+    }
+}
+
+async fn hash_password(password: String) -> Result<String> {
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        Argon2::default()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|err| anyhow!("hashing password: {err}"))
+    })
+    .await
+    .context("password hashing task failed")?
+}
+
+fn verify_password_hash(hash: &str, password: &str) -> bool {
+    PasswordHash::new(hash).is_ok_and(|parsed| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok()
+    })
+}
+
+fn row_to_profile(row: &Row) -> Result<Profile> {
+    Ok(Profile {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        is_admin: row.get::<i64>(2)? != 0,
+        has_password: row.get::<i64>(3)? != 0,
+        created_at: row.get(4)?,
+    })
+}
+
+/// Inserts a reaction. With `only_if_newer`, an existing row is replaced only
+/// when the incoming `saved_at` is more recent (used by `merge`).
+async fn upsert_reaction(
+    conn: &Connection,
+    profile_id: i64,
+    videoid: &str,
+    reaction: Reaction,
+    saved: &SavedVideo,
+    only_if_newer: bool,
+) -> Result<()> {
+    let saved_at = saved.saved_at.unwrap_or_else(now_millis);
+    let guard = if only_if_newer {
+        "WHERE excluded.saved_at > reactions.saved_at"
+    } else {
+        ""
+    };
+    conn.execute(
+        &format!(
+            r#"
+            INSERT INTO reactions
+                (profile_id, videoid, reaction, title, author, channel_id, thumbnail, saved_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            ON CONFLICT(profile_id, videoid) DO UPDATE SET
+                reaction = excluded.reaction,
+                title = excluded.title,
+                author = excluded.author,
+                channel_id = excluded.channel_id,
+                thumbnail = excluded.thumbnail,
+                saved_at = excluded.saved_at
+            {guard}
+            "#
+        ),
+        params![
+            profile_id,
+            videoid,
+            reaction.as_str(),
+            saved.title.as_deref(),
+            saved.author.as_deref(),
+            saved.channel_id.as_deref(),
+            saved.thumbnail.as_deref(),
+            saved_at,
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Adds a subscription or fills in missing details, keeping the earliest
+/// subscription time.
+async fn upsert_subscription(
+    conn: &Connection,
+    profile_id: i64,
+    subscription: &Subscription,
+) -> Result<()> {
+    if subscription.channel_id.is_empty() {
+        bail!("subscription is missing a channel id");
+    }
+    conn.execute(
+        r#"
+        INSERT INTO subscriptions (profile_id, channel_id, name, channel_url, subscribed_at)
+        VALUES (?1, ?2, ?3, ?4, ?5)
+        ON CONFLICT(profile_id, channel_id) DO UPDATE SET
+            name = COALESCE(excluded.name, subscriptions.name),
+            channel_url = COALESCE(excluded.channel_url, subscriptions.channel_url),
+            subscribed_at = MIN(excluded.subscribed_at, subscriptions.subscribed_at)
+        "#,
+        params![
+            profile_id,
+            subscription.channel_id.as_str(),
+            subscription.name.as_deref(),
+            subscription.channel_url.as_deref(),
+            subscription.subscribed_at.unwrap_or_else(now_millis),
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Merges one watch entry: newer progress wins, `watched` is sticky and
+/// descriptive fields are only filled in, never cleared.
+async fn merge_watch_entry(
+    conn: &Connection,
+    profile_id: i64,
+    videoid: &str,
+    entry: &WatchEntry,
+) -> Result<()> {
+    conn.execute(
+        r#"
+        INSERT INTO watch_history
+            (profile_id, videoid, progress, watched, title, author, thumbnail, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        ON CONFLICT(profile_id, videoid) DO UPDATE SET
+            progress = CASE WHEN excluded.updated_at >= watch_history.updated_at
+                THEN excluded.progress ELSE watch_history.progress END,
+            watched = MAX(watch_history.watched, excluded.watched),
+            title = COALESCE(excluded.title, watch_history.title),
+            author = COALESCE(excluded.author, watch_history.author),
+            thumbnail = COALESCE(excluded.thumbnail, watch_history.thumbnail),
+            updated_at = MAX(watch_history.updated_at, excluded.updated_at)
+        "#,
+        params![
+            profile_id,
+            videoid,
+            entry.progress.unwrap_or(0.0),
+            entry.watched as i64,
+            entry.title.as_deref(),
+            entry.author.as_deref(),
+            entry.thumbnail.as_deref(),
+            entry.updated_at.unwrap_or_else(now_millis),
+        ],
+    )
+    .await?;
+    Ok(())
+}
+
+/// Merges a playlist from an export: the newer side names it, and videos the
+/// server has not seen are appended in the incoming order.
+async fn merge_playlist(
+    conn: &Connection,
+    profile_id: i64,
+    key: &str,
+    playlist: &UserPlaylist,
+) -> Result<()> {
+    let playlist_id = if playlist.id.is_empty() {
+        key
+    } else {
+        playlist.id.as_str()
+    };
+    let name = if playlist.name.is_empty() {
+        playlist_id
+    } else {
+        playlist.name.as_str()
+    };
+    let now = now_millis();
+    let updated_at = playlist.updated_at.unwrap_or(now);
+    let last_entry = playlist
+        .last_entry
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .context("serializing playlist entry")?;
+
+    conn.execute(
+        r#"
+        INSERT INTO user_playlists
+            (profile_id, playlist_id, name, created_at, updated_at, last_entry_json)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        ON CONFLICT(profile_id, playlist_id) DO UPDATE SET
+            name = CASE WHEN excluded.updated_at > user_playlists.updated_at
+                THEN excluded.name ELSE user_playlists.name END,
+            last_entry_json = CASE WHEN excluded.updated_at > user_playlists.updated_at
+                THEN COALESCE(excluded.last_entry_json, user_playlists.last_entry_json)
+                ELSE user_playlists.last_entry_json END,
+            created_at = MIN(user_playlists.created_at, excluded.created_at),
+            updated_at = MAX(user_playlists.updated_at, excluded.updated_at)
+        "#,
+        params![
+            profile_id,
+            playlist_id,
+            name,
+            playlist.created_at.unwrap_or(updated_at),
+            updated_at,
+            last_entry,
+        ],
+    )
+    .await?;
+
+    for videoid in &playlist.video_ids {
+        append_playlist_item(conn, profile_id, playlist_id, videoid).await?;
+    }
+    Ok(())
+}
+
+/// Appends `videoid` after the current last item. Returns false when the
+/// video was already in the playlist.
+async fn append_playlist_item(
+    conn: &Connection,
+    profile_id: i64,
+    playlist_id: &str,
+    videoid: &str,
+) -> Result<bool> {
+    let inserted = conn
+        .execute(
+            r#"
+            INSERT OR IGNORE INTO user_playlist_items (profile_id, playlist_id, videoid, position)
+            SELECT ?1, ?2, ?3, COALESCE(MAX(position) + 1, 0)
+            FROM user_playlist_items
+            WHERE profile_id = ?1 AND playlist_id = ?2
+            "#,
+            params![profile_id, playlist_id, videoid],
+        )
+        .await?;
+    Ok(inserted > 0)
+}
+
+fn row_to_playlist(row: &Row) -> Result<UserPlaylist> {
+    let last_entry_json: Option<String> = row.get(4)?;
+    Ok(UserPlaylist {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        video_ids: Vec::new(),
+        created_at: row.get(2)?,
+        updated_at: row.get(3)?,
+        last_entry: last_entry_json
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+            .context("parsing playlist last entry")?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    async fn open_store() -> Result<(tempfile::TempDir, UserDataStore)> {
+        let dir = tempdir()?;
+        let store = UserDataStore::open(&dir.path().join(USER_DATA_DB_FILE)).await?;
+        Ok((dir, store))
+    }
+
+    fn saved(title: &str, saved_at: i64) -> SavedVideo {
+        SavedVideo {
+            title: Some(title.into()),
+            saved_at: Some(saved_at),
+            ..SavedVideo::default()
+        }
+    }
+
+    #[test]
+    fn normalize_playlist_key_matches_frontend() {
+        assert_eq!(
+            normalize_playlist_key("  Road   Trip Mix "),
+            "road-trip-mix"
+        );
+        assert_eq!(normalize_playlist_key("   "), "default");
+        assert_eq!(normalize_playlist_key(&"x".repeat(80)).len(), 64);
+    }
+
+    /// A like replaces a dislike for the same video and vice versa.
+    #[tokio::test]
+    async fn reactions_are_mutually_exclusive() -> Result<()> {
+        let (_dir, store) = open_store().await?;
+        store
+            .profile(DEFAULT_PROFILE_ID)
+            .set_reaction("alpha", Reaction::Dislike, &saved("Alpha", 1))
+            .await?;
+        store
+            .profile(DEFAULT_PROFILE_ID)
+            .set_reaction("alpha", Reaction::Like, &saved("Alpha", 2))
+            .await?;
+
+        let snapshot = store.profile(DEFAULT_PROFILE_ID).snapshot().await?;
+        assert!(snapshot.likes.contains_key("alpha"));
+        assert!(snapshot.dislikes.is_empty());
+        assert!(
+            !store
+                .profile(DEFAULT_PROFILE_ID)
+                .clear_reaction("alpha", Reaction::Dislike)
+                .await?
+        );
+        assert!(
+            store
+                .profile(DEFAULT_PROFILE_ID)
+                .clear_reaction("alpha", Reaction::Like)
+                .await?
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn playlists_keep_insertion_order_without_duplicates() -> Result<()> {
+        let (_dir, store) = open_store().await?;
+        store
+            .profile(DEFAULT_PROFILE_ID)
+            .upsert_playlist("favorites", "Favorites")
+            .await?;
+        store
+            .profile(DEFAULT_PROFILE_ID)
+            .add_to_playlist("favorites", "b", &SavedVideo::default())
+            .await?;
+        store
+            .profile(DEFAULT_PROFILE_ID)
+            .add_to_playlist("favorites", "a", &saved("A", 5))
+            .await?;
+        let playlist = store
+            .profile(DEFAULT_PROFILE_ID)
+            .add_to_playlist("favorites", "b", &SavedVideo::default())
+            .await?;
+        assert_eq!(playlist.video_ids, ["b", "a"]);
+        assert_eq!(
+            playlist
+                .last_entry
+                .and_then(|entry| entry.videoid)
+                .as_deref(),
+            Some("a")
+        );
+
+        assert!(
+            store
+                .profile(DEFAULT_PROFILE_ID)
+                .remove_from_playlist("favorites", "b")
+                .await?
+        );
+        assert!(
+            store
+                .profile(DEFAULT_PROFILE_ID)
+                .add_to_playlist("missing", "a", &SavedVideo::default())
+                .await
+                .is_err()
+        );
+        assert!(
+            store
+                .profile(DEFAULT_PROFILE_ID)
+                .delete_playlist("favorites")
+                .await?
+        );
+        assert!(
+            store
+                .profile(DEFAULT_PROFILE_ID)
+                .list_playlists()
+                .await?
+                .is_empty()
+        );
+        Ok(())
+    }
+
+    /// Progress clamps to 0..=1 and `watched` never flips back to false.
+    #[tokio::test]
+    async fn watch_progress_is_clamped_and_watched_is_sticky() -> Result<()> {
+        let (_dir, store) = open_store().await?;
+        let progress = |value: f64| WatchEntry {
+            progress: Some(value),
+            ..WatchEntry::default()
+        };
+        store
+            .profile(DEFAULT_PROFILE_ID)
+            .record_watch("alpha", &progress(1.7))
+            .await?;
+        store
+            .profile(DEFAULT_PROFILE_ID)
+            .record_watch("alpha", &progress(0.2))
+            .await?;
+
+        let history = store.profile(DEFAULT_PROFILE_ID).list_history().await?;
+        let (_, entry) = &history[0];
+        assert_eq!(entry.progress, Some(0.2));
+        assert!(entry.watched);
+        assert!(
+            store
+                .profile(DEFAULT_PROFILE_ID)
+                .delete_history_entry("alpha")
+                .await?
+        );
+        Ok(())
+    }
+
+    /// Syncing two devices' exports produces the union, with newer progress
+    /// and reactions winning and playlists appending unseen videos.
+    #[tokio::test]
+    async fn merge_combines_device_exports() -> Result<()> {
+        let (_dir, store) = open_store().await?;
+        let laptop: UserDataImport = serde_json::from_str(
+            r#"{
+                "exportedAt": "2024-05-01T00:00:00.000Z",
+                "data": {
+                    "version": 1,
+                    "likes": {"a": {"videoid": "a", "title": "A", "savedAt": 100}},
+                    "dislikes": {},
+                    "playlists": {"favorites": {"id": "favorites", "name": "Favorites",
+                        "videoIds": ["a", "b"], "createdAt": 10, "updatedAt": 10}},
+                    "subscriptions": {"UC1": {"channelId": "UC1", "name": "One", "subscribedAt": 50}},
+                    "watchHistory": {"a": {"progress": 0.95, "watched": true, "updatedAt": 100}},
+                    "metadata": {"createdAt": 1, "updatedAt": 100}
+                }
+            }"#,
+        )?;
+        store
+            .profile(DEFAULT_PROFILE_ID)
+            .merge(&laptop.into_snapshot())
+            .await?;
+
+        let phone: UserDataImport = serde_json::from_str(
+            r#"{
+                "likes": {},
+                "dislikes": {"a": {"videoid": "a", "savedAt": 200}},
+                "playlists": {"favorites": {"id": "favorites", "name": "Faves",
+                    "videoIds": ["c", "a"], "createdAt": 5, "updatedAt": 300}},
+                "subscriptions": {"UC1": {"channelId": "UC1", "subscribedAt": 20},
+                                  "UC2": {"name": "Two"}},
+                "watchHistory": {"a": {"progress": 0.1, "watched": false, "updatedAt": 150, "title": "A"}}
+            }"#,
+        )?;
+        let merged = store
+            .profile(DEFAULT_PROFILE_ID)
+            .merge(&phone.into_snapshot())
+            .await?;
+
+        assert!(merged.likes.is_empty());
+        assert_eq!(merged.dislikes["a"].saved_at, Some(200));
+        let favorites = &merged.playlists["favorites"];
+        assert_eq!(favorites.name, "Faves");
+        assert_eq!(favorites.video_ids, ["a", "b", "c"]);
+        assert_eq!(favorites.created_at, Some(5));
+        assert_eq!(merged.subscriptions["UC1"].subscribed_at, Some(20));
+        assert_eq!(merged.subscriptions["UC1"].name.as_deref(), Some("One"));
+        assert_eq!(merged.subscriptions["UC2"].channel_id, "UC2");
+        let watch = &merged.watch_history["a"];
+        assert_eq!(watch.progress, Some(0.1));
+        assert!(watch.watched);
+        assert_eq!(watch.title.as_deref(), Some("A"));
+
+        let stale: UserDataImport =
+            serde_json::from_str(r#"{"likes": {"a": {"videoid": "a", "savedAt": 150}}}"#)?;
+        let merged = store
+            .profile(DEFAULT_PROFILE_ID)
+            .merge(&stale.into_snapshot())
+            .await?;
+        assert!(merged.likes.is_empty());
+        assert_eq!(merged.version, 1);
+        Ok(())
+    }
+
+    /// Each profile only sees its own rows, and deleting a profile takes its
+    /// data and sessions with it.
+    #[tokio::test]
+    async fn profiles_isolate_user_data() -> Result<()> {
+        let (_dir, store) = open_store().await?;
+        let kid = store.create_profile("Kid", None, false).await?;
+        assert!(store.create_profile(" kid ", None, false).await.is_err());
+
+        store
+            .profile(DEFAULT_PROFILE_ID)
+            .set_reaction("alpha", Reaction::Like, &saved("Alpha", 1))
+            .await?;
+        store
+            .profile(kid.id)
+            .set_reaction("alpha", Reaction::Dislike, &saved("Alpha", 2))
+            .await?;
+        store
+            .profile(kid.id)
+            .upsert_playlist("cartoons", "Cartoons")
+            .await?;
+
+        let admin = store.profile(DEFAULT_PROFILE_ID).snapshot().await?;
+        assert!(admin.likes.contains_key("alpha"));
+        assert!(admin.dislikes.is_empty());
+        assert!(admin.playlists.is_empty());
+        let kids = store.profile(kid.id).snapshot().await?;
+        assert!(kids.dislikes.contains_key("alpha"));
+
+        store.create_session(kid.id).await?;
+        assert!(store.delete_profile(kid.id).await?);
+        assert!(store.profile(kid.id).list_playlists().await?.is_empty());
+        assert!(store.profile(kid.id).settings().await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn passwords_and_sessions() -> Result<()> {
+        let (_dir, store) = open_store().await?;
+        let anonymous = store
+            .anonymous_profile()
+            .await?
+            .expect("single open profile");
+        assert_eq!(anonymous.id, DEFAULT_PROFILE_ID);
+        assert!(anonymous.is_admin);
+
+        let parent = store.create_profile("Parent", Some("1234"), false).await?;
+        assert!(parent.has_password);
+        assert!(store.anonymous_profile().await?.is_none());
+        assert!(store.verify_password(parent.id, Some("1234")).await?);
+        assert!(!store.verify_password(parent.id, Some("4321")).await?);
+        assert!(!store.verify_password(parent.id, None).await?);
+        assert!(store.verify_password(DEFAULT_PROFILE_ID, None).await?);
+
+        let session = store.create_session(parent.id).await?;
+        assert_eq!(session.token.len(), 64);
+        let resolved = store.session_profile(&session.token).await?;
+        assert_eq!(resolved.map(|profile| profile.id), Some(parent.id));
+        assert!(store.session_profile("bogus").await?.is_none());
+
+        // Changing the password signs every device out.
+        store.set_password(parent.id, None).await?;
+        assert!(store.session_profile(&session.token).await?.is_none());
+        let session = store.create_session(parent.id).await?;
+        assert!(store.delete_session(&session.token).await?);
+        assert!(store.session_profile(&session.token).await?.is_none());
+        Ok(())
+    }
+
+    /// Data synced before profiles existed ends up under the default profile.
+    #[tokio::test]
+    async fn profile_migration_keeps_existing_data() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join(USER_DATA_DB_FILE);
+        {
+            let db = Builder::new_local(&path).build().await?;
+            let conn = db.connect()?;
+            configure_connection(&conn).await?;
+            ensure_schema_with(&conn, "user data", &MIGRATIONS[..1]).await?;
+            conn.execute_batch(
+                r#"
+                INSERT INTO reactions (videoid, reaction, saved_at) VALUES ('alpha', 'like', 1);
+                INSERT INTO user_playlists (playlist_id, name, created_at, updated_at)
+                VALUES ('mix', 'Mix', 1, 1);
+                INSERT INTO user_playlist_items (playlist_id, videoid, position)
+                VALUES ('mix', 'beta', 0);
+                INSERT INTO watch_history (videoid, progress, watched, updated_at)
+                VALUES ('alpha', 0.5, 0, 1);
+                "#,
+            )
+            .await?;
+        }
+
+        let store = UserDataStore::open(&path).await?;
+        let snapshot = store.profile(DEFAULT_PROFILE_ID).snapshot().await?;
+        assert!(snapshot.likes.contains_key("alpha"));
+        assert_eq!(snapshot.playlists["mix"].video_ids, ["beta"]);
+        assert_eq!(snapshot.watch_history["alpha"].progress, Some(0.5));
+
+        // The rebuilt playlist tables still cascade on delete.
+        store
+            .profile(DEFAULT_PROFILE_ID)
+            .delete_playlist("mix")
+            .await?;
+        store
+            .profile(DEFAULT_PROFILE_ID)
+            .upsert_playlist("mix", "Mix")
+            .await?;
+        let playlist = store
+            .profile(DEFAULT_PROFILE_ID)
+            .get_playlist("mix")
+            .await?;
+        assert_eq!(
+            playlist.map(|playlist| playlist.video_ids),
+            Some(Vec::new())
+        );
+        Ok(())
+    }
+
+    /// Bytes accumulate on every call while plays only grow when a session
+    /// crossed the threshold; unplayed videos stay out of the listing.
+    #[tokio::test]
+    async fn play_stats_accumulate_and_rank() -> Result<()> {
+        let (_dir, store) = open_store().await?;
+        assert_eq!(store.play_stats("alpha").await?.plays, 0);
+        assert_eq!(store.play_stats("alpha").await?.last_played_at, None);
+
+        store.record_play("alpha", 100, false).await?;
+        store.record_play("alpha", 400, true).await?;
+        store.record_play("beta", 50, true).await?;
+        store.record_play("beta", 50, true).await?;
+        store.record_play("gamma", 10, false).await?;
+
+        let alpha = store.play_stats("alpha").await?;
+        assert_eq!((alpha.plays, alpha.watched_bytes), (1, 500));
+        assert!(alpha.last_played_at.is_some());
+
+        let top = store.most_played(10).await?;
+        let ids: Vec<&str> = top.iter().map(|stats| stats.videoid.as_str()).collect();
+        assert_eq!(ids, ["beta", "alpha"]);
+        assert_eq!(store.most_played(1).await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn profile_settings_must_be_objects() -> Result<()> {
+        let (_dir, store) = open_store().await?;
+        let data = store.profile(DEFAULT_PROFILE_ID);
+        assert_eq!(data.settings().await?, serde_json::json!({}));
+        data.set_settings(&serde_json::json!({"theme": "dark"}))
+            .await?;
+        assert_eq!(data.settings().await?["theme"], "dark");
+        assert!(data.set_settings(&serde_json::json!([1, 2])).await.is_err());
+        Ok(())
+    }
+}
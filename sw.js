@@ -1,5 +1,5 @@
 // Service Worker for newtube
-const CACHE_NAME = 'newtube-static-v3';
+const CACHE_NAME = 'newtube-static-v4';
 
 // List of files to cache on install
 const urlsToCache = [
@@ -23,4 +23,12 @@ describe('ApiClient', () => {
 
     await expect(client.fetchVideos()).rejects.toThrow('Request failed (500)');
   });
+
+  it('Keeps the status on failed requests', async () => {
+    const client = new ApiClient('/api');
+    // The sign-in flow keys off 401/403, so the status must survive
+    global.fetch.mockResolvedValueOnce({ ok: false, status: 401 });
+
+    await expect(client.startVideoDownload('abc', 'video')).rejects.toMatchObject({ status: 401 });
+  });
 });
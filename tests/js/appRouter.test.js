@@ -1,4 +1,4 @@
-const { App, DatabaseManager } = require('../../app');
+const { App, DatabaseManager, SignInDialog } = require('../../app');
 
 function createPageClass(label) {
   const init = jest.fn().mockResolvedValue();
@@ -68,4 +68,57 @@ describe('App router and loader', () => {
     await expect(second).resolves.toBeUndefined();
     expect(document.querySelectorAll('script[src="/foo.js"]').length).toBe(1);
   });
+
+  it('withSession signs in and retries once the backend asks for a profile', async () => {
+    const app = new App();
+    const denied = Object.assign(new Error('Request failed (401)'), { status: 401 });
+    const action = jest.fn().mockRejectedValueOnce(denied).mockResolvedValueOnce({ id: 'job' });
+    jest.spyOn(app.signInDialog, 'open').mockResolvedValue({ id: 1, name: 'Default' });
+
+    await expect(app.withSession(action)).resolves.toEqual({ id: 'job' });
+    expect(app.signInDialog.open).toHaveBeenCalledTimes(1);
+    expect(action).toHaveBeenCalledTimes(2);
+  });
+
+  it('withSession gives up when sign in is cancelled or the error is unrelated', async () => {
+    const app = new App();
+    jest.spyOn(app.signInDialog, 'open').mockResolvedValue(null);
+    const forbidden = Object.assign(new Error('Request failed (403)'), { status: 403 });
+    await expect(app.withSession(() => Promise.reject(forbidden))).rejects.toBe(forbidden);
+
+    const broken = Object.assign(new Error('Request failed (500)'), { status: 500 });
+    await expect(app.withSession(() => Promise.reject(broken))).rejects.toBe(broken);
+    expect(app.signInDialog.open).toHaveBeenCalledTimes(1);
+  });
+});
+
+describe('SignInDialog', () => {
+  it('asks for a password only for protected profiles and signs in', async () => {
+    const api = {
+      fetchProfiles: jest.fn().mockResolvedValue([
+        { id: 1, name: 'Default', isAdmin: true, hasPassword: true },
+        { id: 2, name: 'Kid', isAdmin: false, hasPassword: false }
+      ]),
+      signIn: jest.fn().mockResolvedValue({ id: 1, name: 'Default' })
+    };
+    const opened = new SignInDialog(api).open();
+    await new Promise((resolve) => setTimeout(resolve, 0));
+
+    const select = document.querySelector('.signin-dialog select');
+    const passwordField = document.querySelector('.signin-password');
+    expect(select.options).toHaveLength(2);
+    expect(passwordField.hidden).toBe(false);
+    select.value = '2';
+    select.dispatchEvent(new Event('change'));
+    expect(passwordField.hidden).toBe(true);
+
+    select.value = '1';
+    select.dispatchEvent(new Event('change'));
+    document.querySelector('input[name="password"]').value = 'secret';
+    document.querySelector('.signin-dialog').dispatchEvent(new Event('submit', { cancelable: true }));
+
+    await expect(opened).resolves.toEqual({ id: 1, name: 'Default' });
+    expect(api.signIn).toHaveBeenCalledWith(1, 'secret');
+    expect(document.querySelector('.signin-overlay')).toBeNull();
+  });
 });